@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
 use criterion::{BatchSize, Criterion, Throughput, black_box, criterion_group, criterion_main};
-use understory_index::{Aabb2D, Index};
+use understory_index::{Aabb2D, Backend, Index, IndexGeneric, RTree8, RTree16};
 
 fn gen_grid_rects(n: usize, cell: f64) -> Vec<Aabb2D<f64>> {
     let mut out = Vec::with_capacity(n * n);
@@ -354,6 +354,78 @@ fn bench_bvh_clustered_f64(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_query_points_clustered_f64(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rtree_f64_query_points_clustered");
+    let rects = gen_grid_rects(128, 8.0);
+    let mut idx = Index::<f64, u32>::with_rtree();
+    for (i, r) in rects.iter().copied().enumerate() {
+        let _ = idx.insert(r, i as u32);
+    }
+    let _ = idx.commit();
+    // A stroke-like cluster of points within a few cells.
+    let mut rng = Rng::new(0x57A0_CE55_0123_4567);
+    let pts: Vec<(f64, f64)> = (0..64)
+        .map(|_| (400.0 + rng.next_f64() * 24.0, 400.0 + rng.next_f64() * 24.0))
+        .collect();
+    group.throughput(Throughput::Elements(pts.len() as u64));
+    group.bench_function("per_point", |b| {
+        b.iter(|| {
+            let mut total = 0usize;
+            for &(x, y) in &pts {
+                total += idx.query_point(x, y).count();
+            }
+            black_box(total);
+        })
+    });
+    group.bench_function("batched", |b| {
+        b.iter(|| {
+            let total: usize = idx.query_points(&pts).iter().map(Vec::len).sum();
+            black_box(total);
+        })
+    });
+    group.finish();
+}
+
+fn bench_rtree_const_fanout_f64(c: &mut Criterion) {
+    fn run<B: Backend<f64>>(mut idx: IndexGeneric<f64, u32, B>, rects: &[Aabb2D<f64>]) -> usize {
+        for (i, r) in rects.iter().copied().enumerate() {
+            let _ = idx.insert(r, i as u32);
+        }
+        let _ = idx.commit();
+        let mut total = 0usize;
+        for q in 0..256 {
+            let x = (q % 64) as f64 * 8.0;
+            let y = (q / 64) as f64 * 8.0;
+            total += idx
+                .query_rect(Aabb2D::<f64>::from_xywh(x, y, 64.0, 64.0))
+                .count();
+        }
+        total
+    }
+
+    let mut group = c.benchmark_group("rtree_f64_const_fanout");
+    let rects = gen_clustered_rects(16, 256, 128.0);
+    group.bench_function("runtime_fanout_8", |b| {
+        b.iter(|| black_box(run(Index::<f64, u32>::with_rtree(), &rects)))
+    });
+    group.bench_function("runtime_fanout_16", |b| {
+        b.iter(|| black_box(run(Index::<f64, u32>::with_rtree_params(16, 8), &rects)))
+    });
+    group.bench_function("const_fanout_8", |b| {
+        b.iter(|| {
+            let idx = IndexGeneric::with_backend(RTree8::<f64, u32>::default());
+            black_box(run(idx, &rects))
+        })
+    });
+    group.bench_function("const_fanout_16", |b| {
+        b.iter(|| {
+            let idx = IndexGeneric::with_backend(RTree16::<f64, u32>::default());
+            black_box(run(idx, &rects))
+        })
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_flatvec,
@@ -365,5 +437,7 @@ criterion_group!(
     bench_update_heavy_rtree_i64,
     bench_query_heavy_rtree_f64,
     bench_bvh_clustered_f64,
+    bench_query_points_clustered_f64,
+    bench_rtree_const_fanout_f64,
 );
 criterion_main!(benches);
@@ -29,6 +29,7 @@ fn main() {
         local_clip: None,
         z_index: 0,
         flags: NodeFlags::VISIBLE | NodeFlags::PICKABLE,
+        layer: 0,
     };
     let root = bt.insert(None, root_local);
 
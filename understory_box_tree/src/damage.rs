@@ -3,13 +3,28 @@
 
 //! Damage summary types returned from commit.
 
+use alloc::vec::Vec;
 use kurbo::Rect;
 
+use crate::types::NodeId;
+
 /// A batched set of changes derived from [`crate::Tree::commit`].
 #[derive(Clone, Debug, Default)]
 pub struct Damage {
     /// World-space rectangles that should be repainted.
-    pub dirty_rects: alloc::vec::Vec<Rect>,
+    pub dirty_rects: Vec<Rect>,
+    /// `dirty_rects` mapped to device space, index for index.
+    ///
+    /// Only filled by [`crate::Tree::commit_with_viewport`]; empty otherwise. A
+    /// [`DamageAccumulator`] keeps the pairing, and leaves this empty if any merged
+    /// commit had no device rects.
+    pub device_rects: Vec<Rect>,
+    /// Nodes committed for the first time, with their world bounds.
+    pub added: Vec<(NodeId, Rect)>,
+    /// Nodes removed since the last commit, with their last committed world bounds.
+    pub removed: Vec<(NodeId, Rect)>,
+    /// Nodes whose world bounds changed, as `(node, old, new)`.
+    pub moved: Vec<(NodeId, Rect, Rect)>,
 }
 
 impl Damage {
@@ -19,4 +34,118 @@ impl Damage {
         let first = it.next()?;
         Some(it.fold(first, |acc, r| acc.union(r)))
     }
+
+    /// Returns true if there is nothing to repaint and no node-level changes.
+    pub fn is_empty(&self) -> bool {
+        self.dirty_rects.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+    }
+}
+
+/// Merges the [`Damage`] of several commits into one, e.g. once per painted frame.
+///
+/// Node-level changes are netted out: repeated moves of a node collapse into a single
+/// oldest→newest move, moves of a node added in the same window update its added bounds,
+/// and a node added and removed in the same window is dropped. Dirty rects stay paired
+/// with their device rects, including when coalesced. Call [`DamageAccumulator::take`]
+/// to flush.
+#[derive(Clone, Debug, Default)]
+pub struct DamageAccumulator {
+    damage: Damage,
+    coalesce: bool,
+    // Set once a merged commit had dirty rects but no device rects; `device_rects` then
+    // stays empty until the next flush.
+    unpaired: bool,
+}
+
+impl DamageAccumulator {
+    /// Create an empty accumulator that keeps dirty rects as reported.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merge overlapping dirty rects as they arrive, trading precision for a shorter list.
+    pub fn set_coalesce(&mut self, coalesce: bool) {
+        self.coalesce = coalesce;
+    }
+
+    /// Fold the damage of one commit into the accumulated damage.
+    pub fn merge(&mut self, damage: Damage) {
+        if damage.device_rects.len() != damage.dirty_rects.len() {
+            self.unpaired = true;
+            self.damage.device_rects.clear();
+        }
+        let mut device = damage.device_rects.into_iter();
+        for r in damage.dirty_rects {
+            let d = device.next().filter(|_| !self.unpaired);
+            self.push_rect(r, d);
+        }
+        let acc = &mut self.damage;
+        for (id, r) in damage.added {
+            acc.added.push((id, r));
+        }
+        for (id, old, new) in damage.moved {
+            if let Some(a) = acc.added.iter_mut().find(|a| a.0 == id) {
+                a.1 = new;
+            } else if let Some(i) = acc.moved.iter().position(|m| m.0 == id) {
+                if acc.moved[i].1 == new {
+                    acc.moved.swap_remove(i);
+                } else {
+                    acc.moved[i].2 = new;
+                }
+            } else {
+                acc.moved.push((id, old, new));
+            }
+        }
+        for (id, r) in damage.removed {
+            if let Some(i) = acc.added.iter().position(|a| a.0 == id) {
+                acc.added.swap_remove(i);
+            } else if let Some(i) = acc.moved.iter().position(|m| m.0 == id) {
+                let (_, old, _) = acc.moved.swap_remove(i);
+                acc.removed.push((id, old));
+            } else {
+                acc.removed.push((id, r));
+            }
+        }
+    }
+
+    /// The damage accumulated so far.
+    pub fn damage(&self) -> &Damage {
+        &self.damage
+    }
+
+    /// Returns true if nothing has been accumulated since the last flush.
+    pub fn is_empty(&self) -> bool {
+        self.damage.is_empty()
+    }
+
+    /// Return the accumulated damage and reset the accumulator.
+    pub fn take(&mut self) -> Damage {
+        self.unpaired = false;
+        core::mem::take(&mut self.damage)
+    }
+
+    /// Push a dirty rect and, while paired, its device rect at the same index.
+    fn push_rect(&mut self, mut r: Rect, mut device: Option<Rect>) {
+        let Damage {
+            dirty_rects,
+            device_rects,
+            ..
+        } = &mut self.damage;
+        if self.coalesce {
+            // Absorb every overlapping rect; growing `r` may create new overlaps.
+            while let Some(i) = dirty_rects.iter().position(|o| o.overlaps(r)) {
+                r = r.union(dirty_rects.swap_remove(i));
+                if let Some(d) = &mut device {
+                    *d = d.union(device_rects.swap_remove(i));
+                }
+            }
+        }
+        dirty_rects.push(r);
+        if let Some(d) = device {
+            device_rects.push(d);
+        }
+    }
 }
@@ -48,24 +48,42 @@
 //! - [`NodeId`]: generational handle of a node.
 //! - [`QueryFilter`]: restricts hit/intersect results (visible/pickable/focusable).
 //!   See [`NodeFlags::VISIBLE`], [`NodeFlags::PICKABLE`], and [`NodeFlags::FOCUSABLE`].
+//! - [`PixelTree`]: integer-pixel variant with exact world bounds and damage (integer scale/translate transforms, `i64` index).
 //!
 //! Key operations:
 //! - [`Tree::insert`](Tree::insert) → [`NodeId`]
+//! - [`Tree::build`](Tree::build) inserts a flat list of [`NodeSpec`]s (parents by index) and commits.
 //! - [`Tree::set_local_transform`](Tree::set_local_transform) / [`Tree::set_local_clip`](Tree::set_local_clip) / [`Tree::set_local_bounds`](Tree::set_local_bounds) / [`Tree::set_flags`](Tree::set_flags)
 //! - [`Tree::commit`](Tree::commit) → damage summary; updates world data and the spatial index.
+//! - [`Tree::commit_with_viewport`](Tree::commit_with_viewport) also maps dirty rects to device space through a view transform.
+//! - [`Tree::clear`](Tree::clear) removes every node and reports them all as damage (scene teardown).
+//! - [`Tree::set_index_sync`](Tree::set_index_sync) pauses spatial index updates across commits (queries go stale until re-enabled).
 //! - [`Tree::hit_test_point`](Tree::hit_test_point) and [`Tree::intersect_rect`](Tree::intersect_rect).
+//! - [`Tree::intersect_rect_clipped`](Tree::intersect_rect_clipped) pairs each hit with its overlap with the query rect.
+//! - [`Tree::nodes_contained_in`](Tree::nodes_contained_in) lists nodes fully inside another node's world bounds.
+//! - [`Tree::iter_bfs`](Tree::iter_bfs) walks live nodes level by level with their depth (parents before children).
+//! - [`Tree::hit_candidates`](Tree::hit_candidates) / [`Tree::refine_hit`](Tree::refine_hit) split hit testing into a cacheable broad phase and a precise narrow phase.
+//! - [`Tree::build_snapshot_index`](Tree::build_snapshot_index) bulk-builds a standalone R-tree of committed world bounds.
+//! - [`Tree::switch_index_backend`](Tree::switch_index_backend) rebuilds the spatial indexes of a `Tree<AnyBackend<f64>>` on another backend kind, keeping every [`NodeId`] valid.
+//! - [`Tree::with_world_bounds`](Tree::with_world_bounds) builds a quadtree-indexed tree over fixed world bounds; a [`WorldBoundsPolicy`] grows them or leaves outside nodes unindexed.
+//! - [`Tree::nearest_within`](Tree::nearest_within) picks the closest node within a radius (touch-friendly picking).
+//! - [`Tree::hit_test_point_layer`](Tree::hit_test_point_layer) / [`Tree::intersect_rect_layer`](Tree::intersect_rect_layer) query one [`LocalNode::layer`](LocalNode::layer); each layer has its own spatial index.
+//! - [`Tree::with_layer_backends`](Tree::with_layer_backends) gives layers other than 0 their own indexes in a [`Tree::with_backend`](Tree::with_backend) tree (they share the layer-0 index otherwise).
 //! - [`Tree::z_index`](Tree::z_index) exposes the stacking order of a live [`NodeId`].
 //! - [`Tree::parent_of`](Tree::parent_of) returns the parent of a live [`NodeId`].
 //! - [`Tree::flags`](Tree::flags) returns the [`NodeFlags`] of a live [`NodeId`].
 //! - [`Tree::world_transform`](Tree::world_transform) / [`Tree::world_bounds`](Tree::world_bounds)
 //!   expose the local→world transform and world-space AABB for a live [`NodeId`].
+//! - [`Tree::subtree_bounds`](Tree::subtree_bounds) unions the world bounds of a node and all its descendants ("fit to group").
 //! - [`Tree::children_of`](Tree::children_of) returns the children of a live [`NodeId`].
 //! - [`Tree::next_depth_first`](Tree::next_depth_first) and [`Tree::prev_depth_first`](Tree::prev_depth_first) provide depth-first tree traversal.
+//! - [`Tree::nodes_with_flags`](Tree::nodes_with_flags) enumerates live nodes by flag mask (all or any bits).
 //!
 //! ## Damage and debugging notes
 //!
 //! - [`Tree::commit`] batches adds/updates/removals and produces coarse damage (added/removed AABBs and
 //!   old/new pairs for moved nodes). This is enough to bound a paint traversal in most UIs.
+//! - [`DamageAccumulator`] merges the damage of several commits (netting out repeated moves) for one paint per frame.
 //! - World AABBs are conservative under rotation/shear and rounded-rect clips are approximated by
 //!   their axis-aligned bounds for acceleration; precise hit-filtering is applied where cheap.
 //!
@@ -82,10 +100,13 @@
 extern crate alloc;
 
 mod damage;
+mod pixel;
+mod slots;
 mod tree;
 mod types;
 mod util;
 
-pub use damage::Damage;
-pub use tree::{Hit, QueryFilter, Tree};
-pub use types::{LocalNode, NodeFlags, NodeId};
+pub use damage::{Damage, DamageAccumulator};
+pub use pixel::{PixelDamage, PixelNode, PixelTransform, PixelTree};
+pub use tree::{Hit, QueryFilter, Tree, WorldBoundsPolicy};
+pub use types::{LocalNode, NodeFlags, NodeId, NodeSpec};
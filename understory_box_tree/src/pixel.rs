@@ -0,0 +1,571 @@
+// Copyright 2025 the Understory Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Integer-pixel box tree: exact world bounds and damage for pixel-snapped UIs.
+//!
+//! [`PixelTree`] shares node storage and hit-test ordering with [`Tree`](crate::Tree) but
+//! keeps geometry in `i64` pixels end to end. Transforms are restricted to integer scale and translation
+//! ([`PixelTransform`]), so world bounds are computed exactly and the spatial index is
+//! `i64`-backed; there is no float rounding between local geometry, the index, and damage.
+//! Clips and rotations are not supported here; use [`Tree`](crate::Tree) for those.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use kurbo::Affine;
+use understory_index::{Aabb2D, Backend, FlatVec, IndexGeneric, Key as AabbKey};
+
+use crate::slots::{Linked, Slots, stacks_above};
+use crate::tree::{Hit, QueryFilter};
+use crate::types::{NodeFlags, NodeId};
+
+/// Integer local→parent transform: a per-axis scale followed by a translation.
+///
+/// Rotation and skew cannot be represented; see [`PixelTransform::from_affine`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelTransform {
+    /// Horizontal scale factor.
+    pub scale_x: i64,
+    /// Vertical scale factor.
+    pub scale_y: i64,
+    /// Horizontal translation in parent pixels.
+    pub translate_x: i64,
+    /// Vertical translation in parent pixels.
+    pub translate_y: i64,
+}
+
+impl Default for PixelTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl PixelTransform {
+    /// The identity transform.
+    pub const IDENTITY: Self = Self {
+        scale_x: 1,
+        scale_y: 1,
+        translate_x: 0,
+        translate_y: 0,
+    };
+
+    /// A pure translation.
+    pub const fn translate(x: i64, y: i64) -> Self {
+        Self {
+            scale_x: 1,
+            scale_y: 1,
+            translate_x: x,
+            translate_y: y,
+        }
+    }
+
+    /// Convert an [`Affine`] with integer scale and translation.
+    ///
+    /// Returns `None` if the affine rotates or skews, or if any coefficient is not an
+    /// integer.
+    pub fn from_affine(affine: Affine) -> Option<Self> {
+        let [a, b, c, d, e, f] = affine.as_coeffs();
+        if b != 0.0 || c != 0.0 {
+            return None;
+        }
+        Some(Self {
+            scale_x: exact_i64(a)?,
+            scale_y: exact_i64(d)?,
+            translate_x: exact_i64(e)?,
+            translate_y: exact_i64(f)?,
+        })
+    }
+
+    /// Compose `self` (parent→world) with `child` (local→parent), giving local→world.
+    ///
+    /// Coefficients saturate at the `i64` range instead of overflowing.
+    pub const fn then(self, child: Self) -> Self {
+        Self {
+            scale_x: self.scale_x.saturating_mul(child.scale_x),
+            scale_y: self.scale_y.saturating_mul(child.scale_y),
+            translate_x: self
+                .scale_x
+                .saturating_mul(child.translate_x)
+                .saturating_add(self.translate_x),
+            translate_y: self
+                .scale_y
+                .saturating_mul(child.translate_y)
+                .saturating_add(self.translate_y),
+        }
+    }
+
+    /// Map a rectangle exactly; negative scales are normalized to min ≤ max.
+    ///
+    /// Coordinates saturate at the `i64` range instead of overflowing.
+    pub fn apply_rect(self, r: Aabb2D<i64>) -> Aabb2D<i64> {
+        let map =
+            |v: i64, scale: i64, translate: i64| v.saturating_mul(scale).saturating_add(translate);
+        let (x0, x1) = (
+            map(r.min_x, self.scale_x, self.translate_x),
+            map(r.max_x, self.scale_x, self.translate_x),
+        );
+        let (y0, y1) = (
+            map(r.min_y, self.scale_y, self.translate_y),
+            map(r.max_y, self.scale_y, self.translate_y),
+        );
+        Aabb2D::new(x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1))
+    }
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    reason = "The round trip rejects any value the cast does not preserve."
+)]
+fn exact_i64(v: f64) -> Option<i64> {
+    let i = v as i64;
+    (i as f64 == v).then_some(i)
+}
+
+/// Local geometry for a node of a [`PixelTree`].
+#[derive(Clone, Debug)]
+pub struct PixelNode {
+    /// Local bounds in pixels.
+    pub local_bounds: Aabb2D<i64>,
+    /// Local transform relative to parent space.
+    pub local_transform: PixelTransform,
+    /// Z-order within parent stacking context. Higher is drawn on top.
+    pub z_index: i32,
+    /// Visibility and picking flags.
+    pub flags: NodeFlags,
+}
+
+impl Default for PixelNode {
+    fn default() -> Self {
+        Self {
+            local_bounds: Aabb2D::new(0, 0, 0, 0),
+            local_transform: PixelTransform::IDENTITY,
+            z_index: 0,
+            flags: NodeFlags::default(),
+        }
+    }
+}
+
+/// A batched set of changes derived from [`PixelTree::commit`].
+#[derive(Clone, Debug, Default)]
+pub struct PixelDamage {
+    /// World-space pixel rectangles that should be repainted.
+    pub dirty_rects: Vec<Aabb2D<i64>>,
+}
+
+#[derive(Clone, Debug)]
+struct PixelSlot {
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    local: PixelNode,
+    world_transform: PixelTransform,
+    world_bounds: Option<Aabb2D<i64>>,
+    index_key: Option<AabbKey>,
+    // Set when the node's world bounds need recomputing at the next commit.
+    dirty: bool,
+}
+
+impl Linked for PixelSlot {
+    fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    fn set_parent(&mut self, parent: Option<NodeId>) {
+        self.parent = parent;
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<NodeId> {
+        &mut self.children
+    }
+
+    fn flags(&self) -> NodeFlags {
+        self.local.flags
+    }
+}
+
+/// Box tree with integer-pixel geometry and an `i64` spatial index.
+///
+/// Like [`Tree`](crate::Tree), changes are batched until [`PixelTree::commit`], which
+/// recomputes world bounds exactly and reports pixel damage.
+pub struct PixelTree<B: Backend<i64> = FlatVec<i64>> {
+    nodes: Slots<PixelSlot>,
+    // Nodes marked dirty since the last commit; may contain stale ids.
+    dirty: Vec<NodeId>,
+    // World bounds of nodes removed since the last commit.
+    removed: Vec<Aabb2D<i64>>,
+    index: IndexGeneric<i64, NodeId, B>,
+}
+
+impl<B: Backend<i64> + core::fmt::Debug> core::fmt::Debug for PixelTree<B> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PixelTree")
+            .field("nodes_total", &self.nodes.len())
+            .field("nodes_alive", &self.nodes.iter().count())
+            .field("free_list", &self.nodes.free_len())
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B: Backend<i64> + Default> Default for PixelTree<B> {
+    fn default() -> Self {
+        Self::with_backend(B::default())
+    }
+}
+
+impl PixelTree {
+    /// Create a new empty tree using the default backend (`FlatVec<i64>`).
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<B: Backend<i64>> PixelTree<B> {
+    /// Create a new tree with a specific backend.
+    pub fn with_backend(backend: B) -> Self {
+        Self {
+            nodes: Slots::default(),
+            dirty: Vec::new(),
+            removed: Vec::new(),
+            index: IndexGeneric::with_backend(backend),
+        }
+    }
+
+    /// Insert a new node as a child of `parent` (or as a root if `None`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` is stale, as [`Tree::insert`](crate::Tree::insert) does.
+    pub fn insert(&mut self, parent: Option<NodeId>, local: PixelNode) -> NodeId {
+        let id = self.nodes.insert(PixelSlot {
+            parent: None,
+            children: Vec::new(),
+            local,
+            world_transform: PixelTransform::IDENTITY,
+            world_bounds: None,
+            index_key: None,
+            dirty: true,
+        });
+        self.dirty.push(id);
+        if let Some(p) = parent {
+            self.nodes.link(id, p);
+        }
+        id
+    }
+
+    /// Remove a node (and its subtree) from the tree.
+    pub fn remove(&mut self, id: NodeId) {
+        if !self.is_alive(id) {
+            return;
+        }
+        self.nodes.unlink(id);
+        for child in self.nodes.node(id).children.clone() {
+            self.remove(child);
+        }
+        let Some(slot) = self.nodes.take(id) else {
+            return;
+        };
+        if let Some(key) = slot.index_key {
+            self.index.remove(key);
+        }
+        if let Some(wb) = slot.world_bounds {
+            self.removed.push(wb);
+        }
+    }
+
+    /// Returns true if `id` refers to a live node.
+    pub fn is_alive(&self, id: NodeId) -> bool {
+        self.nodes.is_alive(id)
+    }
+
+    /// Update the local transform.
+    pub fn set_local_transform(&mut self, id: NodeId, tf: PixelTransform) {
+        if let Some(n) = self.nodes.get_mut(id) {
+            n.local.local_transform = tf;
+            self.mark_dirty(id);
+        }
+    }
+
+    /// Update the local bounds.
+    pub fn set_local_bounds(&mut self, id: NodeId, bounds: Aabb2D<i64>) {
+        if let Some(n) = self.nodes.get_mut(id) {
+            n.local.local_bounds = bounds;
+            self.mark_dirty(id);
+        }
+    }
+
+    /// Update the node flags.
+    pub fn set_flags(&mut self, id: NodeId, flags: NodeFlags) {
+        if let Some(n) = self.nodes.get_mut(id) {
+            n.local.flags = flags;
+        }
+    }
+
+    /// Returns the exact world-space bounds of a live node as of the last commit.
+    pub fn world_bounds(&self, id: NodeId) -> Option<Aabb2D<i64>> {
+        self.nodes.get(id)?.world_bounds
+    }
+
+    /// Recompute world bounds, synchronize the spatial index, and report damage.
+    ///
+    /// Only nodes changed since the last commit, and descendants whose world transform
+    /// changed with them, are revisited. Damage holds the old and new bounds of every
+    /// node whose world bounds changed and the last bounds of removed nodes; empty
+    /// rectangles are omitted.
+    pub fn commit(&mut self) -> PixelDamage {
+        let mut damage = PixelDamage {
+            dirty_rects: core::mem::take(&mut self.removed),
+        };
+        for id in core::mem::take(&mut self.dirty) {
+            // Skip stale ids, subtrees already refreshed, and nodes a dirty ancestor will refresh.
+            if !self.nodes.get(id).is_some_and(|n| n.dirty) || self.has_dirty_ancestor(id) {
+                continue;
+            }
+            let parent_tf = self
+                .nodes
+                .node(id)
+                .parent
+                .map_or(PixelTransform::IDENTITY, |p| {
+                    self.nodes.node(p).world_transform
+                });
+            self.update_subtree(id, parent_tf, &mut damage);
+        }
+        damage
+            .dirty_rects
+            .retain(|r| r.max_x > r.min_x && r.max_y > r.min_y);
+        let _ = self.index.commit();
+        damage
+    }
+
+    /// Hit test a world-space pixel. Returns the topmost node.
+    ///
+    /// Ordering matches [`Tree::hit_test_point`](crate::Tree::hit_test_point): higher
+    /// z-index, then deeper node, then newer [`NodeId`].
+    pub fn hit_test_point(&self, x: i64, y: i64, filter: QueryFilter) -> Option<Hit> {
+        let mut best: Option<(NodeId, i32, usize)> = None;
+        for (_, id) in self.index.query_point(x, y) {
+            if !self.matches(id, filter) {
+                continue;
+            }
+            let cand = (id, self.nodes.node(id).local.z_index, self.nodes.depth(id));
+            if best.is_none_or(|b| stacks_above(cand, b)) {
+                best = Some(cand);
+            }
+        }
+        best.map(|(node, ..)| Hit {
+            node,
+            path: self.nodes.path_to_root(node),
+        })
+    }
+
+    /// Nodes whose world bounds intersect a world-space pixel rectangle.
+    pub fn intersect_rect(&self, rect: Aabb2D<i64>, filter: QueryFilter) -> Vec<NodeId> {
+        self.index
+            .query_rect(rect)
+            .map(|(_, id)| id)
+            .filter(|&id| self.matches(id, filter))
+            .collect()
+    }
+
+    fn matches(&self, id: NodeId, filter: QueryFilter) -> bool {
+        let Some(node) = self.nodes.get(id) else {
+            return false;
+        };
+        filter.matches(node.local.flags)
+            && !(filter.respect_ancestor_visibility && self.nodes.has_hidden_ancestor(id))
+    }
+
+    fn mark_dirty(&mut self, id: NodeId) {
+        let n = self.nodes.node_mut(id);
+        if !core::mem::replace(&mut n.dirty, true) {
+            self.dirty.push(id);
+        }
+    }
+
+    fn has_dirty_ancestor(&self, id: NodeId) -> bool {
+        let mut cur = self.nodes.node(id).parent;
+        while let Some(p) = cur {
+            let n = self.nodes.node(p);
+            if n.dirty {
+                return true;
+            }
+            cur = n.parent;
+        }
+        false
+    }
+
+    /// Recompute world data below `root`, descending only where it can change.
+    fn update_subtree(
+        &mut self,
+        root: NodeId,
+        parent_tf: PixelTransform,
+        damage: &mut PixelDamage,
+    ) {
+        let mut stack = vec![(root, parent_tf)];
+        while let Some((id, parent_tf)) = stack.pop() {
+            let node = self.nodes.node_mut(id);
+            node.dirty = false;
+            let tf = parent_tf.then(node.local.local_transform);
+            let tf_changed = core::mem::replace(&mut node.world_transform, tf) != tf;
+            let new = tf.apply_rect(node.local.local_bounds);
+            let old = node.world_bounds.replace(new);
+            let key = node.index_key;
+            let children = node.children.clone();
+            for child in children {
+                if tf_changed || self.nodes.node(child).dirty {
+                    stack.push((child, tf));
+                }
+            }
+            if old == Some(new) {
+                continue;
+            }
+            damage.dirty_rects.extend(old);
+            damage.dirty_rects.push(new);
+            match key {
+                Some(key) => self.index.update(key, new),
+                None => {
+                    let key = self.index.insert(new, id);
+                    self.nodes.node_mut(id).index_key = Some(key);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f64::consts::FRAC_PI_4;
+
+    #[test]
+    #[should_panic(expected = "dangling NodeId")]
+    fn insert_under_stale_parent_panics() {
+        let mut tree = PixelTree::new();
+        let parent = tree.insert(None, PixelNode::default());
+        tree.remove(parent);
+        let _ = tree.insert(Some(parent), PixelNode::default());
+    }
+
+    #[test]
+    fn translated_integer_nodes_are_pixel_exact() {
+        let mut tree = PixelTree::new();
+        let root = tree.insert(
+            None,
+            PixelNode {
+                local_bounds: Aabb2D::new(0, 0, 1000, 1000),
+                local_transform: PixelTransform::translate(3, 7),
+                ..Default::default()
+            },
+        );
+        let child = tree.insert(
+            Some(root),
+            PixelNode {
+                local_bounds: Aabb2D::new(0, 0, 33, 17),
+                local_transform: PixelTransform::translate(101, 203),
+                z_index: 1,
+                ..Default::default()
+            },
+        );
+        let _ = tree.commit();
+        assert_eq!(
+            tree.world_bounds(child),
+            Some(Aabb2D::new(104, 210, 137, 227))
+        );
+
+        // Move by one pixel: damage is exactly the old and new bounds.
+        tree.set_local_transform(child, PixelTransform::translate(102, 203));
+        let dmg = tree.commit();
+        assert_eq!(
+            dmg.dirty_rects,
+            vec![
+                Aabb2D::new(104, 210, 137, 227),
+                Aabb2D::new(105, 210, 138, 227)
+            ]
+        );
+        assert!(tree.commit().dirty_rects.is_empty());
+
+        let f = QueryFilter::new();
+        let hit = tree.hit_test_point(105, 210, f).unwrap();
+        assert_eq!((hit.node, hit.path), (child, vec![root, child]));
+        assert_eq!(tree.hit_test_point(104, 210, f).map(|h| h.node), Some(root));
+
+        // Scale composes exactly too.
+        tree.set_local_transform(
+            root,
+            PixelTransform {
+                scale_x: 2,
+                scale_y: 2,
+                translate_x: 0,
+                translate_y: 0,
+            },
+        );
+        let _ = tree.commit();
+        assert_eq!(
+            tree.world_bounds(child),
+            Some(Aabb2D::new(204, 406, 270, 440))
+        );
+
+        // Removal damages the last bounds.
+        tree.remove(child);
+        assert_eq!(
+            tree.commit().dirty_rects,
+            vec![Aabb2D::new(204, 406, 270, 440)]
+        );
+        assert_eq!(
+            tree.intersect_rect(Aabb2D::new(204, 406, 270, 440), f),
+            vec![root]
+        );
+    }
+
+    #[test]
+    fn from_affine_rejects_rotation_and_fractions() {
+        assert_eq!(
+            PixelTransform::from_affine(Affine::translate((4.0, -2.0))),
+            Some(PixelTransform::translate(4, -2))
+        );
+        assert!(PixelTransform::from_affine(Affine::rotate(FRAC_PI_4)).is_none());
+        assert!(PixelTransform::from_affine(Affine::translate((0.5, 0.0))).is_none());
+    }
+
+    #[test]
+    fn commit_refreshes_dirty_subtrees_only() {
+        let mut tree = PixelTree::new();
+        let node = |x| PixelNode {
+            local_bounds: Aabb2D::new(0, 0, 10, 10),
+            local_transform: PixelTransform::translate(x, 0),
+            ..Default::default()
+        };
+        let a = tree.insert(None, node(0));
+        let child = tree.insert(Some(a), node(5));
+        let b = tree.insert(None, node(100));
+        let _ = tree.commit();
+
+        // The child is marked before its parent; both changes land in one pass.
+        tree.set_local_bounds(child, Aabb2D::new(0, 0, 20, 10));
+        tree.set_local_transform(a, PixelTransform::translate(1, 0));
+        let dmg = tree.commit();
+        assert_eq!(tree.world_bounds(a), Some(Aabb2D::new(1, 0, 11, 10)));
+        assert_eq!(tree.world_bounds(child), Some(Aabb2D::new(6, 0, 26, 10)));
+        assert_eq!(tree.world_bounds(b), Some(Aabb2D::new(100, 0, 110, 10)));
+        assert_eq!(dmg.dirty_rects.len(), 4);
+
+        // A parent bounds change leaves clean children alone.
+        tree.set_local_bounds(a, Aabb2D::new(0, 0, 30, 30));
+        assert_eq!(tree.commit().dirty_rects.len(), 2);
+    }
+
+    #[test]
+    fn transforms_saturate_instead_of_overflowing() {
+        let big = PixelTransform {
+            scale_x: i64::MAX,
+            scale_y: 2,
+            translate_x: i64::MAX,
+            translate_y: i64::MIN,
+        };
+        let tf = big.then(big);
+        assert_eq!((tf.scale_x, tf.translate_x), (i64::MAX, i64::MAX));
+        assert_eq!(tf.translate_y, i64::MIN);
+        assert_eq!(
+            big.apply_rect(Aabb2D::new(-4, -4, 4, 4)),
+            Aabb2D::new(-1, i64::MIN, i64::MAX, i64::MIN + 8)
+        );
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright 2025 the Understory Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Generational node storage shared by [`Tree`](crate::Tree) and [`PixelTree`](crate::PixelTree).
+
+use alloc::vec::Vec;
+
+use crate::types::{NodeFlags, NodeId};
+
+/// Hierarchy links and flags of a stored node.
+pub(crate) trait Linked {
+    fn parent(&self) -> Option<NodeId>;
+    fn set_parent(&mut self, parent: Option<NodeId>);
+    fn children_mut(&mut self) -> &mut Vec<NodeId>;
+    fn flags(&self) -> NodeFlags;
+}
+
+#[derive(Clone, Debug)]
+struct Slot<N> {
+    // Generation of the current (or last) occupant; kept while the slot is free.
+    generation: u32,
+    node: Option<N>,
+}
+
+/// Slot storage handing out generational [`NodeId`]s, with parent/child links.
+#[derive(Clone, Debug)]
+pub(crate) struct Slots<N> {
+    slots: Vec<Slot<N>>,
+    free_list: Vec<usize>,
+}
+
+impl<N> Default for Slots<N> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free_list: Vec::new(),
+        }
+    }
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    reason = "NodeId uses 32-bit indices by design."
+)]
+const fn id_at(idx: usize, generation: u32) -> NodeId {
+    NodeId::new(idx as u32, generation)
+}
+
+impl<N> Slots<N> {
+    /// Number of slots, live or free.
+    pub(crate) fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Number of free slots awaiting reuse.
+    pub(crate) fn free_len(&self) -> usize {
+        self.free_list.len()
+    }
+
+    /// Store `node` in a free slot (bumping its generation) or a new one.
+    pub(crate) fn insert(&mut self, node: N) -> NodeId {
+        if let Some(idx) = self.free_list.pop() {
+            let slot = &mut self.slots[idx];
+            slot.generation = slot.generation.saturating_add(1);
+            slot.node = Some(node);
+            id_at(idx, slot.generation)
+        } else {
+            self.slots.push(Slot {
+                generation: 1,
+                node: Some(node),
+            });
+            id_at(self.slots.len() - 1, 1)
+        }
+    }
+
+    /// Free the slot of a live `id` and return its node.
+    pub(crate) fn take(&mut self, id: NodeId) -> Option<N> {
+        self.get(id)?;
+        self.free_list.push(id.idx());
+        self.slots[id.idx()].node.take()
+    }
+
+    /// Free every slot, returning the live nodes in slot order.
+    ///
+    /// Generations are kept, so ids handed out afterwards never collide with earlier ones.
+    pub(crate) fn take_all(&mut self) -> Vec<(NodeId, N)> {
+        let mut out = Vec::new();
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            if let Some(n) = slot.node.take() {
+                out.push((id_at(i, slot.generation), n));
+                self.free_list.push(i);
+            }
+        }
+        out
+    }
+
+    pub(crate) fn is_alive(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub(crate) fn get(&self, id: NodeId) -> Option<&N> {
+        let slot = self.slots.get(id.idx())?;
+        if slot.generation != id.1 {
+            return None;
+        }
+        slot.node.as_ref()
+    }
+
+    pub(crate) fn get_mut(&mut self, id: NodeId) -> Option<&mut N> {
+        let slot = self.slots.get_mut(id.idx())?;
+        if slot.generation != id.1 {
+            return None;
+        }
+        slot.node.as_mut()
+    }
+
+    /// Access a live node; panics if `id` is stale.
+    pub(crate) fn node(&self, id: NodeId) -> &N {
+        self.get(id).expect("dangling NodeId")
+    }
+
+    /// Access a live node mutably; panics if `id` is stale.
+    pub(crate) fn node_mut(&mut self, id: NodeId) -> &mut N {
+        self.get_mut(id).expect("dangling NodeId")
+    }
+
+    /// Iterate live nodes in slot order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (NodeId, &N)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| Some((id_at(i, s.generation), s.node.as_ref()?)))
+    }
+}
+
+impl<N: Linked> Slots<N> {
+    /// Live nodes without a parent, in slot order.
+    pub(crate) fn roots(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.iter()
+            .filter(|(_, n)| n.parent().is_none())
+            .map(|(id, _)| id)
+    }
+
+    /// Append `id` to the children of `parent`.
+    pub(crate) fn link(&mut self, id: NodeId, parent: NodeId) {
+        self.node_mut(parent).children_mut().push(id);
+        self.node_mut(id).set_parent(Some(parent));
+    }
+
+    /// Detach `id` from its parent, if any.
+    pub(crate) fn unlink(&mut self, id: NodeId) {
+        if let Some(parent) = self.node(id).parent() {
+            self.node_mut(parent).children_mut().retain(|c| *c != id);
+            self.node_mut(id).set_parent(None);
+        }
+    }
+
+    /// Depth of a live node (roots have depth 1), or 0 if `id` is stale.
+    pub(crate) fn depth(&self, mut id: NodeId) -> usize {
+        if !self.is_alive(id) {
+            return 0;
+        }
+        let mut d = 1;
+        while let Some(p) = self.node(id).parent() {
+            d += 1;
+            id = p;
+        }
+        d
+    }
+
+    /// Path from the root to `id` (inclusive).
+    pub(crate) fn path_to_root(&self, mut id: NodeId) -> Vec<NodeId> {
+        let mut out = Vec::new();
+        loop {
+            out.push(id);
+            match self.node(id).parent() {
+                Some(p) => id = p,
+                None => break,
+            }
+        }
+        out.reverse();
+        out
+    }
+
+    /// Returns true if any ancestor of `id` lacks [`NodeFlags::VISIBLE`].
+    pub(crate) fn has_hidden_ancestor(&self, id: NodeId) -> bool {
+        let mut cur = self.node(id).parent();
+        while let Some(p) = cur {
+            let n = self.node(p);
+            if !n.flags().contains(NodeFlags::VISIBLE) {
+                return true;
+            }
+            cur = n.parent();
+        }
+        false
+    }
+}
+
+/// Hit-test stacking order: higher z-index, then deeper node, then [newer](NodeId#newer) id.
+///
+/// Each side is `(id, z_index, depth)`; returns true if `a` is drawn above `b`.
+pub(crate) fn stacks_above(a: (NodeId, i32, usize), b: (NodeId, i32, usize)) -> bool {
+    let ((id_a, z_a, depth_a), (id_b, z_b, depth_b)) = (a, b);
+    z_a > z_b
+        || (z_a == z_b && (depth_a > depth_b || (depth_a == depth_b && id_is_newer(id_a, id_b))))
+}
+
+/// Returns true if `a` is [newer](NodeId#newer) than `b`.
+#[inline]
+pub(crate) fn id_is_newer(a: NodeId, b: NodeId) -> bool {
+    (a.1 > b.1) || (a.1 == b.1 && a.0 > b.0)
+}
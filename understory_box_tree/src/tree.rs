@@ -3,13 +3,21 @@
 
 //! Core tree implementation: structure, updates, queries.
 
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::vec::Vec;
 use kurbo::{Affine, Point, Rect, RoundedRect};
-use understory_index::{Aabb2D, Backend, FlatVec, IndexGeneric, Key as AabbKey};
+use understory_index::{
+    Aabb2D, AnyBackend, Backend, BackendKind, FlatVec, Index, IndexGeneric, Key as AabbKey,
+    QuadtreeF64, RTreeF64,
+};
 
 use crate::damage::Damage;
-use crate::types::{LocalNode, NodeFlags, NodeId};
-use crate::util::{rect_to_aabb, transform_rect_bbox};
+use crate::slots::{Linked, Slots, stacks_above};
+use crate::types::{LocalNode, NodeFlags, NodeId, NodeSpec};
+use crate::util::{
+    rect_to_aabb, rounded_rect_contains, rounded_rect_intersects_rect, transform_rect_bbox,
+};
 
 /// Top-level region tree.
 ///
@@ -17,35 +25,62 @@ use crate::util::{rect_to_aabb, transform_rect_bbox};
 /// defaults to a flat-vector backend (`FlatVec<f64>`), so most callers can
 /// simply use [`Tree`] without specifying `B`. Advanced callers can override
 /// `B` to use an R-tree or BVH backend from `understory_index`.
+///
+/// Each [layer](LocalNode::layer) gets its own index. Layer 0 uses the backend the tree was
+/// created with; other layers are created on first use by the tree's layer backend factory
+/// (see [`Tree::with_layer_backends`]). Without a factory, every layer shares the layer-0
+/// index.
 pub struct Tree<B: Backend<f64> = FlatVec<f64>> {
-    nodes: Vec<Option<Node>>, // slots
-    generations: Vec<u32>,    // last generation per slot (persists across frees)
-    pub(crate) free_list: Vec<usize>,
+    nodes: Slots<Node>,
+    removed: Vec<(NodeId, Rect)>, // committed nodes removed since the last commit
     pub(crate) epoch: u64,
     pub(crate) index: IndexGeneric<f64, NodeId, B>,
+    layers: BTreeMap<u8, IndexGeneric<f64, NodeId, B>>,
+    /// Builds the index of a new layer, or of every layer after the world bounds grow.
+    new_backend: Option<BackendFactory<B>>,
+    world_bounds: Option<(Rect, WorldBoundsPolicy)>,
+    index_sync: bool,
+}
+
+/// Builds an empty backend, given the tree's world bounds if it has any.
+type BackendFactory<B> = Box<dyn Fn(Option<Rect>) -> B + Send + Sync>;
+
+/// What a tree with [world bounds](Tree::with_world_bounds) does with nodes outside them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WorldBoundsPolicy {
+    /// Grow the bounds to cover the node and rebuild the spatial indexes.
+    ///
+    /// The bounds grow to twice the size needed, so steady outward drift only rebuilds
+    /// a logarithmic number of times.
+    Expand,
+    /// Leave a node lying entirely outside the bounds out of the spatial indexes: it is
+    /// not reported by hit tests or rect queries until it overlaps them again. Nodes that
+    /// straddle the bounds stay indexed.
+    Reject,
 }
 
 impl<B: Backend<f64> + core::fmt::Debug> core::fmt::Debug for Tree<B> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let total = self.nodes.len();
-        let alive = self.nodes.iter().filter(|n| n.is_some()).count();
-        let free = self.free_list.len();
+        let alive = self.nodes.iter().count();
+        let free = self.nodes.free_len();
         f.debug_struct("Tree")
             .field("nodes_total", &total)
             .field("nodes_alive", &alive)
             .field("free_list", &free)
             .field("epoch", &self.epoch)
             .field("index", &self.index)
+            .field("layers", &self.layers)
             .finish_non_exhaustive()
     }
 }
 
 impl<B> Default for Tree<B>
 where
-    B: Backend<f64> + Default,
+    B: Backend<f64> + Default + 'static,
 {
     fn default() -> Self {
-        Self::with_backend(B::default())
+        Self::with_backend(B::default()).with_layer_backends(B::default)
     }
 }
 
@@ -65,12 +100,25 @@ pub struct Hit {
 pub struct QueryFilter {
     /// Bitfield of required node flags. Only nodes containing all these flags will be included.
     pub required_flags: NodeFlags,
+    /// Also reject nodes that have an ancestor without [`NodeFlags::VISIBLE`].
+    ///
+    /// A node's own flags are checked by `required_flags`; this walks its ancestors,
+    /// so a visible child of a hidden parent is treated as hidden.
+    pub respect_ancestor_visibility: bool,
+    /// In [`Tree::intersect_rect`], test the query rect against each node's own
+    /// (rounded) clip shape instead of only its clipped world AABB.
+    ///
+    /// This rejects nodes whose AABB overlaps the query only in a clipped-away
+    /// rounded corner. Nodes without a clip are unaffected.
+    pub precise_clip: bool,
 }
 
 impl Default for QueryFilter {
     fn default() -> Self {
         Self {
             required_flags: NodeFlags::empty(),
+            respect_ancestor_visibility: false,
+            precise_clip: false,
         }
     }
 }
@@ -99,7 +147,22 @@ impl QueryFilter {
         self
     }
 
+    /// Reject nodes that have any ancestor lacking [`NodeFlags::VISIBLE`].
+    pub fn respect_ancestor_visibility(mut self) -> Self {
+        self.respect_ancestor_visibility = true;
+        self
+    }
+
+    /// Test rect queries against rounded clip shapes rather than AABBs.
+    pub fn precise_clip(mut self) -> Self {
+        self.precise_clip = true;
+        self
+    }
+
     /// Check if a node's flags satisfy this filter.
+    ///
+    /// This only inspects the node's own flags; ancestor visibility (see
+    /// [`QueryFilter::respect_ancestor_visibility`]) is applied by the tree queries.
     pub fn matches(&self, node_flags: NodeFlags) -> bool {
         node_flags.contains(self.required_flags)
     }
@@ -123,19 +186,20 @@ struct Dirty {
 
 #[derive(Clone, Debug)]
 pub(crate) struct Node {
-    generation: u32,
     parent: Option<NodeId>,
     children: Vec<NodeId>,
     local: LocalNode,
     world: WorldNode,
     dirty: Dirty,
+    committed: bool,
+    // Key in the index of `index_layer`, which may lag `local.layer` until the next sync.
     index_key: Option<AabbKey>,
+    index_layer: u8,
 }
 
 impl Node {
-    fn new(generation: u32, local: LocalNode) -> Self {
+    fn new(local: LocalNode) -> Self {
         Self {
-            generation,
             parent: None,
             children: Vec::new(),
             local,
@@ -147,33 +211,221 @@ impl Node {
                 z: true,
                 index: true,
             },
+            committed: false,
             index_key: None,
+            index_layer: 0,
         }
     }
 }
 
+impl Linked for Node {
+    fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    fn set_parent(&mut self, parent: Option<NodeId>) {
+        self.parent = parent;
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<NodeId> {
+        &mut self.children
+    }
+
+    fn flags(&self) -> NodeFlags {
+        self.local.flags
+    }
+}
+
 impl Tree {
     /// Create a new empty tree using the default backend (`FlatVec<f64>`).
     pub fn new() -> Self {
+        Self::from_parts(
+            IndexGeneric::new(),
+            Some(Box::new(|_| FlatVec::default())),
+            None,
+        )
+    }
+}
+
+impl<B: Backend<f64>> Tree<B> {
+    fn from_parts(
+        index: IndexGeneric<f64, NodeId, B>,
+        new_backend: Option<BackendFactory<B>>,
+        world_bounds: Option<(Rect, WorldBoundsPolicy)>,
+    ) -> Self {
         Self {
-            nodes: Vec::new(),
-            generations: Vec::new(),
-            free_list: Vec::new(),
+            nodes: Slots::default(),
+            removed: Vec::new(),
             epoch: 0,
-            index: IndexGeneric::new(),
+            index,
+            layers: BTreeMap::new(),
+            new_backend,
+            world_bounds,
+            index_sync: true,
         }
     }
-}
 
-impl<B: Backend<f64>> Tree<B> {
     /// Create a new tree with a specific backend.
+    ///
+    /// `backend` indexes layer 0. Nodes on other [layers](LocalNode::layer) share it
+    /// unless a factory for per-layer indexes is set with [`Tree::with_layer_backends`].
     pub fn with_backend(backend: B) -> Self {
-        Self {
-            nodes: Vec::new(),
-            generations: Vec::new(),
-            free_list: Vec::new(),
-            epoch: 0,
-            index: IndexGeneric::with_backend(backend),
+        Self::from_parts(IndexGeneric::with_backend(backend), None, None)
+    }
+
+    /// Build the indexes of layers other than 0 with `new_backend`.
+    ///
+    /// Each layer's index is created on first use. This lets backends configured at
+    /// construction (or without a [`Default`] impl) be used on every layer.
+    ///
+    /// Call this before inserting nodes: nodes already indexed on a shared layer-0 index
+    /// are not moved.
+    pub fn with_layer_backends(
+        mut self,
+        new_backend: impl Fn() -> B + Send + Sync + 'static,
+    ) -> Self
+    where
+        B: 'static,
+    {
+        debug_assert!(
+            self.nodes.iter().all(|(_, n)| n.index_layer == 0),
+            "set layer backends before indexing nodes on other layers"
+        );
+        self.new_backend = Some(Box::new(move |_| new_backend()));
+        self
+    }
+}
+
+impl Tree<AnyBackend<f64>> {
+    /// Rebuild every layer's spatial index on a backend of the given kind.
+    ///
+    /// Index entries are carried over as is, so every [`NodeId`] stays valid and queries
+    /// return the same results; layers created later use the new kind too. This is O(n) in
+    /// the number of nodes, so switch sparingly (e.g. once a scene outgrows `FlatVec`).
+    pub fn switch_index_backend(&mut self, kind: BackendKind) {
+        if self.new_backend.is_some() {
+            self.new_backend = Some(Box::new(move |_| AnyBackend::new(kind)));
+        }
+        let _ = self.index.replace_backend(AnyBackend::new(kind));
+        for index in self.layers.values_mut() {
+            let _ = index.replace_backend(AnyBackend::new(kind));
+        }
+    }
+}
+
+impl Tree<QuadtreeF64> {
+    /// Maximum quadtree depth used by [`Tree::with_world_bounds`].
+    pub const QUADTREE_MAX_DEPTH: usize = 8;
+
+    /// Items per quadtree node before it splits, used by [`Tree::with_world_bounds`].
+    pub const QUADTREE_CAPACITY: usize = 8;
+
+    /// Create an empty tree whose spatial indexes are quadtrees over `bounds`.
+    ///
+    /// Nodes whose world bounds leave `bounds` are handled according to `policy`.
+    /// Every layer's index covers the same bounds.
+    pub fn with_world_bounds(bounds: Rect, policy: WorldBoundsPolicy) -> Self {
+        let new_backend: fn(Option<Rect>) -> QuadtreeF64 = |bounds| {
+            QuadtreeF64::new(
+                rect_to_aabb(bounds.unwrap_or_default()),
+                Self::QUADTREE_MAX_DEPTH,
+                Self::QUADTREE_CAPACITY,
+            )
+        };
+        Self::from_parts(
+            IndexGeneric::with_backend(new_backend(Some(bounds))),
+            Some(Box::new(new_backend)),
+            Some((bounds, policy)),
+        )
+    }
+}
+
+impl<B: Backend<f64>> Tree<B> {
+    /// The current world bounds of a tree made by [`Tree::with_world_bounds`], or `None`.
+    ///
+    /// Under [`WorldBoundsPolicy::Expand`] this grows as nodes move outside.
+    pub fn world_extent(&self) -> Option<Rect> {
+        self.world_bounds.map(|(bounds, _)| bounds)
+    }
+
+    /// The index holding `layer`'s nodes; it may hold other layers' nodes too.
+    fn layer_index(&self, layer: u8) -> Option<&IndexGeneric<f64, NodeId, B>> {
+        if layer == 0 || self.new_backend.is_none() {
+            Some(&self.index)
+        } else {
+            self.layers.get(&layer)
+        }
+    }
+
+    fn layer_index_mut(&mut self, layer: u8) -> &mut IndexGeneric<f64, NodeId, B> {
+        match self.new_backend.as_ref() {
+            Some(new_backend) if layer != 0 => {
+                let bounds = self.world_bounds.map(|(bounds, _)| bounds);
+                self.layers
+                    .entry(layer)
+                    .or_insert_with(|| IndexGeneric::with_backend(new_backend(bounds)))
+            }
+            _ => &mut self.index,
+        }
+    }
+
+    fn all_indexes(&self) -> impl Iterator<Item = &IndexGeneric<f64, NodeId, B>> {
+        core::iter::once(&self.index).chain(self.layers.values())
+    }
+
+    /// Insert, update, or move a node's index entry to match `aabb` on its current layer.
+    fn sync_index_entry(&mut self, id: NodeId, aabb: Aabb2D<f64>) {
+        if let Some((bounds, policy)) = self.world_bounds {
+            let r = Rect::new(aabb.min_x, aabb.min_y, aabb.max_x, aabb.max_y);
+            match policy {
+                WorldBoundsPolicy::Expand if !bounds.contains_rect(r) => {
+                    self.grow_world_bounds(bounds.union(r));
+                }
+                WorldBoundsPolicy::Reject if rect_to_aabb(bounds).intersect(&aabb).is_empty() => {
+                    self.drop_index_entry(id);
+                    return;
+                }
+                _ => {}
+            }
+        }
+        let node = self.node(id);
+        let layer = node.local.layer;
+        match node.index_key {
+            Some(key) if node.index_layer == layer => self.layer_index_mut(layer).update(key, aabb),
+            stale => {
+                if let Some(key) = stale {
+                    let old = node.index_layer;
+                    self.layer_index_mut(old).remove(key);
+                }
+                let key = self.layer_index_mut(layer).insert(aabb, id);
+                let node = self.node_mut(id);
+                node.index_key = Some(key);
+                node.index_layer = layer;
+            }
+        }
+    }
+
+    fn drop_index_entry(&mut self, id: NodeId) {
+        let node = self.node(id);
+        if let Some(key) = node.index_key {
+            let layer = node.index_layer;
+            self.layer_index_mut(layer).remove(key);
+            self.node_mut(id).index_key = None;
+        }
+    }
+
+    /// Grow the world bounds to twice the size of `needed` and rebuild every index on them.
+    fn grow_world_bounds(&mut self, needed: Rect) {
+        let grown = needed.inflate(needed.width() / 2.0, needed.height() / 2.0);
+        if let Some((bounds, _)) = self.world_bounds.as_mut() {
+            *bounds = grown;
+        }
+        let Some(new_backend) = self.new_backend.as_ref() else {
+            return;
+        };
+        let _ = self.index.replace_backend(new_backend(Some(grown)));
+        for index in self.layers.values_mut() {
+            let _ = index.replace_backend(new_backend(Some(grown)));
         }
     }
 
@@ -197,49 +449,80 @@ impl<B: Backend<f64>> Tree<B> {
 
     /// Insert a new node as a child of `parent` (or as a root if `None`).
     pub fn insert(&mut self, parent: Option<NodeId>, local: LocalNode) -> NodeId {
-        let (idx, generation) = if let Some(idx) = self.free_list.pop() {
-            let generation = self.generations[idx].saturating_add(1);
-            self.generations[idx] = generation;
-            self.nodes[idx] = Some(Node::new(generation, local));
-            #[allow(
-                clippy::cast_possible_truncation,
-                reason = "NodeId uses 32-bit indices by design."
-            )]
-            (idx as u32, generation)
-        } else {
-            let generation = 1_u32;
-            self.nodes.push(Some(Node::new(generation, local)));
-            self.generations.push(generation);
-            #[allow(
-                clippy::cast_possible_truncation,
-                reason = "NodeId uses 32-bit indices by design."
-            )]
-            ((self.nodes.len() - 1) as u32, generation)
-        };
-        let id = NodeId::new(idx, generation);
+        let id = self.nodes.insert(Node::new(local));
         if let Some(p) = parent {
-            self.link_parent(id, p);
+            self.nodes.link(id, p);
         }
         id
     }
 
+    /// Insert a batch of nodes described by `spec` and commit.
+    ///
+    /// Each [`NodeSpec::parent`] refers to an earlier position in `spec`. Returns the
+    /// new [`NodeId`]s in spec order, so `ids[i]` is the node built from `spec[i]`.
+    /// The damage from the implicit [`Tree::commit`] is discarded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a parent index does not refer to an earlier entry.
+    pub fn build(&mut self, spec: &[NodeSpec]) -> Vec<NodeId> {
+        let mut ids: Vec<NodeId> = Vec::with_capacity(spec.len());
+        for (i, s) in spec.iter().enumerate() {
+            let parent = s.parent.map(|p| {
+                assert!(p < i, "NodeSpec parent must refer to an earlier entry");
+                ids[p]
+            });
+            ids.push(self.insert(parent, s.local.clone()));
+        }
+        let _ = self.commit();
+        ids
+    }
+
     /// Remove a node (and its subtree) from the tree.
     pub fn remove(&mut self, id: NodeId) {
         if !self.is_alive(id) {
             return;
         }
-        if let Some(parent) = self.node(id).parent {
-            self.unlink_parent(id, parent);
-        }
+        self.nodes.unlink(id);
         let children = self.node(id).children.clone();
         for child in children {
             self.remove(child);
         }
-        if let Some(key) = self.node(id).index_key {
-            self.index.remove(key);
+        let node = self.node(id);
+        let (committed, bounds) = (node.committed, node.world.world_bounds);
+        if let Some(key) = node.index_key {
+            let layer = node.index_layer;
+            self.layer_index_mut(layer).remove(key);
+        }
+        if committed {
+            self.removed.push((id, bounds));
+        }
+        let _ = self.nodes.take(id);
+    }
+
+    /// Remove every node and return the damage needed to repaint the cleared scene.
+    ///
+    /// Each committed node is reported in [`Damage::removed`] and its last committed world
+    /// bounds in [`Damage::dirty_rects`], along with removals still pending from
+    /// [`Tree::remove`]. Nodes inserted since the last commit were never painted and are
+    /// not reported. The spatial index is reset and every existing [`NodeId`] becomes
+    /// stale. Slot generations are kept, so ids handed out after the clear never
+    /// collide with earlier ones.
+    pub fn clear(&mut self) -> Damage {
+        let mut removed = core::mem::take(&mut self.removed);
+        for (id, n) in self.nodes.take_all() {
+            if n.committed {
+                removed.push((id, n.world.world_bounds));
+            }
+        }
+        self.index.clear();
+        self.layers.clear();
+        self.epoch += 1;
+        Damage {
+            dirty_rects: removed.iter().map(|&(_, r)| r).collect(),
+            removed,
+            ..Damage::default()
         }
-        self.nodes[id.idx()] = None;
-        self.free_list.push(id.idx());
     }
 
     /// Reparent `id` under `new_parent`.
@@ -247,11 +530,9 @@ impl<B: Backend<f64>> Tree<B> {
         if !self.is_alive(id) {
             return;
         }
-        if let Some(parent) = self.node(id).parent {
-            self.unlink_parent(id, parent);
-        }
+        self.nodes.unlink(id);
         if let Some(p) = new_parent {
-            self.link_parent(id, p);
+            self.nodes.link(id, p);
         }
         self.mark_subtree_dirty(
             id,
@@ -308,18 +589,20 @@ impl<B: Backend<f64>> Tree<B> {
         }
     }
 
+    /// Move a node to another spatial index layer; takes effect at the next [`Tree::commit`].
+    pub fn set_layer(&mut self, id: NodeId, layer: u8) {
+        if let Some(n) = self.node_opt_mut(id) {
+            n.local.layer = layer;
+            n.dirty.index = true;
+        }
+    }
+
     /// Return the world transform for a live node as of the last [`Tree::commit`].
     ///
     /// The returned [`Affine`] maps from the node's local coordinate space into
     /// the tree's root/world space. Returns `None` for stale identifiers.
     pub fn world_transform(&self, id: NodeId) -> Option<Affine> {
-        if !self.is_alive(id) {
-            return None;
-        }
-        self.nodes
-            .get(id.idx())
-            .and_then(|slot| slot.as_ref())
-            .map(|node| node.world.world_transform)
+        self.nodes.get(id).map(|node| node.world.world_transform)
     }
 
     /// Return the world-space axis-aligned bounding box for a live node.
@@ -328,96 +611,203 @@ impl<B: Backend<f64>> Tree<B> {
     /// applying local transforms and any active clips. Returns `None` for stale
     /// identifiers.
     pub fn world_bounds(&self, id: NodeId) -> Option<Rect> {
-        if !self.is_alive(id) {
-            return None;
+        self.nodes.get(id).map(|node| node.world.world_bounds)
+    }
+
+    /// Return the union of the world bounds of `id` and all of its descendants.
+    ///
+    /// Uses the bounds from the last [`Tree::commit`]. Returns `None` for stale identifiers.
+    pub fn subtree_bounds(&self, id: NodeId) -> Option<Rect> {
+        let mut bounds = self.world_bounds(id)?;
+        let mut stack: Vec<NodeId> = self.children_of(id).to_vec();
+        while let Some(n) = stack.pop() {
+            if let Some(b) = self.world_bounds(n) {
+                bounds = bounds.union(b);
+            }
+            stack.extend_from_slice(self.children_of(n));
         }
-        self.nodes
-            .get(id.idx())
-            .and_then(|slot| slot.as_ref())
-            .map(|node| node.world.world_bounds)
+        Some(bounds)
     }
 
     /// Access a node for debugging; panics if `id` is stale.
     pub(crate) fn node(&self, id: NodeId) -> &Node {
-        self.nodes[id.idx()].as_ref().expect("dangling NodeId")
+        self.nodes.node(id)
     }
 
     /// Access a node mutably for debugging; panics if `id` is stale.
     pub(crate) fn node_mut(&mut self, id: NodeId) -> &mut Node {
-        self.nodes[id.idx()].as_mut().expect("dangling NodeId")
+        self.nodes.node_mut(id)
     }
 
     /// Run the batched update and return coarse damage.
     pub fn commit(&mut self) -> Damage {
-        let mut damage = Damage::default();
-        let roots: Vec<NodeId> = self
-            .nodes
-            .iter()
-            .enumerate()
-            .filter_map(|(i, n)| match n {
-                Some(n) if n.parent.is_none() =>
-                {
-                    #[allow(
-                        clippy::cast_possible_truncation,
-                        reason = "NodeId uses 32-bit indices by design."
-                    )]
-                    Some(NodeId::new(i as u32, n.generation))
-                }
-                _ => None,
-            })
-            .collect();
+        let mut damage = Damage {
+            removed: core::mem::take(&mut self.removed),
+            ..Damage::default()
+        };
+        let roots: Vec<NodeId> = self.nodes.roots().collect();
 
         for root in roots {
             self.update_world_recursive(root, Affine::IDENTITY, None, &mut damage);
         }
 
-        let idx_damage = self.index.commit();
-        if let Some(u) = idx_damage.union() {
-            let r = Rect::new(u.min_x, u.min_y, u.max_x, u.max_y);
-            damage.dirty_rects.push(r);
+        if self.index_sync {
+            let layers = self.layers.values_mut();
+            for index in core::iter::once(&mut self.index).chain(layers) {
+                let idx_damage = index.commit();
+                if let Some(u) = idx_damage.union() {
+                    let r = Rect::new(u.min_x, u.min_y, u.max_x, u.max_y);
+                    damage.dirty_rects.push(r);
+                }
+            }
         }
 
         damage
     }
 
+    /// Like [`Tree::commit`], but also report the damage in device space.
+    ///
+    /// Each world-space dirty rect is mapped through `view_transform` (world → device)
+    /// and its conservative bounding box is stored in [`Damage::device_rects`], at the
+    /// same position as the world rect in [`Damage::dirty_rects`].
+    pub fn commit_with_viewport(&mut self, view_transform: Affine) -> Damage {
+        let mut damage = self.commit();
+        damage.device_rects = damage
+            .dirty_rects
+            .iter()
+            .map(|&r| transform_rect_bbox(view_transform, r))
+            .collect();
+        damage
+    }
+
+    /// Enable or disable spatial index synchronization.
+    ///
+    /// While disabled, [`Tree::commit`] still updates world transforms, bounds, and damage,
+    /// but the spatial index is left untouched: [`Tree::hit_test_point`] and
+    /// [`Tree::intersect_rect`] answer from the state at the last synchronized commit
+    /// (nodes removed since then are never reported). Re-enabling runs a catch-up
+    /// sync of every committed node's current world bounds; nodes inserted since the
+    /// last commit are indexed by the next [`Tree::commit`].
+    ///
+    /// This is meant for bulk edits spread over several frames, to avoid repeated
+    /// index work. Synchronization is enabled by default.
+    pub fn set_index_sync(&mut self, enabled: bool) {
+        if self.index_sync == enabled {
+            return;
+        }
+        self.index_sync = enabled;
+        if !enabled {
+            return;
+        }
+        let entries: Vec<(NodeId, Aabb2D<f64>)> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.committed)
+            .map(|(id, node)| (id, rect_to_aabb(node.world.world_bounds)))
+            .collect();
+        for (id, aabb) in entries {
+            self.sync_index_entry(id, aabb);
+        }
+        let _ = self.index.commit();
+        for index in self.layers.values_mut() {
+            let _ = index.commit();
+        }
+    }
+
+    /// Returns whether spatial index synchronization is enabled.
+    ///
+    /// See [`Tree::set_index_sync`].
+    pub fn index_sync(&self) -> bool {
+        self.index_sync
+    }
+
+    /// Build a standalone R-tree index of every committed node's world bounds.
+    ///
+    /// The snapshot is bulk-built and owns its data, so it can be queried (or sent
+    /// elsewhere) while the tree keeps changing. Each entry's payload is its [`NodeId`].
+    /// Node flags and layers are not recorded; nodes inserted since the last commit
+    /// are left out.
+    pub fn build_snapshot_index(&self) -> IndexGeneric<f64, NodeId, RTreeF64<NodeId>> {
+        let entries: Vec<(Aabb2D<f64>, NodeId)> = self
+            .nodes
+            .iter()
+            .filter(|(_, n)| n.committed)
+            .map(|(id, n)| (rect_to_aabb(n.world.world_bounds), id))
+            .collect();
+        Index::<f64, NodeId>::with_rtree_bulk(&entries)
+    }
+
     /// Hit test a world-space point. Returns the topmost node.
     ///
     /// If multiple nodes overlap with the same `z_index`, the newer [`NodeId`] wins.
     /// This tie-break is intentionally deterministic for now.
     /// In the future this may be made configurable (for example via a `TieBreakPolicy`).
+    ///
+    /// This is [`Tree::hit_candidates`] followed by [`Tree::refine_hit`].
     pub fn hit_test_point(&self, pt: Point, filter: QueryFilter) -> Option<Hit> {
-        let candidates: Vec<NodeId> = self
-            .index
-            .query_point(pt.x, pt.y)
+        self.refine_hit(&self.hit_candidates(pt, filter), pt)
+    }
+
+    /// Broad phase of [`Tree::hit_test_point`]: nodes whose world AABB contains `pt`.
+    ///
+    /// Applies `filter` but no clip or z resolution, so the result is conservative and
+    /// may include nodes that [`Tree::refine_hit`] rejects. The order is unspecified.
+    /// Candidates may be cached and refined later, e.g. while the pointer stays put.
+    pub fn hit_candidates(&self, pt: Point, filter: QueryFilter) -> Vec<NodeId> {
+        self.point_candidates(self.all_indexes(), None, pt, filter)
+    }
+
+    /// Hit test a world-space point against a single [layer](LocalNode::layer).
+    ///
+    /// Like [`Tree::hit_test_point`], but only nodes on `layer` are considered.
+    pub fn hit_test_point_layer(&self, pt: Point, layer: u8, filter: QueryFilter) -> Option<Hit> {
+        let candidates = self.point_candidates(self.layer_index(layer), Some(layer), pt, filter);
+        self.refine_hit(&candidates, pt)
+    }
+
+    fn point_candidates<'a>(
+        &'a self,
+        indexes: impl IntoIterator<Item = &'a IndexGeneric<f64, NodeId, B>>,
+        layer: Option<u8>,
+        pt: Point,
+        filter: QueryFilter,
+    ) -> Vec<NodeId> {
+        indexes
+            .into_iter()
+            .flat_map(|index| index.query_point(pt.x, pt.y))
             .map(|(_, id)| id)
-            .collect();
+            .filter(|&id| {
+                // Generation check: while index sync is paused, the index may still hold stale ids.
+                let Some(node) = self.nodes.get(id) else {
+                    return false;
+                };
+                layer.is_none_or(|l| node.index_layer == l)
+                    && filter.matches(node.local.flags)
+                    && !(filter.respect_ancestor_visibility && self.has_hidden_ancestor(id))
+            })
+            .collect()
+    }
+
+    /// Narrow phase of [`Tree::hit_test_point`]: pick the topmost of `candidates` at `pt`.
+    ///
+    /// Applies each candidate's rounded clip and resolves stacking by z-index, then depth, then
+    /// [newer](NodeId#newer) id. Stale candidates are skipped.
+    pub fn refine_hit(&self, candidates: &[NodeId], pt: Point) -> Option<Hit> {
         let mut best: Option<(NodeId, i32, usize)> = None;
-        for id in candidates {
-            let Some(node) = self.nodes[id.idx()].as_ref() else {
-                continue;
-            };
-            if !filter.matches(node.local.flags) {
+        for &id in candidates {
+            if !self.is_alive(id) {
                 continue;
             }
+            let node = self.node(id);
             if let Some(clip) = node.local.local_clip {
                 let world_pt = node.world.world_transform.inverse() * pt;
-                if !clip.rect().contains(world_pt) {
+                if !rounded_rect_contains(&clip, world_pt) {
                     continue;
                 }
             }
-            let depth = self.depth(id);
-            match best {
-                None => best = Some((id, node.local.z_index, depth)),
-                Some((best_id, z_best, depth_best)) => {
-                    let z = node.local.z_index;
-                    if z > z_best
-                        || (z == z_best
-                            && (depth > depth_best
-                                || (depth == depth_best && id_is_newer(id, best_id))))
-                    {
-                        best = Some((id, z, depth));
-                    }
-                }
+            let cand = (id, node.local.z_index, self.depth(id));
+            if best.is_none_or(|b| stacks_above(cand, b)) {
+                best = Some(cand);
             }
         }
         best.map(|(node, _, _)| Hit {
@@ -431,21 +821,142 @@ impl<B: Backend<f64>> Tree<B> {
         &'a self,
         rect: Rect,
         filter: QueryFilter,
+    ) -> impl Iterator<Item = NodeId> + 'a {
+        self.rect_candidates(self.all_indexes(), None, rect, filter)
+    }
+
+    /// Like [`Tree::intersect_rect`], but pair each node with its overlap with `rect`.
+    ///
+    /// The overlap is the node's committed [world bounds](Tree::world_bounds) intersected
+    /// with `rect`, e.g. for drawing the visible part of each node.
+    pub fn intersect_rect_clipped<'a>(
+        &'a self,
+        rect: Rect,
+        filter: QueryFilter,
+    ) -> impl Iterator<Item = (NodeId, Rect)> + 'a {
+        self.intersect_rect(rect, filter)
+            .map(move |id| (id, self.node(id).world.world_bounds.intersect(rect)))
+    }
+
+    /// Iterate nodes whose world bounds lie fully inside `container`'s world bounds.
+    ///
+    /// Uses committed world bounds; shared edges count as inside. The container and its
+    /// ancestors are never reported. Yields nothing if `container` is stale.
+    pub fn nodes_contained_in<'a>(
+        &'a self,
+        container: NodeId,
+        filter: QueryFilter,
+    ) -> impl Iterator<Item = NodeId> + 'a {
+        let bounds = self.world_bounds(container);
+        let excluded = bounds.map_or_else(Vec::new, |_| self.path_to_root(container));
+        bounds.into_iter().flat_map(move |outer| {
+            let excluded = excluded.clone();
+            self.intersect_rect(outer, filter).filter(move |&id| {
+                !excluded.contains(&id)
+                    && rect_to_aabb(outer)
+                        .contains_aabb(&rect_to_aabb(self.node(id).world.world_bounds))
+            })
+        })
+    }
+
+    /// Iterate nodes on a single [layer](LocalNode::layer) intersecting a world-space rect.
+    pub fn intersect_rect_layer<'a>(
+        &'a self,
+        rect: Rect,
+        layer: u8,
+        filter: QueryFilter,
+    ) -> impl Iterator<Item = NodeId> + 'a {
+        self.rect_candidates(self.layer_index(layer), Some(layer), rect, filter)
+    }
+
+    fn rect_candidates<'a>(
+        &'a self,
+        indexes: impl IntoIterator<Item = &'a IndexGeneric<f64, NodeId, B>>,
+        layer: Option<u8>,
+        rect: Rect,
+        filter: QueryFilter,
     ) -> impl Iterator<Item = NodeId> + 'a {
         let q = rect_to_aabb(rect);
-        let ids: Vec<NodeId> = self.index.query_rect(q).map(|(_, id)| id).collect();
+        let ids: Vec<NodeId> = indexes
+            .into_iter()
+            .flat_map(|index| index.query_rect(q))
+            .map(|(_, id)| id)
+            .collect();
         ids.into_iter().filter(move |id| {
-            let Some(node) = self.nodes[id.idx()].as_ref() else {
+            let Some(node) = self.nodes.get(*id) else {
                 return false;
             };
-            filter.matches(node.local.flags)
+            layer.is_none_or(|l| node.index_layer == l)
+                && filter.matches(node.local.flags)
+                && !(filter.respect_ancestor_visibility && self.has_hidden_ancestor(*id))
+                && (!filter.precise_clip || Self::clip_intersects_rect(node, rect))
+        })
+    }
+
+    /// Find the node closest to `pt` within `radius`, e.g. for touch picking.
+    ///
+    /// Candidates come from a rect query inflated by `radius` and are measured by the
+    /// distance from `pt` to the node's bounds, restricted to its own clip (zero when
+    /// `pt` is inside). Among nodes within `radius`, the closest wins; equal distances
+    /// are broken by higher z-index, then as in [`Tree::hit_test_point`].
+    ///
+    /// Distances are exact for translations, rotations, and uniform scales; under
+    /// non-uniform scale or skew they are approximate.
+    pub fn nearest_within(&self, pt: Point, radius: f64, filter: QueryFilter) -> Option<Hit> {
+        let q = Rect::new(pt.x - radius, pt.y - radius, pt.x + radius, pt.y + radius);
+        let mut best: Option<(NodeId, f64, i32, usize)> = None;
+        for id in self.intersect_rect(q, filter) {
+            let Some(dist) = self.distance_to(id, pt) else {
+                continue;
+            };
+            if dist > radius {
+                continue;
+            }
+            let node = self.node(id);
+            let (z, depth) = (node.local.z_index, self.depth(id));
+            let better = match best {
+                None => true,
+                Some((best_id, d_best, z_best, depth_best)) => {
+                    dist < d_best
+                        || (dist == d_best
+                            && stacks_above((id, z, depth), (best_id, z_best, depth_best)))
+                }
+            };
+            if better {
+                best = Some((id, dist, z, depth));
+            }
+        }
+        best.map(|(node, ..)| Hit {
+            node,
+            path: self.path_to_root(node),
         })
     }
-}
 
-#[inline]
-fn id_is_newer(a: NodeId, b: NodeId) -> bool {
-    (a.1 > b.1) || (a.1 == b.1 && a.0 > b.0)
+    /// World-space distance from `pt` to a node's (own-clipped) bounds, or `None`
+    /// if the node is stale or fully clipped away.
+    fn distance_to(&self, id: NodeId, pt: Point) -> Option<f64> {
+        let node = self.nodes.get(id)?;
+        let mut region = node.local.local_bounds;
+        if let Some(clip) = node.local.local_clip {
+            let c = clip.rect();
+            region = Rect::new(
+                region.x0.max(c.x0),
+                region.y0.max(c.y0),
+                region.x1.min(c.x1),
+                region.y1.min(c.y1),
+            );
+            if region.x0 > region.x1 || region.y0 > region.y1 {
+                return None;
+            }
+        }
+        let tf = node.world.world_transform;
+        let local = tf.inverse() * pt;
+        let nearest = Point::new(
+            local.x.clamp(region.x0, region.x1),
+            local.y.clamp(region.y0, region.y1),
+        );
+        Some((tf * nearest - pt).hypot())
+    }
 }
 
 impl<B: Backend<f64>> Tree<B> {
@@ -457,44 +968,43 @@ impl<B: Backend<f64>> Tree<B> {
     /// the current generation stored in that slot.
     /// See [`NodeId`] docs for the generational semantics.
     pub fn is_alive(&self, id: NodeId) -> bool {
-        self.nodes
-            .get(id.idx())
-            .and_then(|n| n.as_ref())
-            .map(|n| n.generation == id.1)
-            .unwrap_or(false)
+        self.nodes.is_alive(id)
     }
 
     /// Returns the z-index of a node if the identifier is live.
     pub fn z_index(&self, id: NodeId) -> Option<i32> {
-        if !self.is_alive(id) {
-            return None;
-        }
-        self.nodes
-            .get(id.idx())
-            .and_then(|slot| slot.as_ref())
-            .map(|node| node.local.z_index)
+        self.nodes.get(id).map(|node| node.local.z_index)
     }
 
     /// Returns the parent of a node if live, or `None` for roots or stale ids.
     pub fn parent_of(&self, id: NodeId) -> Option<NodeId> {
-        if !self.is_alive(id) {
-            return None;
-        }
-        self.nodes
-            .get(id.idx())
-            .and_then(|slot| slot.as_ref())
-            .and_then(|node| node.parent)
+        self.nodes.get(id).and_then(|node| node.parent)
     }
 
     /// Returns the flags of a node if the identifier is live.
     pub fn flags(&self, id: NodeId) -> Option<NodeFlags> {
-        if !self.is_alive(id) {
-            return None;
-        }
-        self.nodes
-            .get(id.idx())
-            .and_then(|slot| slot.as_ref())
-            .map(|node| node.local.flags)
+        self.nodes.get(id).map(|node| node.local.flags)
+    }
+
+    /// Iterate live nodes whose flags match `mask`.
+    ///
+    /// When `all` is true a node matches if its flags contain every bit in `mask`;
+    /// otherwise it matches if any bit in `mask` is set. Nodes are yielded in slot
+    /// order. This does not consult the spatial index, so uncommitted flag changes
+    /// are reflected immediately.
+    pub fn nodes_with_flags(
+        &self,
+        mask: NodeFlags,
+        all: bool,
+    ) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes.iter().filter_map(move |(id, n)| {
+            let matches = if all {
+                n.local.flags.contains(mask)
+            } else {
+                n.local.flags.intersects(mask)
+            };
+            matches.then_some(id)
+        })
     }
 
     /// Get the next node in depth-first traversal order.
@@ -529,6 +1039,19 @@ impl<B: Backend<f64>> Tree<B> {
         &self.node(id).children
     }
 
+    /// Iterate live nodes breadth-first, level by level, with their depth.
+    ///
+    /// Roots (depth 0) come first in slot order, then their children in child order, and
+    /// so on, so every node is yielded after its parent. Suited to top-down passes.
+    pub fn iter_bfs(&self) -> impl Iterator<Item = (NodeId, u32)> + '_ {
+        let mut queue: VecDeque<(NodeId, u32)> = self.nodes.roots().map(|id| (id, 0)).collect();
+        core::iter::from_fn(move || {
+            let (id, depth) = queue.pop_front()?;
+            queue.extend(self.node(id).children.iter().map(|&c| (c, depth + 1)));
+            Some((id, depth))
+        })
+    }
+
     fn next_in_order(&self, current: NodeId) -> Option<NodeId> {
         let children = &self.node(current).children;
         if let Some(&first_child) = children.first()
@@ -588,124 +1111,781 @@ impl<B: Backend<f64>> Tree<B> {
         None
     }
 
-    /// Return the depth of a node in the tree (1-based), or 0 if the id is stale.
-    ///
-    /// Roots have depth 1, direct children of roots have depth 2, and so on.
-    fn depth(&self, mut id: NodeId) -> usize {
-        if !self.is_alive(id) {
-            return 0;
-        }
-        let mut d = 0;
-        loop {
-            d += 1;
-            match self.node(id).parent {
-                Some(p) => id = p,
-                None => break,
-            }
-        }
-        d
-    }
+    /// Return the depth of a node in the tree (1-based), or 0 if the id is stale.
+    ///
+    /// Roots have depth 1, direct children of roots have depth 2, and so on.
+    fn depth(&self, id: NodeId) -> usize {
+        self.nodes.depth(id)
+    }
+
+    /// Returns true if any ancestor of `id` lacks [`NodeFlags::VISIBLE`].
+    fn has_hidden_ancestor(&self, id: NodeId) -> bool {
+        self.nodes.has_hidden_ancestor(id)
+    }
+
+    /// Returns true if the world-space `rect` overlaps the node's own rounded clip.
+    ///
+    /// The rect is mapped into the node's local space by its bounding box, which is
+    /// exact for axis-aligned transforms and conservative under rotation or skew.
+    fn clip_intersects_rect(node: &Node, rect: Rect) -> bool {
+        let Some(clip) = node.local.local_clip else {
+            return true;
+        };
+        let local = transform_rect_bbox(node.world.world_transform.inverse(), rect);
+        rounded_rect_intersects_rect(&clip, local)
+    }
+
+    fn node_opt_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id)
+    }
+
+    fn path_to_root(&self, id: NodeId) -> Vec<NodeId> {
+        self.nodes.path_to_root(id)
+    }
+
+    fn update_world_recursive(
+        &mut self,
+        id: NodeId,
+        parent_tf: Affine,
+        parent_clip: Option<Rect>,
+        damage: &mut Damage,
+    ) {
+        let (old_bounds, was_committed, child_ids, (_local, world), aabb) = {
+            let node = self.node_mut(id);
+            let old = node.world.world_bounds;
+            let was_committed = core::mem::replace(&mut node.committed, true);
+            node.world.world_transform = parent_tf * node.local.local_transform;
+            let mut world_bounds =
+                transform_rect_bbox(node.world.world_transform, node.local.local_bounds);
+            let world_clip = node
+                .local
+                .local_clip
+                .map(|rr| transform_rect_bbox(node.world.world_transform, rr.rect()))
+                .or(parent_clip);
+            if let Some(c) = world_clip {
+                world_bounds = world_bounds.intersect(c);
+            }
+            node.world.world_bounds = world_bounds;
+            node.world.world_clip = world_clip;
+            let aabb = rect_to_aabb(world_bounds);
+            let child_ids = node.children.clone();
+            let snapshot = (node.local.clone(), node.world.clone());
+            (old, was_committed, child_ids, snapshot, aabb)
+        };
+
+        if self.index_sync {
+            self.sync_index_entry(id, aabb);
+        }
+
+        if !was_committed {
+            damage.added.push((id, world.world_bounds));
+        } else if old_bounds != world.world_bounds {
+            damage.moved.push((id, old_bounds, world.world_bounds));
+        }
+        if old_bounds != world.world_bounds {
+            if old_bounds.width() > 0.0 && old_bounds.height() > 0.0 {
+                damage.dirty_rects.push(old_bounds);
+            }
+            if world.world_bounds.width() > 0.0 && world.world_bounds.height() > 0.0 {
+                damage.dirty_rects.push(world.world_bounds);
+            }
+        }
+
+        for child in child_ids {
+            self.update_world_recursive(child, world.world_transform, world.world_clip, damage);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slots::id_is_newer;
+    use alloc::vec;
+    use core::f64::consts::FRAC_PI_4;
+    use kurbo::Vec2;
+
+    /// A node with the given local bounds and defaults otherwise.
+    fn node_at(x0: f64, y0: f64, x1: f64, y1: f64) -> LocalNode {
+        LocalNode {
+            local_bounds: Rect::new(x0, y0, x1, y1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn damage_accumulator_nets_out_repeated_moves() {
+        use crate::DamageAccumulator;
+
+        let mut tree = Tree::new();
+        let local = LocalNode {
+            local_bounds: Rect::new(0.0, 0.0, 10.0, 10.0),
+            ..Default::default()
+        };
+        let a = tree.insert(None, local.clone());
+        let b = tree.insert(None, local);
+        let first = tree.commit();
+        assert_eq!(first.added.len(), 2);
+        assert!(first.moved.is_empty());
+
+        let mut acc = DamageAccumulator::new();
+        tree.set_local_transform(a, Affine::translate((20.0, 0.0)));
+        acc.merge(tree.commit());
+        tree.set_local_transform(a, Affine::translate((40.0, 0.0)));
+        tree.remove(b);
+        acc.merge(tree.commit());
+
+        let dmg = acc.take();
+        assert_eq!(
+            dmg.moved,
+            vec![(
+                a,
+                Rect::new(0.0, 0.0, 10.0, 10.0),
+                Rect::new(40.0, 0.0, 50.0, 10.0)
+            )]
+        );
+        assert_eq!(dmg.removed, vec![(b, Rect::new(0.0, 0.0, 10.0, 10.0))]);
+        assert!(dmg.added.is_empty());
+        assert_eq!(dmg.union_rect(), Some(Rect::new(0.0, 0.0, 50.0, 10.0)));
+        assert!(acc.is_empty());
+
+        // Coalescing folds overlapping rects together.
+        acc.set_coalesce(true);
+        acc.merge(Damage {
+            dirty_rects: vec![
+                Rect::new(0.0, 0.0, 10.0, 10.0),
+                Rect::new(5.0, 5.0, 15.0, 15.0),
+            ],
+            ..Damage::default()
+        });
+        assert_eq!(
+            acc.damage().dirty_rects,
+            vec![Rect::new(0.0, 0.0, 15.0, 15.0)]
+        );
+    }
+
+    #[test]
+    fn damage_accumulator_keeps_device_rects_paired() {
+        use crate::DamageAccumulator;
+
+        let mut tree = Tree::new();
+        // Without index sync a commit reports only per-node rects, not the index union.
+        tree.set_index_sync(false);
+        let a = tree.insert(None, node_at(0.0, 0.0, 10.0, 10.0));
+        let _b = tree.insert(None, node_at(11.0, -3.0, 21.0, 7.0));
+        let mut acc = DamageAccumulator::new();
+        acc.set_coalesce(true);
+        // Rotated, the two device rects overlap although the world rects do not; they are
+        // coalesced by their world rects only.
+        let rotated = Affine::rotate(FRAC_PI_4);
+        acc.merge(tree.commit_with_viewport(rotated));
+        let dmg = acc.take();
+        assert_eq!(dmg.dirty_rects.len(), 2);
+        assert_eq!(dmg.device_rects.len(), 2);
+        for (world, device) in dmg.dirty_rects.iter().zip(&dmg.device_rects) {
+            assert_eq!(*device, transform_rect_bbox(rotated, *world));
+        }
+
+        // Overlaps `a`'s old bounds, so the world rects coalesce out of order.
+        let view = Affine::scale(2.0).then_translate((5.0, 0.0).into());
+        tree.set_local_transform(a, Affine::translate((5.0, 5.0)));
+        acc.merge(tree.commit_with_viewport(view));
+        tree.set_local_transform(a, Affine::translate((50.0, 0.0)));
+        acc.merge(tree.commit_with_viewport(view));
+
+        let dmg = acc.damage();
+        assert_eq!(dmg.dirty_rects.len(), dmg.device_rects.len());
+        for (world, device) in dmg.dirty_rects.iter().zip(&dmg.device_rects) {
+            assert_eq!(*device, transform_rect_bbox(view, *world));
+        }
+
+        // A plain commit has no device rects to pair with.
+        tree.set_local_transform(a, Affine::translate((70.0, 0.0)));
+        acc.merge(tree.commit());
+        assert!(!acc.damage().dirty_rects.is_empty());
+        assert!(acc.damage().device_rects.is_empty());
+        acc.merge(tree.commit_with_viewport(view));
+        assert!(acc.damage().device_rects.is_empty());
+
+        // Flushing starts pairing again.
+        let _ = acc.take();
+        tree.set_local_transform(a, Affine::translate((90.0, 0.0)));
+        acc.merge(tree.commit_with_viewport(view));
+        let dmg = acc.damage();
+        assert!(!dmg.dirty_rects.is_empty());
+        assert_eq!(dmg.dirty_rects.len(), dmg.device_rects.len());
+    }
+
+    #[test]
+    fn intersect_rect_clipped_returns_overlaps() {
+        let mut tree = Tree::new();
+        let ids = tree.build(&[
+            NodeSpec {
+                parent: None,
+                local: LocalNode {
+                    local_bounds: Rect::new(0.0, 0.0, 50.0, 50.0),
+                    ..Default::default()
+                },
+            },
+            NodeSpec {
+                parent: Some(0),
+                local: LocalNode {
+                    local_bounds: Rect::new(0.0, 0.0, 20.0, 20.0),
+                    local_transform: Affine::translate((30.0, 30.0)),
+                    ..Default::default()
+                },
+            },
+        ]);
+        let q = Rect::new(25.0, 25.0, 100.0, 40.0);
+        let mut got: Vec<_> = tree.intersect_rect_clipped(q, QueryFilter::new()).collect();
+        got.sort_by_key(|(id, _)| id.0);
+        let expected: Vec<_> = ids
+            .iter()
+            .map(|&id| (id, tree.world_bounds(id).unwrap().intersect(q)))
+            .collect();
+        assert_eq!(got, expected);
+        assert_eq!(got[1].1, Rect::new(30.0, 30.0, 50.0, 40.0));
+    }
+
+    #[test]
+    fn nodes_contained_in_skips_partial_overlaps() {
+        let mut tree = Tree::new();
+        let root = tree.insert(None, node_at(0.0, 0.0, 500.0, 500.0));
+        let panel = tree.insert(Some(root), node_at(100.0, 100.0, 300.0, 300.0));
+        let a = tree.insert(Some(panel), node_at(110.0, 110.0, 150.0, 150.0));
+        let b = tree.insert(Some(root), node_at(200.0, 200.0, 300.0, 300.0)); // touches the edge
+        let _partial = tree.insert(Some(root), node_at(280.0, 280.0, 320.0, 320.0));
+        let _outside = tree.insert(None, node_at(400.0, 400.0, 420.0, 420.0));
+        let _ = tree.commit();
+
+        let mut inside: Vec<_> = tree.nodes_contained_in(panel, QueryFilter::new()).collect();
+        inside.sort_by_key(|id| id.0);
+        assert_eq!(inside, vec![a, b]);
+
+        tree.remove(panel);
+        assert_eq!(
+            tree.nodes_contained_in(panel, QueryFilter::new()).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn clear_reports_all_committed_nodes_and_stales_ids() {
+        let mut tree = Tree::new();
+        let root = tree.insert(None, node_at(0.0, 0.0, 100.0, 100.0));
+        let a = tree.insert(Some(root), node_at(10.0, 10.0, 20.0, 20.0));
+        let b = tree.insert(Some(a), node_at(12.0, 12.0, 18.0, 18.0));
+        let gone = tree.insert(None, node_at(200.0, 200.0, 210.0, 210.0));
+        let _ = tree.commit();
+        tree.remove(gone);
+        let pending = tree.insert(Some(root), node_at(50.0, 50.0, 60.0, 60.0));
+
+        let dmg = tree.clear();
+        let mut removed: Vec<_> = dmg.removed.iter().map(|&(id, _)| id).collect();
+        removed.sort_by_key(|id| id.0);
+        assert_eq!(removed, vec![root, a, b, gone]);
+        assert_eq!(dmg.union_rect(), Some(Rect::new(0.0, 0.0, 210.0, 210.0)));
+
+        for id in [root, a, b, gone, pending] {
+            assert!(!tree.is_alive(id));
+        }
+        assert!(
+            tree.hit_test_point(Point::new(15.0, 15.0), QueryFilter::new())
+                .is_none()
+        );
+        assert!(tree.commit().is_empty());
+
+        // Reused slots get fresh generations.
+        let fresh = tree.insert(None, node_at(0.0, 0.0, 10.0, 10.0));
+        assert!(![root, a, b, gone, pending].contains(&fresh));
+        assert!(!tree.is_alive(root));
+    }
+
+    #[test]
+    fn snapshot_index_matches_hit_candidates() {
+        let mut tree = Tree::new();
+        let root = tree.insert(None, node_at(0.0, 0.0, 200.0, 200.0));
+        for i in 0..6_u32 {
+            let o = 25.0 * f64::from(i);
+            let child = tree.insert(Some(root), node_at(o, o, o + 40.0, o + 40.0));
+            tree.set_local_transform(child, Affine::translate((5.0, 0.0)));
+        }
+        let overlay = tree.insert(None, node_at(50.0, 50.0, 150.0, 150.0));
+        tree.set_layer(overlay, 1);
+        let _ = tree.commit();
+        let _pending = tree.insert(Some(root), node_at(0.0, 0.0, 200.0, 200.0));
+
+        let snapshot = tree.build_snapshot_index();
+        for pt in [
+            (10.0, 10.0),
+            (60.0, 60.0),
+            (80.0, 70.0),
+            (190.0, 5.0),
+            (300.0, 300.0),
+        ] {
+            let pt = Point::new(pt.0, pt.1);
+            let mut from_snapshot: Vec<_> =
+                snapshot.query_point(pt.x, pt.y).map(|(_, id)| id).collect();
+            let mut from_tree = tree.hit_candidates(pt, QueryFilter::new());
+            from_snapshot.sort_by_key(|id| id.0);
+            from_tree.sort_by_key(|id| id.0);
+            assert_eq!(from_snapshot, from_tree, "at {pt:?}");
+        }
+    }
+
+    #[test]
+    fn switch_index_backend_keeps_hits_and_ids() {
+        let mut tree = Tree::with_backend(AnyBackend::new(BackendKind::FlatVec))
+            .with_layer_backends(|| AnyBackend::new(BackendKind::FlatVec));
+        let root = tree.insert(None, node_at(0.0, 0.0, 200.0, 200.0));
+        let mut ids = vec![root];
+        for i in 0..8_u32 {
+            let o = 20.0 * f64::from(i);
+            ids.push(tree.insert(Some(root), node_at(o, o, o + 30.0, o + 30.0)));
+        }
+        let overlay = tree.insert(None, node_at(50.0, 50.0, 150.0, 150.0));
+        tree.set_layer(overlay, 1);
+        let _ = tree.commit();
+
+        let points = [(5.0, 5.0), (45.0, 45.0), (100.0, 100.0), (170.0, 10.0)];
+        let hits = |tree: &Tree<AnyBackend<f64>>| -> Vec<_> {
+            points
+                .iter()
+                .map(|&(x, y)| {
+                    let pt = Point::new(x, y);
+                    let top = tree.hit_test_point(pt, QueryFilter::new()).map(|h| h.node);
+                    let overlay = tree.hit_test_point_layer(pt, 1, QueryFilter::new());
+                    (top, overlay.map(|h| h.node))
+                })
+                .collect()
+        };
+        let before = hits(&tree);
+        tree.switch_index_backend(BackendKind::RTree);
+        assert_eq!(hits(&tree), before);
+        assert!(ids.iter().all(|&id| tree.is_alive(id)));
+
+        // The tree keeps working on the new backend, including for new layers.
+        tree.set_local_bounds(ids[1], Rect::new(180.0, 180.0, 190.0, 190.0));
+        let badge = tree.insert(None, node_at(0.0, 0.0, 10.0, 10.0));
+        tree.set_layer(badge, 2);
+        let _ = tree.commit();
+        let pt = Point::new(185.0, 185.0);
+        assert_eq!(
+            tree.hit_test_point(pt, QueryFilter::new()).map(|h| h.node),
+            Some(ids[1])
+        );
+        let pt = Point::new(5.0, 5.0);
+        let badge_hit = tree.hit_test_point_layer(pt, 2, QueryFilter::new());
+        assert_eq!(badge_hit.map(|h| h.node), Some(badge));
+    }
+
+    #[test]
+    fn commit_with_viewport_reports_device_rects() {
+        let mut tree = Tree::new();
+        let a = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(10.0, 20.0, 30.0, 40.0),
+                ..Default::default()
+            },
+        );
+        let view = Affine::scale(2.0).then_translate((5.0, 0.0).into());
+        let dmg = tree.commit_with_viewport(view);
+        assert_eq!(dmg.dirty_rects.len(), dmg.device_rects.len());
+        for (world, device) in dmg.dirty_rects.iter().zip(&dmg.device_rects) {
+            let expected = Rect::new(
+                2.0 * world.x0 + 5.0,
+                2.0 * world.y0,
+                2.0 * world.x1 + 5.0,
+                2.0 * world.y1,
+            );
+            assert_eq!(*device, expected);
+        }
+        assert!(
+            dmg.device_rects
+                .contains(&Rect::new(25.0, 40.0, 65.0, 80.0))
+        );
+
+        // Rotation: the device rect bounds every transformed corner.
+        tree.set_local_bounds(a, Rect::new(0.0, 0.0, 10.0, 20.0));
+        let view = Affine::rotate(FRAC_PI_4);
+        let dmg = tree.commit_with_viewport(view);
+        for (world, device) in dmg.dirty_rects.iter().zip(&dmg.device_rects) {
+            for corner in [
+                Point::new(world.x0, world.y0),
+                Point::new(world.x1, world.y0),
+                Point::new(world.x0, world.y1),
+                Point::new(world.x1, world.y1),
+            ] {
+                let p = view * corner;
+                assert!(
+                    device.inflate(1e-9, 1e-9).contains(p),
+                    "{p:?} not in {device:?}"
+                );
+            }
+        }
+
+        // Plain commits leave device rects empty.
+        tree.set_local_bounds(a, Rect::new(0.0, 0.0, 5.0, 5.0));
+        let dmg = tree.commit();
+        assert!(!dmg.dirty_rects.is_empty());
+        assert!(dmg.device_rects.is_empty());
+    }
+
+    #[test]
+    fn layer_queries_ignore_other_layers() {
+        let mut tree = Tree::new();
+        let base = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 100.0, 100.0),
+                ..Default::default()
+            },
+        );
+        let overlay = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 50.0, 50.0),
+                z_index: 10,
+                layer: 1,
+                ..Default::default()
+            },
+        );
+        let _ = tree.commit();
+
+        let f = QueryFilter::new();
+        let pt = Point::new(10.0, 10.0);
+        assert_eq!(tree.hit_test_point(pt, f).unwrap().node, overlay);
+        assert_eq!(tree.hit_test_point_layer(pt, 0, f).unwrap().node, base);
+        assert_eq!(tree.hit_test_point_layer(pt, 1, f).unwrap().node, overlay);
+        assert!(tree.hit_test_point_layer(pt, 2, f).is_none());
+        let q = Rect::new(0.0, 0.0, 20.0, 20.0);
+        let layer0: Vec<_> = tree.intersect_rect_layer(q, 0, f).collect();
+        assert_eq!(layer0, vec![base]);
+        assert_eq!(tree.intersect_rect(q, f).count(), 2);
+
+        tree.set_layer(overlay, 0);
+        let _ = tree.commit();
+        assert_eq!(tree.hit_test_point_layer(pt, 0, f).unwrap().node, overlay);
+        assert!(tree.hit_test_point_layer(pt, 1, f).is_none());
+    }
+
+    #[test]
+    fn layers_share_the_base_index_without_a_factory() {
+        let mut tree = Tree::with_backend(RTreeF64::<NodeId>::with_params(4, 2));
+        let base = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 100.0, 100.0),
+                ..Default::default()
+            },
+        );
+        let overlay = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 50.0, 50.0),
+                z_index: 10,
+                layer: 2,
+                ..Default::default()
+            },
+        );
+        let _ = tree.commit();
+
+        let f = QueryFilter::new();
+        let pt = Point::new(10.0, 10.0);
+        assert_eq!(tree.hit_test_point(pt, f).unwrap().node, overlay);
+        assert_eq!(tree.hit_test_point_layer(pt, 0, f).unwrap().node, base);
+        assert_eq!(tree.hit_test_point_layer(pt, 2, f).unwrap().node, overlay);
+        assert!(tree.hit_test_point_layer(pt, 1, f).is_none());
+        let q = Rect::new(0.0, 0.0, 20.0, 20.0);
+        assert_eq!(
+            tree.intersect_rect_layer(q, 2, f).collect::<Vec<_>>(),
+            vec![overlay]
+        );
+
+        tree.set_layer(overlay, 1);
+        let _ = tree.commit();
+        assert!(tree.hit_test_point_layer(pt, 2, f).is_none());
+        assert_eq!(tree.hit_test_point_layer(pt, 1, f).unwrap().node, overlay);
+        tree.remove(overlay);
+        let _ = tree.commit();
+        assert_eq!(tree.hit_test_point(pt, f).unwrap().node, base);
+    }
+
+    #[test]
+    fn layer_backends_come_from_the_factory() {
+        let mut tree = Tree::with_backend(RTreeF64::<NodeId>::with_params(4, 2))
+            .with_layer_backends(|| RTreeF64::with_params(4, 2));
+        let node = |x: f64, layer| LocalNode {
+            local_bounds: Rect::new(x, 0.0, x + 10.0, 10.0),
+            layer,
+            ..Default::default()
+        };
+        let ids: Vec<_> = (0..12_u8)
+            .map(|i| tree.insert(None, node(f64::from(i) * 20.0, i % 3)))
+            .collect();
+        let _ = tree.commit();
+        for (i, &id) in ids.iter().enumerate() {
+            let pt = Point::new(i as f64 * 20.0 + 5.0, 5.0);
+            let layer = u8::try_from(i % 3).unwrap();
+            assert_eq!(
+                tree.hit_test_point_layer(pt, layer, QueryFilter::new())
+                    .unwrap()
+                    .node,
+                id
+            );
+        }
+    }
+
+    #[test]
+    fn paused_index_sync_keeps_queries_stale_until_resumed() {
+        let mut tree = Tree::new();
+        let a = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 10.0, 10.0),
+                ..Default::default()
+            },
+        );
+        let b = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(20.0, 0.0, 30.0, 10.0),
+                ..Default::default()
+            },
+        );
+        let _ = tree.commit();
+
+        tree.set_index_sync(false);
+        assert!(!tree.index_sync());
+        tree.set_local_transform(a, Affine::translate((100.0, 0.0)));
+        tree.remove(b);
+        let c = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(50.0, 0.0, 60.0, 10.0),
+                ..Default::default()
+            },
+        );
+        let dmg = tree.commit();
+        assert!(!dmg.dirty_rects.is_empty(), "damage is still reported");
+        let _ = tree.commit();
 
-    fn node_opt_mut(&mut self, id: NodeId) -> Option<&mut Node> {
-        let n = self.nodes.get_mut(id.idx())?.as_mut()?;
-        if n.generation != id.1 {
-            return None;
-        }
-        Some(n)
+        // Queries answer from the pre-pause state.
+        let f = QueryFilter::new();
+        assert_eq!(
+            tree.hit_test_point(Point::new(5.0, 5.0), f).unwrap().node,
+            a
+        );
+        assert!(tree.hit_test_point(Point::new(105.0, 5.0), f).is_none());
+        assert!(tree.hit_test_point(Point::new(55.0, 5.0), f).is_none());
+        // Removed nodes are never reported, even though the index still holds them.
+        assert!(tree.hit_test_point(Point::new(25.0, 5.0), f).is_none());
+
+        tree.set_index_sync(true);
+        assert!(tree.hit_test_point(Point::new(5.0, 5.0), f).is_none());
+        assert_eq!(
+            tree.hit_test_point(Point::new(105.0, 5.0), f).unwrap().node,
+            a
+        );
+        assert_eq!(
+            tree.hit_test_point(Point::new(55.0, 5.0), f).unwrap().node,
+            c
+        );
+        assert!(tree.hit_test_point(Point::new(25.0, 5.0), f).is_none());
+        let all: Vec<_> = tree
+            .intersect_rect(Rect::new(0.0, 0.0, 200.0, 10.0), f)
+            .collect();
+        assert_eq!(all.len(), 2);
     }
 
-    fn link_parent(&mut self, id: NodeId, parent: NodeId) {
-        let parent_node = self.node_mut(parent);
-        parent_node.children.push(id);
-        self.node_mut(id).parent = Some(parent);
-    }
+    #[test]
+    fn index_sync_catch_up_skips_uncommitted_nodes() {
+        let mut tree = Tree::new();
+        tree.set_index_sync(false);
+        let a = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(10.0, 10.0, 20.0, 20.0),
+                ..Default::default()
+            },
+        );
+        tree.set_index_sync(true);
+
+        // `a` has no world bounds yet, so it must not show up at the origin.
+        let f = QueryFilter::new();
+        assert!(tree.hit_test_point(Point::ZERO, f).is_none());
+        assert_eq!(
+            tree.intersect_rect(Rect::new(-1.0, -1.0, 1.0, 1.0), f)
+                .count(),
+            0
+        );
 
-    fn unlink_parent(&mut self, id: NodeId, parent: NodeId) {
-        let p = self.node_mut(parent);
-        p.children.retain(|c| *c != id);
-        self.node_mut(id).parent = None;
+        let _ = tree.commit();
+        assert_eq!(
+            tree.hit_test_point(Point::new(15.0, 15.0), f).unwrap().node,
+            a
+        );
+        assert!(tree.hit_test_point(Point::ZERO, f).is_none());
     }
 
-    fn path_to_root(&self, mut id: NodeId) -> Vec<NodeId> {
-        let mut out = Vec::new();
-        loop {
-            out.push(id);
-            let parent = self.node(id).parent;
-            match parent {
-                Some(p) => id = p,
-                None => break,
-            }
-        }
-        out.reverse();
-        out
+    #[test]
+    fn precise_clip_excludes_rounded_corner() {
+        let mut tree = Tree::new();
+        let node = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 100.0, 100.0),
+                local_clip: Some(RoundedRect::new(0.0, 0.0, 100.0, 100.0, 50.0)),
+                local_transform: Affine::translate((10.0, 10.0)),
+                ..Default::default()
+            },
+        );
+        let _ = tree.commit();
+
+        // Touches only the top-left corner of the AABB, outside the circular clip.
+        let corner = Rect::new(5.0, 5.0, 15.0, 15.0);
+        let f = QueryFilter::new();
+        assert_eq!(
+            tree.intersect_rect(corner, f).collect::<Vec<_>>(),
+            vec![node]
+        );
+        assert_eq!(tree.intersect_rect(corner, f.precise_clip()).count(), 0);
+
+        // Reaching past the corner arc into the clipped region still matches.
+        let deeper = Rect::new(5.0, 5.0, 30.0, 30.0);
+        assert_eq!(
+            tree.intersect_rect(deeper, f.precise_clip())
+                .collect::<Vec<_>>(),
+            vec![node]
+        );
+        // Along an edge (not a corner) the AABB and the clip agree.
+        let edge = Rect::new(55.0, 5.0, 65.0, 15.0);
+        assert_eq!(tree.intersect_rect(edge, f.precise_clip()).count(), 1);
     }
 
-    fn update_world_recursive(
-        &mut self,
-        id: NodeId,
-        parent_tf: Affine,
-        parent_clip: Option<Rect>,
-        damage: &mut Damage,
-    ) {
-        enum IndexOp {
-            Update(AabbKey, Aabb2D<f64>),
-            Insert(Aabb2D<f64>),
+    #[test]
+    fn two_phase_hit_test_matches_hit_test_point() {
+        let mut tree = Tree::new();
+        let base = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(-100.0, 0.0, 100.0, 200.0),
+                ..Default::default()
+            },
+        );
+        // A clipped square rotated 45°: its world AABB has corners outside the clip.
+        let diamond = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 100.0, 100.0),
+                local_clip: Some(RoundedRect::new(0.0, 0.0, 100.0, 100.0, 0.0)),
+                local_transform: Affine::rotate(FRAC_PI_4),
+                z_index: 1,
+                ..Default::default()
+            },
+        );
+        let _ = tree.commit();
+        let f = QueryFilter::new();
+
+        for pt in [
+            Point::new(-60.0, 10.0),
+            Point::new(0.0, 70.0),
+            Point::new(90.0, 190.0),
+        ] {
+            let composed = tree.refine_hit(&tree.hit_candidates(pt, f), pt);
+            assert_eq!(
+                composed.map(|h| h.node),
+                tree.hit_test_point(pt, f).map(|h| h.node)
+            );
         }
-        let (old_bounds, child_ids, (_local, world), index_op) = {
-            let node = self.node_mut(id);
-            let old = node.world.world_bounds;
-            node.world.world_transform = parent_tf * node.local.local_transform;
-            let mut world_bounds =
-                transform_rect_bbox(node.world.world_transform, node.local.local_bounds);
-            let world_clip = node
-                .local
-                .local_clip
-                .map(|rr| transform_rect_bbox(node.world.world_transform, rr.rect()))
-                .or(parent_clip);
-            if let Some(c) = world_clip {
-                world_bounds = world_bounds.intersect(c);
-            }
-            node.world.world_bounds = world_bounds;
-            node.world.world_clip = world_clip;
-            let aabb = rect_to_aabb(world_bounds);
-            let op = if let Some(key) = node.index_key {
-                IndexOp::Update(key, aabb)
-            } else {
-                IndexOp::Insert(aabb)
-            };
-            let child_ids = node.children.clone();
-            (old, child_ids, (node.local.clone(), node.world.clone()), op)
-        };
 
-        match index_op {
-            IndexOp::Update(key, aabb) => self.index.update(key, aabb),
-            IndexOp::Insert(aabb) => {
-                let key = self.index.insert(aabb, id);
-                self.node_mut(id).index_key = Some(key);
-            }
-        }
+        // Inside the diamond's AABB corner but outside its clip: a candidate the
+        // narrow phase rejects.
+        let pt = Point::new(-60.0, 10.0);
+        let candidates = tree.hit_candidates(pt, f);
+        assert!(candidates.contains(&diamond));
+        assert_eq!(tree.refine_hit(&candidates, pt).unwrap().node, base);
+        // Inside the clip the diamond wins on z.
+        let pt = Point::new(0.0, 70.0);
+        assert_eq!(
+            tree.refine_hit(&tree.hit_candidates(pt, f), pt)
+                .unwrap()
+                .node,
+            diamond
+        );
 
-        if old_bounds != world.world_bounds {
-            if old_bounds.width() > 0.0 && old_bounds.height() > 0.0 {
-                damage.dirty_rects.push(old_bounds);
-            }
-            if world.world_bounds.width() > 0.0 && world.world_bounds.height() > 0.0 {
-                damage.dirty_rects.push(world.world_bounds);
-            }
-        }
+        // Inside the corner's bounding square but outside its arc: no hit.
+        let pill = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(300.0, 0.0, 400.0, 100.0),
+                local_clip: Some(RoundedRect::new(300.0, 0.0, 400.0, 100.0, 40.0)),
+                z_index: 1,
+                ..Default::default()
+            },
+        );
+        let _ = tree.commit();
+        let pt = Point::new(303.0, 3.0);
+        let candidates = tree.hit_candidates(pt, f);
+        assert!(candidates.contains(&pill));
+        assert!(tree.refine_hit(&candidates, pt).is_none());
+        assert!(tree.hit_test_point(pt, f).is_none());
+        // Just inside the arc it is hit.
+        let pt = Point::new(315.0, 15.0);
+        assert_eq!(tree.hit_test_point(pt, f).unwrap().node, pill);
+    }
 
-        for child in child_ids {
-            self.update_world_recursive(child, world.world_transform, world.world_clip, damage);
-        }
+    #[test]
+    fn nearest_within_prefers_closer_then_higher_z() {
+        let mut tree = Tree::new();
+        let left = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 10.0, 10.0),
+                z_index: 5,
+                ..Default::default()
+            },
+        );
+        let right = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(20.0, 0.0, 30.0, 10.0),
+                ..Default::default()
+            },
+        );
+        let _ = tree.commit();
+        let f = QueryFilter::new();
+
+        // Between the boxes, 3 from `right` and 7 from `left`.
+        let hit = tree.nearest_within(Point::new(17.0, 5.0), 8.0, f).unwrap();
+        assert_eq!(hit.node, right);
+        // Out of reach of both.
+        assert!(
+            tree.nearest_within(Point::new(15.0, 30.0), 4.0, f)
+                .is_none()
+        );
+
+        // Equidistant: the higher z wins.
+        let hit = tree.nearest_within(Point::new(15.0, 5.0), 8.0, f).unwrap();
+        assert_eq!(hit.node, left);
+        tree.set_z_index(right, 9);
+        let _ = tree.commit();
+        let hit = tree.nearest_within(Point::new(15.0, 5.0), 8.0, f).unwrap();
+        assert_eq!(hit.node, right);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use alloc::vec;
-    use core::f64::consts::FRAC_PI_4;
-    use kurbo::Vec2;
+    #[test]
+    fn node_id_display_and_raw_round_trip() {
+        let id = NodeId::new(3, 1);
+        assert_eq!(alloc::format!("{id}"), "#3@g1");
+        assert_eq!(NodeId::from_raw(id.to_raw()), id);
+
+        let big = NodeId::new(u32::MAX, u32::MAX - 1);
+        assert_eq!(alloc::format!("{big}"), "#4294967295@g4294967294");
+        assert_eq!(NodeId::from_raw(big.to_raw()), big);
+        assert_ne!(NodeId::new(1, 2).to_raw(), NodeId::new(2, 1).to_raw());
+    }
 
     #[test]
     fn insert_and_hit_test() {
@@ -1234,6 +2414,174 @@ mod tests {
         assert!(all_visible_intersections.contains(&non_pickable_child));
     }
 
+    #[test]
+    fn nodes_with_flags_all_and_any() {
+        let mut tree = Tree::new();
+        let root = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 100.0, 100.0),
+                flags: NodeFlags::VISIBLE | NodeFlags::PICKABLE,
+                ..Default::default()
+            },
+        );
+        let hidden_pickable = tree.insert(
+            Some(root),
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 10.0, 10.0),
+                flags: NodeFlags::PICKABLE,
+                ..Default::default()
+            },
+        );
+        let focusable = tree.insert(
+            Some(root),
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 10.0, 10.0),
+                flags: NodeFlags::VISIBLE | NodeFlags::FOCUSABLE,
+                ..Default::default()
+            },
+        );
+        let removed = tree.insert(
+            Some(root),
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 10.0, 10.0),
+                flags: NodeFlags::PICKABLE,
+                ..Default::default()
+            },
+        );
+        tree.remove(removed);
+
+        // Invisible-but-pickable: pickable nodes, then drop the visible ones.
+        let invisible_pickable: Vec<NodeId> = tree
+            .nodes_with_flags(NodeFlags::PICKABLE, true)
+            .filter(|id| !tree.flags(*id).unwrap().contains(NodeFlags::VISIBLE))
+            .collect();
+        assert_eq!(invisible_pickable, vec![hidden_pickable]);
+
+        let all: Vec<NodeId> = tree
+            .nodes_with_flags(NodeFlags::VISIBLE | NodeFlags::PICKABLE, true)
+            .collect();
+        assert_eq!(all, vec![root]);
+
+        let any: Vec<NodeId> = tree
+            .nodes_with_flags(NodeFlags::PICKABLE | NodeFlags::FOCUSABLE, false)
+            .collect();
+        assert_eq!(any, vec![root, hidden_pickable, focusable]);
+    }
+
+    #[test]
+    fn intersect_rect_respects_ancestor_visibility() {
+        let mut tree = Tree::new();
+        let root = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 200.0, 200.0),
+                ..Default::default()
+            },
+        );
+        let hidden_parent = tree.insert(
+            Some(root),
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 100.0, 100.0),
+                flags: NodeFlags::PICKABLE,
+                ..Default::default()
+            },
+        );
+        let child = tree.insert(
+            Some(hidden_parent),
+            LocalNode {
+                local_bounds: Rect::new(10.0, 10.0, 50.0, 50.0),
+                ..Default::default()
+            },
+        );
+        let grandchild = tree.insert(
+            Some(child),
+            LocalNode {
+                local_bounds: Rect::new(20.0, 20.0, 30.0, 30.0),
+                ..Default::default()
+            },
+        );
+        let _ = tree.commit();
+        let q = Rect::new(0.0, 0.0, 200.0, 200.0);
+
+        // Without the option, visible descendants of a hidden parent are included.
+        let plain: Vec<NodeId> = tree
+            .intersect_rect(q, QueryFilter::new().visible())
+            .collect();
+        assert!(plain.contains(&child));
+        assert!(plain.contains(&grandchild));
+        assert!(!plain.contains(&hidden_parent));
+
+        // With the option, every descendant of the hidden parent is culled.
+        let culled: Vec<NodeId> = tree
+            .intersect_rect(
+                q,
+                QueryFilter::new().visible().respect_ancestor_visibility(),
+            )
+            .collect();
+        assert_eq!(culled, vec![root]);
+
+        // Hit testing honors the same option.
+        let pt = Point::new(25.0, 25.0);
+        let hit = tree
+            .hit_test_point(pt, QueryFilter::new().visible())
+            .unwrap();
+        assert_eq!(hit.node, grandchild);
+        let hit = tree
+            .hit_test_point(
+                pt,
+                QueryFilter::new().visible().respect_ancestor_visibility(),
+            )
+            .unwrap();
+        assert_eq!(hit.node, root);
+    }
+
+    #[test]
+    fn build_from_spec_hit_tests() {
+        let mut tree = Tree::new();
+        let ids = tree.build(&[
+            NodeSpec {
+                parent: None,
+                local: LocalNode {
+                    local_bounds: Rect::new(0.0, 0.0, 200.0, 200.0),
+                    ..Default::default()
+                },
+            },
+            NodeSpec {
+                parent: Some(0),
+                local: LocalNode {
+                    local_bounds: Rect::new(0.0, 0.0, 100.0, 100.0),
+                    local_transform: Affine::translate(Vec2::new(50.0, 50.0)),
+                    ..Default::default()
+                },
+            },
+            NodeSpec {
+                parent: Some(1),
+                local: LocalNode {
+                    local_bounds: Rect::new(10.0, 10.0, 20.0, 20.0),
+                    ..Default::default()
+                },
+            },
+        ]);
+        assert_eq!(ids.len(), 3);
+        assert_eq!(tree.parent_of(ids[1]), Some(ids[0]));
+        assert_eq!(tree.parent_of(ids[2]), Some(ids[1]));
+
+        // Already committed: the grandchild sits at (60..70) in world space.
+        let filter = QueryFilter::new().visible().pickable();
+        let hit = tree.hit_test_point(Point::new(65.0, 65.0), filter).unwrap();
+        assert_eq!(hit.node, ids[2]);
+        assert_eq!(hit.path, ids);
+        let hit = tree
+            .hit_test_point(Point::new(100.0, 100.0), filter)
+            .unwrap();
+        assert_eq!(hit.node, ids[1]);
+        let hit = tree
+            .hit_test_point(Point::new(190.0, 190.0), filter)
+            .unwrap();
+        assert_eq!(hit.node, ids[0]);
+    }
+
     #[test]
     fn world_transform_and_bounds_match_updates() {
         let mut tree = Tree::new();
@@ -1448,6 +2796,125 @@ mod tests {
         assert!(tree.children_of(a).is_empty());
     }
 
+    #[test]
+    fn world_bounds_quadtree_tree_hit_tests_and_applies_policy() {
+        let at = |x: f64, y: f64| LocalNode {
+            local_bounds: Rect::new(0.0, 0.0, 10.0, 10.0),
+            local_transform: Affine::translate((x, y)),
+            ..Default::default()
+        };
+        let world = Rect::new(0.0, 0.0, 1000.0, 1000.0);
+        let ids_at = |tree: &Tree<QuadtreeF64>, x: f64, y: f64| -> Vec<NodeId> {
+            tree.intersect_rect(Rect::new(x, y, x + 1.0, y + 1.0), QueryFilter::new())
+                .collect()
+        };
+
+        let mut tree = Tree::with_world_bounds(world, WorldBoundsPolicy::Reject);
+        let inside: Vec<_> = (0..200_u32)
+            .map(|i| tree.insert(None, at(f64::from(i % 20) * 45.0, f64::from(i / 20) * 45.0)))
+            .collect();
+        let outside = tree.insert(None, at(2000.0, 5.0));
+        let straddling = tree.insert(None, at(995.0, 500.0));
+        let _ = tree.commit();
+        // Only the part inside the bounds matters for a straddling node.
+        assert_eq!(ids_at(&tree, 997.0, 502.0), vec![straddling]);
+        assert_eq!(ids_at(&tree, 1002.0, 502.0), vec![straddling]);
+        for (i, &id) in inside.iter().enumerate() {
+            let i = u32::try_from(i).unwrap();
+            let (x, y) = (
+                f64::from(i % 20) * 45.0 + 5.0,
+                f64::from(i / 20) * 45.0 + 5.0,
+            );
+            assert_eq!(
+                tree.hit_test_point(Point::new(x, y), QueryFilter::new())
+                    .map(|h| h.node),
+                Some(id)
+            );
+        }
+        assert!(ids_at(&tree, 2005.0, 8.0).is_empty());
+        assert_eq!(tree.world_extent(), Some(world));
+        // Moving back inside makes the node hittable again.
+        tree.set_local_transform(outside, Affine::translate((950.0, 980.0)));
+        let _ = tree.commit();
+        assert_eq!(ids_at(&tree, 955.0, 985.0), vec![outside]);
+
+        let mut tree = Tree::with_world_bounds(world, WorldBoundsPolicy::Expand);
+        let a = tree.insert(None, at(100.0, 100.0));
+        let far = tree.insert(None, at(-3000.0, 2500.0));
+        let _ = tree.commit();
+        let extent = tree.world_extent().unwrap();
+        assert!(extent.contains_rect(Rect::new(-3000.0, 0.0, 1000.0, 2510.0)));
+        assert_eq!(ids_at(&tree, -2995.0, 2505.0), vec![far]);
+        assert_eq!(ids_at(&tree, 105.0, 105.0), vec![a]);
+    }
+
+    #[test]
+    fn subtree_bounds_unions_descendants() {
+        let mut tree = Tree::new();
+        let at = |x: f64, y: f64, w: f64| LocalNode {
+            local_bounds: Rect::new(0.0, 0.0, w, w),
+            local_transform: Affine::translate((x, y)),
+            ..Default::default()
+        };
+        let other = tree.insert(None, at(500.0, 500.0, 10.0));
+        let parent = tree.insert(None, at(10.0, 10.0, 20.0));
+        let a = tree.insert(Some(parent), at(-30.0, 5.0, 5.0));
+        let b = tree.insert(Some(parent), at(40.0, 60.0, 8.0));
+        let c = tree.insert(Some(b), at(100.0, -50.0, 4.0));
+        let _ = tree.commit();
+
+        let expected = [parent, a, b, c]
+            .into_iter()
+            .map(|n| tree.world_bounds(n).unwrap())
+            .reduce(|u, r| u.union(r))
+            .unwrap();
+        assert_eq!(tree.subtree_bounds(parent), Some(expected));
+        assert_eq!(expected, Rect::new(-20.0, 10.0, 154.0, 78.0));
+        assert_eq!(tree.subtree_bounds(c), tree.world_bounds(c));
+        assert!(!expected.contains(tree.world_bounds(other).unwrap().origin()));
+
+        tree.remove(b);
+        assert_eq!(tree.subtree_bounds(b), None);
+    }
+
+    #[test]
+    fn iter_bfs_visits_levels_in_order() {
+        let mut tree = Tree::new();
+        let node = LocalNode::default;
+        let r0 = tree.insert(None, node());
+        let a = tree.insert(Some(r0), node());
+        let b = tree.insert(Some(r0), node());
+        let a1 = tree.insert(Some(a), node());
+        let r1 = tree.insert(None, node());
+        let b1 = tree.insert(Some(b), node());
+        let a1x = tree.insert(Some(a1), node());
+        let c = tree.insert(Some(r1), node());
+        let gone = tree.insert(Some(c), node());
+        tree.remove(gone);
+
+        let order: Vec<_> = tree.iter_bfs().collect();
+        assert_eq!(
+            order,
+            vec![
+                (r0, 0),
+                (r1, 0),
+                (a, 1),
+                (b, 1),
+                (c, 1),
+                (a1, 2),
+                (b1, 2),
+                (a1x, 3),
+            ]
+        );
+        assert!(order.windows(2).all(|w| w[0].1 <= w[1].1));
+        for &(id, depth) in &order {
+            let expected = tree
+                .parent_of(id)
+                .map_or(0, |p| order.iter().find(|(n, _)| *n == p).unwrap().1 + 1);
+            assert_eq!(depth, expected);
+        }
+    }
+
     #[test]
     fn traversal_respects_liveness() {
         let mut tree = Tree::new();
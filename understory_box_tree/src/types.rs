@@ -44,6 +44,31 @@ impl NodeId {
     pub(crate) const fn idx(self) -> usize {
         self.0 as usize
     }
+
+    /// Pack this id into a `u64` (generation in the high 32 bits, slot in the low 32 bits).
+    ///
+    /// Useful for storing ids in external systems; see [`NodeId::from_raw`].
+    pub const fn to_raw(self) -> u64 {
+        ((self.1 as u64) << 32) | self.0 as u64
+    }
+
+    /// Unpack an id previously produced by [`NodeId::to_raw`].
+    ///
+    /// The result is only meaningful for the tree that issued the original id.
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "Each half of the packed u64 is exactly 32 bits."
+    )]
+    pub const fn from_raw(raw: u64) -> Self {
+        Self(raw as u32, (raw >> 32) as u32)
+    }
+}
+
+impl core::fmt::Display for NodeId {
+    /// Formats as `#slot@ggeneration`, e.g. `#3@g1`.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#{}@g{}", self.0, self.1)
+    }
 }
 
 bitflags::bitflags! {
@@ -78,6 +103,10 @@ pub struct LocalNode {
     pub z_index: i32,
     /// Visibility and picking flags.
     pub flags: NodeFlags,
+    /// Spatial index layer. Each layer has its own index, so
+    /// [`Tree::hit_test_point_layer`](crate::Tree::hit_test_point_layer) and
+    /// [`Tree::intersect_rect_layer`](crate::Tree::intersect_rect_layer) never see other layers.
+    pub layer: u8,
 }
 
 impl Default for LocalNode {
@@ -88,6 +117,21 @@ impl Default for LocalNode {
             local_clip: None,
             z_index: 0,
             flags: NodeFlags::default(),
+            layer: 0,
         }
     }
 }
+
+/// Declarative description of one node for [`Tree::build`](crate::Tree::build).
+///
+/// Parents are referenced by their position in the same spec slice, so a whole
+/// scene can be written as a flat list.
+#[derive(Clone, Debug, Default)]
+pub struct NodeSpec {
+    /// Index of the parent within the spec slice, or `None` for a root.
+    ///
+    /// Must refer to an earlier entry.
+    pub parent: Option<usize>,
+    /// Local geometry for the node.
+    pub local: LocalNode,
+}
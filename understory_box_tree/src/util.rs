@@ -1,7 +1,7 @@
 // Copyright 2025 the Understory Authors
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
-use kurbo::{Affine, Rect};
+use kurbo::{Affine, Point, Rect, RoundedRect};
 use understory_index::Aabb2D;
 
 /// Transform an axis-aligned `Rect` by an `Affine` and return a conservative
@@ -15,6 +15,49 @@ pub(crate) fn transform_rect_bbox(affine: Affine, rect: Rect) -> Rect {
     Rect::new(min_x + e, min_y + f, max_x + e, max_y + f)
 }
 
+/// Returns true if `pt` lies inside the rounded rectangle, honoring its corner radii.
+///
+/// Radii are clamped to half the shorter side.
+pub(crate) fn rounded_rect_contains(rr: &RoundedRect, pt: Point) -> bool {
+    let r = rr.rect();
+    if pt.x < r.x0 || pt.x > r.x1 || pt.y < r.y0 || pt.y > r.y1 {
+        return false;
+    }
+    let radii = rr.radii();
+    let max_r = 0.5 * r.width().min(r.height());
+    // (radius, corner x, corner y, inward x sign, inward y sign)
+    let corners = [
+        (radii.top_left, r.x0, r.y0, 1.0, 1.0),
+        (radii.top_right, r.x1, r.y0, -1.0, 1.0),
+        (radii.bottom_right, r.x1, r.y1, -1.0, -1.0),
+        (radii.bottom_left, r.x0, r.y1, 1.0, -1.0),
+    ];
+    for (radius, x, y, sx, sy) in corners {
+        let radius = radius.clamp(0.0, max_r);
+        let (cx, cy) = (x + sx * radius, y + sy * radius);
+        // Only the square between the corner and its circle center is rounded off.
+        if (pt.x - cx) * sx < 0.0 && (pt.y - cy) * sy < 0.0 {
+            let (dx, dy) = (pt.x - cx, pt.y - cy);
+            return dx * dx + dy * dy <= radius * radius;
+        }
+    }
+    true
+}
+
+/// Returns true if the axis-aligned `rect` overlaps the rounded rectangle's region.
+pub(crate) fn rounded_rect_intersects_rect(rr: &RoundedRect, rect: Rect) -> bool {
+    let r = rr.rect();
+    let (x0, y0) = (r.x0.max(rect.x0), r.y0.max(rect.y0));
+    let (x1, y1) = (r.x1.min(rect.x1), r.y1.min(rect.y1));
+    if x0 > x1 || y0 > y1 {
+        return false;
+    }
+    // The point of the overlap closest to the rounded rect's center is inside the
+    // rounded region whenever any point of the overlap is.
+    let c = r.center();
+    rounded_rect_contains(rr, Point::new(c.x.clamp(x0, x1), c.y.clamp(y0, y1)))
+}
+
 pub(crate) fn rect_to_aabb(r: Rect) -> Aabb2D<f64> {
     Aabb2D::new(r.x0, r.y0, r.x1, r.y1)
 }
@@ -4,11 +4,23 @@
 //! Backend trait for spatial indexing implementations.
 
 use alloc::boxed::Box;
+use alloc::collections::BinaryHeap;
 use alloc::vec::Vec;
+use core::cmp::{Ordering, Reverse};
 
-use crate::types::Aabb2D;
+use crate::stats::BackendStats;
+#[cfg(feature = "stats")]
+use crate::stats::QueryStats;
+use crate::types::{Aabb2D, Scalar};
 use core::fmt::Debug;
 
+/// Identifies a spatial group (a leaf node, cell, or bucket) within a backend.
+///
+/// Returned by [`Backend::query_rect_grouped`]. Ids are only meaningful for the backend
+/// that produced them and only until its next mutation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GroupId(pub usize);
+
 /// Spatial backend abstraction used by `IndexGeneric`.
 pub trait Backend<T: Copy + PartialOrd + Debug> {
     /// Insert a new slot into the spatial structure.
@@ -17,18 +29,205 @@ pub trait Backend<T: Copy + PartialOrd + Debug> {
     /// Update an existing slot's AABB.
     fn update(&mut self, slot: usize, aabb: Aabb2D<T>);
 
+    /// Insert a new slot whose AABB is expected to lie near the existing slot `hint`.
+    ///
+    /// Tree backends start the descent from the deepest node on `hint`'s path that already
+    /// contains `aabb`, skipping the upper levels. Unknown hints fall back to
+    /// [`insert`](Self::insert), which is also the default.
+    fn insert_near(&mut self, slot: usize, aabb: Aabb2D<T>, hint: usize) {
+        let _ = hint;
+        self.insert(slot, aabb);
+    }
+
     /// Remove a slot from the spatial structure.
     fn remove(&mut self, slot: usize);
 
-    /// Clear all spatial structures.
+    /// Clear all spatial structures and release their storage.
     fn clear(&mut self);
 
+    /// Clear all spatial structures, keeping allocated storage for reuse.
+    ///
+    /// Default: [`clear`](Self::clear). The built-in backends truncate their tables
+    /// instead, so refilling up to the previous size does not reallocate them.
+    fn clear_retain(&mut self) {
+        self.clear();
+    }
+
+    /// Release storage held for removed items, without changing query results.
+    ///
+    /// Default: no-op. The R-tree and BVH drop unreachable arena nodes.
+    fn compact(&mut self) {}
+
+    /// Live and dead arena nodes, `(live, dead)`, tracked as the structure changes.
+    ///
+    /// Unlike [`stats`](Self::stats) this does not walk the structure, so it is cheap enough
+    /// to check every frame. Default: `(0, 0)`. The R-tree and BVH report their arenas.
+    fn arena_usage(&self) -> (usize, usize) {
+        (0, 0)
+    }
+
+    /// Do at most `max_nodes` steps of compaction; returns whether dead nodes remain.
+    ///
+    /// Each step drops or relocates one arena node, so many calls together release what
+    /// [`compact`](Self::compact) does at once, without changing query results.
+    /// Default: [`compact`](Self::compact) in one call.
+    fn compact_step(&mut self, max_nodes: usize) -> bool {
+        let _ = max_nodes;
+        self.compact();
+        false
+    }
+
+    /// Replace all contents with `pairs` of `(slot, aabb)`.
+    ///
+    /// Default: [`clear`](Self::clear), then [`insert`](Self::insert) each pair. The R-tree
+    /// and BVH override this with their bulk builders, which yield better balanced trees,
+    /// keeping their configuration (fan-out, leaf size).
+    fn bulk_load(&mut self, pairs: &[(usize, Aabb2D<T>)]) {
+        self.clear();
+        for &(slot, aabb) in pairs {
+            self.insert(slot, aabb);
+        }
+    }
+
+    /// Recompute node bounds bottom-up from the stored boxes, without restructuring.
+    ///
+    /// Much cheaper than a rebuild, and leaves the tree shape (and so query cost) as is.
+    /// Default: no-op. The BVH overrides this.
+    fn refit(&mut self) {}
+
     /// Visit slots whose AABB contains the point.
     fn visit_point<F: FnMut(usize)>(&self, x: T, y: T, f: F);
 
     /// Visit slots whose AABB intersects the rectangle.
     fn visit_rect<F: FnMut(usize)>(&self, rect: Aabb2D<T>, f: F);
 
+    /// Visit slots whose AABB contains the point until `f` returns `false`.
+    ///
+    /// Returns `true` if every match was visited and `false` if `f` stopped the query,
+    /// which makes "is anything here?" checks cheap. The built-in backends stop traversing
+    /// as soon as `f` returns `false`; the default runs [`visit_point`](Self::visit_point)
+    /// to the end but stops calling `f`.
+    fn query_point_visit<F: FnMut(usize) -> bool>(&self, x: T, y: T, mut f: F) -> bool {
+        let mut go = true;
+        self.visit_point(x, y, |i| {
+            if go {
+                go = f(i);
+            }
+        });
+        go
+    }
+
+    /// Visit slots whose AABB intersects the rectangle until `f` returns `false`.
+    ///
+    /// Returns `true` if every match was visited and `false` if `f` stopped the query.
+    /// The built-in backends stop traversing as soon as `f` returns `false`; the default
+    /// runs [`visit_rect`](Self::visit_rect) to the end but stops calling `f`.
+    fn query_rect_visit<F: FnMut(usize) -> bool>(&self, rect: Aabb2D<T>, mut f: F) -> bool {
+        let mut go = true;
+        self.visit_rect(rect, |i| {
+            if go {
+                go = f(i);
+            }
+        });
+        go
+    }
+
+    /// Return the traversal counts recorded by [`visit_point`](Self::visit_point) and
+    /// [`visit_rect`](Self::visit_rect) since the last call, and reset them.
+    ///
+    /// The built-in backends count their work; the default reports nothing.
+    #[cfg(feature = "stats")]
+    fn take_query_stats(&self) -> QueryStats {
+        QueryStats::default()
+    }
+
+    /// Describe the backend's current structure: node, leaf and item counts and depth.
+    ///
+    /// Computed on demand, typically in O(nodes). The built-in backends report their
+    /// structure; the default reports nothing.
+    fn stats(&self) -> BackendStats {
+        BackendStats::default()
+    }
+
+    /// Approximate heap bytes held by the backend, counting `Vec` capacities.
+    ///
+    /// Meant for comparing backends, not exact accounting: allocator overhead is ignored and
+    /// map-based storage is estimated per entry. The default reports nothing.
+    fn memory_bytes(&self) -> usize {
+        0
+    }
+
+    /// Like [`visit_rect`](Self::visit_rect), but stop after visiting `max_nodes_visited` tree nodes.
+    ///
+    /// Returns `true` if the traversal finished, i.e. every intersecting slot was visited.
+    /// Tree backends override this; the default ignores the budget and always completes.
+    fn visit_rect_budgeted<F: FnMut(usize)>(
+        &self,
+        rect: Aabb2D<T>,
+        max_nodes_visited: usize,
+        f: F,
+    ) -> bool {
+        let _ = max_nodes_visited;
+        self.visit_rect(rect, f);
+        true
+    }
+
+    /// Return up to `k` slots nearest to the point, as `(slot, squared distance)`.
+    ///
+    /// Distances are measured from the point to each slot's AABB and are zero inside it.
+    /// Results are ordered by distance, with equal distances in ascending slot order.
+    /// Tree backends override this with a best-first traversal; the default returns
+    /// `None`, and [`IndexGeneric::query_knn`](crate::IndexGeneric::query_knn) then
+    /// falls back to a full scan.
+    fn query_knn(&self, x: T, y: T, k: usize) -> Option<Vec<(usize, T::Acc)>>
+    where
+        T: Scalar,
+    {
+        let _ = (x, y, k);
+        None
+    }
+
+    /// Return the slot nearest to the point, as `(slot, squared distance)`.
+    ///
+    /// Distances are as in [`query_knn`](Self::query_knn), with ties going to the lowest
+    /// slot. Returns `None` when the backend is empty. The built-in backends override this:
+    /// `FlatVec` with a linear scan, tree backends with a depth-first search that prunes
+    /// subtrees farther than the best hit so far. Default: the first result of
+    /// `query_knn(x, y, 1)`, so a backend supporting neither returns `None`.
+    fn nearest(&self, x: T, y: T) -> Option<(usize, T::Acc)>
+    where
+        T: Scalar,
+    {
+        self.query_knn(x, y, 1)?.into_iter().next()
+    }
+
+    /// Write slots whose AABB contains the point into `out`, without allocating.
+    ///
+    /// Returns the total number of matches; only the first `out.len()` are written, so a
+    /// return value larger than `out.len()` means the result was truncated. Default: counts
+    /// through `visit_point`, which is allocation-free for the built-in backends as long as
+    /// their traversal stack stays small.
+    fn query_point_into_buf(&self, x: T, y: T, out: &mut [usize]) -> usize {
+        let mut n = 0;
+        self.visit_point(x, y, |s| {
+            if let Some(o) = out.get_mut(n) {
+                *o = s;
+            }
+            n += 1;
+        });
+        n
+    }
+
+    /// Visit slots whose AABB contains each of `points`, calling `f(point_index, slot)`.
+    ///
+    /// Tree backends override this to descend once per node for all points inside it,
+    /// which shares traversal work when points cluster. Default: one `visit_point` per point.
+    fn visit_points<F: FnMut(usize, usize)>(&self, points: &[(T, T)], mut f: F) {
+        for (i, &(x, y)) in points.iter().enumerate() {
+            self.visit_point(x, y, |s| f(i, s));
+        }
+    }
+
     /// Query slots whose AABB contains the point. Default: collects `visit_point`.
     fn query_point<'a>(&'a self, x: T, y: T) -> Box<dyn Iterator<Item = usize> + 'a> {
         let mut out = Vec::new();
@@ -42,4 +241,139 @@ pub trait Backend<T: Copy + PartialOrd + Debug> {
         self.visit_rect(rect, |i| out.push(i));
         Box::new(out.into_iter())
     }
+
+    /// Query slots whose AABB intersects the rectangle, grouped by spatial locality.
+    ///
+    /// Each group holds the hits stored in the same leaf node or cell, which is handy for
+    /// batching work (e.g. instanced draws) by locality. Empty groups are not reported.
+    /// Default: a single group `GroupId(0)` containing every hit.
+    fn query_rect_grouped<'a>(
+        &'a self,
+        rect: Aabb2D<T>,
+    ) -> Box<dyn Iterator<Item = (GroupId, Vec<usize>)> + 'a> {
+        let mut out = Vec::new();
+        self.visit_rect(rect, |i| out.push(i));
+        if out.is_empty() {
+            Box::new(core::iter::empty())
+        } else {
+            Box::new(core::iter::once((GroupId(0), out)))
+        }
+    }
+}
+
+/// Candidate of a best-first k-nearest traversal, ordered by distance, then nodes before
+/// items, then id.
+struct KnnCandidate<A> {
+    dist: A,
+    item: bool,
+    id: usize,
+}
+
+impl<A: PartialOrd> Ord for KnnCandidate<A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.dist
+            .partial_cmp(&other.dist)
+            .unwrap_or(Ordering::Equal)
+            .then(self.item.cmp(&other.item))
+            .then(self.id.cmp(&other.id))
+    }
+}
+
+impl<A: PartialOrd> PartialOrd for KnnCandidate<A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<A: PartialOrd> PartialEq for KnnCandidate<A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<A: PartialOrd> Eq for KnnCandidate<A> {}
+
+/// Best-first k-nearest traversal shared by the tree backends.
+///
+/// Starts from node `root` at distance `root_dist` and repeatedly pops the nearest
+/// candidate: items are emitted, nodes are passed to `expand`, which pushes their children
+/// as `(is_item, id, dist)`. Child distances must not be smaller than their parent's. At
+/// equal distance nodes pop before items, so ties come out in slot order.
+pub(crate) fn best_first_knn<A: Copy + PartialOrd>(
+    root: usize,
+    root_dist: A,
+    k: usize,
+    mut expand: impl FnMut(usize, &mut dyn FnMut(bool, usize, A)),
+) -> Vec<(usize, A)> {
+    let mut heap = BinaryHeap::new();
+    heap.push(Reverse(KnnCandidate {
+        dist: root_dist,
+        item: false,
+        id: root,
+    }));
+    let mut out = Vec::new();
+    while out.len() < k
+        && let Some(Reverse(c)) = heap.pop()
+    {
+        if c.item {
+            out.push((c.id, c.dist));
+        } else {
+            expand(c.id, &mut |item, id, dist| {
+                heap.push(Reverse(KnnCandidate { dist, item, id }));
+            });
+        }
+    }
+    out
+}
+
+/// Heap bytes reserved by `v`: its capacity, not just its length.
+#[inline]
+pub(crate) fn vec_bytes<X>(v: &Vec<X>) -> usize {
+    v.capacity() * size_of::<X>()
+}
+
+/// Whether a hit at `dist` in `slot` beats `best`: nearer, or equally near in a lower slot.
+#[inline]
+pub(crate) fn improves_nearest<A: PartialOrd>(
+    best: &Option<(usize, A)>,
+    slot: usize,
+    dist: A,
+) -> bool {
+    best.as_ref()
+        .is_none_or(|(s, d)| dist < *d || (dist == *d && slot < *s))
+}
+
+/// Traversal stack that keeps its first `N` entries inline and only spills to the heap
+/// beyond that, so shallow traversals do not allocate.
+pub(crate) struct SmallStack<T: Copy, const N: usize> {
+    inline: [T; N],
+    len: usize,
+    spill: Vec<T>,
+}
+
+impl<T: Copy, const N: usize> SmallStack<T, N> {
+    /// Create a stack holding `first`.
+    pub(crate) fn new(first: T) -> Self {
+        Self {
+            inline: [first; N],
+            len: 1,
+            spill: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, v: T) {
+        if self.len < N {
+            self.inline[self.len] = v;
+            self.len += 1;
+        } else {
+            self.spill.push(v);
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<T> {
+        self.spill.pop().or_else(|| {
+            self.len = self.len.checked_sub(1)?;
+            Some(self.inline[self.len])
+        })
+    }
 }
@@ -0,0 +1,194 @@
+// Copyright 2025 the Understory Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Backend chosen at runtime from the built-in ones.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::backend::{Backend, GroupId};
+use crate::backends::bvh::Bvh;
+use crate::backends::flatvec::FlatVec;
+use crate::backends::rtree::RTree;
+use crate::stats::BackendStats;
+#[cfg(feature = "stats")]
+use crate::stats::QueryStats;
+use crate::types::{Aabb2D, Scalar};
+
+/// Which built-in backend an [`AnyBackend`] uses.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BackendKind {
+    /// [`FlatVec`]: linear scans.
+    #[default]
+    FlatVec,
+    /// [`RTree`] with the default fan-out.
+    RTree,
+    /// [`Bvh`] with default parameters.
+    Bvh,
+}
+
+/// A built-in backend selected at runtime.
+///
+/// Lets a single index type start small on `FlatVec` and move to a tree once it grows; see
+/// [`IndexGeneric::replace_backend`](crate::IndexGeneric::replace_backend). Every call is
+/// forwarded to the wrapped backend.
+#[derive(Debug)]
+pub enum AnyBackend<T: Scalar> {
+    /// A [`FlatVec`] backend.
+    FlatVec(FlatVec<T>),
+    /// An [`RTree`] backend.
+    RTree(RTree<T, ()>),
+    /// A [`Bvh`] backend.
+    Bvh(Bvh<T>),
+}
+
+impl<T: Scalar> AnyBackend<T> {
+    /// Create an empty backend of the given kind.
+    pub fn new(kind: BackendKind) -> Self {
+        match kind {
+            BackendKind::FlatVec => Self::FlatVec(FlatVec::default()),
+            BackendKind::RTree => Self::RTree(RTree::default()),
+            BackendKind::Bvh => Self::Bvh(Bvh::default()),
+        }
+    }
+
+    /// The kind of the wrapped backend.
+    pub fn kind(&self) -> BackendKind {
+        match self {
+            Self::FlatVec(_) => BackendKind::FlatVec,
+            Self::RTree(_) => BackendKind::RTree,
+            Self::Bvh(_) => BackendKind::Bvh,
+        }
+    }
+}
+
+impl<T: Scalar> Default for AnyBackend<T> {
+    fn default() -> Self {
+        Self::new(BackendKind::default())
+    }
+}
+
+macro_rules! forward {
+    ($self:expr, $b:ident => $e:expr) => {
+        match $self {
+            AnyBackend::FlatVec($b) => $e,
+            AnyBackend::RTree($b) => $e,
+            AnyBackend::Bvh($b) => $e,
+        }
+    };
+}
+
+impl<T: Scalar> Backend<T> for AnyBackend<T> {
+    fn insert(&mut self, slot: usize, aabb: Aabb2D<T>) {
+        forward!(self, b => b.insert(slot, aabb));
+    }
+
+    fn update(&mut self, slot: usize, aabb: Aabb2D<T>) {
+        forward!(self, b => b.update(slot, aabb));
+    }
+
+    fn insert_near(&mut self, slot: usize, aabb: Aabb2D<T>, hint: usize) {
+        forward!(self, b => b.insert_near(slot, aabb, hint));
+    }
+
+    fn remove(&mut self, slot: usize) {
+        forward!(self, b => b.remove(slot));
+    }
+
+    fn clear(&mut self) {
+        forward!(self, b => b.clear());
+    }
+
+    fn clear_retain(&mut self) {
+        forward!(self, b => b.clear_retain());
+    }
+
+    fn compact(&mut self) {
+        forward!(self, b => b.compact());
+    }
+
+    fn arena_usage(&self) -> (usize, usize) {
+        forward!(self, b => b.arena_usage())
+    }
+
+    fn compact_step(&mut self, max_nodes: usize) -> bool {
+        forward!(self, b => b.compact_step(max_nodes))
+    }
+
+    fn bulk_load(&mut self, pairs: &[(usize, Aabb2D<T>)]) {
+        forward!(self, b => b.bulk_load(pairs));
+    }
+
+    fn refit(&mut self) {
+        forward!(self, b => b.refit());
+    }
+
+    fn visit_point<F: FnMut(usize)>(&self, x: T, y: T, f: F) {
+        forward!(self, b => b.visit_point(x, y, f));
+    }
+
+    fn visit_rect<F: FnMut(usize)>(&self, rect: Aabb2D<T>, f: F) {
+        forward!(self, b => b.visit_rect(rect, f));
+    }
+
+    fn query_point_visit<F: FnMut(usize) -> bool>(&self, x: T, y: T, f: F) -> bool {
+        forward!(self, b => b.query_point_visit(x, y, f))
+    }
+
+    fn query_rect_visit<F: FnMut(usize) -> bool>(&self, rect: Aabb2D<T>, f: F) -> bool {
+        forward!(self, b => b.query_rect_visit(rect, f))
+    }
+
+    #[cfg(feature = "stats")]
+    fn take_query_stats(&self) -> QueryStats {
+        forward!(self, b => b.take_query_stats())
+    }
+
+    fn stats(&self) -> BackendStats {
+        forward!(self, b => b.stats())
+    }
+
+    fn memory_bytes(&self) -> usize {
+        forward!(self, b => b.memory_bytes())
+    }
+
+    fn visit_rect_budgeted<F: FnMut(usize)>(
+        &self,
+        rect: Aabb2D<T>,
+        max_nodes_visited: usize,
+        f: F,
+    ) -> bool {
+        forward!(self, b => b.visit_rect_budgeted(rect, max_nodes_visited, f))
+    }
+
+    fn query_knn(&self, x: T, y: T, k: usize) -> Option<Vec<(usize, T::Acc)>> {
+        forward!(self, b => b.query_knn(x, y, k))
+    }
+
+    fn nearest(&self, x: T, y: T) -> Option<(usize, T::Acc)> {
+        forward!(self, b => b.nearest(x, y))
+    }
+
+    fn query_point_into_buf(&self, x: T, y: T, out: &mut [usize]) -> usize {
+        forward!(self, b => b.query_point_into_buf(x, y, out))
+    }
+
+    fn visit_points<F: FnMut(usize, usize)>(&self, points: &[(T, T)], f: F) {
+        forward!(self, b => b.visit_points(points, f));
+    }
+
+    fn query_point<'a>(&'a self, x: T, y: T) -> Box<dyn Iterator<Item = usize> + 'a> {
+        forward!(self, b => b.query_point(x, y))
+    }
+
+    fn query_rect<'a>(&'a self, rect: Aabb2D<T>) -> Box<dyn Iterator<Item = usize> + 'a> {
+        forward!(self, b => b.query_rect(rect))
+    }
+
+    fn query_rect_grouped<'a>(
+        &'a self,
+        rect: Aabb2D<T>,
+    ) -> Box<dyn Iterator<Item = (GroupId, Vec<usize>)> + 'a> {
+        forward!(self, b => b.query_rect_grouped(rect))
+    }
+}
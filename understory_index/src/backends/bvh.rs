@@ -3,19 +3,42 @@
 
 //! Binary bounding hierarchy backend generic over scalar `T: Scalar`.
 
+use alloc::boxed::Box;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt::Debug;
 
-use crate::backend::Backend;
-use crate::types::{Aabb2D, Scalar, area, union_aabb};
+use crate::backend::{Backend, GroupId, SmallStack, best_first_knn, improves_nearest, vec_bytes};
+#[cfg(feature = "stats")]
+use crate::stats::QueryStats;
+use crate::stats::{BackendStats, StatsCounter};
+use crate::types::{Aabb2D, Scalar, area, point_dist2, sah_cost, union_aabb};
 
 /// A simple BVH backend using SAH-like splits.
+///
+/// # Result order
+///
+/// Queries walk the tree depth-first and report the items of each leaf in storage order.
+/// By default the right child of a node is visited before the left one. With
+/// [`set_left_to_right`](Self::set_left_to_right) the left child goes first; since splits
+/// put the lower half of the split axis on the left, results then come out roughly in
+/// ascending coordinate order. Either way the order depends only on the tree's shape, so
+/// building from the same input in the same sequence yields the same order, but it can
+/// change after any structural update.
 pub struct Bvh<T: Scalar> {
     max_leaf: usize,
+    update_slack: Option<T>,
+    left_to_right: bool,
     root: Option<NodeIdx>,
     arena: Vec<Node<T>>,
     slots: Vec<Option<Aabb2D<T>>>,
+    // Arena index of the leaf holding each live slot.
+    leaves: Vec<usize>,
+    // Arena indices of nodes that died since the last compaction; may hold stale entries.
+    holes: Vec<usize>,
+    // Number of dead nodes still in the arena.
+    dead: usize,
+    stats: StatsCounter,
 }
 
 enum Kind<T: Scalar> {
@@ -26,6 +49,9 @@ enum Kind<T: Scalar> {
 struct Node<T: Scalar> {
     bbox: Aabb2D<T>,
     kind: Kind<T>,
+    parent: Option<NodeIdx>,
+    // Unreachable from the root; reclaimed by compaction.
+    dead: bool,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -44,27 +70,229 @@ impl NodeIdx {
 impl<T: Scalar> Default for Bvh<T> {
     fn default() -> Self {
         Self {
-            max_leaf: 8,
+            max_leaf: DEFAULT_MAX_LEAF,
+            update_slack: None,
+            left_to_right: false,
             root: None,
             arena: Vec::new(),
             slots: Vec::new(),
+            leaves: Vec::new(),
+            holes: Vec::new(),
+            dead: 0,
+            stats: StatsCounter::default(),
         }
     }
 }
 
+/// Leaf capacity of a BVH built without an explicit `max_leaf`.
+const DEFAULT_MAX_LEAF: usize = 8;
+
 // Reduce clippy::type_complexity noise for local helpers.
 type BvhItem<TS> = (usize, Aabb2D<TS>);
 type BvhItems<TS> = Vec<BvhItem<TS>>;
-type BvhBestSplit<TS> = Option<(crate::types::ScalarAcc<TS>, BvhItems<TS>, BvhItems<TS>)>;
+type BvhBestSplit<TS> = Option<(crate::types::ScalarAcc<TS>, usize, usize)>;
 
 impl<T: Scalar> Bvh<T> {
+    /// Create a BVH whose leaves hold at most `max_leaf` items (default 8).
+    ///
+    /// Smaller leaves favor queries; larger leaves make builds and updates cheaper.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_leaf < 2`: an overflowing leaf must have at least two items to split.
+    pub fn with_max_leaf(max_leaf: usize) -> Self {
+        assert!(
+            max_leaf >= 2,
+            "BVH max_leaf must be at least 2 (got {max_leaf})"
+        );
+        Self {
+            max_leaf,
+            ..Self::default()
+        }
+    }
+
+    /// Create a BVH that updates items in place while they stay near their leaf.
+    ///
+    /// On [`update`](Backend::update), if the new AABB still fits inside the item's current
+    /// leaf bounds grown by `slack` on every side, the item is changed in place and its
+    /// ancestors are refit; otherwise it is removed and reinserted as usual. This avoids
+    /// restructuring for small per-frame motion, at the cost of looser leaves. `slack` is an
+    /// absolute distance in scene units; zero only allows moves within the current leaf bounds.
+    pub fn with_update_slack(slack: T) -> Self {
+        Self {
+            update_slack: Some(slack),
+            ..Self::default()
+        }
+    }
+
+    /// Visit the left child of each node before the right one during queries.
+    ///
+    /// See [Result order](Self#result-order). Only affects the order of results.
+    pub fn set_left_to_right(&mut self, enabled: bool) {
+        self.left_to_right = enabled;
+    }
+
+    /// Drop arena nodes that are no longer reachable from the root.
+    ///
+    /// Removals and splits leave dead nodes behind, so the arena only grows. This rebuilds it
+    /// with just the live nodes, children before parents in depth-first order, and remaps
+    /// all node indices. The tree shape and query results are unchanged.
+    pub fn compact_arena(&mut self) {
+        let mut old = core::mem::take(&mut self.arena);
+        if let Some(root) = self.root {
+            let root = Self::move_node(&mut old, &mut self.arena, root);
+            self.root = Some(root);
+        }
+        self.holes.clear();
+        self.dead = 0;
+        self.relink();
+    }
+
+    /// Reclaim at most `max_nodes` dead arena nodes; returns whether any remain.
+    ///
+    /// Dead nodes at the end of the arena are dropped, others are overwritten by the last
+    /// node, whose links are patched in O(1). Unlike [`compact_arena`](Self::compact_arena)
+    /// this keeps the live nodes where they are, apart from the moved ones.
+    pub fn compact_arena_step(&mut self, max_nodes: usize) -> bool {
+        for _ in 0..max_nodes {
+            if self.dead == 0 {
+                break;
+            }
+            if self.arena.last().is_some_and(|n| n.dead) {
+                self.arena.pop();
+                self.dead -= 1;
+                continue;
+            }
+            // A dead node below the live last one is always listed.
+            let hole = self.holes.pop().expect("dead nodes are listed in holes");
+            if hole < self.arena.len() && self.arena[hole].dead {
+                let from = self.arena.len() - 1;
+                self.arena.swap_remove(hole);
+                self.dead -= 1;
+                self.relocate(from, hole);
+            }
+        }
+        if self.dead == 0 {
+            self.holes.clear();
+        }
+        self.dead > 0
+    }
+
+    /// Point every link to the node moved from arena index `from` at its new index `to`.
+    fn relocate(&mut self, from: usize, to: usize) {
+        let (from, to) = (NodeIdx::new(from), NodeIdx::new(to));
+        match self.arena[to.get()].parent {
+            None => self.root = Some(to),
+            Some(p) => {
+                if let Kind::Internal { left, right } = &mut self.arena[p.get()].kind {
+                    for c in [left, right] {
+                        if *c == from {
+                            *c = to;
+                        }
+                    }
+                }
+            }
+        }
+        match &self.arena[to.get()].kind {
+            Kind::Leaf(items) => {
+                for &(s, _) in items {
+                    self.leaves[s] = to.get();
+                }
+            }
+            &Kind::Internal { left, right } => {
+                for c in [left, right] {
+                    self.arena[c.get()].parent = Some(to);
+                }
+            }
+        }
+    }
+
+    /// Remove `slot`, stored at `old`, from the tree and count the nodes that died.
+    fn remove_from_tree(&mut self, root: NodeIdx, slot: usize, old: &Aabb2D<T>) {
+        let before = self.holes.len();
+        let _ = Self::remove_node(
+            &mut self.arena,
+            &mut self.leaves,
+            &mut self.holes,
+            root.get(),
+            slot,
+            old,
+        );
+        self.dead += self.holes.len() - before;
+    }
+
+    /// Move the subtree at `i` from `old` into `new`; returns its index in `new`.
+    fn move_node(old: &mut [Node<T>], new: &mut Vec<Node<T>>, i: NodeIdx) -> NodeIdx {
+        let kind = match core::mem::replace(&mut old[i.get()].kind, Kind::Leaf(Vec::new())) {
+            Kind::Leaf(items) => Kind::Leaf(items),
+            Kind::Internal { left, right } => Kind::Internal {
+                left: Self::move_node(old, new, left),
+                right: Self::move_node(old, new, right),
+            },
+        };
+        new.push(Node {
+            bbox: old[i.get()].bbox,
+            kind,
+            parent: None,
+            dead: false,
+        });
+        NodeIdx::new(new.len() - 1)
+    }
+
+    /// Children of an internal node in stack push order (the last one is visited first).
+    fn push_order(&self, left: NodeIdx, right: NodeIdx) -> [NodeIdx; 2] {
+        if self.left_to_right {
+            [right, left]
+        } else {
+            [left, right]
+        }
+    }
+
     fn ensure_slot(&mut self, slot: usize, bbox: Aabb2D<T>) {
         if self.slots.len() <= slot {
             self.slots.resize_with(slot + 1, || None);
+            self.leaves.resize(slot + 1, 0);
         }
         self.slots[slot] = Some(bbox);
     }
 
+    /// Rebuild parent links and the slot→leaf map from the tree shape.
+    fn relink(&mut self) {
+        self.leaves.resize(self.slots.len(), 0);
+        let Some(root) = self.root else {
+            return;
+        };
+        self.arena[root.get()].parent = None;
+        let mut stack = vec![root];
+        while let Some(i) = stack.pop() {
+            match &self.arena[i.get()].kind {
+                Kind::Leaf(items) => {
+                    for &(s, _) in items {
+                        self.leaves[s] = i.get();
+                    }
+                }
+                &Kind::Internal { left, right } => {
+                    for c in [left, right] {
+                        self.arena[c.get()].parent = Some(i);
+                        stack.push(c);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Root→leaf arena path to the leaf holding the live `slot`, found by following
+    /// parent links up from the leaf in O(depth).
+    fn leaf_path(&self, slot: usize) -> Option<Vec<usize>> {
+        self.slots.get(slot).copied().flatten()?;
+        let mut path = vec![self.leaves[slot]];
+        while let Some(p) = self.arena[path[path.len() - 1]].parent {
+            path.push(p.get());
+        }
+        path.reverse();
+        Some(path)
+    }
+
     fn bbox_items(items: &[(usize, Aabb2D<T>)]) -> Aabb2D<T> {
         let mut it = items.iter();
         if let Some((_, b)) = it.next() {
@@ -82,25 +310,11 @@ impl<T: Scalar> Bvh<T> {
     /// choose `k` that minimizes `area(LB_k) * k + area(RB_k) * (n - k)`.
     fn split_sah(mut items: BvhItems<T>, max_leaf: usize) -> (BvhItems<T>, BvhItems<T>) {
         let n = items.len();
-        let min_children = (max_leaf / 2).max(2).min(n.saturating_sub(2));
+        // At least one item per side; never more than half of `n`.
+        let min_children = (max_leaf / 2).max(2).min(n / 2).max(1);
         let mut best: BvhBestSplit<T> = None;
         for axis in 0..2 {
-            items.sort_by(|a, b| {
-                let ca = if axis == 0 {
-                    Scalar::mid(a.1.min_x, a.1.max_x)
-                } else {
-                    Scalar::mid(a.1.min_y, a.1.max_y)
-                };
-                let cb = if axis == 0 {
-                    Scalar::mid(b.1.min_x, b.1.max_x)
-                } else {
-                    Scalar::mid(b.1.min_y, b.1.max_y)
-                };
-                match ca.partial_cmp(&cb) {
-                    Some(ord) => ord,
-                    None => core::cmp::Ordering::Equal,
-                }
-            });
+            Self::sort_by_centroid(&mut items, axis);
 
             // Precompute prefix/suffix bboxes for O(1) split evaluation
             let mut prefix: Vec<Aabb2D<T>> = Vec::with_capacity(n);
@@ -123,44 +337,121 @@ impl<T: Scalar> Bvh<T> {
             }
             suffix.reverse();
 
-            for k in min_children..=(n - min_children) {
+            for k in min_children..=n.saturating_sub(min_children) {
                 let lb = prefix[k - 1];
                 let rb = suffix[k];
-                let cost = area(&lb) * T::acc_from_usize(k) + area(&rb) * T::acc_from_usize(n - k);
+                // An overflowing cost is treated as maximally costly.
+                let Some(cost) = sah_cost(&lb, k, &rb, n - k) else {
+                    continue;
+                };
                 if best.as_ref().map(|(bc, _, _)| cost < *bc).unwrap_or(true) {
-                    let left = items[..k].to_vec();
-                    let right = items[k..].to_vec();
-                    best = Some((cost, left, right));
+                    best = Some((cost, axis, k));
                 }
             }
         }
-        let (_, l, r) = best.expect("BVH split requires at least 4 items");
-        (l, r)
+        match best {
+            Some((_, axis, k)) => {
+                // Items are left sorted along the last axis; restore the winning order.
+                if axis == 0 {
+                    Self::sort_by_centroid(&mut items, 0);
+                }
+                let right = items.split_off(k);
+                (items, right)
+            }
+            // No admissible SAH split (fewer than two items): fall back to a median
+            // split of the current order rather than panicking.
+            None => {
+                let right = items.split_off(n.div_ceil(2));
+                (items, right)
+            }
+        }
+    }
+
+    /// Stable sort by centroid along `axis` (0 = x, 1 = y).
+    fn sort_by_centroid(items: &mut [BvhItem<T>], axis: usize) {
+        let key = |b: &Aabb2D<T>| {
+            if axis == 0 {
+                Scalar::mid(b.min_x, b.max_x)
+            } else {
+                Scalar::mid(b.min_y, b.max_y)
+            }
+        };
+        items.sort_by(|a, b| {
+            key(&a.1)
+                .partial_cmp(&key(&b.1))
+                .unwrap_or(core::cmp::Ordering::Equal)
+        });
+    }
+
+    /// Build a BVH top-down from (slot, bbox) pairs, splitting each node with the SAH
+    /// split used by incremental inserts until leaves hold at most 8 items.
+    ///
+    /// For large static sets this gives a better balanced tree than inserting one by one.
+    /// To bulk-load a configured BVH, use [`Backend::bulk_load`].
+    pub fn bulk_build_default(pairs: &[(usize, Aabb2D<T>)]) -> Self {
+        let mut bvh = Self::default();
+        bvh.bulk_load(pairs);
+        bvh
+    }
+
+    /// Like [`bulk_build_default`](Self::bulk_build_default), with leaves of at most
+    /// `max_leaf` items; see [`with_max_leaf`](Self::with_max_leaf).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_leaf < 2`.
+    pub fn bulk_build_with_params(pairs: &[(usize, Aabb2D<T>)], max_leaf: usize) -> Self {
+        let mut bvh = Self::with_max_leaf(max_leaf);
+        bvh.bulk_load(pairs);
+        bvh
+    }
+
+    /// A node bounding `items`, to be filled in by the bulk builder.
+    fn placeholder(items: &[(usize, Aabb2D<T>)]) -> Node<T> {
+        Node {
+            bbox: Self::bbox_items(items),
+            kind: Kind::Leaf(Vec::new()),
+            parent: None,
+            dead: false,
+        }
     }
 
+    /// Insert `slot` below `node_idx`, adding the number of node bounds it tests to `visited`.
     fn insert_node(
         arena: &mut Vec<Node<T>>,
+        leaves: &mut [usize],
         node_idx: usize,
         slot: usize,
         bbox: Aabb2D<T>,
         max_leaf: usize,
+        visited: &mut u64,
     ) {
         let kind = core::mem::replace(&mut arena[node_idx].kind, Kind::Leaf(Vec::new()));
         match kind {
             Kind::Leaf(mut items) => {
                 items.push((slot, bbox));
+                leaves[slot] = node_idx;
                 let mut node_bbox = union_aabb(arena[node_idx].bbox, bbox);
                 let new_kind = if items.len() > max_leaf {
                     let (l, r) = Self::split_sah(items, max_leaf);
-                    let l_idx = arena.len();
+                    let (l_idx, r_idx) = (arena.len(), arena.len() + 1);
+                    for (side, idx) in [(&l, l_idx), (&r, r_idx)] {
+                        for &(s, _) in side {
+                            leaves[s] = idx;
+                        }
+                    }
+                    let parent = Some(NodeIdx::new(node_idx));
                     arena.push(Node {
                         bbox: Self::bbox_items(&l),
                         kind: Kind::Leaf(l),
+                        parent,
+                        dead: false,
                     });
-                    let r_idx = arena.len();
                     arena.push(Node {
                         bbox: Self::bbox_items(&r),
                         kind: Kind::Leaf(r),
+                        parent,
+                        dead: false,
                     });
                     node_bbox = union_aabb(arena[l_idx].bbox, arena[r_idx].bbox);
                     Kind::Internal {
@@ -174,14 +465,15 @@ impl<T: Scalar> Bvh<T> {
                 arena[node_idx].bbox = node_bbox;
             }
             Kind::Internal { left, right } => {
+                *visited += 2;
                 let lb = arena[left.get()].bbox;
                 let rb = arena[right.get()].bbox;
                 let cost_l = area(&union_aabb(lb, bbox)) - area(&lb);
                 let cost_r = area(&union_aabb(rb, bbox)) - area(&rb);
                 if cost_l <= cost_r {
-                    Self::insert_node(arena, left.get(), slot, bbox, max_leaf);
+                    Self::insert_node(arena, leaves, left.get(), slot, bbox, max_leaf, visited);
                 } else {
-                    Self::insert_node(arena, right.get(), slot, bbox, max_leaf);
+                    Self::insert_node(arena, leaves, right.get(), slot, bbox, max_leaf, visited);
                 }
                 let node_bbox = union_aabb(arena[node_idx].bbox, bbox);
                 arena[node_idx].kind = Kind::Internal { left, right };
@@ -190,8 +482,121 @@ impl<T: Scalar> Bvh<T> {
         }
     }
 
+    /// Insert `slot` by descending from the root; returns the number of node bounds tested.
+    fn insert_from_root(&mut self, slot: usize, aabb: Aabb2D<T>) -> u64 {
+        self.ensure_slot(slot, aabb);
+        let mut visited = 0;
+        match self.root {
+            None => {
+                let idx = self.arena.len();
+                self.arena.push(Node {
+                    bbox: aabb,
+                    kind: Kind::Leaf(vec![(slot, aabb)]),
+                    parent: None,
+                    dead: false,
+                });
+                self.leaves[slot] = idx;
+                self.root = Some(NodeIdx::new(idx));
+            }
+            Some(root_idx) => {
+                Self::insert_node(
+                    &mut self.arena,
+                    &mut self.leaves,
+                    root_idx.get(),
+                    slot,
+                    aabb,
+                    self.max_leaf,
+                    &mut visited,
+                );
+            }
+        }
+        visited
+    }
+
+    /// Insert `slot` below the deepest node on `hint`'s path that already contains `aabb`.
+    ///
+    /// Returns the depth the descent started at (0 is the root, `None` if it fell back to a
+    /// plain insert from the root) and the number of node bounds tested, as recorded in the stats.
+    /// Leaf splits stay local, so ancestors need no update.
+    fn insert_near_depth(
+        &mut self,
+        slot: usize,
+        aabb: Aabb2D<T>,
+        hint: usize,
+    ) -> (Option<usize>, u64) {
+        let path = self.leaf_path(hint).unwrap_or_default();
+        let start = path
+            .iter()
+            .rposition(|&i| self.arena[i].bbox.contains_aabb(&aabb));
+        // Walking up from the hint tests bounds until one contains `aabb`.
+        let mut visited = (path.len() - start.unwrap_or(0)) as u64;
+        let Some(depth) = start else {
+            visited += self.insert_from_root(slot, aabb);
+            self.stats.record_insert(visited);
+            return (None, visited);
+        };
+        self.ensure_slot(slot, aabb);
+        Self::insert_node(
+            &mut self.arena,
+            &mut self.leaves,
+            path[depth],
+            slot,
+            aabb,
+            self.max_leaf,
+            &mut visited,
+        );
+        self.stats.record_insert(visited);
+        (Some(depth), visited)
+    }
+
+    /// Move `slot` from `old` to `new` in place if `new` fits its leaf grown by `slack`.
+    ///
+    /// Returns `None` if the slot is not under `node_idx`, `Some(false)` if it was found
+    /// but does not fit, and `Some(true)` if it was updated (ancestors refit on the way up).
+    fn refit_node(
+        arena: &mut Vec<Node<T>>,
+        node_idx: usize,
+        slot: usize,
+        old: &Aabb2D<T>,
+        new: Aabb2D<T>,
+        slack: T,
+    ) -> Option<bool> {
+        if arena[node_idx].bbox.intersect(old).is_empty() {
+            return None;
+        }
+        let bbox = arena[node_idx].bbox;
+        match &mut arena[node_idx].kind {
+            Kind::Leaf(items) => {
+                let item = items.iter_mut().find(|(s, _)| *s == slot)?;
+                let fits = T::sub(bbox.min_x, slack) <= new.min_x
+                    && T::sub(bbox.min_y, slack) <= new.min_y
+                    && new.max_x <= T::add(bbox.max_x, slack)
+                    && new.max_y <= T::add(bbox.max_y, slack);
+                if !fits {
+                    return Some(false);
+                }
+                item.1 = new;
+                arena[node_idx].bbox = Self::bbox_items(items);
+                Some(true)
+            }
+            Kind::Internal { left, right } => {
+                let (left, right) = (left.get(), right.get());
+                let found = Self::refit_node(arena, left, slot, old, new, slack)
+                    .or_else(|| Self::refit_node(arena, right, slot, old, new, slack));
+                if found == Some(true) {
+                    arena[node_idx].bbox = union_aabb(arena[left].bbox, arena[right].bbox);
+                }
+                found
+            }
+        }
+    }
+
+    /// Remove `slot` below `node_idx`, collapsing nodes with an empty side and recording the
+    /// nodes that die in `holes`.
     fn remove_node(
         arena: &mut Vec<Node<T>>,
+        leaves: &mut [usize],
+        holes: &mut Vec<usize>,
         node_idx: usize,
         slot: usize,
         old: &Aabb2D<T>,
@@ -209,12 +614,19 @@ impl<T: Scalar> Bvh<T> {
                 (Kind::Leaf(items), bbox, removed)
             }
             Kind::Internal { left, right } => {
-                let removed = Self::remove_node(arena, left.get(), slot, old)
-                    | Self::remove_node(arena, right.get(), slot, old);
+                let removed = Self::remove_node(arena, leaves, holes, left.get(), slot, old)
+                    | Self::remove_node(arena, leaves, holes, right.get(), slot, old);
                 let is_left_empty =
                     matches!(arena[left.get()].kind, Kind::Leaf(ref v) if v.is_empty());
                 let is_right_empty =
                     matches!(arena[right.get()].kind, Kind::Leaf(ref v) if v.is_empty());
+                let collapse = removed && is_left_empty != is_right_empty;
+                if collapse {
+                    for c in [left, right] {
+                        arena[c.get()].dead = true;
+                        holes.push(c.get());
+                    }
+                }
                 if removed {
                     if is_left_empty && !is_right_empty {
                         let kind = core::mem::replace(
@@ -238,6 +650,18 @@ impl<T: Scalar> Bvh<T> {
                 }
             }
         };
+        // A collapsed node takes over its surviving child's contents.
+        match &new_kind {
+            Kind::Leaf(items) => {
+                for &(s, _) in items {
+                    leaves[s] = node_idx;
+                }
+            }
+            &Kind::Internal { left, right } => {
+                arena[left.get()].parent = Some(NodeIdx::new(node_idx));
+                arena[right.get()].parent = Some(NodeIdx::new(node_idx));
+            }
+        }
         arena[node_idx].kind = new_kind;
         arena[node_idx].bbox = new_bbox;
         removed
@@ -246,27 +670,26 @@ impl<T: Scalar> Bvh<T> {
 
 impl<T: Scalar> Backend<T> for Bvh<T> {
     fn insert(&mut self, slot: usize, aabb: Aabb2D<T>) {
-        self.ensure_slot(slot, aabb);
-        match self.root {
-            None => {
-                let idx = self.arena.len();
-                self.arena.push(Node {
-                    bbox: aabb,
-                    kind: Kind::Leaf(vec![(slot, aabb)]),
-                });
-                self.root = Some(NodeIdx::new(idx));
-            }
-            Some(root_idx) => {
-                Self::insert_node(&mut self.arena, root_idx.get(), slot, aabb, self.max_leaf);
-            }
-        }
+        let visited = self.insert_from_root(slot, aabb);
+        self.stats.record_insert(visited);
+    }
+
+    fn insert_near(&mut self, slot: usize, aabb: Aabb2D<T>, hint: usize) {
+        let _ = self.insert_near_depth(slot, aabb, hint);
     }
 
     fn update(&mut self, slot: usize, aabb: Aabb2D<T>) {
         if let Some(old) = self.slots.get(slot).and_then(|x| *x)
             && let Some(root_idx) = self.root
         {
-            let _ = Self::remove_node(&mut self.arena, root_idx.get(), slot, &old);
+            if let Some(slack) = self.update_slack
+                && Self::refit_node(&mut self.arena, root_idx.get(), slot, &old, aabb, slack)
+                    == Some(true)
+            {
+                self.slots[slot] = Some(aabb);
+                return;
+            }
+            self.remove_from_tree(root_idx, slot, &old);
         }
         self.insert(slot, aabb);
     }
@@ -275,7 +698,7 @@ impl<T: Scalar> Backend<T> for Bvh<T> {
         if let Some(old) = self.slots.get(slot).and_then(|x| *x)
             && let Some(root_idx) = self.root
         {
-            let _ = Self::remove_node(&mut self.arena, root_idx.get(), slot, &old);
+            self.remove_from_tree(root_idx, slot, &old);
             if let Some(s) = self.slots.get_mut(slot) {
                 *s = None;
             }
@@ -283,27 +706,184 @@ impl<T: Scalar> Backend<T> for Bvh<T> {
     }
 
     fn clear(&mut self) {
+        self.root = None;
+        self.arena = Vec::new();
+        self.slots = Vec::new();
+        self.leaves = Vec::new();
+        self.holes = Vec::new();
+        self.dead = 0;
+    }
+
+    fn clear_retain(&mut self) {
         self.root = None;
         self.arena.clear();
         self.slots.clear();
+        self.leaves.clear();
+        self.holes.clear();
+        self.dead = 0;
+    }
+
+    fn compact(&mut self) {
+        self.compact_arena();
+    }
+
+    fn arena_usage(&self) -> (usize, usize) {
+        (self.arena.len() - self.dead, self.dead)
+    }
+
+    fn compact_step(&mut self, max_nodes: usize) -> bool {
+        self.compact_arena_step(max_nodes)
+    }
+
+    fn bulk_load(&mut self, pairs: &[(usize, Aabb2D<T>)]) {
+        self.clear_retain();
+        for &(slot, bbox) in pairs {
+            self.ensure_slot(slot, bbox);
+        }
+        if pairs.is_empty() {
+            return;
+        }
+        let max_leaf = self.max_leaf;
+        // Explicit work stack: SAH splits can be lopsided, so recursion depth is unbounded.
+        let mut work: Vec<(usize, BvhItems<T>)> = Vec::new();
+        self.arena.push(Self::placeholder(pairs));
+        work.push((0, pairs.to_vec()));
+        while let Some((idx, items)) = work.pop() {
+            if items.len() <= max_leaf {
+                self.arena[idx].kind = Kind::Leaf(items);
+                continue;
+            }
+            let (l, r) = Self::split_sah(items, max_leaf);
+            let (l_idx, r_idx) = (self.arena.len(), self.arena.len() + 1);
+            self.arena.push(Self::placeholder(&l));
+            self.arena.push(Self::placeholder(&r));
+            self.arena[idx].kind = Kind::Internal {
+                left: NodeIdx::new(l_idx),
+                right: NodeIdx::new(r_idx),
+            };
+            work.push((l_idx, l));
+            work.push((r_idx, r));
+        }
+        self.root = Some(NodeIdx::new(0));
+        self.relink();
+    }
+
+    fn refit(&mut self) {
+        let Some(root) = self.root else {
+            return;
+        };
+        // Pre-order walk; visiting it backwards handles children before their parents.
+        let mut order = Vec::new();
+        let mut stack = vec![root];
+        while let Some(i) = stack.pop() {
+            order.push(i);
+            if let Kind::Internal { left, right } = self.arena[i.get()].kind {
+                stack.push(left);
+                stack.push(right);
+            }
+        }
+        for &i in order.iter().rev() {
+            let bbox = match &self.arena[i.get()].kind {
+                Kind::Leaf(items) => Self::bbox_items(items),
+                Kind::Internal { left, right } => {
+                    let is_empty = |n: NodeIdx| matches!(&self.arena[n.get()].kind, Kind::Leaf(v) if v.is_empty());
+                    let (lb, rb) = (self.arena[left.get()].bbox, self.arena[right.get()].bbox);
+                    match (is_empty(*left), is_empty(*right)) {
+                        (false, true) => lb,
+                        (true, false) => rb,
+                        _ => union_aabb(lb, rb),
+                    }
+                }
+            };
+            self.arena[i.get()].bbox = bbox;
+        }
     }
 
     fn visit_point<F: FnMut(usize)>(&self, x: T, y: T, mut f: F) {
+        let _ = self.query_point_visit(x, y, |i| {
+            f(i);
+            true
+        });
+    }
+
+    fn visit_points<F: FnMut(usize, usize)>(&self, points: &[(T, T)], mut f: F) {
         let Some(root_idx) = self.root else {
             return;
         };
-        let p = Aabb2D::new(x, y, x, y);
-        let mut stack = vec![root_idx];
+        let pts: Vec<Aabb2D<T>> = points
+            .iter()
+            .map(|&(x, y)| Aabb2D::new(x, y, x, y))
+            .collect();
+        // Each stack entry carries the points still inside the node's ancestors.
+        let mut stack = vec![(root_idx, (0..pts.len()).collect::<Vec<usize>>())];
+        while let Some((i, active)) = stack.pop() {
+            let n = &self.arena[i.get()];
+            let active: Vec<usize> = active
+                .into_iter()
+                .filter(|&pi| !n.bbox.intersect(&pts[pi]).is_empty())
+                .collect();
+            if active.is_empty() {
+                continue;
+            }
+            match &n.kind {
+                Kind::Leaf(items) => {
+                    for (s, b) in items {
+                        for &pi in &active {
+                            if !b.intersect(&pts[pi]).is_empty() {
+                                f(pi, *s);
+                            }
+                        }
+                    }
+                }
+                Kind::Internal { left, right } => {
+                    let [first, second] = self.push_order(*left, *right);
+                    stack.push((first, active.clone()));
+                    stack.push((second, active));
+                }
+            }
+        }
+    }
+
+    fn query_knn(&self, x: T, y: T, k: usize) -> Option<Vec<(usize, T::Acc)>> {
+        let Some(root_idx) = self.root else {
+            return Some(Vec::new());
+        };
+        let root_dist = point_dist2(&self.arena[root_idx.get()].bbox, x, y);
+        Some(best_first_knn(
+            root_idx.get(),
+            root_dist,
+            k,
+            |i, push| match &self.arena[i].kind {
+                Kind::Leaf(items) => {
+                    for (s, b) in items {
+                        push(true, *s, point_dist2(b, x, y));
+                    }
+                }
+                Kind::Internal { left, right } => {
+                    for c in [*left, *right] {
+                        push(false, c.get(), point_dist2(&self.arena[c.get()].bbox, x, y));
+                    }
+                }
+            },
+        ))
+    }
+
+    fn nearest(&self, x: T, y: T) -> Option<(usize, T::Acc)> {
+        let root_idx = self.root?;
+        let mut best: Option<(usize, T::Acc)> = None;
+        let mut stack = SmallStack::<_, 32>::new(root_idx);
         while let Some(i) = stack.pop() {
             let n = &self.arena[i.get()];
-            if n.bbox.intersect(&p).is_empty() {
+            // Ties are kept so a lower slot at the same distance can still win.
+            if best.is_some_and(|(_, d)| point_dist2(&n.bbox, x, y) > d) {
                 continue;
             }
             match &n.kind {
                 Kind::Leaf(items) => {
                     for (s, b) in items {
-                        if !b.intersect(&p).is_empty() {
-                            f(*s);
+                        let d = point_dist2(b, x, y);
+                        if improves_nearest(&best, *s, d) {
+                            best = Some((*s, d));
                         }
                     }
                 }
@@ -313,14 +893,114 @@ impl<T: Scalar> Backend<T> for Bvh<T> {
                 }
             }
         }
+        best
     }
 
     fn visit_rect<F: FnMut(usize)>(&self, rect: Aabb2D<T>, mut f: F) {
+        let _ = self.query_rect_visit(rect, |i| {
+            f(i);
+            true
+        });
+    }
+
+    fn query_point_visit<F: FnMut(usize) -> bool>(&self, x: T, y: T, f: F) -> bool {
+        self.query_rect_visit(Aabb2D::new(x, y, x, y), f)
+    }
+
+    fn query_rect_visit<F: FnMut(usize) -> bool>(&self, rect: Aabb2D<T>, mut f: F) -> bool {
         let Some(root_idx) = self.root else {
-            return;
+            return true;
         };
-        let mut stack = vec![root_idx];
+        let (mut nodes, mut tested) = (0, 0);
+        let mut finished = true;
+        // Inline stack: typical traversals do not allocate.
+        let mut stack = SmallStack::<_, 32>::new(root_idx);
+        'walk: while let Some(i) = stack.pop() {
+            let n = &self.arena[i.get()];
+            nodes += 1;
+            if n.bbox.intersect(&rect).is_empty() {
+                continue;
+            }
+            match &n.kind {
+                Kind::Leaf(items) => {
+                    tested += items.len() as u64;
+                    for (s, b) in items {
+                        if !b.intersect(&rect).is_empty() && !f(*s) {
+                            finished = false;
+                            break 'walk;
+                        }
+                    }
+                }
+                Kind::Internal { left, right } => {
+                    for c in self.push_order(*left, *right) {
+                        stack.push(c);
+                    }
+                }
+            }
+        }
+        self.stats.record(nodes, tested);
+        finished
+    }
+
+    #[cfg(feature = "stats")]
+    fn take_query_stats(&self) -> QueryStats {
+        self.stats.take()
+    }
+
+    fn stats(&self) -> BackendStats {
+        let mut out = BackendStats {
+            item_count: self.slots.iter().flatten().count(),
+            ..BackendStats::default()
+        };
+        // Only reachable nodes count; the arena may hold dead ones until compacted.
+        let mut stack: Vec<(NodeIdx, usize)> = self.root.map(|r| (r, 0)).into_iter().collect();
+        while let Some((i, depth)) = stack.pop() {
+            out.node_count += 1;
+            out.max_depth = out.max_depth.max(depth);
+            match self.arena[i.get()].kind {
+                Kind::Leaf(_) => out.leaf_count += 1,
+                Kind::Internal { left, right } => {
+                    stack.push((left, depth + 1));
+                    stack.push((right, depth + 1));
+                }
+            }
+        }
+        out.dead_nodes = self.arena.len() - out.node_count;
+        out
+    }
+
+    fn memory_bytes(&self) -> usize {
+        let items: usize = self
+            .arena
+            .iter()
+            .map(|n| match &n.kind {
+                Kind::Leaf(items) => vec_bytes(items),
+                Kind::Internal { .. } => 0,
+            })
+            .sum();
+        vec_bytes(&self.arena)
+            + items
+            + vec_bytes(&self.slots)
+            + vec_bytes(&self.leaves)
+            + vec_bytes(&self.holes)
+    }
+
+    fn visit_rect_budgeted<F: FnMut(usize)>(
+        &self,
+        rect: Aabb2D<T>,
+        max_nodes_visited: usize,
+        mut f: F,
+    ) -> bool {
+        let Some(root_idx) = self.root else {
+            return true;
+        };
+        let mut stack = SmallStack::<_, 32>::new(root_idx);
+        let mut visited = 0;
         while let Some(i) = stack.pop() {
+            if visited == max_nodes_visited {
+                return false;
+            }
+            visited += 1;
             let n = &self.arena[i.get()];
             if n.bbox.intersect(&rect).is_empty() {
                 continue;
@@ -334,11 +1014,49 @@ impl<T: Scalar> Backend<T> for Bvh<T> {
                     }
                 }
                 Kind::Internal { left, right } => {
-                    stack.push(*left);
-                    stack.push(*right);
+                    for c in self.push_order(*left, *right) {
+                        stack.push(c);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn query_rect_grouped<'a>(
+        &'a self,
+        rect: Aabb2D<T>,
+    ) -> Box<dyn Iterator<Item = (GroupId, Vec<usize>)> + 'a> {
+        // One group per leaf node, identified by its arena index.
+        let mut groups = Vec::new();
+        let Some(root_idx) = self.root else {
+            return Box::new(groups.into_iter());
+        };
+        let mut stack = vec![root_idx];
+        while let Some(i) = stack.pop() {
+            let n = &self.arena[i.get()];
+            if n.bbox.intersect(&rect).is_empty() {
+                continue;
+            }
+            match &n.kind {
+                Kind::Leaf(items) => {
+                    let hits: Vec<usize> = items
+                        .iter()
+                        .filter(|(_, b)| !b.intersect(&rect).is_empty())
+                        .map(|(s, _)| *s)
+                        .collect();
+                    if !hits.is_empty() {
+                        groups.push((GroupId(i.get()), hits));
+                    }
+                }
+                Kind::Internal { left, right } => {
+                    for c in self.push_order(*left, *right) {
+                        stack.push(c);
+                    }
                 }
             }
         }
+        Box::new(groups.into_iter())
     }
 }
 
@@ -349,6 +1067,8 @@ impl<T: Scalar> Debug for Bvh<T> {
         let has_root = self.root.is_some();
         f.debug_struct("Bvh")
             .field("max_leaf", &self.max_leaf)
+            .field("update_slack", &self.update_slack)
+            .field("left_to_right", &self.left_to_right)
             .field("arena_nodes", &self.arena.len())
             .field("total_slots", &total)
             .field("alive", &alive)
@@ -364,6 +1084,9 @@ pub type BvhF32 = Bvh<f32>;
 /// BVH with f64 coordinates and f64 metrics.
 pub type BvhF64 = Bvh<f64>;
 
+/// BVH with i32 coordinates and i64 metrics.
+pub type BvhI32 = Bvh<i32>;
+
 /// BVH with i64 coordinates and i128 metrics.
 pub type BvhI64 = Bvh<i64>;
 
@@ -372,6 +1095,83 @@ mod tests {
     use super::*;
     use crate::index::Index;
 
+    #[test]
+    fn compact_arena_drops_dead_nodes() {
+        fn reachable(b: &Bvh<i64>, i: NodeIdx) -> usize {
+            match b.arena[i.get()].kind {
+                Kind::Leaf(_) => 1,
+                Kind::Internal { left, right } => 1 + reachable(b, left) + reachable(b, right),
+            }
+        }
+        let mut b: Bvh<i64> = Bvh::default();
+        for i in 0..400_i64 {
+            let (x, y) = (i % 20 * 10, i / 20 * 10);
+            b.insert(
+                usize::try_from(i).unwrap(),
+                Aabb2D::new(x, y, x + 15, y + 15),
+            );
+        }
+        for s in (0..400).filter(|s| s % 7 != 0) {
+            b.remove(s);
+        }
+        let live = reachable(&b, b.root.unwrap());
+        assert!(live < b.arena.len());
+
+        let q = Aabb2D::new(20, 20, 120, 90);
+        let mut expected = Vec::new();
+        b.visit_rect(q, |s| expected.push(s));
+        b.compact_arena();
+        assert_eq!(b.arena.len(), live);
+        let mut got = Vec::new();
+        b.visit_rect(q, |s| got.push(s));
+        assert_eq!(got, expected);
+        assert!(!got.is_empty() && got.iter().all(|s| s % 7 == 0));
+    }
+
+    #[test]
+    fn left_to_right_order_is_documented_and_stable() {
+        let build = |left_to_right: bool| {
+            let mut b: Bvh<i64> = Bvh {
+                max_leaf: 3,
+                ..Bvh::default()
+            };
+            b.set_left_to_right(left_to_right);
+            for i in 0..4_u8 {
+                let x = i64::from(i) * 20;
+                b.insert(usize::from(i), Aabb2D::new(x, 0, x + 10, 10));
+            }
+            b
+        };
+        let all = Aabb2D::new(0, 0, 100, 10);
+
+        // Two leaves: {0, 1} on the left and {2, 3} on the right.
+        let b = build(true);
+        let leaves = b
+            .arena
+            .iter()
+            .filter(|n| matches!(n.kind, Kind::Leaf(_)))
+            .count();
+        assert_eq!(leaves, 2);
+        assert_eq!(leaf_of(&b, 0), leaf_of(&b, 1));
+        assert_eq!(leaf_of(&b, 2), leaf_of(&b, 3));
+
+        let collect = |b: &Bvh<i64>| b.query_rect(all).collect::<Vec<_>>();
+        assert_eq!(collect(&b), vec![0, 1, 2, 3]);
+        assert_eq!(collect(&build(false)), vec![2, 3, 0, 1]);
+        let mut points = Vec::new();
+        b.visit_points(&[(5, 5), (65, 5)], |_, s| points.push(s));
+        assert_eq!(points, vec![0, 3]);
+
+        // Rebuilding from the same input gives the same order.
+        let mut rebuilt = build(true);
+        rebuilt.clear();
+        for i in 0..4_u8 {
+            let x = i64::from(i) * 20;
+            rebuilt.insert(usize::from(i), Aabb2D::new(x, 0, x + 10, 10));
+        }
+        assert_eq!(collect(&rebuilt), collect(&b));
+    }
+
     #[test]
     fn bvh_f64_basic() {
         let mut idx = Index::<f64, u32>::with_bvh();
@@ -444,6 +1244,92 @@ mod tests {
         assert!(b.arena.len() <= baseline_nodes + 2);
     }
 
+    fn leaf_of<T: Scalar>(b: &Bvh<T>, slot: usize) -> Option<usize> {
+        b.arena.iter().position(
+            |n| matches!(&n.kind, Kind::Leaf(items) if items.iter().any(|(s, _)| *s == slot)),
+        )
+    }
+
+    #[test]
+    fn update_slack_refits_small_moves_in_place() {
+        let mut b = Bvh::<f64>::with_update_slack(2.0);
+        for i in 0..32_u32 {
+            let x = f64::from(i) * 10.0;
+            b.insert(i as usize, Aabb2D::new(x, 0.0, x + 5.0, 5.0));
+        }
+        let arena_len = b.arena.len();
+        let leaf = leaf_of(&b, 3);
+
+        // Nudge slot 3 within its leaf's slack: no restructuring.
+        b.update(3, Aabb2D::new(31.0, 1.0, 36.0, 6.0));
+        assert_eq!(b.arena.len(), arena_len);
+        assert_eq!(leaf_of(&b, 3), leaf);
+        assert_eq!(b.query_point(35.5, 5.5).collect::<Vec<_>>(), vec![3]);
+        assert!(b.query_point(30.5, 0.5).next().is_none());
+
+        // Move it far away: it is reinserted into a different leaf.
+        b.update(3, Aabb2D::new(305.0, 0.0, 309.0, 5.0));
+        assert_ne!(leaf_of(&b, 3), leaf);
+        let mut hits: Vec<_> = b.query_point(305.0, 1.0).collect();
+        hits.sort_unstable();
+        assert_eq!(hits, vec![3, 30]);
+        assert!(b.query_point(35.5, 5.5).next().is_none());
+    }
+
+    #[test]
+    fn refit_recomputes_bounds_without_restructuring() {
+        let mut b = Bvh::<f64>::with_update_slack(4.0);
+        for i in 0..32_u32 {
+            let x = f64::from(i) * 10.0;
+            b.insert(i as usize, Aabb2D::new(x, 0.0, x + 5.0, 5.0));
+        }
+        for i in 0..32_u32 {
+            let x = f64::from(i) * 10.0;
+            b.update(i as usize, Aabb2D::new(x + 1.0, 1.0, x + 2.0, 2.0));
+        }
+        let tight: Vec<_> = b.arena.iter().map(|n| n.bbox).collect();
+        // Loosen every node, as if the bounds had drifted.
+        for n in &mut b.arena {
+            n.bbox = union_aabb(n.bbox, Aabb2D::new(-50.0, -50.0, 500.0, 50.0));
+        }
+        b.refit();
+        assert_eq!(b.arena.iter().map(|n| n.bbox).collect::<Vec<_>>(), tight);
+        let root = b.root.unwrap().get();
+        assert_eq!(b.arena[root].bbox, Aabb2D::new(1.0, 1.0, 312.0, 2.0));
+    }
+
+    #[test]
+    fn bulk_load_keeps_leaf_size() {
+        let pairs: Vec<(usize, Aabb2D<f64>)> = (0..200_u32)
+            .map(|i| {
+                let x = f64::from(i) * 3.0;
+                (i as usize, Aabb2D::new(x, 0.0, x + 2.0, 2.0))
+            })
+            .collect();
+        let mut b = Bvh::<f64>::with_max_leaf(4);
+        b.insert(999, Aabb2D::new(-10.0, -10.0, -9.0, -9.0));
+        b.bulk_load(&pairs);
+        assert!(b.query_point(-9.5, -9.5).next().is_none());
+        assert!(
+            b.arena
+                .iter()
+                .all(|n| !matches!(&n.kind, Kind::Leaf(v) if v.len() > 4))
+        );
+        assert_eq!(b.query_point(31.0, 1.0).collect::<Vec<_>>(), vec![10]);
+
+        let built = Bvh::<f64>::bulk_build_with_params(&pairs, 32);
+        assert_eq!(built.max_leaf, 32);
+        let leaf_sizes = || {
+            built.arena.iter().filter_map(|n| match &n.kind {
+                Kind::Leaf(v) => Some(v.len()),
+                Kind::Internal { .. } => None,
+            })
+        };
+        assert!(leaf_sizes().all(|len| len <= 32));
+        assert!(leaf_sizes().any(|len| len > 8));
+        assert_eq!(built.query_point(31.0, 1.0).collect::<Vec<_>>(), vec![10]);
+    }
+
     #[test]
     fn bvh_f64_split_then_updates_on_internal() {
         // Force a split by exceeding max_leaf (8), then update several items and
@@ -495,4 +1381,235 @@ mod tests {
         // Structure sanity: arena should not grow unboundedly due to updates
         assert!(b.arena.len() <= baseline_nodes + 4);
     }
+
+    #[test]
+    fn bulk_build_matches_incremental_queries() {
+        let mut next = {
+            let mut state = 0x2545_f491_u64;
+            move |m: i64| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1);
+                i64::try_from(state >> 33).unwrap() % m
+            }
+        };
+        let entries: Vec<(Aabb2D<i64>, u32)> = (0..3000_u32)
+            .map(|i| {
+                let (x, y) = (next(4000), next(4000));
+                (Aabb2D::new(x, y, x + 1 + next(30), y + 1 + next(12)), i)
+            })
+            .collect();
+        let bulk = Index::<i64, u32>::with_bvh_bulk(&entries);
+        let mut grown: crate::IndexGeneric<i64, u32, BvhI64> = crate::IndexGeneric::new();
+        for &(b, p) in &entries {
+            let _ = grown.insert(b, p);
+        }
+        let _ = grown.commit();
+        for _ in 0..100 {
+            let (x, y) = (next(4000), next(4000));
+            let q = Aabb2D::new(x, y, x + next(300), y + next(300));
+            let mut a: Vec<u32> = bulk.query_rect(q).map(|(_, p)| p).collect();
+            let mut b: Vec<u32> = grown.query_rect(q).map(|(_, p)| p).collect();
+            a.sort_unstable();
+            b.sort_unstable();
+            assert_eq!(a, b);
+        }
+        // Keys are slot-ordered, and the tree keeps accepting updates.
+        let mut bulk = bulk;
+        let k = bulk
+            .query_point(entries[7].0.min_x, entries[7].0.min_y)
+            .find(|&(_, p)| p == 7);
+        let (k, _) = k.unwrap();
+        bulk.update(k, Aabb2D::new(-50, -50, -40, -40));
+        let _ = bulk.commit();
+        assert_eq!(
+            bulk.query_point(-45, -45)
+                .map(|(_, p)| p)
+                .collect::<Vec<_>>(),
+            [7]
+        );
+
+        // Every leaf respects the default leaf size.
+        let b = BvhI64::bulk_build_default(
+            &entries
+                .iter()
+                .enumerate()
+                .map(|(i, e)| (i, e.0))
+                .collect::<Vec<_>>(),
+        );
+        assert!(b.arena.iter().all(|n| match &n.kind {
+            Kind::Leaf(items) => !items.is_empty() && items.len() <= 8,
+            Kind::Internal { .. } => true,
+        }));
+        assert!(BvhF64::bulk_build_default(&[]).root.is_none());
+    }
+
+    #[test]
+    fn custom_max_leaf_splits_repeatedly() {
+        for max_leaf in [2, 3, 5, 32] {
+            let mut b = BvhF64::with_max_leaf(max_leaf);
+            let n = 200_u32;
+            for i in 0..n {
+                let (x, y) = (f64::from(i % 20) * 10.0, f64::from(i / 20) * 10.0);
+                b.insert(i as usize, Aabb2D::new(x, y, x + 4.0, y + 4.0));
+            }
+            let leaves: Vec<usize> = b
+                .arena
+                .iter()
+                .filter_map(|node| match &node.kind {
+                    Kind::Leaf(items) if !items.is_empty() => Some(items.len()),
+                    _ => None,
+                })
+                .collect();
+            assert!(leaves.len() >= (n as usize).div_ceil(max_leaf));
+            assert!(leaves.iter().all(|&len| len <= max_leaf));
+            for i in 0..n {
+                let (x, y) = (
+                    f64::from(i % 20) * 10.0 + 2.0,
+                    f64::from(i / 20) * 10.0 + 2.0,
+                );
+                let hits: Vec<_> = b.query_point(x, y).collect();
+                assert_eq!(hits, vec![i as usize]);
+            }
+        }
+        let mut idx = Index::<f64, u32>::with_bvh_params(4);
+        for i in 0..30_u32 {
+            let x = f64::from(i) * 3.0;
+            let _ = idx.insert(Aabb2D::new(x, 0.0, x + 2.0, 2.0), i);
+        }
+        let _ = idx.commit();
+        let hits: Vec<u32> = idx.query_point(46.0, 1.0).map(|(_, p)| p).collect();
+        assert_eq!(hits, vec![15]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 2")]
+    fn with_max_leaf_rejects_tiny_leaves() {
+        let _ = BvhI64::with_max_leaf(1);
+    }
+
+    #[test]
+    fn bvh_pathological_leaf_size_does_not_panic() {
+        // Bypass construction-time validation: a leaf size of 1 forces splits of
+        // two items, which the old `min_children` derivation could not satisfy.
+        let mut b: Bvh<i64> = Bvh {
+            max_leaf: 1,
+            ..Bvh::default()
+        };
+        let n = 20_usize;
+        for i in 0..n {
+            let x = i as i64 * 20;
+            b.insert(i, Aabb2D::new(x, 0, x + 10, 10));
+        }
+        for i in 0..n {
+            let x = i as i64 * 20 + 5;
+            let hits: Vec<_> = b.query_point(x, 5).collect();
+            assert_eq!(hits, vec![i], "each box must remain reachable");
+        }
+
+        // A single item has no admissible SAH split; the fallback must not panic.
+        let (l, r) = Bvh::<i64>::split_sah(vec![(0, Aabb2D::new(0, 0, 1, 1))], 8);
+        assert_eq!(l.len() + r.len(), 1);
+    }
+
+    /// Assert that parent links and the slot→leaf map match the tree shape.
+    fn check_links(b: &Bvh<f64>) {
+        let Some(root) = b.root else {
+            return;
+        };
+        assert_eq!(b.arena[root.get()].parent, None);
+        let mut stack = vec![root];
+        while let Some(i) = stack.pop() {
+            match &b.arena[i.get()].kind {
+                Kind::Leaf(items) => {
+                    for &(s, _) in items {
+                        assert_eq!(b.leaves[s], i.get());
+                    }
+                }
+                &Kind::Internal { left, right } => {
+                    for c in [left, right] {
+                        assert_eq!(b.arena[c.get()].parent, Some(i));
+                        stack.push(c);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn compact_arena_step_reclaims_dead_nodes_in_bounded_steps() {
+        let mut b: Bvh<f64> = Bvh::with_max_leaf(4);
+        let bx = |i: usize| {
+            let (x, y) = ((i % 29) as f64 * 10.0, (i / 29) as f64 * 10.0);
+            Aabb2D::new(x, y, x + 12.0, y + 12.0)
+        };
+        for i in 0..600 {
+            b.insert(i, bx(i));
+        }
+        for i in (0..600).filter(|i| i % 5 != 0) {
+            b.remove(i);
+        }
+        let s = b.stats();
+        assert!(s.dead_nodes > 0);
+        assert_eq!(b.arena_usage(), (s.node_count, s.dead_nodes));
+
+        let q = Aabb2D::new(35.0, 35.0, 185.0, 125.0);
+        let mut expected = Vec::new();
+        b.visit_rect(q, |s| expected.push(s));
+        let mut steps = 0;
+        while b.compact_arena_step(3) {
+            steps += 1;
+            check_links(&b);
+            let mut got = Vec::new();
+            b.visit_rect(q, |s| got.push(s));
+            assert_eq!(got, expected);
+            // Nodes that die mid-compaction are reclaimed too.
+            if steps == 10 {
+                b.remove(5);
+                expected.retain(|&s| s != 5);
+            }
+        }
+        assert!(steps >= s.dead_nodes / 3, "{steps}");
+        let s = b.stats();
+        assert_eq!(s.dead_nodes, 0);
+        assert_eq!(b.arena_usage(), (s.node_count, 0));
+        assert_eq!(b.arena.len(), s.node_count);
+        check_links(&b);
+    }
+
+    #[test]
+    fn insert_near_follows_parent_links() {
+        let mut b: Bvh<f64> = Bvh::with_max_leaf(4);
+        let bx = |i: usize| {
+            let (x, y) = ((i % 23) as f64 * 10.0, (i / 23) as f64 * 10.0);
+            Aabb2D::new(x, y, x + 5.0, y + 5.0)
+        };
+        b.insert(0, bx(0));
+        for i in 1..300 {
+            // Locating the hint visits only the nodes on its root→leaf path.
+            let visited = b.leaf_path(i - 1).unwrap().len();
+            assert!(
+                visited <= b.stats().max_depth + 1,
+                "visited {visited} nodes"
+            );
+            b.insert_near(i, bx(i), i - 1);
+        }
+        check_links(&b);
+        for i in (0..300).step_by(4) {
+            b.remove(i);
+        }
+        for i in (1..300).step_by(4) {
+            b.update(i, bx(i + 150));
+        }
+        check_links(&b);
+        b.compact_arena();
+        check_links(&b);
+        let all: Vec<_> = b
+            .query_rect(Aabb2D::new(0.0, 0.0, 1000.0, 1000.0))
+            .collect();
+        assert_eq!(all.len(), 225);
+
+        let bulk: Vec<_> = (0..100).map(|i| (i, bx(i))).collect();
+        check_links(&Bvh::bulk_build_default(&bulk));
+    }
 }
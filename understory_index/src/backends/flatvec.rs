@@ -6,18 +6,23 @@
 use alloc::vec::Vec;
 use core::fmt::Debug;
 
-use crate::backend::Backend;
-use crate::types::Aabb2D;
+use crate::backend::{Backend, improves_nearest, vec_bytes};
+#[cfg(feature = "stats")]
+use crate::stats::QueryStats;
+use crate::stats::{BackendStats, StatsCounter};
+use crate::types::{Aabb2D, Scalar, point_dist2};
 
 /// Flat vector backend with linear scans.
 pub struct FlatVec<T: Copy + PartialOrd + Debug> {
     entries: Vec<Option<Aabb2D<T>>>,
+    stats: StatsCounter,
 }
 
 impl<T: Copy + PartialOrd + Debug> Default for FlatVec<T> {
     fn default() -> Self {
         Self {
             entries: Vec::new(),
+            stats: StatsCounter::default(),
         }
     }
 }
@@ -33,6 +38,29 @@ impl<T: Copy + PartialOrd + Debug> Debug for FlatVec<T> {
     }
 }
 
+impl<T: Copy + PartialOrd + Debug> FlatVec<T> {
+    /// Call `f` on live slots matching `hit` until it returns `false`; `true` if it never did.
+    fn scan_until(
+        &self,
+        hit: impl Fn(&Aabb2D<T>) -> bool,
+        mut f: impl FnMut(usize) -> bool,
+    ) -> bool {
+        let mut items = 0;
+        let mut finished = true;
+        for (i, slot) in self.entries.iter().enumerate() {
+            if let Some(a) = slot.as_ref() {
+                items += 1;
+                if hit(a) && !f(i) {
+                    finished = false;
+                    break;
+                }
+            }
+        }
+        self.stats.record(0, items);
+        finished
+    }
+}
+
 impl<T: Copy + PartialOrd + Debug> Backend<T> for FlatVec<T> {
     fn insert(&mut self, slot: usize, aabb: Aabb2D<T>) {
         if self.entries.len() <= slot {
@@ -51,26 +79,64 @@ impl<T: Copy + PartialOrd + Debug> Backend<T> for FlatVec<T> {
         }
     }
     fn clear(&mut self) {
+        self.entries = Vec::new();
+    }
+
+    fn clear_retain(&mut self) {
         self.entries.clear();
     }
 
-    fn visit_point<F: FnMut(usize)>(&self, x: T, y: T, mut f: F) {
+    fn nearest(&self, x: T, y: T) -> Option<(usize, T::Acc)>
+    where
+        T: Scalar,
+    {
+        let mut best = None;
         for (i, slot) in self.entries.iter().enumerate() {
-            if let Some(a) = slot.as_ref()
-                && a.contains_point(x, y)
-            {
-                f(i);
+            if let Some(a) = slot {
+                let d = point_dist2(a, x, y);
+                if improves_nearest(&best, i, d) {
+                    best = Some((i, d));
+                }
             }
         }
+        best
+    }
+
+    fn visit_point<F: FnMut(usize)>(&self, x: T, y: T, mut f: F) {
+        let _ = self.query_point_visit(x, y, |i| {
+            f(i);
+            true
+        });
     }
 
     fn visit_rect<F: FnMut(usize)>(&self, rect: Aabb2D<T>, mut f: F) {
-        for (i, slot) in self.entries.iter().enumerate() {
-            if let Some(a) = slot.as_ref()
-                && !a.intersect(&rect).is_empty()
-            {
-                f(i);
-            }
+        let _ = self.query_rect_visit(rect, |i| {
+            f(i);
+            true
+        });
+    }
+
+    fn query_point_visit<F: FnMut(usize) -> bool>(&self, x: T, y: T, f: F) -> bool {
+        self.scan_until(|a| a.contains_point(x, y), f)
+    }
+
+    fn query_rect_visit<F: FnMut(usize) -> bool>(&self, rect: Aabb2D<T>, f: F) -> bool {
+        self.scan_until(|a| !a.intersect(&rect).is_empty(), f)
+    }
+
+    #[cfg(feature = "stats")]
+    fn take_query_stats(&self) -> QueryStats {
+        self.stats.take()
+    }
+
+    fn stats(&self) -> BackendStats {
+        BackendStats {
+            item_count: self.entries.iter().flatten().count(),
+            ..BackendStats::default()
         }
     }
+
+    fn memory_bytes(&self) -> usize {
+        vec_bytes(&self.entries)
+    }
 }
@@ -0,0 +1,549 @@
+// Copyright 2025 the Understory Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Unbounded uniform grid backend with cells kept in a `BTreeMap`.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use crate::backend::{Backend, vec_bytes};
+#[cfg(feature = "stats")]
+use crate::stats::QueryStats;
+use crate::stats::{BackendStats, StatsCounter};
+use crate::types::{Aabb2D, Scalar, floor_i64};
+
+/// Scalars that can be bucketed into integer grid cells.
+pub trait GridScalar: Scalar {
+    /// Index of the cell containing `v`, i.e. `floor((v - origin) / size)`, saturating
+    /// at the `i64` range.
+    fn cell_index(v: Self, origin: Self, size: Self) -> i64;
+}
+
+impl GridScalar for f32 {
+    fn cell_index(v: Self, origin: Self, size: Self) -> i64 {
+        floor_i64((f64::from(v) - f64::from(origin)) / f64::from(size))
+    }
+}
+
+impl GridScalar for f64 {
+    fn cell_index(v: Self, origin: Self, size: Self) -> i64 {
+        floor_i64((v - origin) / size)
+    }
+}
+
+impl GridScalar for i64 {
+    fn cell_index(v: Self, origin: Self, size: Self) -> i64 {
+        let c = (i128::from(v) - i128::from(origin)).div_euclid(i128::from(size));
+        Self::try_from(c).unwrap_or(if c < 0 { Self::MIN } else { Self::MAX })
+    }
+}
+
+/// A uniform grid over the whole plane.
+///
+/// Cell `(i, j)` covers `[ox + i·cell_w, ox + (i + 1)·cell_w] × [oy + j·cell_h, oy + (j + 1)·cell_h]`
+/// with `origin = (ox, oy)`, matching [`tiles_for_box`](crate::tiles_for_box). Only occupied
+/// cells (and cells emptied by [`clear_retain`](Backend::clear_retain)) are stored, keyed by
+/// `(i, j)` in a `BTreeMap`, so lookups are logarithmic in the number of occupied cells and
+/// the grid has no bounds. Each box is registered in every cell it touches, up to
+/// [`MAX_ITEM_CELLS`](Self::MAX_ITEM_CELLS) cells; larger boxes go to an overflow list that
+/// every query tests. Queries report each box once. Removing a box costs one swap per cell
+/// it is registered in.
+///
+/// # Result order
+///
+/// Queries visit cells in `(i, j)` order and report the items of each cell in storage
+/// order, then the overflow list. The order can change after any update.
+pub struct HashGrid<T: GridScalar> {
+    cell_w: T,
+    cell_h: T,
+    origin: (T, T),
+    cells: BTreeMap<(i64, i64), Cell<T>>,
+    slots: Vec<Option<Placed<T>>>,
+    // Slots of boxes spanning more than `MAX_ITEM_CELLS` cells.
+    overflow: Vec<usize>,
+    stats: StatsCounter,
+}
+
+/// The items registered in one cell.
+type Cell<T> = Vec<(usize, Aabb2D<T>)>;
+
+/// A stored box and where it is registered.
+#[derive(Clone, Debug)]
+struct Placed<T: Scalar> {
+    aabb: Aabb2D<T>,
+    at: Placement,
+}
+
+#[derive(Clone, Debug)]
+enum Placement {
+    /// Empty boxes are not registered anywhere.
+    Nowhere,
+    /// Position of the item in each cell of its span, in `(i, j)` order.
+    Cells(Vec<usize>),
+    /// Position in the overflow list.
+    Overflow(usize),
+}
+
+/// Inclusive cell index ranges `(i0, i1, j0, j1)` covered by a box.
+type CellSpan = (i64, i64, i64, i64);
+
+impl<T: GridScalar> HashGrid<T> {
+    /// Most cells a box is registered in; larger boxes are kept in the overflow list.
+    pub const MAX_ITEM_CELLS: usize = 64;
+
+    /// Create an empty grid with cells of `cell_w × cell_h` aligned to `origin`.
+    ///
+    /// Cell sizes must be positive.
+    pub fn new(cell_w: T, cell_h: T, origin: (T, T)) -> Self {
+        debug_assert!(
+            cell_w > T::zero() && cell_h > T::zero(),
+            "cell sizes must be positive"
+        );
+        Self {
+            cell_w,
+            cell_h,
+            origin,
+            cells: BTreeMap::new(),
+            slots: Vec::new(),
+            overflow: Vec::new(),
+            stats: StatsCounter::default(),
+        }
+    }
+
+    /// Number of occupied cells.
+    pub fn occupied_cells(&self) -> usize {
+        self.cells.values().filter(|c| !c.is_empty()).count()
+    }
+
+    fn span(&self, aabb: &Aabb2D<T>) -> CellSpan {
+        (
+            T::cell_index(aabb.min_x, self.origin.0, self.cell_w),
+            T::cell_index(aabb.max_x, self.origin.0, self.cell_w),
+            T::cell_index(aabb.min_y, self.origin.1, self.cell_h),
+            T::cell_index(aabb.max_y, self.origin.1, self.cell_h),
+        )
+    }
+
+    /// Number of boxes kept in the overflow list.
+    pub fn overflow_len(&self) -> usize {
+        self.overflow.len()
+    }
+
+    /// Offset of `cell` within the span of `aabb`, in `(i, j)` order.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "Offsets are below MAX_ITEM_CELLS for boxes registered in cells."
+    )]
+    fn span_offset(&self, aabb: &Aabb2D<T>, (i, j): (i64, i64)) -> usize {
+        let (i0, _, j0, j1) = self.span(aabb);
+        ((i - i0) * (j1 - j0 + 1) + (j - j0)) as usize
+    }
+
+    fn ensure_slot(&mut self, slot: usize) {
+        if self.slots.len() <= slot {
+            self.slots.resize(slot + 1, None);
+        }
+    }
+}
+
+/// Number of cells in a span, saturating.
+fn span_cells((i0, i1, j0, j1): CellSpan) -> u128 {
+    let w = u128::try_from(i128::from(i1) - i128::from(i0) + 1).unwrap_or(0);
+    let h = u128::try_from(i128::from(j1) - i128::from(j0) + 1).unwrap_or(0);
+    w.saturating_mul(h)
+}
+
+fn in_span((i0, i1, j0, j1): CellSpan, (i, j): (i64, i64)) -> bool {
+    (i0..=i1).contains(&i) && (j0..=j1).contains(&j)
+}
+
+impl<T: GridScalar> Backend<T> for HashGrid<T> {
+    fn insert(&mut self, slot: usize, aabb: Aabb2D<T>) {
+        self.ensure_slot(slot);
+        let span = self.span(&aabb);
+        let at = if aabb.is_empty() {
+            Placement::Nowhere
+        } else if span_cells(span) > Self::MAX_ITEM_CELLS as u128 {
+            self.overflow.push(slot);
+            Placement::Overflow(self.overflow.len() - 1)
+        } else {
+            let (i0, i1, j0, j1) = span;
+            let mut places = Vec::new();
+            for i in i0..=i1 {
+                for j in j0..=j1 {
+                    let items = self.cells.entry((i, j)).or_default();
+                    places.push(items.len());
+                    items.push((slot, aabb));
+                }
+            }
+            Placement::Cells(places)
+        };
+        self.slots[slot] = Some(Placed { aabb, at });
+    }
+
+    fn update(&mut self, slot: usize, aabb: Aabb2D<T>) {
+        self.remove(slot);
+        self.insert(slot, aabb);
+    }
+
+    fn remove(&mut self, slot: usize) {
+        let Some(old) = self.slots.get_mut(slot).and_then(Option::take) else {
+            return;
+        };
+        match old.at {
+            Placement::Nowhere => {}
+            Placement::Overflow(pos) => {
+                self.overflow.swap_remove(pos);
+                if let Some(&moved) = self.overflow.get(pos)
+                    && let Some(Some(Placed {
+                        at: Placement::Overflow(p),
+                        ..
+                    })) = self.slots.get_mut(moved)
+                {
+                    *p = pos;
+                }
+            }
+            Placement::Cells(places) => {
+                let (i0, i1, j0, j1) = self.span(&old.aabb);
+                let cells = (i0..=i1).flat_map(|i| (j0..=j1).map(move |j| (i, j)));
+                for (cell, pos) in cells.zip(places) {
+                    let Some(items) = self.cells.get_mut(&cell) else {
+                        continue;
+                    };
+                    items.swap_remove(pos);
+                    let moved = items.get(pos).copied();
+                    if items.is_empty() {
+                        self.cells.remove(&cell);
+                    }
+                    // Point the item swapped into `pos` back at its new position.
+                    if let Some((s, b)) = moved {
+                        let k = self.span_offset(&b, cell);
+                        if let Some(Some(Placed {
+                            at: Placement::Cells(p),
+                            ..
+                        })) = self.slots.get_mut(s)
+                        {
+                            p[k] = pos;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+        self.slots = Vec::new();
+        self.overflow = Vec::new();
+    }
+
+    /// Empty every cell in place, so refilling a similar scene reuses each cell's list.
+    /// Cells that stayed empty since the previous reset are dropped.
+    fn clear_retain(&mut self) {
+        self.cells.retain(|_, items| {
+            let used = !items.is_empty();
+            items.clear();
+            used
+        });
+        self.slots.clear();
+        self.overflow.clear();
+    }
+
+    fn visit_point<F: FnMut(usize)>(&self, x: T, y: T, mut f: F) {
+        let _ = self.query_point_visit(x, y, |i| {
+            f(i);
+            true
+        });
+    }
+
+    fn visit_rect<F: FnMut(usize)>(&self, rect: Aabb2D<T>, mut f: F) {
+        let _ = self.query_rect_visit(rect, |i| {
+            f(i);
+            true
+        });
+    }
+
+    fn query_point_visit<F: FnMut(usize) -> bool>(&self, x: T, y: T, f: F) -> bool {
+        self.query_rect_visit(Aabb2D::new(x, y, x, y), f)
+    }
+
+    fn query_rect_visit<F: FnMut(usize) -> bool>(&self, rect: Aabb2D<T>, mut f: F) -> bool {
+        if rect.is_empty() {
+            return true;
+        }
+        let q = self.span(&rect);
+        let (mut nodes, mut tested) = (0, 0);
+        let mut finished = true;
+        let mut visit_cell = |cell: (i64, i64), items: &[(usize, Aabb2D<T>)]| {
+            nodes += 1;
+            tested += items.len() as u64;
+            for (s, b) in items {
+                if b.intersect(&rect).is_empty() {
+                    continue;
+                }
+                // Report each box only from the first cell shared by it and the query.
+                let (i0, _, j0, _) = self.span(b);
+                if cell != (i0.max(q.0), j0.max(q.2)) {
+                    continue;
+                }
+                if !f(*s) {
+                    return false;
+                }
+            }
+            true
+        };
+        if span_cells(q) > self.cells.len() as u128 {
+            // Cheaper to scan the occupied cells than to probe every cell in the query.
+            for (&cell, items) in &self.cells {
+                if in_span(q, cell) && !visit_cell(cell, items) {
+                    finished = false;
+                    break;
+                }
+            }
+        } else {
+            'walk: for i in q.0..=q.1 {
+                for (&cell, items) in self.cells.range((i, q.2)..=(i, q.3)) {
+                    if !visit_cell(cell, items) {
+                        finished = false;
+                        break 'walk;
+                    }
+                }
+            }
+        }
+        if finished {
+            for &s in &self.overflow {
+                tested += 1;
+                if let Some(Some(p)) = self.slots.get(s)
+                    && !p.aabb.intersect(&rect).is_empty()
+                    && !f(s)
+                {
+                    finished = false;
+                    break;
+                }
+            }
+        }
+        self.stats.record(nodes, tested);
+        finished
+    }
+
+    #[cfg(feature = "stats")]
+    fn take_query_stats(&self) -> QueryStats {
+        self.stats.take()
+    }
+
+    fn stats(&self) -> BackendStats {
+        BackendStats {
+            node_count: self.occupied_cells(),
+            leaf_count: self.occupied_cells(),
+            max_depth: 0,
+            item_count: self.slots.iter().flatten().count(),
+            dead_nodes: 0,
+        }
+    }
+
+    fn memory_bytes(&self) -> usize {
+        // Map nodes are estimated at one key-value pair per occupied cell.
+        let cells: usize = self
+            .cells
+            .values()
+            .map(|c| size_of::<((i64, i64), Cell<T>)>() + vec_bytes(c))
+            .sum();
+        let places: usize = self
+            .slots
+            .iter()
+            .flatten()
+            .map(|p| match &p.at {
+                Placement::Cells(places) => vec_bytes(places),
+                Placement::Nowhere | Placement::Overflow(_) => 0,
+            })
+            .sum();
+        cells + places + vec_bytes(&self.slots) + vec_bytes(&self.overflow)
+    }
+}
+
+impl<T: GridScalar> Debug for HashGrid<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let alive = self.slots.iter().filter(|e| e.is_some()).count();
+        f.debug_struct("HashGrid")
+            .field("cell_w", &self.cell_w)
+            .field("cell_h", &self.cell_h)
+            .field("origin", &self.origin)
+            .field("occupied_cells", &self.occupied_cells())
+            .field("overflow", &self.overflow.len())
+            .field("alive", &alive)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Convenience type aliases for common scalar choices.
+/// Hashed grid with f32 coordinates.
+pub type HashGridF32 = HashGrid<f32>;
+
+/// Hashed grid with f64 coordinates.
+pub type HashGridF64 = HashGrid<f64>;
+
+/// Hashed grid with i64 coordinates.
+pub type HashGridI64 = HashGrid<i64>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn boxes_spanning_cells_are_reported_once() {
+        let mut g = HashGridI64::new(10, 10, (0, 0));
+        g.insert(0, Aabb2D::new(5, 5, 35, 25));
+        g.insert(1, Aabb2D::new(-25, -25, -21, -21));
+        assert_eq!(g.occupied_cells(), 4 * 3 + 1);
+
+        let mut hits = Vec::new();
+        g.visit_rect(Aabb2D::new(-100, -100, 100, 100), |s| hits.push(s));
+        hits.sort_unstable();
+        assert_eq!(hits, vec![0, 1]);
+        hits.clear();
+        // Touches the box exactly on a cell edge.
+        g.visit_point(20, 20, |s| hits.push(s));
+        assert_eq!(hits, vec![0]);
+
+        g.remove(0);
+        assert_eq!(g.occupied_cells(), 1);
+        hits.clear();
+        g.visit_rect(Aabb2D::new(-100, -100, 100, 100), |s| hits.push(s));
+        assert_eq!(hits, vec![1]);
+    }
+
+    #[test]
+    fn moving_one_box_only_touches_its_own_cells() {
+        let mut g = HashGridF64::new(8.0, 8.0, (0.0, 0.0));
+        for s in 1..200_u32 {
+            let x = f64::from(s % 20) * 8.0 + 1.0;
+            let y = f64::from(s / 20) * 8.0 + 1.0;
+            g.insert(s as usize, Aabb2D::new(x, y, x + 4.0, y + 4.0));
+        }
+        let base_cells = g.occupied_cells();
+        for step in 0..10_000_u32 {
+            let x = f64::from(step % 1000) * 0.37 - 50.0;
+            let y = f64::from(step % 700) * 0.23 - 30.0;
+            g.update(0, Aabb2D::new(x, y, x + 3.0, y + 3.0));
+            // A 3×3 box spans at most 2×2 cells, so at most four cells were added.
+            assert!(g.occupied_cells() <= base_cells + 4);
+            if step % 997 == 0 {
+                let mut hits = Vec::new();
+                g.visit_point(x + 1.5, y + 1.5, |s| hits.push(s));
+                assert!(hits.contains(&0));
+            }
+        }
+        // Every other slot is still in exactly the cell it started in.
+        let total: usize = g.cells.values().map(Vec::len).sum();
+        assert!(total <= 199 + 4);
+        g.remove(0);
+        assert_eq!(g.occupied_cells(), base_cells);
+        assert_eq!(g.cells.values().map(Vec::len).sum::<usize>(), 199);
+    }
+
+    #[test]
+    fn oversized_boxes_go_to_the_overflow_list() {
+        let mut g = HashGridF64::new(1.0, 1.0, (0.0, 0.0));
+        g.insert(0, Aabb2D::new(0.0, 0.0, 100.0, 100.0));
+        g.insert(1, Aabb2D::new(-1e300, 0.0, 1e300, 1.0));
+        g.insert(2, Aabb2D::new(2.5, 2.5, 3.5, 3.5));
+        // Far outside any cell range, but only one cell wide.
+        g.insert(3, Aabb2D::new(-1e300, 0.0, -1e299, 1.0));
+        assert_eq!(g.overflow_len(), 2);
+        assert_eq!(g.occupied_cells(), 2 * 2 + 2);
+
+        let mut hits = Vec::new();
+        g.visit_point(3.0, 3.0, |s| hits.push(s));
+        hits.sort_unstable();
+        assert_eq!(hits, vec![0, 2]);
+        hits.clear();
+        g.visit_point(50.0, 0.5, |s| hits.push(s));
+        hits.sort_unstable();
+        assert_eq!(hits, vec![0, 1]);
+        hits.clear();
+        g.visit_point(-5e299, 0.5, |s| hits.push(s));
+        hits.sort_unstable();
+        assert_eq!(hits, vec![1, 3]);
+
+        g.remove(0);
+        assert_eq!(g.overflow_len(), 1);
+        hits.clear();
+        g.visit_point(50.0, 0.5, |s| hits.push(s));
+        assert_eq!(hits, vec![1]);
+    }
+
+    #[test]
+    fn churn_matches_flatvec() {
+        use crate::backends::flatvec::FlatVec;
+
+        let mut grid = HashGridI64::new(16, 16, (0, 0));
+        let mut flat: FlatVec<i64> = FlatVec::default();
+        let mut seed = 0x9E37_79B9_u64;
+        let mut next = |m: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            i64::try_from(seed % m).unwrap()
+        };
+        for round in 0..4 {
+            for slot in 0..200_usize {
+                let (x, y) = (next(400), next(400));
+                // Mostly small boxes, with some spanning enough cells to overflow.
+                let size = if next(10) == 0 { 200 } else { 1 + next(40) };
+                let aabb = Aabb2D::new(x, y, x + size, y + size);
+                if round == 0 {
+                    grid.insert(slot, aabb);
+                    flat.insert(slot, aabb);
+                } else if slot % 5 == round {
+                    grid.remove(slot);
+                    flat.remove(slot);
+                } else {
+                    grid.update(slot, aabb);
+                    flat.update(slot, aabb);
+                }
+            }
+            for _ in 0..40 {
+                let (x, y) = (next(600), next(600));
+                let q = Aabb2D::new(x, y, x + next(100), y + next(100));
+                let mut a = Vec::new();
+                grid.visit_rect(q, |s| a.push(s));
+                let mut b: Vec<_> = flat.query_rect(q).collect();
+                a.sort_unstable();
+                b.sort_unstable();
+                assert_eq!(a, b);
+            }
+        }
+        assert!(grid.overflow_len() > 0);
+        // Every registered item sits at the position its slot records.
+        for (cell, items) in &grid.cells {
+            for (pos, (s, b)) in items.iter().enumerate() {
+                let Some(Placed {
+                    at: Placement::Cells(places),
+                    ..
+                }) = &grid.slots[*s]
+                else {
+                    panic!("slot {s} is registered in a cell without a cell placement");
+                };
+                assert_eq!(places[grid.span_offset(b, *cell)], pos);
+            }
+        }
+    }
+
+    #[test]
+    fn far_apart_cells_do_not_need_a_bounded_grid() {
+        let mut g = HashGridF64::new(1.0, 1.0, (0.5, 0.5));
+        g.insert(0, Aabb2D::new(1e12, 1e12, 1e12 + 1.0, 1e12 + 1.0));
+        g.insert(1, Aabb2D::new(-1e12, 3.0, -1e12 + 0.5, 3.5));
+        let mut hits = Vec::new();
+        // A huge query falls back to scanning the occupied cells.
+        g.visit_rect(Aabb2D::new(-1e13, -1e13, 1e13, 1e13), |s| hits.push(s));
+        hits.sort_unstable();
+        assert_eq!(hits, vec![0, 1]);
+        hits.clear();
+        g.visit_point(-1e12 + 0.25, 3.25, |s| hits.push(s));
+        assert_eq!(hits, vec![1]);
+    }
+}
@@ -4,8 +4,11 @@
 //! Backend implementations for different spatial strategies.
 //!
 //! - `flatvec`: flat vector with linear scans (small, simple).
-//! - `rtree`: generic R-tree (`T: Scalar`) with SAH-like split (aliases: `RTreeI64`, `RTreeF32`, `RTreeF64`).
-//! - `bvh`: generic BVH (`T: Scalar`) with SAH-like split (aliases: `BvhF32`, `BvhF64`, `BvhI64`).
+//! - `rtree`: generic R-tree (`T: Scalar`) with SAH-like split (aliases: `RTreeI32`, `RTreeI64`, `RTreeF32`, `RTreeF64`).
+//! - `bvh`: generic BVH (`T: Scalar`) with SAH-like split (aliases: `BvhF32`, `BvhF64`, `BvhI32`, `BvhI64`).
+//! - `hashgrid`: unbounded uniform grid (`T: GridScalar`) with occupied cells in a `BTreeMap` (aliases: `HashGridF32`, `HashGridF64`, `HashGridI64`).
+//! - `quadtree`: region quadtree (`T: Scalar`) over fixed bounds with lazy subdivision (aliases: `QuadtreeF32`, `QuadtreeF64`, `QuadtreeI64`).
+//! - `any`: one of the above, chosen at runtime (`AnyBackend`, `BackendKind`).
 //!
 //! SAH note
 //! --------
@@ -17,8 +20,12 @@
 //! where `LB_k` and `RB_k` are the bounding boxes of the first `k` and remaining `n - k` items.
 //! We evaluate all `k` in O(n) per axis using prefix/suffix bounding boxes, and pick the lowest cost.
 //! Accumulators are widened (`f32`→`f64`, `f64`→`f64`, `i64`→`i128`) for robust comparisons.
+//! Costs use checked arithmetic; a candidate whose cost overflows the accumulator is skipped.
 //! Bulk builders use an STR-like pass to seed packed leaves and parents.
 
+pub mod any;
 pub mod bvh;
 pub mod flatvec;
+pub mod hashgrid;
+pub mod quadtree;
 pub mod rtree;
@@ -0,0 +1,288 @@
+// Copyright 2025 the Understory Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Region quadtree backend generic over scalar `T: Scalar`.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use crate::backend::{Backend, SmallStack, vec_bytes};
+#[cfg(feature = "stats")]
+use crate::stats::QueryStats;
+use crate::stats::{BackendStats, StatsCounter};
+use crate::types::{Aabb2D, Scalar};
+
+/// A region quadtree over fixed root bounds.
+///
+/// Nodes subdivide lazily: a node splits into four quadrants (see
+/// [`Aabb2D::quadrants`]) only once it holds more than `capacity` items and sits above
+/// `max_depth`. Each item lives at the deepest node whose region fully contains it, so
+/// boxes that straddle a quadrant boundary stay at the parent. Boxes that are not
+/// contained in the root bounds are kept at the root and are always tested.
+///
+/// Nodes are not merged back when items are removed, nor by
+/// [`clear_retain`](Backend::clear_retain); use [`clear`](Backend::clear) to reclaim them.
+///
+/// # Result order
+///
+/// Queries walk the tree depth-first, reporting the items of a node before those of its
+/// children, in storage order. The order can change after any update.
+pub struct Quadtree<T: Scalar> {
+    bounds: Aabb2D<T>,
+    max_depth: usize,
+    capacity: usize,
+    nodes: Vec<Node<T>>,
+    /// Per slot: the box and the index of the node holding it.
+    slots: Vec<Option<(Aabb2D<T>, usize)>>,
+    stats: StatsCounter,
+}
+
+struct Node<T: Scalar> {
+    region: Aabb2D<T>,
+    depth: usize,
+    items: Vec<(usize, Aabb2D<T>)>,
+    children: Option<[usize; 4]>,
+}
+
+impl<T: Scalar> Node<T> {
+    fn new(region: Aabb2D<T>, depth: usize) -> Self {
+        Self {
+            region,
+            depth,
+            items: Vec::new(),
+            children: None,
+        }
+    }
+}
+
+impl<T: Scalar> Quadtree<T> {
+    /// Create an empty quadtree covering `bounds`.
+    ///
+    /// Nodes split once they hold more than `capacity` items (at least 1), down to
+    /// `max_depth` levels below the root.
+    pub fn new(bounds: Aabb2D<T>, max_depth: usize, capacity: usize) -> Self {
+        Self {
+            bounds,
+            max_depth,
+            capacity: capacity.max(1),
+            nodes: vec![Node::new(bounds, 0)],
+            slots: Vec::new(),
+            stats: StatsCounter::default(),
+        }
+    }
+
+    /// The root bounds.
+    pub fn bounds(&self) -> Aabb2D<T> {
+        self.bounds
+    }
+
+    /// Index of the deepest existing node under `start` whose region contains `aabb`.
+    fn descend(&self, start: usize, aabb: &Aabb2D<T>) -> usize {
+        let mut at = start;
+        while let Some(children) = self.nodes[at].children {
+            match children
+                .into_iter()
+                .find(|&c| self.nodes[c].region.contains_aabb(aabb))
+            {
+                Some(c) => at = c,
+                None => break,
+            }
+        }
+        at
+    }
+
+    fn place(&mut self, start: usize, slot: usize, aabb: Aabb2D<T>) {
+        let at = self.descend(start, &aabb);
+        self.nodes[at].items.push((slot, aabb));
+        self.slots[slot] = Some((aabb, at));
+        let node = &self.nodes[at];
+        if node.children.is_none()
+            && node.items.len() > self.capacity
+            && node.depth < self.max_depth
+        {
+            self.split(at);
+        }
+    }
+
+    /// Subdivide a leaf and push down every item that fits a quadrant.
+    fn split(&mut self, at: usize) {
+        let depth = self.nodes[at].depth + 1;
+        let first = self.nodes.len();
+        for q in self.nodes[at].region.quadrants() {
+            self.nodes.push(Node::new(q, depth));
+        }
+        self.nodes[at].children = Some([first, first + 1, first + 2, first + 3]);
+        let items = core::mem::take(&mut self.nodes[at].items);
+        for (slot, aabb) in items {
+            self.place(at, slot, aabb);
+        }
+    }
+
+    fn ensure_slot(&mut self, slot: usize) {
+        if self.slots.len() <= slot {
+            self.slots.resize(slot + 1, None);
+        }
+    }
+}
+
+impl<T: Scalar> Backend<T> for Quadtree<T> {
+    fn insert(&mut self, slot: usize, aabb: Aabb2D<T>) {
+        self.ensure_slot(slot);
+        self.place(0, slot, aabb);
+    }
+
+    fn update(&mut self, slot: usize, aabb: Aabb2D<T>) {
+        self.remove(slot);
+        self.insert(slot, aabb);
+    }
+
+    fn remove(&mut self, slot: usize) {
+        if let Some((_, at)) = self.slots.get_mut(slot).and_then(Option::take) {
+            let items = &mut self.nodes[at].items;
+            if let Some(pos) = items.iter().position(|(s, _)| *s == slot) {
+                items.swap_remove(pos);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.nodes = vec![Node::new(self.bounds, 0)];
+        self.slots = Vec::new();
+    }
+
+    /// Empty every node but keep the subdivision, so refilling a similar scene reuses
+    /// both the nodes and their item lists.
+    fn clear_retain(&mut self) {
+        for node in &mut self.nodes {
+            node.items.clear();
+        }
+        self.slots.clear();
+    }
+
+    fn visit_point<F: FnMut(usize)>(&self, x: T, y: T, mut f: F) {
+        let _ = self.query_point_visit(x, y, |i| {
+            f(i);
+            true
+        });
+    }
+
+    fn visit_rect<F: FnMut(usize)>(&self, rect: Aabb2D<T>, mut f: F) {
+        let _ = self.query_rect_visit(rect, |i| {
+            f(i);
+            true
+        });
+    }
+
+    fn query_point_visit<F: FnMut(usize) -> bool>(&self, x: T, y: T, f: F) -> bool {
+        self.query_rect_visit(Aabb2D::new(x, y, x, y), f)
+    }
+
+    fn query_rect_visit<F: FnMut(usize) -> bool>(&self, rect: Aabb2D<T>, mut f: F) -> bool {
+        let (mut nodes, mut tested) = (0, 0);
+        let mut finished = true;
+        // The root is always walked: it also holds boxes outside the root bounds.
+        let mut stack = SmallStack::<usize, 32>::new(0);
+        'walk: while let Some(i) = stack.pop() {
+            let n = &self.nodes[i];
+            nodes += 1;
+            tested += n.items.len() as u64;
+            for (s, b) in &n.items {
+                if !b.intersect(&rect).is_empty() && !f(*s) {
+                    finished = false;
+                    break 'walk;
+                }
+            }
+            if let Some(children) = n.children {
+                for c in children.into_iter().rev() {
+                    if !self.nodes[c].region.intersect(&rect).is_empty() {
+                        stack.push(c);
+                    }
+                }
+            }
+        }
+        self.stats.record(nodes, tested);
+        finished
+    }
+
+    #[cfg(feature = "stats")]
+    fn take_query_stats(&self) -> QueryStats {
+        self.stats.take()
+    }
+
+    fn stats(&self) -> BackendStats {
+        BackendStats {
+            node_count: self.nodes.len(),
+            leaf_count: self.nodes.iter().filter(|n| n.children.is_none()).count(),
+            max_depth: self.nodes.iter().map(|n| n.depth).max().unwrap_or(0),
+            item_count: self.slots.iter().flatten().count(),
+            dead_nodes: 0,
+        }
+    }
+
+    fn memory_bytes(&self) -> usize {
+        let items: usize = self.nodes.iter().map(|n| vec_bytes(&n.items)).sum();
+        vec_bytes(&self.nodes) + items + vec_bytes(&self.slots)
+    }
+}
+
+impl<T: Scalar> Debug for Quadtree<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let alive = self.slots.iter().filter(|e| e.is_some()).count();
+        f.debug_struct("Quadtree")
+            .field("bounds", &self.bounds)
+            .field("max_depth", &self.max_depth)
+            .field("capacity", &self.capacity)
+            .field("nodes", &self.nodes.len())
+            .field("alive", &alive)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Convenience type aliases for common scalar choices.
+/// Quadtree with f32 coordinates.
+pub type QuadtreeF32 = Quadtree<f32>;
+
+/// Quadtree with f64 coordinates.
+pub type QuadtreeF64 = Quadtree<f64>;
+
+/// Quadtree with i64 coordinates.
+pub type QuadtreeI64 = Quadtree<i64>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn straddling_items_stay_at_parent() {
+        let mut q = QuadtreeF64::new(Aabb2D::new(0.0, 0.0, 100.0, 100.0), 4, 1);
+        q.insert(0, Aabb2D::new(10.0, 10.0, 20.0, 20.0));
+        q.insert(1, Aabb2D::new(60.0, 60.0, 70.0, 70.0));
+        // Crosses the center lines, so it cannot move into any quadrant.
+        q.insert(2, Aabb2D::new(40.0, 40.0, 60.0, 60.0));
+        assert!(q.nodes[0].children.is_some());
+        assert_eq!(q.slots[2].map(|(_, at)| at), Some(0));
+        assert_ne!(q.slots[0].map(|(_, at)| at), Some(0));
+
+        let mut hits = Vec::new();
+        q.visit_point(55.0, 55.0, |s| hits.push(s));
+        assert_eq!(hits, vec![2]);
+    }
+
+    #[test]
+    fn outside_bounds_and_depth_limit() {
+        let mut q = QuadtreeI64::new(Aabb2D::new(0, 0, 16, 16), 2, 1);
+        q.insert(0, Aabb2D::new(-50, -50, -40, -40));
+        for s in 1..6 {
+            q.insert(s, Aabb2D::new(1, 1, 2, 2));
+        }
+        assert!(q.nodes.iter().all(|n| n.depth <= 2));
+        let mut hits = Vec::new();
+        q.visit_point(-45, -45, |s| hits.push(s));
+        assert_eq!(hits, vec![0]);
+        hits.clear();
+        q.visit_point(1, 1, |s| hits.push(s));
+        hits.sort_unstable();
+        assert_eq!(hits, vec![1, 2, 3, 4, 5]);
+    }
+}
@@ -4,30 +4,273 @@
 //! R-tree backend generic over scalar `T: Scalar` with SAH-like split.
 
 use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt::Debug;
 
-use crate::backend::Backend;
-use crate::types::{Aabb2D, Scalar, area, union_aabb};
+use crate::backend::{Backend, GroupId, SmallStack, best_first_knn, improves_nearest, vec_bytes};
+#[cfg(feature = "stats")]
+use crate::stats::QueryStats;
+use crate::stats::{BackendStats, StatsCounter};
+use crate::types::{Aabb2D, Scalar, area, point_dist2, sah_cost, union_aabb};
 
 /// R-tree backend using SAH-like splits and widened accumulator metrics.
-pub struct RTree<T: Scalar, P: Copy + Debug> {
-    max_children: usize,
-    min_children: usize,
+///
+/// The fan-out is chosen by `N`. The default [`RuntimeFanout`] holds at most 8 children
+/// per node and, once split, at least 4, or whatever [`with_params`](RTree::with_params)
+/// sets, and keeps each node's children in a `Vec`. [`FixedFanout<M>`](FixedFanout) fixes
+/// the limits at `M` and `M / 2` at compile time and stores the children inline in the
+/// node, so nodes need no allocation of their own. See [`RTree8`] and [`RTree16`] for
+/// common fixed fan-outs.
+pub struct RTree<T: Scalar, P: Copy + Debug, N: Fanout = RuntimeFanout> {
+    fanout: N,
     root: Option<NodeIdx>,
-    arena: Vec<RNode<T, P>>,
+    arena: Vec<RNode<T, P, N>>,
     slots: Vec<Option<Aabb2D<T>>>,
+    // Arena index of the leaf holding each live slot.
+    leaves: Vec<usize>,
+    // Arena indices of nodes that died since the last compaction; may hold stale entries.
+    holes: Vec<usize>,
+    // Number of dead nodes still in the arena.
+    dead: usize,
+    stats: StatsCounter,
+}
+
+mod sealed {
+    use core::ops::{Deref, DerefMut};
+
+    #[allow(
+        unnameable_types,
+        reason = "Sealing pattern: the trait is public but intentionally unnameable."
+    )]
+    pub trait Sealed {}
+    impl Sealed for super::RuntimeFanout {}
+    impl<const M: usize> Sealed for super::FixedFanout<M> {}
+
+    /// Storage for the children of one node.
+    #[allow(
+        unnameable_types,
+        reason = "Implementation detail of the sealed `Fanout` trait."
+    )]
+    pub trait ChildList<E>: Clone + Default + Deref<Target = [E]> + DerefMut {
+        /// A list holding a copy of `items`.
+        fn from_slice(items: &[E]) -> Self;
+
+        /// Append `item`.
+        fn push(&mut self, item: E);
+
+        /// Insert `item` at `index`, shifting the later ones up.
+        fn insert(&mut self, index: usize, item: E);
+
+        /// Keep only the items for which `f` returns `true`, in order.
+        fn retain(&mut self, f: impl FnMut(&E) -> bool);
+
+        /// Heap bytes held by the list.
+        fn heap_bytes(&self) -> usize;
+    }
+
+    /// Up to `M` children stored inline.
+    #[allow(
+        unnameable_types,
+        reason = "Implementation detail of the sealed `Fanout` trait."
+    )]
+    #[derive(Clone)]
+    pub struct InlineChildren<E, const M: usize> {
+        len: usize,
+        items: [E; M],
+    }
+
+    impl<E: Copy + Default, const M: usize> Default for InlineChildren<E, M> {
+        fn default() -> Self {
+            Self {
+                len: 0,
+                items: [E::default(); M],
+            }
+        }
+    }
+
+    impl<E: core::fmt::Debug, const M: usize> core::fmt::Debug for InlineChildren<E, M> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_list().entries(self.iter()).finish()
+        }
+    }
+
+    impl<E, const M: usize> Deref for InlineChildren<E, M> {
+        type Target = [E];
+
+        fn deref(&self) -> &[E] {
+            &self.items[..self.len]
+        }
+    }
+
+    impl<E, const M: usize> DerefMut for InlineChildren<E, M> {
+        fn deref_mut(&mut self) -> &mut [E] {
+            &mut self.items[..self.len]
+        }
+    }
+
+    impl<E: Copy + Default, const M: usize> ChildList<E> for InlineChildren<E, M> {
+        fn from_slice(items: &[E]) -> Self {
+            let mut out = Self::default();
+            out.items[..items.len()].copy_from_slice(items);
+            out.len = items.len();
+            out
+        }
+
+        fn push(&mut self, item: E) {
+            self.insert(self.len, item);
+        }
+
+        fn insert(&mut self, index: usize, item: E) {
+            assert!(self.len < M, "an R-tree node holds at most {M} children");
+            assert!(index <= self.len, "child index out of bounds");
+            self.items.copy_within(index..self.len, index + 1);
+            self.items[index] = item;
+            self.len += 1;
+        }
+
+        fn retain(&mut self, mut f: impl FnMut(&E) -> bool) {
+            let mut kept = 0;
+            for i in 0..self.len {
+                let item = self.items[i];
+                if f(&item) {
+                    self.items[kept] = item;
+                    kept += 1;
+                }
+            }
+            self.len = kept;
+        }
+
+        fn heap_bytes(&self) -> usize {
+            0
+        }
+    }
+
+    impl<E: Clone> ChildList<E> for alloc::vec::Vec<E> {
+        fn from_slice(items: &[E]) -> Self {
+            items.to_vec()
+        }
+
+        fn push(&mut self, item: E) {
+            Self::push(self, item);
+        }
+
+        fn insert(&mut self, index: usize, item: E) {
+            Self::insert(self, index, item);
+        }
+
+        fn retain(&mut self, f: impl FnMut(&E) -> bool) {
+            Self::retain(self, f);
+        }
+
+        fn heap_bytes(&self) -> usize {
+            crate::backend::vec_bytes(self)
+        }
+    }
+}
+
+use sealed::{ChildList, InlineChildren};
+
+/// Fan-out of an [`RTree`]: how many children a node holds and where they are stored.
+///
+/// Implemented by [`RuntimeFanout`] and [`FixedFanout`]; the trait is sealed.
+pub trait Fanout: Copy + Debug + Default + sealed::Sealed {
+    /// Storage for the children of one node.
+    #[doc(hidden)]
+    type Children<E: Copy + Default>: ChildList<E>;
+
+    /// `(max_children, min_children)`: a node holds at most `max_children` children and,
+    /// once split, at least `min_children`.
+    fn limits(&self) -> (usize, usize);
+}
+
+/// Fan-out chosen at run time, with each node's children in a `Vec`.
+///
+/// Defaults to at most 8 children and at least 4; see [`RTree::with_params`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RuntimeFanout {
+    max_children: usize,
+    min_children: usize,
+}
+
+impl Default for RuntimeFanout {
+    fn default() -> Self {
+        Self {
+            max_children: 8,
+            min_children: 4,
+        }
+    }
+}
+
+impl RuntimeFanout {
+    fn new(max_children: usize, min_children: usize) -> Self {
+        assert!(
+            min_children >= 2 && min_children.saturating_mul(2) <= max_children,
+            "R-tree fan-out needs min_children >= 2 and 2 * min_children <= max_children \
+             (got max {max_children}, min {min_children})"
+        );
+        Self {
+            max_children,
+            min_children,
+        }
+    }
+}
+
+impl Fanout for RuntimeFanout {
+    type Children<E: Copy + Default> = Vec<E>;
+
+    #[inline]
+    fn limits(&self) -> (usize, usize) {
+        (self.max_children, self.min_children)
+    }
+}
+
+/// Fan-out fixed at compile time: at most `M` children and, once split, at least `M / 2`,
+/// stored inline in an `M`-element array in each node.
+///
+/// An `M` below 4 fails to compile.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FixedFanout<const M: usize>;
+
+impl<const M: usize> FixedFanout<M> {
+    const LIMITS: (usize, usize) = {
+        assert!(M >= 4, "a fixed R-tree fan-out must be at least 4");
+        (M, M / 2)
+    };
+}
+
+impl<const M: usize> Default for FixedFanout<M> {
+    fn default() -> Self {
+        // Every tree starts from a default fan-out, so this rejects a small `M` at compile time.
+        let _ = Self::LIMITS;
+        Self
+    }
+}
+
+impl<const M: usize> Fanout for FixedFanout<M> {
+    type Children<E: Copy + Default> = InlineChildren<E, M>;
+
+    #[inline]
+    fn limits(&self) -> (usize, usize) {
+        Self::LIMITS
+    }
 }
 
 #[derive(Clone)]
-struct RNode<T: Scalar, P: Copy + Debug> {
+struct RNode<T: Scalar, P: Copy + Debug, N: Fanout> {
     bbox: Aabb2D<T>,
     leaf: bool,
-    children: Vec<RChild<T, P>>,
+    children: RChildList<T, P, N>,
+    parent: Option<NodeIdx>,
+    // Unreachable from the root; reclaimed by compaction.
+    dead: bool,
 }
 
-#[derive(Clone)]
+/// Children of a node in a tree with fan-out `N`.
+type RChildList<T, P, N> = <N as Fanout>::Children<RChild<T, P>>;
+
+#[derive(Copy, Clone)]
 enum RChild<T: Scalar, P: Copy + Debug> {
     Node(NodeIdx),
     Item {
@@ -37,6 +280,13 @@ enum RChild<T: Scalar, P: Copy + Debug> {
     },
 }
 
+// Filler for unused inline child slots; never read.
+impl<T: Scalar, P: Copy + Debug> Default for RChild<T, P> {
+    fn default() -> Self {
+        Self::Node(NodeIdx::new(usize::MAX))
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 struct NodeIdx(usize);
 
@@ -50,16 +300,52 @@ impl NodeIdx {
     }
 }
 
-impl<T: Scalar, P: Copy + Debug> Default for RTree<T, P> {
+impl<T: Scalar, P: Copy + Debug, N: Fanout> Default for RTree<T, P, N> {
     fn default() -> Self {
         Self {
-            max_children: 8,
-            min_children: 4,
+            fanout: N::default(),
             root: None,
             arena: Vec::new(),
             slots: Vec::new(),
+            leaves: Vec::new(),
+            holes: Vec::new(),
+            dead: 0,
+            stats: StatsCounter::default(),
+        }
+    }
+}
+
+impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
+    /// Create an empty tree whose nodes hold at most `max_children` children and, once
+    /// split, at least `min_children`.
+    ///
+    /// Higher fan-out gives shallower trees, which suits read-mostly scenes; the default is
+    /// 8 and 4. For a fan-out known at compile time, prefer a [`FixedFanout`] tree such as
+    /// [`RTree16`], which also stores the children inline.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `min_children >= 2` and `2 * min_children <= max_children`.
+    pub fn with_params(max_children: usize, min_children: usize) -> Self {
+        Self {
+            fanout: RuntimeFanout::new(max_children, min_children),
+            ..Self::default()
         }
     }
+
+    /// Like [`bulk_build_default`](Self::bulk_build_default), packing leaves and parents with
+    /// up to `max_children` children; see [`with_params`](Self::with_params).
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `min_children >= 2` and `2 * min_children <= max_children`.
+    pub fn bulk_build_with_params(
+        pairs: &[(usize, Aabb2D<T>)],
+        max_children: usize,
+        min_children: usize,
+    ) -> Self {
+        Self::bulk_build_fanout(pairs, RuntimeFanout::new(max_children, min_children))
+    }
 }
 
 // Reduce clippy::type_complexity noise for local helpers.
@@ -70,14 +356,54 @@ type RBestSplit<TS, PS> = Option<(
     RChildren<TS, PS>,
 )>;
 
-impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
+impl<T: Scalar, P: Copy + Debug, N: Fanout> RTree<T, P, N> {
+    /// `(max_children, min_children)` in effect; constants for a [`FixedFanout`].
+    #[inline]
+    fn fanout(&self) -> (usize, usize) {
+        self.fanout.limits()
+    }
+
     fn ensure_slot(&mut self, slot: usize, bbox: Aabb2D<T>) {
         if self.slots.len() <= slot {
             self.slots.resize_with(slot + 1, || None);
+            self.leaves.resize(slot + 1, 0);
         }
         self.slots[slot] = Some(bbox);
     }
 
+    /// Rebuild parent links and the slot→leaf map from the tree shape.
+    fn relink(&mut self) {
+        self.leaves.resize(self.slots.len(), 0);
+        let Some(root) = self.root else {
+            return;
+        };
+        self.arena[root.get()].parent = None;
+        let mut stack = vec![root.get()];
+        while let Some(i) = stack.pop() {
+            for c in 0..self.arena[i].children.len() {
+                match self.arena[i].children[c] {
+                    RChild::Node(ci) => {
+                        self.arena[ci.get()].parent = Some(NodeIdx::new(i));
+                        stack.push(ci.get());
+                    }
+                    RChild::Item { slot, .. } => self.leaves[slot] = i,
+                }
+            }
+        }
+    }
+
+    /// Root→leaf arena path to the leaf holding the live `slot`, found by following
+    /// parent links up from the leaf in O(depth).
+    fn leaf_path(&self, slot: usize) -> Option<Vec<usize>> {
+        self.slots.get(slot).copied().flatten()?;
+        let mut path = vec![self.leaves[slot]];
+        while let Some(p) = self.arena[path[path.len() - 1]].parent {
+            path.push(p.get());
+        }
+        path.reverse();
+        Some(path)
+    }
+
     #[inline]
     fn ceil_div(a: usize, b: usize) -> usize {
         a.div_ceil(b)
@@ -93,13 +419,27 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
 
     /// STR-like bulk builder: creates a packed tree from items in one pass into `arena`.
     fn bulk_build_nodes(
-        arena: &mut Vec<RNode<T, P>>,
+        arena: &mut Vec<RNode<T, P, N>>,
         items: &mut [(usize, Aabb2D<T>)],
         max_children: usize,
     ) -> Option<NodeIdx> {
         if items.is_empty() {
             return None;
         }
+        let leaves = Self::str_leaves(arena, items, max_children);
+        Self::pack_levels(arena, leaves, max_children)
+    }
+
+    /// STR leaf pass: tile items into x-slices, sort each slice by y, and pack runs of
+    /// `max_children` into leaves. Returns the arena indices of the new leaves.
+    fn str_leaves(
+        arena: &mut Vec<RNode<T, P, N>>,
+        items: &mut [(usize, Aabb2D<T>)],
+        max_children: usize,
+    ) -> Vec<usize> {
+        if items.is_empty() {
+            return Vec::new();
+        }
 
         // Build leaf level (as node indices in the arena)
         let n = items.len();
@@ -122,27 +462,45 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
                     .unwrap_or(core::cmp::Ordering::Equal)
             });
             for chunk in slice.chunks(max_children) {
-                let mut children: Vec<RChild<T, P>> = Vec::with_capacity(chunk.len());
-                for (slot, bbox) in chunk.iter().copied() {
-                    children.push(RChild::Item {
-                        slot,
-                        bbox,
-                        _p: core::marker::PhantomData,
-                    });
-                }
-                let bbox = Self::node_bbox(arena, &children);
-                let idx = arena.len();
-                arena.push(RNode {
-                    bbox,
-                    leaf: true,
-                    children,
-                });
-                leaves.push(idx);
+                leaves.push(Self::push_leaf(arena, chunk));
             }
         }
+        leaves
+    }
+
+    /// Push a leaf holding `items` into `arena` and return its index.
+    fn push_leaf(arena: &mut Vec<RNode<T, P, N>>, items: &[(usize, Aabb2D<T>)]) -> usize {
+        let mut children = RChildList::<T, P, N>::default();
+        for &(slot, bbox) in items {
+            children.push(RChild::Item {
+                slot,
+                bbox,
+                _p: core::marker::PhantomData,
+            });
+        }
+        let bbox = Self::node_bbox(arena, &children);
+        let idx = arena.len();
+        arena.push(RNode {
+            bbox,
+            leaf: true,
+            children,
+            parent: None,
+            dead: false,
+        });
+        idx
+    }
+
+    /// Promote a level of nodes into parents (STR-packed) until a single root remains.
+    fn pack_levels(
+        arena: &mut Vec<RNode<T, P, N>>,
+        mut level: Vec<usize>,
+        max_children: usize,
+    ) -> Option<NodeIdx> {
+        if level.is_empty() {
+            return None;
+        }
 
         // Promote until a single root remains
-        let mut level: Vec<usize> = leaves;
         while level.len() > max_children {
             let n_nodes = level.len();
             let num_parents = Self::ceil_div(n_nodes, max_children);
@@ -167,7 +525,7 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
                 while i < slice.len() {
                     let end = core::cmp::min(i + max_children, slice.len());
                     let chunk = &mut slice[i..end];
-                    let mut children: Vec<RChild<T, P>> = Vec::with_capacity(chunk.len());
+                    let mut children = RChildList::<T, P, N>::default();
                     for child_idx in chunk.iter_mut() {
                         let ch_idx = *child_idx;
                         children.push(RChild::Node(NodeIdx::new(ch_idx)));
@@ -178,6 +536,8 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
                         bbox,
                         leaf: false,
                         children,
+                        parent: None,
+                        dead: false,
                     });
                     next.push(idx);
                     i = end;
@@ -191,7 +551,7 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
             Some(NodeIdx::new(level[0]))
         } else {
             // Pack remaining nodes under a new root
-            let mut children: Vec<RChild<T, P>> = Vec::with_capacity(level.len());
+            let mut children = RChildList::<T, P, N>::default();
             for idx in level.into_iter() {
                 children.push(RChild::Node(NodeIdx::new(idx)));
             }
@@ -201,6 +561,8 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
                 bbox,
                 leaf: false,
                 children,
+                parent: None,
+                dead: false,
             });
             Some(NodeIdx::new(root_idx))
         }
@@ -208,10 +570,175 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
 
     /// Build an `RTree` from a set of (slot, bbox) pairs using a packed layout.
     pub fn bulk_build_default(pairs: &[(usize, Aabb2D<T>)]) -> Self {
-        let max_children = 8; // default matches Self::default
+        Self::bulk_build_fanout(pairs, N::default())
+    }
+
+    fn bulk_build_fanout(pairs: &[(usize, Aabb2D<T>)], fanout: N) -> Self {
+        let (max_children, _) = fanout.limits();
         let mut items = pairs.to_vec();
-        let mut arena: Vec<RNode<T, P>> = Vec::new();
+        let mut arena: Vec<RNode<T, P, N>> = Vec::new();
         let root = Self::bulk_build_nodes(&mut arena, &mut items[..], max_children);
+        let mut tree = Self {
+            fanout,
+            root,
+            arena,
+            slots: Self::slots_from_pairs(pairs),
+            leaves: Vec::new(),
+            holes: Vec::new(),
+            dead: 0,
+            stats: StatsCounter::default(),
+        };
+        tree.relink();
+        tree
+    }
+
+    /// Build an `RTree` whose leaves follow a caller-supplied centroid ordering.
+    ///
+    /// Items are sorted once by `cmp`, applied to their centroids `(x, y)`, and consecutive
+    /// runs are packed into leaves, instead of the per-axis STR slicing of
+    /// [`RTree::bulk_build_default`]. Plug in any single-key order (e.g. by `x + y` for
+    /// diagonal layouts); upper levels are packed as in `bulk_build_default`.
+    pub fn bulk_build_with(
+        pairs: &[(usize, Aabb2D<T>)],
+        mut cmp: impl FnMut(&(T, T), &(T, T)) -> core::cmp::Ordering,
+    ) -> Self {
+        let fanout = N::default();
+        let (max_children, _) = fanout.limits();
+        let centroid = |a: &Aabb2D<T>| (Self::centroid_x_of_aabb(a), Self::centroid_y_of_aabb(a));
+        let mut items = pairs.to_vec();
+        items.sort_by(|a, b| cmp(&centroid(&a.1), &centroid(&b.1)));
+        let mut arena: Vec<RNode<T, P, N>> = Vec::new();
+        let leaves = items
+            .chunks(max_children)
+            .map(|chunk| Self::push_leaf(&mut arena, chunk))
+            .collect();
+        let root = Self::pack_levels(&mut arena, leaves, max_children);
+        let mut tree = Self {
+            fanout,
+            root,
+            arena,
+            slots: Self::slots_from_pairs(pairs),
+            leaves: Vec::new(),
+            holes: Vec::new(),
+            dead: 0,
+            stats: StatsCounter::default(),
+        };
+        tree.relink();
+        tree
+    }
+
+    /// Build an `RTree` packed for a known query workload.
+    ///
+    /// This is a specialized builder for read-mostly scenes that are queried with a fixed
+    /// set of rectangles (for example viewport tiles). Each item is assigned to the query
+    /// rectangle it intersects that occurs most often in `queries` (repeats act as
+    /// weights; ties go to the earliest), or to a shared bucket if it intersects none.
+    /// Every bucket is STR-packed on its own, so no leaf mixes items from different query
+    /// regions. Upper levels are packed as in [`RTree::bulk_build_default`].
+    ///
+    /// This is a bucketing heuristic, not a cost-model (SAH) optimization. It pays off when
+    /// the queries cut across the square-ish leaves of a uniform packing, such as wide
+    /// strips; queries covering only a few items each give small buckets and so more,
+    /// partly filled leaves. So the uniform packing is built too, both are scored by the
+    /// tree nodes the workload visits, and the bucketed tree is kept only if it visits
+    /// strictly fewer: the result is never worse than [`RTree::bulk_build_default`] on
+    /// `queries`, at the price of building and querying two trees.
+    ///
+    /// With an empty `queries` this is equivalent to [`RTree::bulk_build_default`].
+    pub fn bulk_build_for_queries(pairs: &[(usize, Aabb2D<T>)], queries: &[Aabb2D<T>]) -> Self {
+        let uniform = Self::bulk_build_default(pairs);
+        if queries.is_empty() {
+            return uniform;
+        }
+        let bucketed = Self::bulk_build_bucketed(pairs, queries);
+        let cost = |t: &Self| -> u64 { queries.iter().map(|q| t.nodes_visited_by(q)).sum() };
+        if cost(&bucketed) < cost(&uniform) {
+            bucketed
+        } else {
+            uniform
+        }
+    }
+
+    /// Nodes a rect query visits, as counted in [`QueryStats`](crate::QueryStats), without
+    /// recording them.
+    fn nodes_visited_by(&self, rect: &Aabb2D<T>) -> u64 {
+        let Some(root) = self.root else {
+            return 0;
+        };
+        let mut visited = 0;
+        let mut stack = SmallStack::<_, 32>::new(root);
+        while let Some(i) = stack.pop() {
+            visited += 1;
+            let n = &self.arena[i.get()];
+            if n.leaf || n.bbox.intersect(rect).is_empty() {
+                continue;
+            }
+            for c in n.children.iter() {
+                if let RChild::Node(ci) = c {
+                    stack.push(*ci);
+                }
+            }
+        }
+        visited
+    }
+
+    /// The bucketed packing behind [`RTree::bulk_build_for_queries`].
+    fn bulk_build_bucketed(pairs: &[(usize, Aabb2D<T>)], queries: &[Aabb2D<T>]) -> Self {
+        let fanout = N::default();
+        let (max_children, _) = fanout.limits();
+        // Weight of each query rect: how many times it appears in the workload.
+        let weights: Vec<usize> = queries
+            .iter()
+            .map(|q| queries.iter().filter(|o| *o == q).count())
+            .collect();
+        let bucket_of = |bbox: &Aabb2D<T>| -> usize {
+            let mut best: Option<(usize, usize)> = None;
+            for (i, q) in queries.iter().enumerate() {
+                if !q.intersect(bbox).is_empty() && best.is_none_or(|(_, w)| weights[i] > w) {
+                    best = Some((i, weights[i]));
+                }
+            }
+            best.map(|(i, _)| i).unwrap_or(queries.len())
+        };
+        let mut items: Vec<(usize, usize, Aabb2D<T>)> = pairs
+            .iter()
+            .map(|&(slot, bbox)| (bucket_of(&bbox), slot, bbox))
+            .collect();
+        items.sort_by_key(|&(bucket, _, _)| bucket);
+
+        let mut arena: Vec<RNode<T, P, N>> = Vec::new();
+        let mut leaves: Vec<usize> = Vec::new();
+        let mut start = 0;
+        while start < items.len() {
+            let bucket = items[start].0;
+            let end = start
+                + items[start..]
+                    .iter()
+                    .take_while(|&&(b, _, _)| b == bucket)
+                    .count();
+            let mut group: Vec<(usize, Aabb2D<T>)> = items[start..end]
+                .iter()
+                .map(|&(_, slot, bbox)| (slot, bbox))
+                .collect();
+            leaves.extend(Self::str_leaves(&mut arena, &mut group, max_children));
+            start = end;
+        }
+        let root = Self::pack_levels(&mut arena, leaves, max_children);
+        let mut tree = Self {
+            fanout,
+            root,
+            arena,
+            slots: Self::slots_from_pairs(pairs),
+            leaves: Vec::new(),
+            holes: Vec::new(),
+            dead: 0,
+            stats: StatsCounter::default(),
+        };
+        tree.relink();
+        tree
+    }
+
+    fn slots_from_pairs(pairs: &[(usize, Aabb2D<T>)]) -> Vec<Option<Aabb2D<T>>> {
         let mut slots: Vec<Option<Aabb2D<T>>> = Vec::new();
         for (slot, bbox) in pairs.iter().copied() {
             if slots.len() <= slot {
@@ -219,16 +746,102 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
             }
             slots[slot] = Some(bbox);
         }
-        Self {
-            max_children,
-            min_children: 4,
-            root,
-            arena,
-            slots,
+        slots
+    }
+
+    /// Drop arena nodes that are no longer reachable from the root.
+    ///
+    /// Removals and splits leave dead nodes behind, so the arena only grows. This rebuilds it
+    /// with just the live nodes, children before parents in depth-first order, and remaps
+    /// all node indices. The tree shape and query results are unchanged.
+    pub fn compact_arena(&mut self) {
+        let mut old = core::mem::take(&mut self.arena);
+        if let Some(root) = self.root {
+            let root = Self::move_node(&mut old, &mut self.arena, root.get());
+            self.root = Some(NodeIdx::new(root));
+        }
+        self.holes.clear();
+        self.dead = 0;
+        self.relink();
+    }
+
+    /// Reclaim at most `max_nodes` dead arena nodes; returns whether any remain.
+    ///
+    /// Dead nodes at the end of the arena are dropped, others are overwritten by the last
+    /// node, whose links are patched in O(fan-out). Unlike [`compact_arena`](Self::compact_arena)
+    /// this keeps the live nodes where they are, apart from the moved ones.
+    pub fn compact_arena_step(&mut self, max_nodes: usize) -> bool {
+        for _ in 0..max_nodes {
+            if self.dead == 0 {
+                break;
+            }
+            if self.arena.last().is_some_and(|n| n.dead) {
+                self.arena.pop();
+                self.dead -= 1;
+                continue;
+            }
+            // A dead node below the live last one is always listed.
+            let hole = self.holes.pop().expect("dead nodes are listed in holes");
+            if hole < self.arena.len() && self.arena[hole].dead {
+                let from = self.arena.len() - 1;
+                self.arena.swap_remove(hole);
+                self.dead -= 1;
+                self.relocate(from, hole);
+            }
+        }
+        if self.dead == 0 {
+            self.holes.clear();
+        }
+        self.dead > 0
+    }
+
+    /// Point every link to the node moved from arena index `from` at its new index `to`.
+    fn relocate(&mut self, from: usize, to: usize) {
+        let to_idx = NodeIdx::new(to);
+        match self.arena[to].parent {
+            None => self.root = Some(to_idx),
+            Some(p) => {
+                for c in self.arena[p.get()].children.iter_mut() {
+                    if matches!(c, RChild::Node(i) if i.get() == from) {
+                        *c = RChild::Node(to_idx);
+                    }
+                }
+            }
+        }
+        for c in 0..self.arena[to].children.len() {
+            match self.arena[to].children[c] {
+                RChild::Node(ci) => self.arena[ci.get()].parent = Some(to_idx),
+                RChild::Item { slot, .. } => self.leaves[slot] = to,
+            }
+        }
+    }
+
+    /// Remove `slot`, stored at `old`, from the tree and count the nodes that died.
+    fn remove_from_tree(&mut self, root: NodeIdx, slot: usize, old: &Aabb2D<T>) {
+        let before = self.holes.len();
+        let _ = Self::search_remove(&mut self.arena, &mut self.holes, root.get(), slot, old);
+        self.dead += self.holes.len() - before;
+    }
+
+    /// Move the subtree at `i` from `old` into `new`; returns its index in `new`.
+    fn move_node(old: &mut [RNode<T, P, N>], new: &mut Vec<RNode<T, P, N>>, i: usize) -> usize {
+        let mut children = core::mem::take(&mut old[i].children);
+        for c in children.iter_mut() {
+            if let RChild::Node(n) = c {
+                *n = NodeIdx::new(Self::move_node(old, new, n.get()));
+            }
         }
+        new.push(RNode {
+            bbox: old[i].bbox,
+            leaf: old[i].leaf,
+            children,
+            parent: None,
+            dead: false,
+        });
+        new.len() - 1
     }
 
-    fn node_bbox(arena: &[RNode<T, P>], children: &[RChild<T, P>]) -> Aabb2D<T> {
+    fn node_bbox(arena: &[RNode<T, P, N>], children: &[RChild<T, P>]) -> Aabb2D<T> {
         let mut it = children.iter();
         let first = match it.next() {
             Some(RChild::Node(i)) => arena[i.get()].bbox,
@@ -246,7 +859,11 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
         area(&u) - area(a)
     }
 
-    fn choose_child(arena: &[RNode<T, P>], children: &[RChild<T, P>], bbox: &Aabb2D<T>) -> usize {
+    fn choose_child(
+        arena: &[RNode<T, P, N>],
+        children: &[RChild<T, P>],
+        bbox: &Aabb2D<T>,
+    ) -> usize {
         let mut best_idx = 0_usize;
         let mut best_cost: Option<T::Acc> = None;
         for (i, c) in children.iter().enumerate() {
@@ -267,7 +884,6 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
     /// choose `k` that minimizes `area(LB_k) * k + area(RB_k) * (n - k)`.
     fn split_children_with<F>(
         children: &mut [RChild<T, P>],
-        _max_children: usize,
         min_children: usize,
         mut bbox_of: F,
     ) -> (RChildren<T, P>, RChildren<T, P>)
@@ -281,6 +897,8 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
             Scalar::mid(b.min_y, b.max_y)
         }
         let n = children.len();
+        // Keep both sides non-empty even if `min_children` is too large for `n`.
+        let min_children = min_children.clamp(1, (n / 2).max(1));
         let mut best: RBestSplit<T, P> = None;
         for axis in 0..2 {
             let mut v = children.to_owned();
@@ -321,10 +939,13 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
             }
             suffix.reverse();
 
-            for k in min_children..=(n - min_children) {
+            for k in min_children..=n.saturating_sub(min_children) {
                 let lb = prefix[k - 1];
                 let rb = suffix[k];
-                let c = area(&lb) * T::acc_from_usize(k) + area(&rb) * T::acc_from_usize(n - k);
+                // An overflowing cost is treated as maximally costly.
+                let Some(c) = sah_cost(&lb, k, &rb, n - k) else {
+                    continue;
+                };
                 if best.as_ref().map(|(bc, _, _)| c < *bc).unwrap_or(true) {
                     let left = v[..k].to_vec();
                     let right = v[k..].to_vec();
@@ -332,120 +953,276 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
                 }
             }
         }
-        let (_, l, r) = best.expect("split requires overflow");
-        (l, r)
+        match best {
+            Some((_, l, r)) => (l, r),
+            // No admissible SAH split (fewer than two children): fall back to a
+            // median split of the x-sorted order rather than panicking.
+            None => {
+                let mut v = children.to_owned();
+                v.sort_by(|a, b| {
+                    centroid_x::<T>(&bbox_of(a))
+                        .partial_cmp(&centroid_x::<T>(&bbox_of(b)))
+                        .unwrap_or(core::cmp::Ordering::Equal)
+                });
+                let right = v.split_off(n.div_ceil(2));
+                (v, right)
+            }
+        }
     }
 
+    /// Insert `slot` below `node_idx`, adding the number of node bounds it tests to `visited`.
+    ///
+    /// Returns the index of the new right sibling if `node_idx` split.
     fn insert_node(
-        arena: &mut Vec<RNode<T, P>>,
+        arena: &mut Vec<RNode<T, P, N>>,
+        leaves: &mut [usize],
         node_idx: usize,
         slot: usize,
         bbox: Aabb2D<T>,
         max_children: usize,
         min_children: usize,
+        visited: &mut u64,
     ) -> Option<usize> {
         if arena[node_idx].leaf {
-            // Safe separate block to minimize mutable borrows
-            {
-                let node = &mut arena[node_idx];
-                node.children.push(RChild::Item {
-                    slot,
-                    bbox,
-                    _p: core::marker::PhantomData,
-                });
-                node.bbox = union_aabb(node.bbox, bbox);
-                if node.children.len() <= max_children {
-                    return None;
-                }
-            }
-            // Overflow split for a leaf: children are Items
-            let (left, right, l_bbox, r_bbox) =
-                {
-                    let mut items = core::mem::take(&mut arena[node_idx].children);
-                    let (left, right) =
-                        Self::split_children_with(&mut items, max_children, min_children, |c| {
-                            match c {
-                                RChild::Item { bbox, .. } => *bbox,
-                                RChild::Node(_) => unreachable!(),
-                            }
-                        });
-                    let l_bbox = Self::node_bbox(arena, &left);
-                    let r_bbox = Self::node_bbox(arena, &right);
-                    (left, right, l_bbox, r_bbox)
-                };
-            {
-                let node = &mut arena[node_idx];
-                node.leaf = true;
-                node.children = left;
-                node.bbox = l_bbox;
-            }
-            let r_idx = arena.len();
-            arena.push(RNode {
-                bbox: r_bbox,
-                leaf: true,
-                children: right,
-            });
-            Some(r_idx)
+            leaves[slot] = node_idx;
+            arena[node_idx].bbox = union_aabb(arena[node_idx].bbox, bbox);
+            let item = RChild::Item {
+                slot,
+                bbox,
+                _p: core::marker::PhantomData,
+            };
+            let end = arena[node_idx].children.len();
+            Self::insert_child(
+                arena,
+                leaves,
+                node_idx,
+                end,
+                item,
+                max_children,
+                min_children,
+            )
         } else {
             // Choose child without holding &mut to the node across arena borrows
             let idx = {
                 let children = &arena[node_idx].children;
+                *visited += children.len() as u64;
                 Self::choose_child(arena, children, &bbox)
             };
             let split = match arena[node_idx].children[idx] {
                 RChild::Node(child_idx) => Self::insert_node(
                     arena,
+                    leaves,
                     child_idx.get(),
                     slot,
                     bbox,
                     max_children,
                     min_children,
+                    visited,
                 ),
                 RChild::Item { .. } => None,
             };
             // update node bbox
             arena[node_idx].bbox = union_aabb(arena[node_idx].bbox, bbox);
-            if let Some(new_right_idx) = split {
-                // Insert new right sibling and handle possible overflow
-                arena[node_idx]
-                    .children
-                    .insert(idx + 1, RChild::Node(NodeIdx::new(new_right_idx)));
-                if arena[node_idx].children.len() > max_children {
-                    let (left, right, l_bbox, r_bbox) = {
-                        let mut ch = core::mem::take(&mut arena[node_idx].children);
-                        let (left, right) =
-                            Self::split_children_with(&mut ch, max_children, min_children, |c| {
-                                match c {
-                                    RChild::Item { bbox, .. } => *bbox,
-                                    RChild::Node(i) => arena[i.get()].bbox,
-                                }
-                            });
-                        let l_bbox = Self::node_bbox(arena, &left);
-                        let r_bbox = Self::node_bbox(arena, &right);
-                        (left, right, l_bbox, r_bbox)
-                    };
-                    arena[node_idx].leaf = false;
-                    arena[node_idx].children = left;
-                    arena[node_idx].bbox = l_bbox;
-                    let r_idx = arena.len();
-                    arena.push(RNode {
-                        bbox: r_bbox,
-                        leaf: false,
-                        children: right,
-                    });
-                    return Some(r_idx);
-                }
-            }
-            None
+            // Insert the new right sibling and handle possible overflow
+            let new_right_idx = split?;
+            arena[new_right_idx].parent = Some(NodeIdx::new(node_idx));
+            Self::insert_child(
+                arena,
+                leaves,
+                node_idx,
+                idx + 1,
+                RChild::Node(NodeIdx::new(new_right_idx)),
+                max_children,
+                min_children,
+            )
         }
     }
 
-    fn search_remove(
-        arena: &mut Vec<RNode<T, P>>,
+    /// Insert `child` at `pos` among the children of `node_idx`, splitting the node if it
+    /// would hold more than `max_children`; returns the arena index of the new right sibling.
+    ///
+    /// The caller links `child` to `node_idx` beforehand; children moved to the sibling are
+    /// relinked here.
+    fn insert_child(
+        arena: &mut Vec<RNode<T, P, N>>,
+        leaves: &mut [usize],
         node_idx: usize,
-        slot: usize,
-        old: &Aabb2D<T>,
-    ) -> bool {
+        pos: usize,
+        child: RChild<T, P>,
+        max_children: usize,
+        min_children: usize,
+    ) -> Option<usize> {
+        if arena[node_idx].children.len() < max_children {
+            arena[node_idx].children.insert(pos, child);
+            return None;
+        }
+        // A node never stores more than `max_children`, so split a copy holding the new child.
+        let (left, right, l_bbox, r_bbox) = {
+            let mut ch = arena[node_idx].children.to_vec();
+            ch.insert(pos, child);
+            let (left, right) = Self::split_children_with(&mut ch, min_children, |c| match c {
+                RChild::Item { bbox, .. } => *bbox,
+                RChild::Node(i) => arena[i.get()].bbox,
+            });
+            let l_bbox = Self::node_bbox(arena, &left);
+            let r_bbox = Self::node_bbox(arena, &right);
+            (left, right, l_bbox, r_bbox)
+        };
+        let leaf = arena[node_idx].leaf;
+        arena[node_idx].children = ChildList::from_slice(&left);
+        arena[node_idx].bbox = l_bbox;
+        let r_idx = arena.len();
+        for c in &right {
+            match c {
+                RChild::Node(ci) => arena[ci.get()].parent = Some(NodeIdx::new(r_idx)),
+                RChild::Item { slot, .. } => leaves[*slot] = r_idx,
+            }
+        }
+        arena.push(RNode {
+            bbox: r_bbox,
+            leaf,
+            children: ChildList::from_slice(&right),
+            parent: None,
+            dead: false,
+        });
+        Some(r_idx)
+    }
+
+    /// Replace the root with a new internal node over the old root and `right_idx`.
+    fn grow_root(&mut self, root_idx: NodeIdx, right_idx: usize) {
+        let left_bb = self.arena[root_idx.get()].bbox;
+        let right_bb = self.arena[right_idx].bbox;
+        let new_bb = union_aabb(left_bb, right_bb);
+        let children = ChildList::from_slice(&[
+            RChild::Node(root_idx),
+            RChild::Node(NodeIdx::new(right_idx)),
+        ]);
+        let idx = self.arena.len();
+        self.arena.push(RNode {
+            bbox: new_bb,
+            leaf: false,
+            children,
+            parent: None,
+            dead: false,
+        });
+        self.arena[root_idx.get()].parent = Some(NodeIdx::new(idx));
+        self.arena[right_idx].parent = Some(NodeIdx::new(idx));
+        self.root = Some(NodeIdx::new(idx));
+    }
+
+    /// Insert `slot` by descending from the root; returns the number of node bounds tested.
+    fn insert_from_root(&mut self, slot: usize, aabb: Aabb2D<T>) -> u64 {
+        self.ensure_slot(slot, aabb);
+        let mut visited = 0;
+        match self.root {
+            None => {
+                let leaf = RNode::<T, P, N> {
+                    bbox: aabb,
+                    leaf: true,
+                    children: ChildList::from_slice(&[RChild::Item {
+                        slot,
+                        bbox: aabb,
+                        _p: core::marker::PhantomData,
+                    }]),
+                    parent: None,
+                    dead: false,
+                };
+                let idx = self.arena.len();
+                self.arena.push(leaf);
+                self.leaves[slot] = idx;
+                self.root = Some(NodeIdx::new(idx));
+            }
+            Some(root_idx) => {
+                let (max_children, min_children) = self.fanout();
+                let split = Self::insert_node(
+                    &mut self.arena,
+                    &mut self.leaves,
+                    root_idx.get(),
+                    slot,
+                    aabb,
+                    max_children,
+                    min_children,
+                    &mut visited,
+                );
+                if let Some(right_idx) = split {
+                    // Create a new root combining old root and new right child
+                    self.grow_root(root_idx, right_idx);
+                }
+            }
+        }
+        visited
+    }
+
+    /// Insert `slot` below the deepest node on `hint`'s path that already contains `aabb`.
+    ///
+    /// Returns the depth the descent started at (0 is the root, `None` if it fell back to a
+    /// plain insert from the root) and the number of node bounds tested, as recorded in the stats.
+    fn insert_near_depth(
+        &mut self,
+        slot: usize,
+        aabb: Aabb2D<T>,
+        hint: usize,
+    ) -> (Option<usize>, u64) {
+        let path = self.leaf_path(hint).unwrap_or_default();
+        let start = path
+            .iter()
+            .rposition(|&i| self.arena[i].bbox.contains_aabb(&aabb));
+        // Walking up from the hint tests bounds until one contains `aabb`.
+        let mut visited = (path.len() - start.unwrap_or(0)) as u64;
+        let Some(depth) = start else {
+            visited += self.insert_from_root(slot, aabb);
+            self.stats.record_insert(visited);
+            return (None, visited);
+        };
+        self.ensure_slot(slot, aabb);
+        let (max_children, min_children) = self.fanout();
+        let mut split = Self::insert_node(
+            &mut self.arena,
+            &mut self.leaves,
+            path[depth],
+            slot,
+            aabb,
+            max_children,
+            min_children,
+            &mut visited,
+        );
+        self.stats.record_insert(visited);
+        // Ancestors already contain `aabb`; only splits need to be propagated upward.
+        for k in (0..depth).rev() {
+            let Some(right) = split else {
+                break;
+            };
+            let (parent, child) = (path[k], path[k + 1]);
+            let pos = self.arena[parent]
+                .children
+                .iter()
+                .position(|c| matches!(c, RChild::Node(i) if i.get() == child))
+                .expect("path nodes are linked to their parents");
+            self.arena[right].parent = Some(NodeIdx::new(parent));
+            split = Self::insert_child(
+                &mut self.arena,
+                &mut self.leaves,
+                parent,
+                pos + 1,
+                RChild::Node(NodeIdx::new(right)),
+                max_children,
+                min_children,
+            );
+        }
+        if let Some(right) = split {
+            self.grow_root(NodeIdx::new(path[0]), right);
+        }
+        (Some(depth), visited)
+    }
+
+    /// Remove `slot` below `node_idx`, dropping nodes left empty and recording them in `holes`.
+    fn search_remove(
+        arena: &mut Vec<RNode<T, P, N>>,
+        holes: &mut Vec<usize>,
+        node_idx: usize,
+        slot: usize,
+        old: &Aabb2D<T>,
+    ) -> bool {
         let node_bbox = arena[node_idx].bbox;
         if node_bbox.intersect(old).is_empty() {
             return false;
@@ -477,22 +1254,21 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
                 })
                 .collect();
             for ci in child_indices {
-                if Self::search_remove(arena, ci.get(), slot, old) {
+                if Self::search_remove(arena, holes, ci.get(), slot, old) {
                     removed = true;
                 }
             }
             if removed {
-                let new_children = {
-                    let old_children = core::mem::take(&mut arena[node_idx].children);
-                    old_children
-                        .into_iter()
-                        .filter(|c| match c {
-                            RChild::Node(i) => !arena[i.get()].children.is_empty(),
-                            _ => true,
-                        })
-                        .collect::<Vec<_>>()
-                };
-                arena[node_idx].children = new_children;
+                let mut children = core::mem::take(&mut arena[node_idx].children);
+                children.retain(|c| match c {
+                    RChild::Node(i) if arena[i.get()].children.is_empty() => {
+                        arena[i.get()].dead = true;
+                        holes.push(i.get());
+                        false
+                    }
+                    _ => true,
+                });
+                arena[node_idx].children = children;
                 if !arena[node_idx].children.is_empty() {
                     let bb = Self::node_bbox(arena, &arena[node_idx].children);
                     arena[node_idx].bbox = bb;
@@ -505,7 +1281,7 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
     /// Attempt to update an item's AABB in-place without remove+insert.
     /// Returns true if the item was found and updated; recomputes ancestor bboxes on the path.
     fn update_in_place(
-        arena: &mut Vec<RNode<T, P>>,
+        arena: &mut Vec<RNode<T, P, N>>,
         node_idx: usize,
         slot: usize,
         old: Aabb2D<T>,
@@ -517,7 +1293,7 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
         }
         if arena[node_idx].leaf {
             let mut found = false;
-            for c in &mut arena[node_idx].children {
+            for c in arena[node_idx].children.iter_mut() {
                 if let RChild::Item { slot: s, bbox, .. } = c
                     && *s == slot
                 {
@@ -559,53 +1335,14 @@ impl<T: Scalar, P: Copy + Debug> RTree<T, P> {
     }
 }
 
-impl<T: Scalar, P: Copy + Debug> Backend<T> for RTree<T, P> {
+impl<T: Scalar, P: Copy + Debug, N: Fanout> Backend<T> for RTree<T, P, N> {
     fn insert(&mut self, slot: usize, aabb: Aabb2D<T>) {
-        self.ensure_slot(slot, aabb);
-        match self.root {
-            None => {
-                let mut leaf = RNode::<T, P> {
-                    bbox: aabb,
-                    leaf: true,
-                    children: Vec::new(),
-                };
-                leaf.children.push(RChild::Item {
-                    slot,
-                    bbox: aabb,
-                    _p: core::marker::PhantomData,
-                });
-                let idx = self.arena.len();
-                self.arena.push(leaf);
-                self.root = Some(NodeIdx::new(idx));
-            }
-            Some(root_idx) => {
-                let split = Self::insert_node(
-                    &mut self.arena,
-                    root_idx.get(),
-                    slot,
-                    aabb,
-                    self.max_children,
-                    self.min_children,
-                );
-                if let Some(right_idx) = split {
-                    // Create a new root combining old root and new right child
-                    let left_bb = self.arena[root_idx.get()].bbox;
-                    let right_bb = self.arena[right_idx].bbox;
-                    let new_bb = union_aabb(left_bb, right_bb);
-                    let children = vec![
-                        RChild::Node(root_idx),
-                        RChild::Node(NodeIdx::new(right_idx)),
-                    ];
-                    let idx = self.arena.len();
-                    self.arena.push(RNode {
-                        bbox: new_bb,
-                        leaf: false,
-                        children,
-                    });
-                    self.root = Some(NodeIdx::new(idx));
-                }
-            }
-        }
+        let visited = self.insert_from_root(slot, aabb);
+        self.stats.record_insert(visited);
+    }
+
+    fn insert_near(&mut self, slot: usize, aabb: Aabb2D<T>, hint: usize) {
+        let _ = self.insert_near_depth(slot, aabb, hint);
     }
 
     fn update(&mut self, slot: usize, aabb: Aabb2D<T>) {
@@ -618,7 +1355,7 @@ impl<T: Scalar, P: Copy + Debug> Backend<T> for RTree<T, P> {
                 }
                 return;
             }
-            let _ = Self::search_remove(&mut self.arena, root_idx.get(), slot, &old);
+            self.remove_from_tree(root_idx, slot, &old);
         }
         self.insert(slot, aabb);
     }
@@ -626,7 +1363,7 @@ impl<T: Scalar, P: Copy + Debug> Backend<T> for RTree<T, P> {
     fn remove(&mut self, slot: usize) {
         if let Some(old) = self.slots.get(slot).and_then(|x| *x) {
             if let Some(root_idx) = self.root {
-                let _ = Self::search_remove(&mut self.arena, root_idx.get(), slot, &old);
+                self.remove_from_tree(root_idx, slot, &old);
             }
             if let Some(s) = self.slots.get_mut(slot) {
                 *s = None;
@@ -635,34 +1372,125 @@ impl<T: Scalar, P: Copy + Debug> Backend<T> for RTree<T, P> {
     }
 
     fn clear(&mut self) {
+        self.root = None;
+        self.arena = Vec::new();
+        self.slots = Vec::new();
+        self.leaves = Vec::new();
+        self.holes = Vec::new();
+        self.dead = 0;
+    }
+
+    fn clear_retain(&mut self) {
         self.root = None;
         self.arena.clear();
         self.slots.clear();
+        self.leaves.clear();
+        self.holes.clear();
+        self.dead = 0;
+    }
+
+    fn compact(&mut self) {
+        self.compact_arena();
+    }
+
+    fn arena_usage(&self) -> (usize, usize) {
+        (self.arena.len() - self.dead, self.dead)
+    }
+
+    fn compact_step(&mut self, max_nodes: usize) -> bool {
+        self.compact_arena_step(max_nodes)
+    }
+
+    fn bulk_load(&mut self, pairs: &[(usize, Aabb2D<T>)]) {
+        *self = Self::bulk_build_fanout(pairs, self.fanout);
     }
 
     fn visit_point<F: FnMut(usize)>(&self, x: T, y: T, mut f: F) {
+        let _ = self.query_point_visit(x, y, |i| {
+            f(i);
+            true
+        });
+    }
+
+    fn query_knn(&self, x: T, y: T, k: usize) -> Option<Vec<(usize, T::Acc)>> {
         let Some(root_idx) = self.root else {
-            return;
+            return Some(Vec::new());
         };
-        let p = Aabb2D::new(x, y, x, y);
-        let mut stack = vec![root_idx];
+        let root_dist = point_dist2(&self.arena[root_idx.get()].bbox, x, y);
+        Some(best_first_knn(root_idx.get(), root_dist, k, |i, push| {
+            for c in self.arena[i].children.iter() {
+                match c {
+                    RChild::Node(ci) => {
+                        push(
+                            false,
+                            ci.get(),
+                            point_dist2(&self.arena[ci.get()].bbox, x, y),
+                        );
+                    }
+                    RChild::Item { slot, bbox, .. } => push(true, *slot, point_dist2(bbox, x, y)),
+                }
+            }
+        }))
+    }
+
+    fn nearest(&self, x: T, y: T) -> Option<(usize, T::Acc)> {
+        let root_idx = self.root?;
+        let mut best: Option<(usize, T::Acc)> = None;
+        let mut stack = SmallStack::<_, 32>::new(root_idx);
         while let Some(i) = stack.pop() {
             let n = &self.arena[i.get()];
-            if n.bbox.intersect(&p).is_empty() {
+            // Ties are kept so a lower slot at the same distance can still win.
+            if best.is_some_and(|(_, d)| point_dist2(&n.bbox, x, y) > d) {
+                continue;
+            }
+            for c in n.children.iter() {
+                match c {
+                    RChild::Node(ci) => stack.push(*ci),
+                    RChild::Item { slot, bbox, .. } => {
+                        let d = point_dist2(bbox, x, y);
+                        if improves_nearest(&best, *slot, d) {
+                            best = Some((*slot, d));
+                        }
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    fn visit_points<F: FnMut(usize, usize)>(&self, points: &[(T, T)], mut f: F) {
+        let Some(root_idx) = self.root else {
+            return;
+        };
+        let pts: Vec<Aabb2D<T>> = points
+            .iter()
+            .map(|&(x, y)| Aabb2D::new(x, y, x, y))
+            .collect();
+        // Each stack entry carries the points still inside the node's ancestors.
+        let mut stack = vec![(root_idx, (0..pts.len()).collect::<Vec<usize>>())];
+        while let Some((i, active)) = stack.pop() {
+            let n = &self.arena[i.get()];
+            let active: Vec<usize> = active
+                .into_iter()
+                .filter(|&pi| !n.bbox.intersect(&pts[pi]).is_empty())
+                .collect();
+            if active.is_empty() {
                 continue;
             }
             if n.leaf {
-                for c in &n.children {
-                    if let RChild::Item { slot, bbox, .. } = c
-                        && !bbox.intersect(&p).is_empty()
-                    {
-                        f(*slot);
+                for c in n.children.iter() {
+                    if let RChild::Item { slot, bbox, .. } = c {
+                        for &pi in &active {
+                            if !bbox.intersect(&pts[pi]).is_empty() {
+                                f(pi, *slot);
+                            }
+                        }
                     }
                 }
             } else {
-                for c in &n.children {
+                for c in n.children.iter() {
                     if let RChild::Node(ci) = c {
-                        stack.push(*ci);
+                        stack.push((*ci, active.clone()));
                     }
                 }
             }
@@ -670,17 +1498,113 @@ impl<T: Scalar, P: Copy + Debug> Backend<T> for RTree<T, P> {
     }
 
     fn visit_rect<F: FnMut(usize)>(&self, rect: Aabb2D<T>, mut f: F) {
+        let _ = self.query_rect_visit(rect, |i| {
+            f(i);
+            true
+        });
+    }
+
+    fn query_point_visit<F: FnMut(usize) -> bool>(&self, x: T, y: T, f: F) -> bool {
+        self.query_rect_visit(Aabb2D::new(x, y, x, y), f)
+    }
+
+    fn query_rect_visit<F: FnMut(usize) -> bool>(&self, rect: Aabb2D<T>, mut f: F) -> bool {
         let Some(root_idx) = self.root else {
-            return;
+            return true;
         };
-        let mut stack = vec![root_idx];
+        let (mut nodes, mut items) = (0, 0);
+        let mut finished = true;
+        // Inline stack: typical traversals do not allocate.
+        let mut stack = SmallStack::<_, 32>::new(root_idx);
+        'walk: while let Some(i) = stack.pop() {
+            let n = &self.arena[i.get()];
+            nodes += 1;
+            if n.bbox.intersect(&rect).is_empty() {
+                continue;
+            }
+            if n.leaf {
+                for c in n.children.iter() {
+                    if let RChild::Item { slot, bbox, .. } = c {
+                        items += 1;
+                        if !bbox.intersect(&rect).is_empty() && !f(*slot) {
+                            finished = false;
+                            break 'walk;
+                        }
+                    }
+                }
+            } else {
+                for c in n.children.iter() {
+                    if let RChild::Node(ci) = c {
+                        stack.push(*ci);
+                    }
+                }
+            }
+        }
+        self.stats.record(nodes, items);
+        finished
+    }
+
+    #[cfg(feature = "stats")]
+    fn take_query_stats(&self) -> QueryStats {
+        self.stats.take()
+    }
+
+    fn stats(&self) -> BackendStats {
+        let mut out = BackendStats {
+            item_count: self.slots.iter().flatten().count(),
+            ..BackendStats::default()
+        };
+        // Only reachable nodes count; the arena may hold dead ones until compacted.
+        let mut stack: Vec<(NodeIdx, usize)> = self.root.map(|r| (r, 0)).into_iter().collect();
+        while let Some((i, depth)) = stack.pop() {
+            let n = &self.arena[i.get()];
+            out.node_count += 1;
+            out.max_depth = out.max_depth.max(depth);
+            if n.leaf {
+                out.leaf_count += 1;
+                continue;
+            }
+            for c in n.children.iter() {
+                if let RChild::Node(ci) = c {
+                    stack.push((*ci, depth + 1));
+                }
+            }
+        }
+        out.dead_nodes = self.arena.len() - out.node_count;
+        out
+    }
+
+    fn memory_bytes(&self) -> usize {
+        let children: usize = self.arena.iter().map(|n| n.children.heap_bytes()).sum();
+        vec_bytes(&self.arena)
+            + children
+            + vec_bytes(&self.slots)
+            + vec_bytes(&self.leaves)
+            + vec_bytes(&self.holes)
+    }
+
+    fn visit_rect_budgeted<F: FnMut(usize)>(
+        &self,
+        rect: Aabb2D<T>,
+        max_nodes_visited: usize,
+        mut f: F,
+    ) -> bool {
+        let Some(root_idx) = self.root else {
+            return true;
+        };
+        let mut stack = SmallStack::<_, 32>::new(root_idx);
+        let mut visited = 0;
         while let Some(i) = stack.pop() {
+            if visited == max_nodes_visited {
+                return false;
+            }
+            visited += 1;
             let n = &self.arena[i.get()];
             if n.bbox.intersect(&rect).is_empty() {
                 continue;
             }
             if n.leaf {
-                for c in &n.children {
+                for c in n.children.iter() {
                     if let RChild::Item { slot, bbox, .. } = c
                         && !bbox.intersect(&rect).is_empty()
                     {
@@ -688,24 +1612,66 @@ impl<T: Scalar, P: Copy + Debug> Backend<T> for RTree<T, P> {
                     }
                 }
             } else {
-                for c in &n.children {
+                for c in n.children.iter() {
                     if let RChild::Node(ci) = c {
                         stack.push(*ci);
                     }
                 }
             }
         }
+        true
+    }
+
+    fn query_rect_grouped<'a>(
+        &'a self,
+        rect: Aabb2D<T>,
+    ) -> Box<dyn Iterator<Item = (GroupId, Vec<usize>)> + 'a> {
+        // One group per leaf node, identified by its arena index.
+        let mut groups = Vec::new();
+        let Some(root_idx) = self.root else {
+            return Box::new(groups.into_iter());
+        };
+        let mut stack = vec![root_idx];
+        while let Some(i) = stack.pop() {
+            let n = &self.arena[i.get()];
+            if n.bbox.intersect(&rect).is_empty() {
+                continue;
+            }
+            if n.leaf {
+                let hits: Vec<usize> = n
+                    .children
+                    .iter()
+                    .filter_map(|c| match c {
+                        RChild::Item { slot, bbox, .. } if !bbox.intersect(&rect).is_empty() => {
+                            Some(*slot)
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                if !hits.is_empty() {
+                    groups.push((GroupId(i.get()), hits));
+                }
+            } else {
+                for c in n.children.iter() {
+                    if let RChild::Node(ci) = c {
+                        stack.push(*ci);
+                    }
+                }
+            }
+        }
+        Box::new(groups.into_iter())
     }
 }
 
-impl<T: Scalar, P: Copy + Debug> Debug for RTree<T, P> {
+impl<T: Scalar, P: Copy + Debug, N: Fanout> Debug for RTree<T, P, N> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let total = self.slots.len();
         let alive = self.slots.iter().filter(|e| e.is_some()).count();
         let has_root = self.root.is_some();
+        let (max_children, min_children) = self.fanout();
         f.debug_struct("RTree")
-            .field("max_children", &self.max_children)
-            .field("min_children", &self.min_children)
+            .field("max_children", &max_children)
+            .field("min_children", &min_children)
             .field("arena_nodes", &self.arena.len())
             .field("total_slots", &total)
             .field("alive", &alive)
@@ -715,6 +1681,9 @@ impl<T: Scalar, P: Copy + Debug> Debug for RTree<T, P> {
 }
 
 /// Convenience type aliases.
+/// R-tree with i32 coordinates and i64 metrics.
+pub type RTreeI32<P> = RTree<i32, P>;
+
 /// R-tree with i64 coordinates and i128 metrics.
 pub type RTreeI64<P> = RTree<i64, P>;
 
@@ -724,11 +1693,171 @@ pub type RTreeF32<P> = RTree<f32, P>;
 /// R-tree with f64 coordinates and f64 metrics.
 pub type RTreeF64<P> = RTree<f64, P>;
 
+/// R-tree with a fixed fan-out of 8 children per node.
+pub type RTree8<T, P> = RTree<T, P, FixedFanout<8>>;
+
+/// R-tree with a fixed fan-out of 16 children per node.
+pub type RTree16<T, P> = RTree<T, P, FixedFanout<16>>;
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::index::Index;
 
+    #[test]
+    fn compact_arena_drops_dead_nodes() {
+        fn reachable(t: &RTree<f64, ()>, i: usize) -> usize {
+            1 + t.arena[i]
+                .children
+                .iter()
+                .map(|c| match c {
+                    RChild::Node(n) => reachable(t, n.get()),
+                    RChild::Item { .. } => 0,
+                })
+                .sum::<usize>()
+        }
+        let mut t: RTree<f64, ()> = RTree::default();
+        let boxes: Vec<_> = (0..600_u32)
+            .map(|i| {
+                let (x, y) = (f64::from(i % 30) * 10.0, f64::from(i / 30) * 10.0);
+                Aabb2D::new(x, y, x + 12.0, y + 12.0)
+            })
+            .collect();
+        for (s, b) in boxes.iter().enumerate() {
+            t.insert(s, *b);
+        }
+        for s in (0..600).filter(|s| s % 10 != 0) {
+            t.remove(s);
+        }
+        let live = reachable(&t, t.root.unwrap().get());
+        let before = t.arena.len();
+        assert!(live < before);
+
+        let q = Aabb2D::new(35.0, 35.0, 155.0, 95.0);
+        let mut expected = Vec::new();
+        t.visit_rect(q, |s| expected.push(s));
+        t.compact_arena();
+        assert_eq!(t.arena.len(), live);
+        assert_eq!(reachable(&t, t.root.unwrap().get()), live);
+        let mut got = Vec::new();
+        t.visit_rect(q, |s| got.push(s));
+        assert_eq!(got, expected);
+        expected.sort_unstable();
+        let brute: Vec<usize> = (0..600)
+            .filter(|s| s % 10 == 0 && !boxes[*s].intersect(&q).is_empty())
+            .collect();
+        assert_eq!(expected, brute);
+
+        // Still a working tree afterwards.
+        t.insert(600, Aabb2D::new(1000.0, 1000.0, 1001.0, 1001.0));
+        let mut hits = Vec::new();
+        t.visit_point(1000.5, 1000.5, |s| hits.push(s));
+        assert_eq!(hits, vec![600]);
+    }
+
+    #[test]
+    fn insert_near_skips_upper_levels_and_matches_plain_inserts() {
+        // Background tiles, then a scanline of small boxes, each hinted with the previous one.
+        let mut near: RTree<f64, ()> = RTree::default();
+        let mut plain: RTree<f64, ()> = RTree::default();
+        let mut slot = 0;
+        for ty in 0..8_u32 {
+            for tx in 0..8_u32 {
+                let (x, y) = (f64::from(tx) * 64.0, f64::from(ty) * 64.0);
+                let tile = Aabb2D::new(x, y, x + 64.0, y + 64.0);
+                near.insert(slot, tile);
+                plain.insert(slot, tile);
+                slot += 1;
+            }
+        }
+        #[cfg(feature = "stats")]
+        let _ = (near.take_query_stats(), plain.take_query_stats());
+        let (mut started_below_root, mut inserts) = (0, 0);
+        let (mut near_visited, mut plain_visited) = (0, 0);
+        let mut hint = 0;
+        for row in 0..16_u32 {
+            for col in 0..64_u32 {
+                let (x, y) = (f64::from(col) * 8.0, f64::from(row) * 32.0);
+                let b = Aabb2D::new(x + 1.0, y + 1.0, x + 6.0, y + 6.0);
+                // Locating the hint visits only the nodes on its root→leaf path.
+                let path_len = near.leaf_path(hint).unwrap().len();
+                assert!(
+                    path_len <= near.stats().max_depth + 1,
+                    "walked {path_len} nodes"
+                );
+                let (depth, visited) = near.insert_near_depth(slot, b, hint);
+                if depth.is_some_and(|d| d > 0) {
+                    started_below_root += 1;
+                }
+                near_visited += visited;
+                plain_visited += plain.insert_from_root(slot, b);
+                hint = slot;
+                slot += 1;
+                inserts += 1;
+            }
+        }
+        assert!(
+            started_below_root * 2 > inserts,
+            "{started_below_root} of {inserts} inserts skipped the root"
+        );
+        // Counting the walk up from the hint, hinted inserts still test fewer nodes.
+        assert!(
+            near_visited < plain_visited,
+            "hinted inserts tested {near_visited} nodes, plain ones {plain_visited}"
+        );
+        #[cfg(feature = "stats")]
+        {
+            let (n, p) = (near.take_query_stats(), plain.take_query_stats());
+            assert_eq!((n.inserts, n.insert_nodes_visited), (inserts, near_visited));
+            // `insert_from_root` leaves recording to the `Backend::insert` wrapper.
+            assert_eq!(p.inserts, 0);
+        }
+        check_links(&near);
+
+        for q in [
+            Aabb2D::new(0.0, 0.0, 512.0, 512.0),
+            Aabb2D::new(100.0, 30.0, 140.0, 70.0),
+            Aabb2D::new(7.0, 7.0, 7.5, 7.5),
+        ] {
+            let mut a: Vec<_> = near.query_rect(q).collect();
+            let mut b: Vec<_> = plain.query_rect(q).collect();
+            a.sort_unstable();
+            b.sort_unstable();
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn grouped_query_groups_by_leaf() {
+        // Two far-apart clusters of eight boxes each; bulk packing puts each in one leaf.
+        let mut entries: Vec<(Aabb2D<f64>, u32)> = Vec::new();
+        for c in 0..2_u32 {
+            for i in 0..8_u32 {
+                let x = f64::from(c) * 1000.0 + f64::from(i) * 2.0;
+                entries.push((Aabb2D::new(x, 0.0, x + 1.0, 1.0), c * 8 + i));
+            }
+        }
+        let idx = Index::<f64, u32>::with_rtree_bulk(&entries);
+
+        let groups: Vec<_> = idx
+            .query_rect_grouped(Aabb2D::new(0.0, 0.0, 2000.0, 1.0))
+            .collect();
+        assert_eq!(groups.len(), 2);
+        assert_ne!(groups[0].0, groups[1].0);
+        for (_, hits) in &groups {
+            assert_eq!(hits.len(), 8);
+            let cluster = hits[0].1 / 8;
+            assert!(hits.iter().all(|(_, p)| p / 8 == cluster));
+        }
+
+        // A query inside one cluster yields a single group.
+        let groups: Vec<_> = idx
+            .query_rect_grouped(Aabb2D::new(0.0, 0.0, 5.0, 1.0))
+            .collect();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].1.len(), 3);
+    }
+
     #[test]
     fn rtree_i64_basic_insert_query() {
         let mut idx = Index::<i64, u32>::with_rtree();
@@ -789,4 +1918,417 @@ mod tests {
         let v_neighbor: Vec<_> = b.query_point(15, 5).collect();
         assert_eq!(v_neighbor, vec![1]);
     }
+
+    #[test]
+    fn bulk_build_for_queries_visits_fewer_nodes() {
+        // Skewed scene: a dense grid of small boxes plus a sparse scatter further out.
+        let mut pairs: Vec<(usize, Aabb2D<f64>)> = Vec::new();
+        for j in 0..32_u32 {
+            for i in 0..32_u32 {
+                let (x, y) = (f64::from(i) * 10.0, f64::from(j) * 10.0);
+                pairs.push((pairs.len(), Aabb2D::new(x, y, x + 5.0, y + 5.0)));
+            }
+        }
+        for i in 0..10_u32 {
+            let x = f64::from(i) * 400.0;
+            pairs.push((pairs.len(), Aabb2D::new(x, 1000.0, x + 5.0, 1005.0)));
+        }
+        // Full-width strips over the grid, four rows tall; STR's square-ish leaves cut
+        // across them.
+        let tiles: Vec<Aabb2D<f64>> = (0..8_u32)
+            .map(|t| {
+                let y = f64::from(t) * 40.0;
+                Aabb2D::new(0.0, y, 320.0, y + 39.0)
+            })
+            .collect();
+
+        let uniform: RTree<f64, u32> = RTree::bulk_build_default(&pairs);
+        let tuned: RTree<f64, u32> = RTree::bulk_build_for_queries(&pairs, &tiles);
+
+        let mut cost_uniform = 0;
+        let mut cost_tuned = 0;
+        for t in &tiles {
+            // Same results either way.
+            let mut a: Vec<_> = uniform.query_rect(*t).collect();
+            let mut b: Vec<_> = tuned.query_rect(*t).collect();
+            a.sort_unstable();
+            b.sort_unstable();
+            assert_eq!(a, b);
+            cost_uniform += uniform.nodes_visited_by(t);
+            cost_tuned += tuned.nodes_visited_by(t);
+        }
+        assert!(
+            cost_tuned < cost_uniform,
+            "tuned packing should visit fewer nodes ({cost_tuned} vs {cost_uniform})"
+        );
+        // The backends' own counters agree.
+        #[cfg(feature = "stats")]
+        {
+            assert_eq!(uniform.take_query_stats().nodes_visited, cost_uniform);
+            assert_eq!(tuned.take_query_stats().nodes_visited, cost_tuned);
+        }
+
+        // Every item stays reachable.
+        for &(slot, bbox) in &pairs {
+            assert!(tuned.query_rect(bbox).any(|s| s == slot));
+        }
+    }
+
+    #[test]
+    fn bulk_build_for_queries_is_never_worse() {
+        // A dense grid of small boxes.
+        let pairs: Vec<(usize, Aabb2D<f64>)> = (0..1024_u32)
+            .map(|i| {
+                let (x, y) = (f64::from(i % 32) * 10.0, f64::from(i / 32) * 10.0);
+                (i as usize, Aabb2D::new(x, y, x + 5.0, y + 5.0))
+            })
+            .collect();
+        let cell = |size: f64, n: u32| -> Vec<Aabb2D<f64>> {
+            (0..n * n)
+                .map(|t| {
+                    let (x, y) = (f64::from(t % n) * size, f64::from(t / n) * size);
+                    Aabb2D::new(x, y, x + size - 1.0, y + size - 1.0)
+                })
+                .collect()
+        };
+        let workloads = [
+            // Square tiles.
+            cell(40.0, 8),
+            cell(20.0, 16),
+            // Strips, which cut across STR's square-ish leaves.
+            (0..8_u32)
+                .map(|t| {
+                    let y = f64::from(t) * 40.0;
+                    Aabb2D::new(0.0, y, 320.0, y + 39.0)
+                })
+                .collect(),
+            // Point probes on every third box: one-box buckets, where bucketing loses.
+            (0..1024_u32)
+                .step_by(3)
+                .map(|i| {
+                    let (x, y) = (
+                        f64::from(i % 32) * 10.0 + 1.0,
+                        f64::from(i / 32) * 10.0 + 1.0,
+                    );
+                    Aabb2D::new(x, y, x, y)
+                })
+                .collect(),
+            // A hot spot repeated, plus queries that miss everything.
+            vec![
+                Aabb2D::new(100.0, 100.0, 140.0, 140.0),
+                Aabb2D::new(100.0, 100.0, 140.0, 140.0),
+                Aabb2D::new(1000.0, 1000.0, 1100.0, 1100.0),
+            ],
+        ];
+        let uniform: RTree<f64, u32> = RTree::bulk_build_default(&pairs);
+        let (mut won, mut lost) = (false, false);
+        for queries in &workloads {
+            let bucketed = RTree::<f64, u32>::bulk_build_bucketed(&pairs, queries);
+            let tuned: RTree<f64, u32> = RTree::bulk_build_for_queries(&pairs, queries);
+            let cost = |t: &RTree<f64, u32>| -> u64 {
+                queries.iter().map(|q| t.nodes_visited_by(q)).sum()
+            };
+            let (c_uniform, c_bucketed, c_tuned) = (cost(&uniform), cost(&bucketed), cost(&tuned));
+            assert!(c_tuned <= c_uniform, "{c_tuned} vs {c_uniform}");
+            assert_eq!(c_tuned, c_uniform.min(c_bucketed));
+            won |= c_tuned < c_uniform;
+            lost |= c_bucketed > c_uniform;
+            for q in queries {
+                let mut a: Vec<_> = uniform.query_rect(*q).collect();
+                let mut b: Vec<_> = tuned.query_rect(*q).collect();
+                a.sort_unstable();
+                b.sort_unstable();
+                assert_eq!(a, b);
+            }
+        }
+        // Both outcomes are exercised.
+        assert!(won && lost);
+    }
+
+    #[test]
+    fn bulk_build_with_custom_order_is_valid() {
+        // Slots of each leaf, and a check that every node bounds its children.
+        fn leaves(t: &RTree<f64, u32>) -> Vec<Vec<usize>> {
+            let mut out = Vec::new();
+            let mut stack = vec![t.root.unwrap()];
+            while let Some(i) = stack.pop() {
+                let n = &t.arena[i.get()];
+                let mut slots = Vec::new();
+                for c in n.children.iter() {
+                    let b = match c {
+                        RChild::Node(ci) => {
+                            stack.push(*ci);
+                            t.arena[ci.get()].bbox
+                        }
+                        RChild::Item { slot, bbox, .. } => {
+                            slots.push(*slot);
+                            *bbox
+                        }
+                    };
+                    assert!(n.bbox.contains_aabb(&b));
+                }
+                if n.leaf {
+                    slots.sort_unstable();
+                    out.push(slots);
+                }
+            }
+            out.sort();
+            out
+        }
+
+        let mut pairs: Vec<(usize, Aabb2D<f64>)> = Vec::new();
+        for j in 0..12_u32 {
+            for i in 0..12_u32 {
+                let (x, y) = (f64::from(i) * 10.0, f64::from(j) * 10.0);
+                pairs.push((pairs.len(), Aabb2D::new(x, y, x + 4.0, y + 4.0)));
+            }
+        }
+        let diagonal: RTree<f64, u32> = RTree::bulk_build_with(&pairs, |a, b| {
+            (a.0 + a.1)
+                .partial_cmp(&(b.0 + b.1))
+                .unwrap_or(core::cmp::Ordering::Equal)
+        });
+        let default: RTree<f64, u32> = RTree::bulk_build_default(&pairs);
+
+        let diag_leaves = leaves(&diagonal);
+        assert_ne!(diag_leaves, leaves(&default));
+        let mut all: Vec<usize> = diag_leaves.into_iter().flatten().collect();
+        all.sort_unstable();
+        assert_eq!(all, (0..pairs.len()).collect::<Vec<_>>());
+
+        for q in [
+            Aabb2D::new(0.0, 0.0, 30.0, 30.0),
+            Aabb2D::new(55.0, 5.0, 95.0, 115.0),
+            Aabb2D::new(200.0, 200.0, 300.0, 300.0),
+        ] {
+            let mut a: Vec<_> = diagonal.query_rect(q).collect();
+            let mut b: Vec<_> = default.query_rect(q).collect();
+            a.sort_unstable();
+            b.sort_unstable();
+            assert_eq!(a, b);
+        }
+    }
+
+    /// Random inserts, moves, and removals, checked against a linear scan.
+    fn churn_matches_flatvec<N: Fanout>(mut tree: RTree<i64, (), N>) {
+        use crate::backends::flatvec::FlatVec;
+
+        let mut flat: FlatVec<i64> = FlatVec::default();
+        let mut seed = 0x2545_F491_u64;
+        let mut next = |m: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            i64::try_from(seed % m).unwrap()
+        };
+        let n = 300_usize;
+        for round in 0..3 {
+            for slot in 0..n {
+                let (x, y) = (next(1000), next(1000));
+                let aabb = Aabb2D::new(x, y, x + 1 + next(40), y + 1 + next(40));
+                if round == 0 {
+                    tree.insert(slot, aabb);
+                    flat.insert(slot, aabb);
+                } else if slot % 7 == round {
+                    tree.remove(slot);
+                    flat.remove(slot);
+                } else {
+                    tree.update(slot, aabb);
+                    flat.update(slot, aabb);
+                }
+            }
+        }
+        for node in &tree.arena {
+            assert!(node.children.len() <= tree.fanout().0);
+        }
+        for _ in 0..50 {
+            let (x, y) = (next(1000), next(1000));
+            let q = Aabb2D::new(x, y, x + next(200), y + next(200));
+            let mut a: Vec<_> = tree.query_rect(q).collect();
+            let mut b: Vec<_> = flat.query_rect(q).collect();
+            a.sort_unstable();
+            b.sort_unstable();
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn const_fanout_trees_are_correct() {
+        assert_eq!(RTree::<i64, (), FixedFanout<4>>::default().fanout(), (4, 2));
+        assert_eq!(RTree8::<i64, ()>::default().fanout(), (8, 4));
+        assert_eq!(RTree16::<i64, ()>::default().fanout(), (16, 8));
+        churn_matches_flatvec(RTree::<i64, (), FixedFanout<4>>::default());
+        churn_matches_flatvec(RTree16::default());
+        // Fixed fan-outs keep children inline; the runtime variant allocates per node.
+        let pairs: Vec<(usize, Aabb2D<i64>)> = (0..200_i64)
+            .map(|i| (usize::try_from(i).unwrap(), Aabb2D::new(i, i, i + 5, i + 5)))
+            .collect();
+        let fixed = RTree16::<i64, ()>::bulk_build_default(&pairs);
+        assert!(fixed.arena.iter().all(|n| n.children.heap_bytes() == 0));
+        let runtime = RTree::<i64, ()>::bulk_build_default(&pairs);
+        assert!(runtime.arena.iter().all(|n| n.children.heap_bytes() > 0));
+        // The runtime-configured variant.
+        assert_eq!(RTree::<i64, ()>::default().fanout(), (8, 4));
+        churn_matches_flatvec(RTree::<i64, ()>::default());
+        churn_matches_flatvec(RTree::with_params(4, 2));
+    }
+
+    #[test]
+    fn with_params_sets_fanout_for_inserts_and_bulk_builds() {
+        let pairs: Vec<(usize, Aabb2D<i64>)> = (0..1000_i64)
+            .map(|i| {
+                let (x, y) = (i % 40 * 10, i / 40 * 10);
+                (usize::try_from(i).unwrap(), Aabb2D::new(x, y, x + 5, y + 5))
+            })
+            .collect();
+        let mut grown: RTree<i64, ()> = RTree::with_params(32, 8);
+        for &(s, b) in &pairs {
+            grown.insert(s, b);
+        }
+        let bulk = RTree::<i64, ()>::bulk_build_with_params(&pairs, 32, 8);
+        for t in [&grown, &bulk] {
+            assert_eq!(t.fanout(), (32, 8));
+            assert!(t.arena.iter().all(|n| n.children.len() <= 32));
+            assert!(t.arena.iter().any(|n| n.children.len() > 8));
+            let mut hits: Vec<_> = t.query_rect(Aabb2D::new(102, 2, 118, 12)).collect();
+            hits.sort_unstable();
+            assert_eq!(hits, vec![10, 11, 50, 51]);
+        }
+        // Wider nodes pack the same items into far fewer nodes.
+        let narrow = RTree::<i64, ()>::bulk_build_default(&pairs);
+        assert!(bulk.arena.len() * 3 < narrow.arena.len());
+        // Bulk-loading a configured tree keeps its fan-out.
+        grown.bulk_load(&pairs);
+        assert_eq!(grown.fanout(), (32, 8));
+        assert_eq!(grown.arena.len(), bulk.arena.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "min_children >= 2")]
+    fn with_params_rejects_overlapping_min_children() {
+        let _ = RTree::<f64, ()>::with_params(6, 4);
+    }
+
+    #[test]
+    fn rtree_pathological_min_children_does_not_panic() {
+        // Bypass construction-time validation to exercise the split fallback.
+        let mut b: RTree<i64, u8> = RTree {
+            fanout: RuntimeFanout {
+                max_children: 4,
+                min_children: 100,
+            },
+            ..RTree::default()
+        };
+        let n = 50_usize;
+        for i in 0..n {
+            let x = i as i64 * 20;
+            b.insert(i, Aabb2D::new(x, 0, x + 10, 10));
+        }
+        for i in 0..n {
+            let x = i as i64 * 20 + 5;
+            let hits: Vec<_> = b.query_point(x, 5).collect();
+            assert_eq!(hits, vec![i], "each box must remain reachable");
+        }
+
+        // A single child has no admissible SAH split; the fallback must not panic.
+        let mut one = vec![RChild::<i64, u8>::Item {
+            slot: 0,
+            bbox: Aabb2D::new(0, 0, 1, 1),
+            _p: core::marker::PhantomData,
+        }];
+        let (l, r) = RTree::<i64, u8>::split_children_with(&mut one, 2, |c| match c {
+            RChild::Item { bbox, .. } => *bbox,
+            RChild::Node(_) => unreachable!(),
+        });
+        assert_eq!(l.len() + r.len(), 1);
+    }
+
+    /// Assert that parent links and the slot→leaf map match the tree shape.
+    fn check_links<P: Copy + Debug, N: Fanout>(t: &RTree<f64, P, N>) {
+        let Some(root) = t.root else {
+            return;
+        };
+        assert_eq!(t.arena[root.get()].parent, None);
+        let mut stack = vec![root.get()];
+        while let Some(i) = stack.pop() {
+            for c in t.arena[i].children.iter() {
+                match c {
+                    RChild::Node(ci) => {
+                        assert_eq!(t.arena[ci.get()].parent, Some(NodeIdx::new(i)));
+                        stack.push(ci.get());
+                    }
+                    RChild::Item { slot, .. } => assert_eq!(t.leaves[*slot], i),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parent_links_survive_splits_removals_and_compaction() {
+        let mut t: RTree<f64, ()> = RTree::with_params(4, 2);
+        let b = |i: usize| {
+            let (x, y) = ((i % 23) as f64 * 10.0, (i / 23) as f64 * 10.0);
+            Aabb2D::new(x, y, x + 5.0, y + 5.0)
+        };
+        for i in 0..300 {
+            if i % 3 == 0 || i == 0 {
+                t.insert(i, b(i));
+            } else {
+                t.insert_near(i, b(i), i - 1);
+            }
+        }
+        check_links(&t);
+        for i in (0..300).step_by(4) {
+            t.remove(i);
+        }
+        for i in (1..300).step_by(4) {
+            t.update(i, b(i + 150));
+        }
+        check_links(&t);
+        t.compact_arena();
+        check_links(&t);
+        let bulk: Vec<_> = (0..100).map(|i| (i, b(i))).collect();
+        check_links(&RTree::<f64, ()>::bulk_build_default(&bulk));
+    }
+
+    #[test]
+    fn compact_arena_step_reclaims_dead_nodes_in_bounded_steps() {
+        let mut t: RTree<f64, ()> = RTree::with_params(4, 2);
+        let b = |i: usize| {
+            let (x, y) = ((i % 29) as f64 * 10.0, (i / 29) as f64 * 10.0);
+            Aabb2D::new(x, y, x + 12.0, y + 12.0)
+        };
+        for i in 0..600 {
+            t.insert(i, b(i));
+        }
+        for i in (0..600).filter(|i| i % 5 != 0) {
+            t.remove(i);
+        }
+        let s = t.stats();
+        assert!(s.dead_nodes > 0);
+        assert_eq!(t.arena_usage(), (s.node_count, s.dead_nodes));
+
+        let q = Aabb2D::new(35.0, 35.0, 185.0, 125.0);
+        let mut expected = Vec::new();
+        t.visit_rect(q, |s| expected.push(s));
+        let mut steps = 0;
+        while t.compact_arena_step(3) {
+            steps += 1;
+            check_links(&t);
+            let mut got = Vec::new();
+            t.visit_rect(q, |s| got.push(s));
+            assert_eq!(got, expected);
+            // Nodes that die mid-compaction are reclaimed too.
+            if steps == 10 {
+                t.remove(5);
+                expected.retain(|&s| s != 5);
+            }
+        }
+        assert!(steps >= s.dead_nodes / 3, "{steps}");
+        let s = t.stats();
+        assert_eq!(s.dead_nodes, 0);
+        assert_eq!(t.arena_usage(), (s.node_count, 0));
+        assert_eq!(t.arena.len(), s.node_count);
+        check_links(&t);
+    }
 }
@@ -5,10 +5,11 @@
 
 use alloc::vec::Vec;
 
-use crate::types::{Aabb2D, union_aabb};
+use crate::types::{Aabb2D, Scalar, area, try_area, union_aabb};
 
 /// Batched damage summary returned by [`Index::commit`](crate::Index::commit).
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Damage<T> {
     /// Newly added AABBs since last commit.
     pub added: Vec<Aabb2D<T>>,
@@ -16,6 +17,10 @@ pub struct Damage<T> {
     pub removed: Vec<Aabb2D<T>>,
     /// Moved AABBs since last commit: (old, new).
     pub moved: Vec<(Aabb2D<T>, Aabb2D<T>)>,
+    /// Whether [`compact_slots`](crate::IndexGeneric::compact_slots) moved any entry to a
+    /// new slot since last commit, so keys and slot-keyed caches need remapping.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub slots_remapped: bool,
 }
 
 impl<T> Default for Damage<T> {
@@ -24,6 +29,7 @@ impl<T> Default for Damage<T> {
             added: Vec::new(),
             removed: Vec::new(),
             moved: Vec::new(),
+            slots_remapped: false,
         }
     }
 }
@@ -45,4 +51,256 @@ impl<T: Copy + PartialOrd> Damage<T> {
         let first = it.next()?;
         Some(it.fold(first, |acc, r| union_aabb(acc, r)))
     }
+
+    /// Whether both damage sets hold the same added, removed, and moved boxes, ignoring order.
+    ///
+    /// Each list is compared as a multiset, so the result does not depend on backend
+    /// traversal order. Meant for assertions; it is quadratic in the list lengths.
+    pub fn eq_unordered(&self, other: &Self) -> bool {
+        unmatched(&self.added, &other.added).is_empty()
+            && unmatched(&other.added, &self.added).is_empty()
+            && unmatched(&self.removed, &other.removed).is_empty()
+            && unmatched(&other.removed, &self.removed).is_empty()
+            && unmatched(&self.moved, &other.moved).is_empty()
+            && unmatched(&other.moved, &self.moved).is_empty()
+    }
+}
+
+impl<T: Scalar> Damage<T> {
+    /// Merge all affected AABBs into at most `max_rects` dirty regions for repainting.
+    ///
+    /// Boxes that overlap or touch are merged into their union, repeatedly, so the regions
+    /// returned are pairwise disjoint. While more than `max_rects` remain, the two regions
+    /// whose union adds the least area are merged, so distant clusters stay separate as long
+    /// as the budget allows; any damage yields at least one region. Empty and inverted boxes
+    /// are ignored. Each merge is quadratic in the number of regions; this is meant for
+    /// per-frame damage lists.
+    pub fn coalesced(&self, max_rects: usize) -> Vec<Aabb2D<T>> {
+        let boxes = self
+            .added
+            .iter()
+            .copied()
+            .chain(self.removed.iter().copied())
+            .chain(self.moved.iter().flat_map(|(a, b)| [*a, *b]))
+            .filter(|b| !b.is_empty());
+        let mut regions: Vec<Aabb2D<T>> = Vec::new();
+        for b in boxes {
+            absorb(&mut regions, b);
+        }
+        while regions.len() > max_rects.max(1) {
+            let mut best: Option<(usize, usize, Option<T::Acc>)> = None;
+            for a in 0..regions.len() {
+                for b in a + 1..regions.len() {
+                    let cost = merge_cost(&regions[a], &regions[b]);
+                    let better = match (cost, best.map(|(_, _, c)| c)) {
+                        (_, None) => true,
+                        (Some(c), Some(Some(best))) => c < best,
+                        (Some(_), Some(None)) => true,
+                        (None, Some(_)) => false,
+                    };
+                    if better {
+                        best = Some((a, b, cost));
+                    }
+                }
+            }
+            let (a, b, _) = best.expect("at least two regions");
+            let merged = union_aabb(regions[a], regions[b]);
+            // `b > a`, so removing `b` first leaves `a` in place.
+            regions.swap_remove(b);
+            regions.swap_remove(a);
+            absorb(&mut regions, merged);
+        }
+        regions
+    }
+}
+
+/// Add `b` to the disjoint `regions`, merging it with every region it overlaps or touches.
+fn absorb<T: Copy + PartialOrd>(regions: &mut Vec<Aabb2D<T>>, mut b: Aabb2D<T>) {
+    // Absorb every region `b` touches; the grown box may then touch others.
+    loop {
+        let before = regions.len();
+        regions.retain(|r| {
+            if r.intersect(&b).is_empty() {
+                return true;
+            }
+            b = union_aabb(b, *r);
+            false
+        });
+        if regions.len() == before {
+            break;
+        }
+    }
+    regions.push(b);
+}
+
+/// Area the union of two disjoint regions covers beyond them, or `None` if it overflows.
+fn merge_cost<T: Scalar>(a: &Aabb2D<T>, b: &Aabb2D<T>) -> Option<T::Acc> {
+    // The union covers both disjoint regions, so this cannot go negative or overflow.
+    Some(try_area(&union_aabb(*a, *b))? - area(a) - area(b))
+}
+
+#[cfg(test)]
+impl<T: Copy + PartialOrd + core::fmt::Debug> Damage<T> {
+    /// Describe how `self` differs from `other` as multisets, or `None` if they are equal.
+    pub(crate) fn diff_unordered(&self, other: &Self) -> Option<alloc::string::String> {
+        let mut out = alloc::string::String::new();
+        diff_section(&mut out, "added", &self.added, &other.added);
+        diff_section(&mut out, "removed", &self.removed, &other.removed);
+        diff_section(&mut out, "moved", &self.moved, &other.moved);
+        (!out.is_empty()).then_some(out)
+    }
+}
+
+/// Append a line to `out` naming the items only in `left` or only in `right`, if any.
+#[cfg(test)]
+fn diff_section<X: PartialEq + core::fmt::Debug>(
+    out: &mut alloc::string::String,
+    name: &str,
+    left: &[X],
+    right: &[X],
+) {
+    use core::fmt::Write;
+    let (only_left, only_right) = (unmatched(left, right), unmatched(right, left));
+    if !only_left.is_empty() || !only_right.is_empty() {
+        let _ = writeln!(
+            out,
+            "{name}: only in left {only_left:?}, only in right {only_right:?}"
+        );
+    }
+}
+
+/// Items of `a` left over after matching each against a distinct equal item of `b`.
+fn unmatched<'a, X: PartialEq>(a: &'a [X], b: &[X]) -> Vec<&'a X> {
+    let mut used = alloc::vec![false; b.len()];
+    let mut out = Vec::new();
+    for x in a {
+        match (0..b.len()).find(|&j| !used[j] && b[j] == *x) {
+            Some(j) => used[j] = true,
+            None => out.push(x),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eq_unordered_ignores_order_but_not_multiplicity() {
+        let a = Aabb2D::new(0, 0, 1, 1);
+        let b = Aabb2D::new(2, 2, 3, 3);
+        let c = Aabb2D::new(4, 4, 5, 5);
+        let left = Damage {
+            added: alloc::vec![a, b, a],
+            removed: alloc::vec![c],
+            moved: alloc::vec![(a, b), (b, c)],
+            slots_remapped: false,
+        };
+        let right = Damage {
+            added: alloc::vec![a, a, b],
+            removed: alloc::vec![c],
+            moved: alloc::vec![(b, c), (a, b)],
+            slots_remapped: false,
+        };
+        assert!(left.eq_unordered(&right));
+        assert_eq!(left.diff_unordered(&right), None);
+
+        let fewer = Damage {
+            added: alloc::vec![a, b, b],
+            ..right.clone()
+        };
+        assert!(!left.eq_unordered(&fewer));
+        let diff = left.diff_unordered(&fewer).unwrap();
+        assert!(diff.starts_with("added:"), "{diff}");
+        // Direction of a move matters.
+        let flipped = Damage {
+            moved: alloc::vec![(b, a), (b, c)],
+            ..right
+        };
+        assert!(!left.eq_unordered(&flipped));
+    }
+
+    #[test]
+    fn coalesced_merges_touching_boxes_within_budget() {
+        let disjoint = Damage {
+            added: alloc::vec![Aabb2D::new(0, 0, 1, 1), Aabb2D::new(5, 5, 6, 6)],
+            removed: alloc::vec![Aabb2D::new(10, 0, 11, 1)],
+            moved: Vec::new(),
+            slots_remapped: false,
+        };
+        assert_eq!(
+            disjoint.coalesced(8),
+            disjoint
+                .added
+                .iter()
+                .chain(&disjoint.removed)
+                .copied()
+                .collect::<Vec<_>>()
+        );
+
+        // A chain: the move's source overlaps the first box and its target only touches
+        // the second, which joins them all once the first merge has grown the region.
+        let chained = Damage {
+            added: alloc::vec![Aabb2D::new(0, 0, 4, 4), Aabb2D::new(9, 0, 12, 3)],
+            removed: alloc::vec![Aabb2D::new(30, 30, 31, 31), Aabb2D::new(1, 1, 0, 0)],
+            moved: alloc::vec![(Aabb2D::new(3, 3, 6, 6), Aabb2D::new(6, 0, 9, 2))],
+            slots_remapped: false,
+        };
+        assert_eq!(
+            chained.coalesced(8),
+            alloc::vec![Aabb2D::new(30, 30, 31, 31), Aabb2D::new(0, 0, 12, 6)]
+        );
+
+        // Over budget: the pair whose union adds the least area merges first.
+        assert_eq!(
+            disjoint.coalesced(2),
+            alloc::vec![Aabb2D::new(5, 5, 6, 6), Aabb2D::new(0, 0, 11, 1)]
+        );
+        assert_eq!(disjoint.coalesced(1), alloc::vec![Aabb2D::new(0, 0, 11, 6)]);
+        assert_eq!(disjoint.coalesced(0), disjoint.coalesced(1));
+        assert_eq!(disjoint.coalesced(3).len(), 3);
+        assert!(Damage::<i64>::default().coalesced(4).is_empty());
+    }
+
+    #[test]
+    fn coalesced_keeps_distant_clusters_apart_over_budget() {
+        // Two clusters of three separate boxes each, far apart.
+        let cluster = |x: i64, y: i64| {
+            [(0, 0), (3, 0), (0, 3)]
+                .map(|(dx, dy)| Aabb2D::new(x + dx, y + dy, x + dx + 2, y + dy + 2))
+        };
+        let damage = Damage {
+            added: cluster(0, 0).to_vec(),
+            removed: cluster(100, 100).to_vec(),
+            moved: Vec::new(),
+            slots_remapped: false,
+        };
+        let mut two = damage.coalesced(2);
+        two.sort_by_key(|r| r.min_x);
+        assert_eq!(
+            two,
+            alloc::vec![Aabb2D::new(0, 0, 5, 5), Aabb2D::new(100, 100, 105, 105)]
+        );
+        // Regions stay disjoint at every budget.
+        for max_rects in 1..=6 {
+            let regions = damage.coalesced(max_rects);
+            assert_eq!(regions.len(), max_rects, "{regions:?}");
+            for (i, a) in regions.iter().enumerate() {
+                for b in &regions[i + 1..] {
+                    assert!(a.intersect(b).is_empty(), "{a:?} meets {b:?}");
+                }
+            }
+        }
+        let f = Damage {
+            added: alloc::vec![
+                Aabb2D::new(0.0, 0.0, 1.0, 1.0),
+                Aabb2D::new(f64::MAX / 2.0, 0.0, f64::MAX, 1.0)
+            ],
+            removed: alloc::vec![Aabb2D::new(2.0, 0.0, 3.0, 1.0)],
+            moved: Vec::new(),
+            slots_remapped: false,
+        };
+        assert_eq!(f.coalesced(2)[1], Aabb2D::new(0.0, 0.0, 3.0, 1.0));
+    }
 }
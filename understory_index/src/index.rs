@@ -3,125 +3,674 @@
 
 //! Public `Index` API and generic implementation over a pluggable backend.
 
+use alloc::collections::BTreeMap;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt::Debug;
 
-use crate::backend::Backend;
+use crate::backend::{Backend, GroupId, vec_bytes};
 use crate::damage::Damage;
-use crate::types::Aabb2D;
+use crate::types::{Aabb2D, Scalar, point_dist2};
+
+mod sealed {
+    #[allow(
+        unnameable_types,
+        reason = "Sealing pattern: the trait is public but intentionally unnameable."
+    )]
+    pub trait Sealed {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+/// Integer width of the slot and generation halves of a [`Key`].
+///
+/// Implemented for `u32` (the default) and `u16`. A `u16` key is half the size,
+/// which helps when keys are stored in large external maps, but limits the index
+/// to `u16::MAX + 1` slots and wraps generations sooner.
+pub trait KeyIndex:
+    Copy + Eq + Ord + core::hash::Hash + Debug + core::fmt::Display + sealed::Sealed
+{
+    /// First generation handed out for a slot.
+    const FIRST_GENERATION: Self;
+
+    /// Convert a slot index, or `None` if it does not fit in this width.
+    fn from_usize(v: usize) -> Option<Self>;
+
+    /// Widen to `usize`.
+    fn to_usize(self) -> usize;
+
+    /// The generation after `self`, wrapping from the maximum back to the first.
+    fn next_generation(self) -> Self;
+}
+
+impl KeyIndex for u16 {
+    const FIRST_GENERATION: Self = 1;
+
+    #[inline]
+    fn from_usize(v: usize) -> Option<Self> {
+        Self::try_from(v).ok()
+    }
+
+    #[inline]
+    fn to_usize(self) -> usize {
+        usize::from(self)
+    }
+
+    #[inline]
+    fn next_generation(self) -> Self {
+        if self == Self::MAX { 1 } else { self + 1 }
+    }
+}
+
+impl KeyIndex for u32 {
+    const FIRST_GENERATION: Self = 1;
+
+    #[inline]
+    fn from_usize(v: usize) -> Option<Self> {
+        Self::try_from(v).ok()
+    }
+
+    #[inline]
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+
+    #[inline]
+    fn next_generation(self) -> Self {
+        if self == Self::MAX { 1 } else { self + 1 }
+    }
+}
 
 /// Generational handle for entries.
+///
+/// The slot and generation are stored as `I` (see [`KeyIndex`]); `u32` by default.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-pub struct Key(u32, u32);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Key<I: KeyIndex = u32>(I, I);
+
+impl<I: KeyIndex> Key<I> {
+    fn new(idx: usize, generation: I) -> Self {
+        // Slots are range-checked on insert, so this cannot fail for live entries.
+        Self(
+            I::from_usize(idx).expect("slot index exceeds key width"),
+            generation,
+        )
+    }
+
+    fn idx(self) -> usize {
+        self.0.to_usize()
+    }
+}
 
 impl Key {
+    /// Pack this key into a `u64` (generation in the high 32 bits, slot in the low 32 bits).
+    ///
+    /// Useful for storing keys in external systems; see [`Key::from_raw`].
+    pub const fn to_raw(self) -> u64 {
+        ((self.1 as u64) << 32) | self.0 as u64
+    }
+
+    /// Unpack a key previously produced by [`Key::to_raw`].
+    ///
+    /// The result is only meaningful for the index that issued the original key.
     #[allow(
         clippy::cast_possible_truncation,
-        reason = "Index keys are intentionally 32-bit; higher bits are truncated by design."
+        reason = "Each half of the packed u64 is exactly 32 bits."
     )]
-    const fn new(idx: usize, generation: u32) -> Self {
-        Self(idx as u32, generation)
+    pub const fn from_raw(raw: u64) -> Self {
+        Self(raw as u32, (raw >> 32) as u32)
+    }
+}
+
+impl<I: KeyIndex> core::fmt::Display for Key<I> {
+    /// Formats as `#slot@ggeneration`, e.g. `#3@g1`.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#{}@g{}", self.0, self.1)
+    }
+}
+
+/// Keys moved to new slots by [`IndexGeneric::compact_slots`].
+///
+/// Apply it to every key (or slot-keyed cache entry) held from before the compaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlotRemap<I: KeyIndex = u32> {
+    // (old, new), sorted by old slot.
+    moves: Vec<(Key<I>, Key<I>)>,
+}
+
+impl<I: KeyIndex> SlotRemap<I> {
+    /// The key that replaces `old`, or `old` itself if it did not move.
+    pub fn apply(&self, old: Key<I>) -> Key<I> {
+        match self
+            .moves
+            .binary_search_by_key(&old.idx(), |(k, _)| k.idx())
+        {
+            Ok(i) if self.moves[i].0 == old => self.moves[i].1,
+            _ => old,
+        }
+    }
+
+    /// Iterate the moved keys as `(old, new)`, in old slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (Key<I>, Key<I>)> + '_ {
+        self.moves.iter().copied()
+    }
+
+    /// Number of entries that moved.
+    pub fn len(&self) -> usize {
+        self.moves.len()
     }
 
-    const fn idx(self) -> usize {
-        self.0 as usize
+    /// True if no entry moved.
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
     }
 }
 
+/// The entries of an [`IndexGeneric`] without its backend; see
+/// [`IndexGeneric::snapshot`].
+///
+/// With the `serde` feature this is the serialized form of an index.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexSnapshot<T, P, I: KeyIndex = u32> {
+    /// Live entries in slot order.
+    pub entries: Vec<SnapshotEntry<T, P, I>>,
+    /// Free slots in reuse order (the last is reused first), each as the key that last
+    /// held it, so keys issued after a reload never match keys from before the snapshot.
+    pub free: Vec<Key<I>>,
+}
+
+/// Why [`IndexGeneric::from_snapshot`] rejected a snapshot.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// A key names slot `idx`, but the snapshot lists only `listed` keys, live and free,
+    /// so it cannot account for that many slots.
+    SlotOutOfRange {
+        /// The offending slot.
+        idx: usize,
+        /// Number of keys in the snapshot.
+        listed: usize,
+    },
+}
+
+impl core::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::SlotOutOfRange { idx, listed } => {
+                write!(
+                    f,
+                    "slot {idx} is out of range for a snapshot of {listed} keys"
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for SnapshotError {}
+
+/// One live entry of an [`IndexSnapshot`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotEntry<T, P, I: KeyIndex = u32> {
+    /// The entry's key; it stays valid in the reloaded index.
+    pub key: Key<I>,
+    /// The entry's box.
+    pub aabb: Aabb2D<T>,
+    /// The entry's payload.
+    pub payload: P,
+    /// The entry's user flags.
+    pub flags: u8,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum Mark {
-    Added,
+    /// New entry; `hint` is a slot to start the backend insert near.
+    Added {
+        hint: Option<usize>,
+    },
     Updated,
     Removed,
 }
 
 #[derive(Clone, Debug)]
-struct Entry<T, P> {
-    generation: u32,
+struct Entry<T, P, I> {
+    generation: I,
     aabb: Aabb2D<T>,
     payload: P,
     mark: Option<Mark>,
     prev_aabb: Option<Aabb2D<T>>, // for moved damage
+    flags: u8,
 }
 
 /// A generic AABB index parameterized by a spatial backend.
+///
+/// `I` selects the width of the returned [`Key`]s (see [`KeyIndex`]).
+///
+/// There is no `insert_static` here: for content that is packed once and never moves,
+/// use [`TieredIndex`](crate::TieredIndex), which keeps static entries beside an
+/// `IndexGeneric` that holds the dynamic ones.
 #[derive(Debug)]
-pub struct IndexGeneric<T: Copy + PartialOrd + Debug, P: Copy + Debug, B: Backend<T>> {
-    entries: Vec<Option<Entry<T, P>>>,
-    free_list: Vec<usize>,
+pub struct IndexGeneric<
+    T: Copy + PartialOrd + Debug,
+    P: Copy + Debug,
+    B: Backend<T>,
+    I: KeyIndex = u32,
+> {
+    entries: Vec<Option<Entry<T, P, I>>>,
+    // Freed slots with the generation they last held.
+    free_list: Vec<(usize, I)>,
+    // Net change per entry since the last `take_accumulated_damage`, when enabled.
+    accumulated: Option<BTreeMap<(usize, I), Change<T>>>,
+    maintenance: Maintenance,
+    // Whether `compact_slots` moved entries since the last commit.
+    slots_remapped: bool,
     backend: B,
 }
 
-impl<T, P, B> IndexGeneric<T, P, B>
+/// Per-call limits for [`IndexGeneric::maintain`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MaintenanceBudget {
+    /// Entries removed from and reinserted into the backend per call.
+    pub max_reinserts: usize,
+    /// Arena nodes dropped or moved per call while compacting.
+    pub max_compact_nodes: usize,
+}
+
+impl Default for MaintenanceBudget {
+    fn default() -> Self {
+        Self {
+            max_reinserts: 64,
+            max_compact_nodes: 1024,
+        }
+    }
+}
+
+/// Progress of incremental maintenance across [`IndexGeneric::maintain`] calls.
+#[derive(Copy, Clone, Debug, Default)]
+struct Maintenance {
+    /// Backend changes committed since the last reinsertion sweep.
+    churn: usize,
+    /// Next slot to reinsert while a sweep is in progress.
+    sweep: Option<usize>,
+    /// Whether a compaction is in progress; it runs until no dead nodes are left.
+    compacting: bool,
+}
+
+/// Net change of one entry across several commits.
+#[derive(Copy, Clone, Debug)]
+enum Change<T> {
+    Added(Aabb2D<T>),
+    Removed(Aabb2D<T>),
+    Moved(Aabb2D<T>, Aabb2D<T>),
+}
+
+impl<T: Copy + PartialEq> Change<T> {
+    /// Fold a later change of the same entry into this one; `None` means no net change.
+    fn then(self, next: Self) -> Option<Self> {
+        match (self, next) {
+            (Self::Added(_), Self::Moved(_, to)) => Some(Self::Added(to)),
+            (Self::Added(_), Self::Removed(_)) => None,
+            (Self::Moved(from, _), Self::Moved(_, to)) => {
+                (from != to).then_some(Self::Moved(from, to))
+            }
+            (Self::Moved(from, _), Self::Removed(_)) => Some(Self::Removed(from)),
+            // An entry is not re-added or changed after removal; keep the latest change.
+            (_, next) => Some(next),
+        }
+    }
+}
+
+/// Serializes the index as its [`IndexSnapshot`].
+#[cfg(feature = "serde")]
+impl<T, P, B, I> serde::Serialize for IndexGeneric<T, P, B, I>
+where
+    T: Copy + PartialOrd + Debug + serde::Serialize,
+    P: Copy + Debug + serde::Serialize,
+    B: Backend<T>,
+    I: KeyIndex + serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.snapshot().serialize(serializer)
+    }
+}
+
+/// Deserializes an [`IndexSnapshot`] into a default backend; see
+/// [`IndexGeneric::from_snapshot`]. For backends without `Default`, deserialize the
+/// snapshot and call `from_snapshot` directly.
+#[cfg(feature = "serde")]
+impl<'de, T, P, B, I> serde::Deserialize<'de> for IndexGeneric<T, P, B, I>
+where
+    T: Copy + PartialOrd + Debug + serde::Deserialize<'de>,
+    P: Copy + Debug + serde::Deserialize<'de>,
+    B: Backend<T> + Default,
+    I: KeyIndex + serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let snapshot = IndexSnapshot::deserialize(deserializer)?;
+        Self::from_snapshot(snapshot, B::default()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl<T, P, B, I> IndexGeneric<T, P, B, I>
 where
     T: Copy + PartialOrd + Debug,
     P: Copy + Debug,
     B: Backend<T> + Default,
+    I: KeyIndex,
 {
     /// Create an empty index using the backend's default constructor.
     pub fn new() -> Self {
-        Self {
-            entries: Vec::new(),
-            free_list: Vec::new(),
-            backend: B::default(),
-        }
+        Self::with_backend(B::default())
     }
 }
 
-impl<T, P, B> IndexGeneric<T, P, B>
+impl<T, P, B, I> IndexGeneric<T, P, B, I>
 where
     T: Copy + PartialOrd + Debug,
     P: Copy + Debug,
     B: Backend<T>,
+    I: KeyIndex,
 {
     /// Create an empty index using an explicit backend instance.
     ///
     /// This is useful when higher layers want to choose a backend type or
     /// configure it before wiring it into the index.
     pub fn with_backend(backend: B) -> Self {
+        Self::from_parts(Vec::new(), Vec::new(), backend)
+    }
+
+    /// The one place an index is assembled; every constructor goes through here.
+    fn from_parts(
+        entries: Vec<Option<Entry<T, P, I>>>,
+        free_list: Vec<(usize, I)>,
+        backend: B,
+    ) -> Self {
         Self {
-            entries: Vec::new(),
-            free_list: Vec::new(),
+            entries,
+            free_list,
+            accumulated: None,
+            maintenance: Maintenance::default(),
+            slots_remapped: false,
             backend,
         }
     }
+
+    /// Recreate an index from a [`snapshot`](Self::snapshot), bulk-loading `backend`.
+    ///
+    /// Every entry is committed under its original key; see [`Backend::bulk_load`]. If the
+    /// snapshot lists a slot twice, its last live entry wins, and a slot that is both live
+    /// and free stays live.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotError::SlotOutOfRange`] if a key's slot is not below the number of
+    /// keys in the snapshot, which no snapshot taken from an index produces. This bounds the
+    /// slot table by the size of the input.
+    pub fn from_snapshot(
+        snapshot: IndexSnapshot<T, P, I>,
+        mut backend: B,
+    ) -> Result<Self, SnapshotError> {
+        let listed = snapshot.entries.len() + snapshot.free.len();
+        let mut len = 0;
+        for k in snapshot
+            .entries
+            .iter()
+            .map(|e| e.key)
+            .chain(snapshot.free.iter().copied())
+        {
+            if k.idx() >= listed {
+                return Err(SnapshotError::SlotOutOfRange {
+                    idx: k.idx(),
+                    listed,
+                });
+            }
+            len = len.max(k.idx() + 1);
+        }
+        let mut entries: Vec<Option<Entry<T, P, I>>> = Vec::new();
+        entries.resize_with(len, || None);
+        for e in snapshot.entries {
+            entries[e.key.idx()] = Some(Entry {
+                generation: e.key.1,
+                aabb: e.aabb,
+                payload: e.payload,
+                mark: None,
+                prev_aabb: None,
+                flags: e.flags,
+            });
+        }
+        let mut listed = vec![false; len];
+        let mut free_list = Vec::new();
+        for k in snapshot.free {
+            if entries[k.idx()].is_none() && !core::mem::replace(&mut listed[k.idx()], true) {
+                free_list.push((k.idx(), k.1));
+            }
+        }
+        // Slots the snapshot does not mention are reused last.
+        let unlisted = (0..len).filter(|&i| entries[i].is_none() && !listed[i]);
+        let unlisted: Vec<_> = unlisted.map(|i| (i, I::FIRST_GENERATION)).collect();
+        free_list.splice(0..0, unlisted);
+        let pairs: Vec<(usize, Aabb2D<T>)> = entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| e.as_ref().map(|e| (i, e.aabb)))
+            .collect();
+        backend.bulk_load(&pairs);
+        Ok(Self::from_parts(entries, free_list, backend))
+    }
+
+    /// Create a committed index holding `entries`, with a backend bulk-built by `build`
+    /// from `(slot, aabb)` pairs.
+    fn from_bulk(
+        entries: &[(Aabb2D<T>, P)],
+        build: impl FnOnce(&[(usize, Aabb2D<T>)]) -> B,
+    ) -> Self {
+        let mut pairs: Vec<(usize, Aabb2D<T>)> = Vec::with_capacity(entries.len());
+        let entries = entries
+            .iter()
+            .copied()
+            .enumerate()
+            .map(|(i, (aabb, payload))| {
+                pairs.push((i, aabb));
+                Some(Entry {
+                    generation: I::FIRST_GENERATION,
+                    aabb,
+                    payload,
+                    mark: None,
+                    prev_aabb: None,
+                    flags: 0,
+                })
+            })
+            .collect();
+        let backend = build(&pairs);
+        Self::from_parts(entries, Vec::new(), backend)
+    }
 }
 
-impl<T, P, B> IndexGeneric<T, P, B>
+impl<T, P, B, I> IndexGeneric<T, P, B, I>
 where
     T: Copy + PartialOrd + Debug,
     P: Copy + Debug,
     B: Backend<T>,
+    I: KeyIndex,
 {
     /// Reserve space for at least `n` entries.
     pub fn reserve(&mut self, n: usize) {
         self.entries.reserve(n);
     }
 
+    /// Number of live entries.
+    ///
+    /// Counts the entries currently present, matching [`contains_key`](Self::contains_key):
+    /// uncommitted inserts are included and entries removed since the last commit are not.
+    /// This scans the entry table, so it is O(slots).
+    pub fn len(&self) -> usize {
+        self.live_entries().count()
+    }
+
+    /// Whether the index has no live entries; see [`len`](Self::len).
+    pub fn is_empty(&self) -> bool {
+        self.live_entries().next().is_none()
+    }
+
+    fn live_entries(&self) -> impl Iterator<Item = &Entry<T, P, I>> {
+        self.entries
+            .iter()
+            .flatten()
+            .filter(|e| !matches!(e.mark, Some(Mark::Removed)))
+    }
+
+    /// Number of slots in the entry table, live or free.
+    ///
+    /// Every [`Key::idx`] is below this, so it sizes side tables indexed by key.
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
     /// Insert a new AABB with payload. Returns a stable handle `Key`.
-    pub fn insert(&mut self, aabb: Aabb2D<T>, payload: P) -> Key {
-        let (idx, generation) = if let Some(idx) = self.free_list.pop() {
-            let generation = self.entries[idx]
-                .as_ref()
-                .map(|e| e.generation)
-                .unwrap_or(0)
-                + 1;
+    ///
+    /// # Panics
+    ///
+    /// Panics if a new slot is needed and its index does not fit in `I`.
+    pub fn insert(&mut self, aabb: Aabb2D<T>, payload: P) -> Key<I> {
+        self.insert_marked(aabb, payload, Mark::Added { hint: None })
+    }
+
+    /// Insert like [`insert`](Self::insert), tagging the entry with user `flags`.
+    ///
+    /// Flags are opaque to the index; see [`query_rect_flags`](Self::query_rect_flags).
+    pub fn insert_with_flags(&mut self, aabb: Aabb2D<T>, payload: P, flags: u8) -> Key<I> {
+        let key = self.insert(aabb, payload);
+        self.set_flags(key, flags);
+        key
+    }
+
+    /// Replace the user flags of an entry. Stale keys are ignored.
+    ///
+    /// Flags take effect immediately (no commit needed) and do not produce damage.
+    pub fn set_flags(&mut self, key: Key<I>, flags: u8) {
+        if let Some(e) = self.entry_mut(key) {
+            e.flags = flags;
+        }
+    }
+
+    /// The user flags of an entry, or `None` for a stale key.
+    pub fn flags(&self, key: Key<I>) -> Option<u8> {
+        self.entry(key).map(|e| e.flags)
+    }
+
+    /// The AABB and payload of an entry, or `None` for a stale key.
+    ///
+    /// Reflects pending changes: an updated entry reports its new AABB, and a removed
+    /// entry returns `None` even before the next [`commit`](Self::commit). A key whose
+    /// slot has been reused (generation mismatch) never aliases the new entry.
+    pub fn get(&self, key: Key<I>) -> Option<(Aabb2D<T>, P)> {
+        self.entry(key)
+            .filter(|e| !matches!(e.mark, Some(Mark::Removed)))
+            .map(|e| (e.aabb, e.payload))
+    }
+
+    /// Capture the live entries and free slots, without the backend.
+    ///
+    /// Reflects pending changes as [`get`](Self::get) does: uncommitted inserts and updates
+    /// are included and uncommitted removals are not. Restore with
+    /// [`from_snapshot`](Self::from_snapshot); keys stay valid across the round trip.
+    /// Accumulated damage is not captured.
+    pub fn snapshot(&self) -> IndexSnapshot<T, P, I> {
+        let mut free: Vec<Key<I>> = self
+            .free_list
+            .iter()
+            .map(|&(i, generation)| Key::new(i, generation))
+            .collect();
+        let mut entries = Vec::new();
+        for (i, e) in self.entries.iter().enumerate() {
+            let Some(e) = e else {
+                continue;
+            };
+            let key = Key::new(i, e.generation);
+            if matches!(e.mark, Some(Mark::Removed)) {
+                // Freed in slot order, as the next commit would.
+                free.push(key);
+            } else {
+                entries.push(SnapshotEntry {
+                    key,
+                    aabb: e.aabb,
+                    payload: e.payload,
+                    flags: e.flags,
+                });
+            }
+        }
+        IndexSnapshot { entries, free }
+    }
+
+    /// [`get`](Self::get) for each of `keys`, in order; stale keys yield `None`.
+    pub fn get_many(&self, keys: &[Key<I>]) -> Vec<Option<(Aabb2D<T>, P)>> {
+        keys.iter().map(|&k| self.get(k)).collect()
+    }
+
+    /// Whether `key` refers to a live entry: its slot exists and the generation matches.
+    ///
+    /// Like [`get`](Self::get), a removed entry stops being live right away, before the
+    /// commit that applies the removal; a key is never live again once its slot is reused.
+    pub fn contains_key(&self, key: Key<I>) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// The AABB of an entry, or `None` for a stale key; see [`get`](Self::get).
+    pub fn aabb_of(&self, key: Key<I>) -> Option<Aabb2D<T>> {
+        self.get(key).map(|(aabb, _)| aabb)
+    }
+
+    /// The payload of an entry, or `None` for a stale key; see [`get`](Self::get).
+    pub fn payload_of(&self, key: Key<I>) -> Option<P> {
+        self.get(key).map(|(_, p)| p)
+    }
+
+    /// Insert like [`insert`](Self::insert), hinting that `aabb` lies near the entry `hint`.
+    ///
+    /// On commit, tree backends start the insert descent from the deepest node on `hint`'s
+    /// path that already contains `aabb`, falling back to the root otherwise (see
+    /// [`Backend::insert_near`]). This pays off for spatially coherent inserts such as
+    /// scanlines, where each entry is hinted with the previous one. The hint only affects
+    /// tree layout, never query results; a stale or uncommitted `hint` is ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a new slot is needed and its index does not fit in `I`.
+    pub fn insert_near(&mut self, aabb: Aabb2D<T>, payload: P, hint: Key<I>) -> Key<I> {
+        let hint = self
+            .entries
+            .get(hint.idx())
+            .and_then(Option::as_ref)
+            .filter(|e| e.generation == hint.1)
+            .map(|_| hint.idx());
+        self.insert_marked(aabb, payload, Mark::Added { hint })
+    }
+
+    fn insert_marked(&mut self, aabb: Aabb2D<T>, payload: P, mark: Mark) -> Key<I> {
+        let (idx, generation) = if let Some((idx, last)) = self.free_list.pop() {
+            let generation = last.next_generation();
             self.entries[idx] = Some(Entry {
                 generation,
                 aabb,
                 payload,
-                mark: Some(Mark::Added),
+                mark: Some(mark),
                 prev_aabb: None,
+                flags: 0,
             });
             (idx, generation)
         } else {
-            let generation = 1_u32;
+            assert!(
+                I::from_usize(self.entries.len()).is_some(),
+                "index is full: slot index exceeds key width"
+            );
+            let generation = I::FIRST_GENERATION;
             self.entries.push(Some(Entry {
                 generation,
                 aabb,
                 payload,
-                mark: Some(Mark::Added),
+                mark: Some(mark),
                 prev_aabb: None,
+                flags: 0,
             }));
             (self.entries.len() - 1, generation)
         };
@@ -129,57 +678,295 @@ where
     }
 
     /// Update an existing AABB.
-    pub fn update(&mut self, key: Key, aabb: Aabb2D<T>) {
+    pub fn update(&mut self, key: Key<I>, aabb: Aabb2D<T>) {
         if let Some(e) = self.entry_mut(key) {
             if e.mark.is_none() {
                 e.prev_aabb = Some(e.aabb);
             }
             e.aabb = aabb;
             e.mark = Some(match e.mark {
-                Some(Mark::Added) => Mark::Added,
+                Some(added @ Mark::Added { .. }) => added,
                 _ => Mark::Updated,
             });
         }
     }
 
     /// Remove an existing AABB.
-    pub fn remove(&mut self, key: Key) {
+    pub fn remove(&mut self, key: Key<I>) {
         if let Some(e) = self.entry_mut(key) {
-            if matches!(e.mark, Some(Mark::Added)) {
+            if matches!(e.mark, Some(Mark::Added { .. })) {
                 self.entries[key.idx()] = None;
-                self.free_list.push(key.idx());
+                self.free_list.push((key.idx(), key.1));
             } else {
                 e.mark = Some(Mark::Removed);
             }
         }
     }
 
-    /// Clear the index (without reporting damage).
+    /// Remove every live entry for which `f` returns `false`.
+    ///
+    /// Each rejected entry is removed as by [`remove`](Self::remove): committed entries are
+    /// reported in the next commit's `removed` damage, and uncommitted inserts vanish
+    /// without damage. Entries already removed since the last commit are not visited.
+    pub fn retain<F: FnMut(Key<I>, &Aabb2D<T>, &P) -> bool>(&mut self, mut f: F) {
+        let doomed: Vec<Key<I>> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| {
+                let e = e.as_ref()?;
+                let key = Key::new(i, e.generation);
+                (!matches!(e.mark, Some(Mark::Removed)) && !f(key, &e.aabb, &e.payload))
+                    .then_some(key)
+            })
+            .collect();
+        for key in doomed {
+            self.remove(key);
+        }
+    }
+
+    /// Enable or disable damage accumulation across commits.
+    ///
+    /// While enabled, every [`commit`](Self::commit) also merges its changes into an
+    /// internal buffer, retrieved with [`take_accumulated_damage`](Self::take_accumulated_damage).
+    /// Changes to the same entry are coalesced: an entry moved A→B and then B→C is
+    /// reported as a single A→C move, and an entry added and removed within the span
+    /// is not reported at all. Disabling discards anything not yet taken.
+    pub fn set_accumulate_damage(&mut self, enabled: bool) {
+        if !enabled {
+            self.accumulated = None;
+        } else if self.accumulated.is_none() {
+            self.accumulated = Some(BTreeMap::new());
+        }
+    }
+
+    /// Take the damage accumulated since the last call (or since accumulation was enabled).
+    ///
+    /// Returns empty damage when accumulation is disabled. Entries are reported in slot order.
+    pub fn take_accumulated_damage(&mut self) -> Damage<T> {
+        let mut dmg = Damage::default();
+        let Some(acc) = self.accumulated.as_mut() else {
+            return dmg;
+        };
+        for change in core::mem::take(acc).into_values() {
+            match change {
+                Change::Added(a) => dmg.added.push(a),
+                Change::Removed(a) => dmg.removed.push(a),
+                Change::Moved(a, b) => dmg.moved.push((a, b)),
+            }
+        }
+        dmg
+    }
+
+    /// Clear the index (without reporting damage) and release its storage.
     pub fn clear(&mut self) {
+        self.clear_retain();
+        self.entries.shrink_to_fit();
+        self.free_list.shrink_to_fit();
+        self.backend.clear();
+    }
+
+    /// Clear the index like [`clear`](Self::clear), keeping allocations for reuse.
+    ///
+    /// Meant for transient indexes rebuilt every frame: the entry table keeps its
+    /// allocation and the backend is reset with [`Backend::clear_retain`], so refilling up
+    /// to the previous size does not grow them.
+    pub fn clear_retain(&mut self) {
         self.entries.clear();
         self.free_list.clear();
-        self.backend.clear();
+        self.slots_remapped = false;
+        if let Some(acc) = self.accumulated.as_mut() {
+            acc.clear();
+        }
+        self.backend.clear_retain();
+    }
+
+    /// Rebuild the backend from scratch out of the committed entries.
+    ///
+    /// Tree backends degrade after long runs of updates; rebuilding restores a tight layout.
+    /// Backends with a bulk builder load all entries at once; see [`Backend::bulk_load`].
+    /// Pending changes stay pending and are applied by the next [`commit`](Self::commit).
+    /// Query results are unchanged and no damage is reported. Every entry keeps its slot,
+    /// so keys (and slot-keyed caches) stay valid.
+    pub fn rebuild(&mut self) {
+        let pairs: Vec<(usize, Aabb2D<T>)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| match e {
+                // Updated entries are loaded at their new box; commit's update is a no-op move.
+                Some(e) if !matches!(e.mark, Some(Mark::Added { .. })) => Some((i, e.aabb)),
+                _ => None,
+            })
+            .collect();
+        self.backend.bulk_load(&pairs);
+        self.maintenance = Maintenance::default();
+    }
+
+    /// Tighten backend node bounds without restructuring; see [`Backend::refit`].
+    ///
+    /// Much cheaper than [`rebuild`](Self::rebuild). Query results are unchanged.
+    pub fn refit(&mut self) {
+        self.backend.refit();
+    }
+
+    /// Let the backend release storage held for removed entries; see [`Backend::compact`].
+    ///
+    /// Unlike [`rebuild`](Self::rebuild) this keeps the backend's structure, so it is cheap
+    /// and query results (including their order) are unchanged. Only backend-internal nodes
+    /// move; entry slots, and so keys, are never remapped. To pack the slots themselves, see
+    /// [`compact_slots`](Self::compact_slots).
+    pub fn compact(&mut self) {
+        self.backend.compact();
+    }
+
+    /// Move live entries into the lowest free slots so they occupy a dense prefix.
+    ///
+    /// Unlike every other maintenance operation this remaps slots: each moved entry gets a
+    /// new [`Key`] and its old key goes stale. Translate held keys (and slot-keyed caches)
+    /// with the returned [`SlotRemap`]; the next [`commit`](Self::commit) reports
+    /// [`Damage::slots_remapped`]. Pending changes stay pending. If anything moved, the
+    /// backend is rebuilt as with [`rebuild`](Self::rebuild), so this is O(n).
+    pub fn compact_slots(&mut self) -> SlotRemap<I> {
+        // Lowest hole last, so it is filled first.
+        let mut holes = core::mem::take(&mut self.free_list);
+        holes.sort_unstable_by_key(|&(i, _)| core::cmp::Reverse(i));
+        let mut moves = Vec::new();
+        let mut top = self.entries.len();
+        while let Some(&(hole, last)) = holes.last() {
+            while top > 0 && self.entries[top - 1].is_none() {
+                top -= 1;
+            }
+            if top <= hole {
+                break;
+            }
+            holes.pop();
+            top -= 1;
+            let mut entry = self.entries[top].take().expect("top slot is occupied");
+            let old = Key::new(top, entry.generation);
+            // Bump past the hole's last generation, as a fresh insert would.
+            entry.generation = last.next_generation();
+            let new = Key::new(hole, entry.generation);
+            if let Some(acc) = self.accumulated.as_mut()
+                && let Some(change) = acc.remove(&(top, old.1))
+            {
+                acc.insert((hole, new.1), change);
+            }
+            self.entries[hole] = Some(entry);
+            self.free_list.push((top, old.1));
+            moves.push((old, new));
+        }
+        self.free_list.extend(holes);
+        // Highest slot first, so inserts keep filling from the bottom.
+        self.free_list
+            .sort_unstable_by_key(|&(i, _)| core::cmp::Reverse(i));
+        if moves.is_empty() {
+            return SlotRemap { moves };
+        }
+        moves.sort_unstable_by_key(|(old, _)| old.idx());
+        let remap = SlotRemap { moves };
+        for entry in self.entries.iter_mut().flatten() {
+            if let Some(Mark::Added { hint: Some(h) }) = entry.mark.as_mut()
+                && let Ok(i) = remap.moves.binary_search_by_key(h, |(k, _)| k.idx())
+            {
+                *h = remap.moves[i].1.idx();
+            }
+        }
+        self.rebuild();
+        self.slots_remapped = true;
+        remap
+    }
+
+    /// Do a bounded amount of backend upkeep; returns whether more is pending.
+    ///
+    /// Meant to be called once per frame, this spreads the work of [`rebuild`](Self::rebuild)
+    /// and [`compact`](Self::compact) over many calls:
+    ///
+    /// - Once as many changes have been committed as half the slot count, a sweep starts
+    ///   that removes and reinserts committed entries in slot order, at most
+    ///   `max_reinserts` per call, so tree backends re-place them against the current
+    ///   layout.
+    /// - After the sweep, once [`Backend::arena_usage`] shows more than one dead arena node
+    ///   per four live ones, a compaction starts that reclaims at most `max_compact_nodes`
+    ///   dead nodes per call with [`Backend::compact_step`] until none are left.
+    ///
+    /// Checking for fragmentation is O(1), so an idle call costs next to nothing. Queries
+    /// stay correct throughout and keys are never remapped; pending changes are left to
+    /// [`commit`](Self::commit).
+    pub fn maintain(&mut self, budget: MaintenanceBudget) -> bool {
+        let m = &mut self.maintenance;
+        if m.sweep.is_none() && m.churn > 0 && m.churn >= self.entries.len() / 2 {
+            m.churn = 0;
+            m.sweep = Some(0);
+        }
+        if let Some(start) = m.sweep {
+            let (mut i, mut done) = (start, 0);
+            while i < self.entries.len() && done < budget.max_reinserts {
+                if let Some(e) = &self.entries[i]
+                    && e.mark.is_none()
+                {
+                    self.backend.remove(i);
+                    self.backend.insert(i, e.aabb);
+                    done += 1;
+                }
+                i += 1;
+            }
+            m.sweep = (i < self.entries.len()).then_some(i);
+            if m.sweep.is_some() {
+                return true;
+            }
+        }
+        if !m.compacting {
+            let (live, dead) = self.backend.arena_usage();
+            if dead <= live / 4 {
+                return false;
+            }
+            m.compacting = true;
+        }
+        m.compacting = self.backend.compact_step(budget.max_compact_nodes.max(1));
+        m.compacting
+    }
+
+    /// Replace the backend with `backend` and rebuild it from the entries; returns the old one.
+    ///
+    /// Keys stay valid and pending changes stay pending, as with [`rebuild`](Self::rebuild).
+    /// This is O(n) in the number of entries, so switch sparingly.
+    pub fn replace_backend(&mut self, backend: B) -> B {
+        let old = core::mem::replace(&mut self.backend, backend);
+        self.rebuild();
+        old
     }
 
     /// Apply pending changes and compute batched damage. Also synchronizes backend state.
     pub fn commit(&mut self) -> Damage<T> {
-        let mut dmg = Damage::default();
+        let mut dmg = Damage {
+            slots_remapped: core::mem::take(&mut self.slots_remapped),
+            ..Damage::default()
+        };
         for i in 0..self.entries.len() {
+            if self.entries[i].as_ref().is_some_and(|e| e.mark.is_some()) {
+                self.maintenance.churn = self.maintenance.churn.saturating_add(1);
+            }
             let Some(entry) = self.entries[i].as_mut() else {
                 continue;
             };
-            match entry.mark.take() {
-                Some(Mark::Added) => {
-                    self.backend.insert(i, entry.aabb);
+            let generation = entry.generation;
+            let change = match entry.mark.take() {
+                Some(Mark::Added { hint }) => {
+                    match hint {
+                        Some(h) => self.backend.insert_near(i, entry.aabb, h),
+                        None => self.backend.insert(i, entry.aabb),
+                    }
                     dmg.added.push(entry.aabb);
+                    Some(Change::Added(entry.aabb))
                 }
                 Some(Mark::Removed) => {
                     self.backend.remove(i);
                     dmg.removed.push(entry.aabb);
-                    let generation = entry.generation;
+                    let aabb = entry.aabb;
                     self.entries[i] = None;
-                    self.free_list.push(i);
-                    let _ = generation;
+                    self.free_list.push((i, generation));
+                    Some(Change::Removed(aabb))
                 }
                 Some(Mark::Updated) => {
                     self.backend.update(i, entry.aabb);
@@ -187,210 +974,2791 @@ where
                         && prev != entry.aabb
                     {
                         dmg.moved.push((prev, entry.aabb));
+                        Some(Change::Moved(prev, entry.aabb))
+                    } else {
+                        None
                     }
                 }
-                None => {}
+                None => None,
+            };
+            if let (Some(acc), Some(change)) = (self.accumulated.as_mut(), change) {
+                let merged = match acc.remove(&(i, generation)) {
+                    Some(prev) => prev.then(change),
+                    None => Some(change),
+                };
+                if let Some(merged) = merged {
+                    acc.insert((i, generation), merged);
+                }
             }
         }
         dmg
     }
 
     /// Query for entries whose AABB contains the point.
-    pub fn query_point(&self, x: T, y: T) -> impl Iterator<Item = (Key, P)> + '_ {
+    pub fn query_point(&self, x: T, y: T) -> impl Iterator<Item = (Key<I>, P)> + '_ {
         let mut out = Vec::new();
         self.visit_point(x, y, |k, p| out.push((k, p)));
         out.into_iter()
     }
 
-    /// Visit entries whose AABB contains the point (does not allocate result storage).
+    /// Write entries whose AABB contains the point into `out`, without allocating.
     ///
-    /// Calls `f(key, payload)` for each match. The order is backend-dependent.
-    pub fn visit_point<F: FnMut(Key, P)>(&self, x: T, y: T, mut f: F) {
-        self.backend.visit_point(x, y, |i| {
-            if let Some(Some(e)) = self.entries.get(i) {
-                f(Key::new(i, e.generation), e.payload);
+    /// Returns the total number of matches; only the first `out.len()` are written, so a
+    /// return value larger than `out.len()` means the result was truncated. See
+    /// [`Backend::query_point_into_buf`] for when the traversal itself is allocation-free.
+    pub fn query_point_buf(&self, x: T, y: T, out: &mut [(Key<I>, P)]) -> usize {
+        let mut n = 0;
+        self.visit_point(x, y, |k, p| {
+            if let Some(o) = out.get_mut(n) {
+                *o = (k, p);
             }
+            n += 1;
         });
+        n
     }
 
-    /// Query for entries whose AABB intersects the given rectangle.
-    pub fn query_rect(&self, rect: Aabb2D<T>) -> impl Iterator<Item = (Key, P)> + '_ {
-        let mut out = Vec::new();
+    /// Clear `out` and fill it with the entries whose AABB contains the point.
+    ///
+    /// This is the allocation-free path for per-frame queries: reusing one `out` across
+    /// calls only allocates when a result outgrows its capacity. The order is
+    /// backend-dependent.
+    pub fn query_point_into(&self, x: T, y: T, out: &mut Vec<(Key<I>, P)>) {
+        out.clear();
+        self.visit_point(x, y, |k, p| out.push((k, p)));
+    }
+
+    /// Clear `out` and fill it with the entries whose AABB intersects `rect`.
+    ///
+    /// Like [`query_point_into`](Self::query_point_into), reusing `out` keeps amortized
+    /// allocations at zero across frames. The order is backend-dependent.
+    pub fn query_rect_into(&self, rect: Aabb2D<T>, out: &mut Vec<(Key<I>, P)>) {
+        out.clear();
         self.visit_rect(rect, |k, p| out.push((k, p)));
-        out.into_iter()
     }
 
-    /// Visit entries whose AABB intersects the given rectangle (does not allocate result storage).
+    /// Visit entries whose AABB contains the point (does not allocate result storage).
     ///
     /// Calls `f(key, payload)` for each match. The order is backend-dependent.
-    pub fn visit_rect<F: FnMut(Key, P)>(&self, rect: Aabb2D<T>, mut f: F) {
-        self.backend.visit_rect(rect, |i| {
+    pub fn visit_point<F: FnMut(Key<I>, P)>(&self, x: T, y: T, mut f: F) {
+        self.backend.visit_point(x, y, |i| {
             if let Some(Some(e)) = self.entries.get(i) {
                 f(Key::new(i, e.generation), e.payload);
             }
         });
     }
 
-    fn entry_mut(&mut self, key: Key) -> Option<&mut Entry<T, P>> {
-        let e = self.entries.get_mut(key.idx())?.as_mut()?;
-        if e.generation != key.1 {
-            return None;
+    /// Query for at most `k` entries intersecting `rect`, in traversal order.
+    ///
+    /// Built on [`query_rect_visit`](Self::query_rect_visit), so the built-in backends stop
+    /// traversing once `k` hits are collected. Which `k` entries are returned is
+    /// backend-dependent.
+    pub fn query_rect_limit(&self, rect: Aabb2D<T>, k: usize) -> Vec<(Key<I>, P)> {
+        let mut out = Vec::new();
+        if k == 0 {
+            return out;
         }
-        Some(e)
+        let _ = self.query_rect_visit(rect, |key, p| {
+            out.push((key, p));
+            out.len() < k
+        });
+        out
     }
-}
-
-// Debug is derived above; backends implement Debug with concise, partial output.
 
-/// Default index using a flat vector backend.
-pub type Index<T, P> = IndexGeneric<T, P, crate::backends::flatvec::FlatVec<T>>;
-
-impl<T: Copy + PartialOrd + Debug, P: Copy + Debug> Default for Index<T, P> {
+    /// Query for entries whose AABB strictly contains the point, excluding points on an edge.
+    ///
+    /// Useful when edges act as handles that belong to no interior. The order is
+    /// backend-dependent.
+    pub fn query_point_strict(&self, x: T, y: T) -> impl Iterator<Item = (Key<I>, P)> + '_ {
+        let mut out = Vec::new();
+        self.backend.visit_point(x, y, |i| {
+            if let Some(Some(e)) = self.entries.get(i)
+                && e.aabb.contains_point_strict(x, y)
+            {
+                out.push((Key::new(i, e.generation), e.payload));
+            }
+        });
+        out.into_iter()
+    }
+
+    /// Visit entries whose AABB contains the point until `f` returns `false`.
+    ///
+    /// Nothing is allocated. Returns `true` if every match was visited and `false` if `f`
+    /// stopped early; see [`Backend::query_point_visit`]. The order is backend-dependent.
+    pub fn query_point_visit<F: FnMut(Key<I>, P) -> bool>(&self, x: T, y: T, mut f: F) -> bool {
+        self.backend
+            .query_point_visit(x, y, |i| match self.entries.get(i) {
+                Some(Some(e)) => f(Key::new(i, e.generation), e.payload),
+                _ => true,
+            })
+    }
+
+    /// Visit entries whose AABB intersects `rect` until `f` returns `false`.
+    ///
+    /// Nothing is allocated. Returns `true` if every match was visited and `false` if `f`
+    /// stopped early; see [`Backend::query_rect_visit`]. The order is backend-dependent.
+    pub fn query_rect_visit<F: FnMut(Key<I>, P) -> bool>(&self, rect: Aabb2D<T>, mut f: F) -> bool {
+        self.backend
+            .query_rect_visit(rect, |i| match self.entries.get(i) {
+                Some(Some(e)) => f(Key::new(i, e.generation), e.payload),
+                _ => true,
+            })
+    }
+
+    /// Whether any entry's AABB intersects `rect`.
+    ///
+    /// Stops at the first hit via [`query_rect_visit`](Self::query_rect_visit), so tree
+    /// backends skip the rest of the traversal.
+    pub fn any_in_rect(&self, rect: Aabb2D<T>) -> bool {
+        !self.query_rect_visit(rect, |_, _| false)
+    }
+
+    /// Whether any entry's AABB contains the point; stops at the first hit like
+    /// [`any_in_rect`](Self::any_in_rect).
+    pub fn any_at_point(&self, x: T, y: T) -> bool {
+        !self.query_point_visit(x, y, |_, _| false)
+    }
+
+    /// Query for entries whose AABB intersects the given rectangle.
+    pub fn query_rect(&self, rect: Aabb2D<T>) -> impl Iterator<Item = (Key<I>, P)> + '_ {
+        let mut out = Vec::new();
+        self.visit_rect(rect, |k, p| out.push((k, p)));
+        out.into_iter()
+    }
+
+    /// Query for entries intersecting `rect` whose user flags share a bit with `mask`.
+    ///
+    /// Entries with no flags set never match. The order is backend-dependent.
+    pub fn query_rect_flags(
+        &self,
+        rect: Aabb2D<T>,
+        mask: u8,
+    ) -> impl Iterator<Item = (Key<I>, P)> + '_ {
+        let mut out = Vec::new();
+        self.backend.visit_rect(rect, |i| {
+            if let Some(Some(e)) = self.entries.get(i)
+                && e.flags & mask != 0
+            {
+                out.push((Key::new(i, e.generation), e.payload));
+            }
+        });
+        out.into_iter()
+    }
+
+    /// Query for entries intersecting `rect`, projecting each hit through `f` as it is found.
+    ///
+    /// Equivalent to `query_rect(rect).map(|(k, p)| f(k, p))` without the intermediate
+    /// `(Key, P)` buffer. The order is backend-dependent.
+    pub fn query_rect_map<R>(
+        &self,
+        rect: Aabb2D<T>,
+        f: impl Fn(Key<I>, P) -> R,
+    ) -> impl Iterator<Item = R> {
+        let mut out = Vec::new();
+        self.visit_rect(rect, |k, p| out.push(f(k, p)));
+        out.into_iter()
+    }
+
+    /// Query for entries intersecting `rect`, visiting at most `max_nodes_visited` tree nodes.
+    ///
+    /// Returns the hits and a `complete` flag. When `complete` is false the traversal was cut
+    /// short and the hits are a subset of what [`query_rect`](Self::query_rect) returns. Useful
+    /// under a frame budget. Backends without a node hierarchy (e.g. `FlatVec`) ignore the budget.
+    pub fn query_rect_budgeted(
+        &self,
+        rect: Aabb2D<T>,
+        max_nodes_visited: usize,
+    ) -> (Vec<(Key<I>, P)>, bool) {
+        let mut out = Vec::new();
+        let complete = self
+            .backend
+            .visit_rect_budgeted(rect, max_nodes_visited, |i| {
+                if let Some(Some(e)) = self.entries.get(i) {
+                    out.push((Key::new(i, e.generation), e.payload));
+                }
+            });
+        (out, complete)
+    }
+
+    /// Query many points at once; `result[i]` holds the entries containing `points[i]`.
+    ///
+    /// Tree backends share traversal work between clustered points (see
+    /// [`Backend::visit_points`]); scattered points cost about as much as separate
+    /// [`query_point`](Self::query_point) calls. The order within each list is backend-dependent.
+    pub fn query_points(&self, points: &[(T, T)]) -> Vec<Vec<(Key<I>, P)>> {
+        let mut out: Vec<Vec<(Key<I>, P)>> = points.iter().map(|_| Vec::new()).collect();
+        self.backend.visit_points(points, |pi, i| {
+            if let Some(Some(e)) = self.entries.get(i) {
+                out[pi].push((Key::new(i, e.generation), e.payload));
+            }
+        });
+        out
+    }
+
+    /// Returns true if the union of intersecting entries accepted by `opaque` covers `rect`.
+    ///
+    /// Meant for occlusion culling: if this returns true, nothing behind those entries
+    /// is visible within `rect`. The answer is conservative: it never reports coverage
+    /// that is not there, but it returns false for rects with no area or NaN bounds, and
+    /// it only sees committed entries, so pending inserts do not count yet.
+    ///
+    /// Coverage is tested on the grid formed by the clipped entry edges, which costs
+    /// O(k³) in the number `k` of opaque intersecting entries.
+    pub fn is_rect_covered(&self, rect: Aabb2D<T>, opaque: impl Fn(&P) -> bool) -> bool {
+        if !(rect.min_x < rect.max_x && rect.min_y < rect.max_y) {
+            return false;
+        }
+        let mut boxes: Vec<Aabb2D<T>> = Vec::new();
+        self.backend.visit_rect(rect, |i| {
+            if let Some(Some(e)) = self.entries.get(i)
+                && opaque(&e.payload)
+            {
+                boxes.push(e.aabb.intersect(&rect));
+            }
+        });
+        if boxes.iter().any(|b| b.contains_aabb(&rect)) {
+            return true;
+        }
+        // Split `rect` along every box edge; each resulting cell is either inside
+        // some box or not, so checking cells is exact.
+        let edges = |lo: T, hi: T, f: fn(&Aabb2D<T>) -> (T, T)| {
+            let mut v = Vec::with_capacity(2 * boxes.len() + 2);
+            v.push(lo);
+            v.push(hi);
+            for b in &boxes {
+                let (a, z) = f(b);
+                v.extend([a, z]);
+            }
+            v.sort_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+            v.dedup_by(|a, b| a == b);
+            v
+        };
+        let xs = edges(rect.min_x, rect.max_x, |b| (b.min_x, b.max_x));
+        let ys = edges(rect.min_y, rect.max_y, |b| (b.min_y, b.max_y));
+        xs.windows(2).all(|x| {
+            ys.windows(2).all(|y| {
+                boxes.iter().any(|b| {
+                    b.min_x <= x[0] && x[1] <= b.max_x && b.min_y <= y[0] && y[1] <= b.max_y
+                })
+            })
+        })
+    }
+
+    /// Query for entries whose AABB intersects the rectangle, grouped by spatial locality.
+    ///
+    /// Hits stored in the same backend leaf or cell share a [`GroupId`]; see
+    /// [`Backend::query_rect_grouped`]. [`FlatVec`](crate::FlatVec) reports a single group.
+    pub fn query_rect_grouped(
+        &self,
+        rect: Aabb2D<T>,
+    ) -> impl Iterator<Item = (GroupId, Vec<(Key<I>, P)>)> + '_ {
+        self.backend
+            .query_rect_grouped(rect)
+            .map(move |(group, slots)| {
+                let hits = slots
+                    .into_iter()
+                    .filter_map(|i| match self.entries.get(i) {
+                        Some(Some(e)) => Some((Key::new(i, e.generation), e.payload)),
+                        _ => None,
+                    })
+                    .collect();
+                (group, hits)
+            })
+    }
+
+    /// Visit entries whose AABB intersects the given rectangle (does not allocate result storage).
+    ///
+    /// Calls `f(key, payload)` for each match. The order is backend-dependent.
+    pub fn visit_rect<F: FnMut(Key<I>, P)>(&self, rect: Aabb2D<T>, mut f: F) {
+        self.backend.visit_rect(rect, |i| {
+            if let Some(Some(e)) = self.entries.get(i) {
+                f(Key::new(i, e.generation), e.payload);
+            }
+        });
+    }
+
+    /// Return the traversal work of point and rect queries since the last call, and reset it.
+    ///
+    /// Counts come from the backend (see [`Backend::take_query_stats`]); every query path
+    /// built on [`visit_point`](Self::visit_point) or [`visit_rect`](Self::visit_rect) is
+    /// included. Requires the `stats` feature.
+    #[cfg(feature = "stats")]
+    pub fn take_query_stats(&self) -> crate::QueryStats {
+        self.backend.take_query_stats()
+    }
+
+    /// Approximate heap bytes held by the index, for comparing backends.
+    ///
+    /// Counts the entry table, free list and accumulated damage (by `Vec` capacity or per
+    /// map entry) plus [`Backend::memory_bytes`]. Payloads count only their inline size;
+    /// allocations they own are not included, nor is allocator overhead.
+    pub fn memory_bytes(&self) -> usize {
+        let accumulated = self
+            .accumulated
+            .as_ref()
+            .map_or(0, |acc| acc.len() * size_of::<((usize, I), Change<T>)>());
+        vec_bytes(&self.entries)
+            + vec_bytes(&self.free_list)
+            + accumulated
+            + self.backend.memory_bytes()
+    }
+
+    /// Describe the backend's structure; see [`Backend::stats`].
+    ///
+    /// Entries still pending a [`commit`](Self::commit) are not reflected.
+    pub fn stats(&self) -> crate::BackendStats {
+        self.backend.stats()
+    }
+
+    /// Create a cursor for querying a sequence of growing rectangles.
+    ///
+    /// See [`RectQueryCursor`] for details.
+    pub fn rect_query_cursor(&self) -> RectQueryCursor<'_, T, P, B, I> {
+        RectQueryCursor {
+            index: self,
+            prev: None,
+        }
+    }
+
+    fn entry(&self, key: Key<I>) -> Option<&Entry<T, P, I>> {
+        self.entries
+            .get(key.idx())?
+            .as_ref()
+            .filter(|e| e.generation == key.1)
+    }
+
+    fn entry_mut(&mut self, key: Key<I>) -> Option<&mut Entry<T, P, I>> {
+        let e = self.entries.get_mut(key.idx())?.as_mut()?;
+        if e.generation != key.1 {
+            return None;
+        }
+        Some(e)
+    }
+}
+
+// Debug is derived above; backends implement Debug with concise, partial output.
+
+/// Stateful rectangle query that reports only newly entered entries.
+///
+/// Intended for reveal animations and similar cases where the query rectangle grows
+/// each frame. When the rectangle passed to [`RectQueryCursor::advance`] contains the
+/// previous one, entries that already intersected the previous rectangle are skipped,
+/// so each entry is reported once. If the new rectangle does not contain the previous
+/// one, the cursor starts over and reports every intersecting entry.
+///
+/// Created with [`IndexGeneric::rect_query_cursor`].
+#[derive(Debug)]
+pub struct RectQueryCursor<'a, T, P, B, I = u32>
+where
+    T: Copy + PartialOrd + Debug,
+    P: Copy + Debug,
+    B: Backend<T>,
+    I: KeyIndex,
+{
+    index: &'a IndexGeneric<T, P, B, I>,
+    prev: Option<Aabb2D<T>>,
+}
+
+impl<T, P, B, I> RectQueryCursor<'_, T, P, B, I>
+where
+    T: Copy + PartialOrd + Debug,
+    P: Copy + Debug,
+    B: Backend<T>,
+    I: KeyIndex,
+{
+    /// Query `rect` and return the entries that were not reported for the previous rectangle.
+    ///
+    /// The order is backend-dependent.
+    pub fn advance(&mut self, rect: Aabb2D<T>) -> Vec<(Key<I>, P)> {
+        let prev = self.prev.filter(|p| rect.contains_aabb(p));
+        let entries = &self.index.entries;
+        let mut out = Vec::new();
+        self.index.backend.visit_rect(rect, |i| {
+            if let Some(Some(e)) = entries.get(i)
+                && prev.is_none_or(|p| e.aabb.intersect(&p).is_empty())
+            {
+                out.push((Key::new(i, e.generation), e.payload));
+            }
+        });
+        self.prev = Some(rect);
+        out
+    }
+
+    /// The rectangle passed to the most recent [`RectQueryCursor::advance`], if any.
+    pub fn previous(&self) -> Option<Aabb2D<T>> {
+        self.prev
+    }
+
+    /// Forget the previous rectangle; the next advance reports all intersecting entries.
+    pub fn reset(&mut self) {
+        self.prev = None;
+    }
+}
+
+impl<T, P, B, I> IndexGeneric<T, P, B, I>
+where
+    T: Scalar,
+    P: Copy + Debug,
+    B: Backend<T>,
+    I: KeyIndex,
+{
+    /// Return up to `k` entries nearest to the point, with their squared distance.
+    ///
+    /// Distances are measured from the point to each entry's AABB and are zero when the
+    /// point is inside. Results are ordered by distance, with ties in ascending slot order.
+    /// Tree backends answer with a best-first traversal; other backends fall back to a
+    /// scan of the committed entries and a partial sort.
+    pub fn query_knn(&self, x: T, y: T, k: usize) -> Vec<(Key<I>, P, T::Acc)> {
+        if k == 0 {
+            return Vec::new();
+        }
+        let slots = self.backend.query_knn(x, y, k).unwrap_or_else(|| {
+            let mut all: Vec<(usize, T::Acc)> = self
+                .entries
+                .iter()
+                .enumerate()
+                .filter_map(|(i, e)| {
+                    let e = e.as_ref()?;
+                    (!matches!(e.mark, Some(Mark::Added { .. })))
+                        .then(|| (i, point_dist2(&e.aabb, x, y)))
+                })
+                .collect();
+            let by_dist = |a: &(usize, T::Acc), b: &(usize, T::Acc)| {
+                a.1.partial_cmp(&b.1)
+                    .unwrap_or(core::cmp::Ordering::Equal)
+                    .then(a.0.cmp(&b.0))
+            };
+            if all.len() > k {
+                all.select_nth_unstable_by(k - 1, by_dist);
+                all.truncate(k);
+            }
+            all.sort_unstable_by(by_dist);
+            all
+        });
+        slots
+            .into_iter()
+            .filter_map(|(i, d)| {
+                let e = self.entries.get(i)?.as_ref()?;
+                Some((Key::new(i, e.generation), e.payload, d))
+            })
+            .collect()
+    }
+
+    /// Query for entries intersecting `rect`, nearest to `origin` first.
+    ///
+    /// Distances are squared distances from `origin` to the closest point of each AABB,
+    /// as in [`query_knn`](Self::query_knn), so entries containing `origin` come first.
+    /// Ties fall back to ascending slot order, which makes the result order stable.
+    pub fn query_rect_ordered(&self, rect: Aabb2D<T>, origin: (T, T)) -> Vec<(Key<I>, P)> {
+        let mut hits: Vec<(Key<I>, P, T::Acc)> = Vec::new();
+        self.backend.visit_rect(rect, |i| {
+            if let Some(Some(e)) = self.entries.get(i) {
+                let d = point_dist2(&e.aabb, origin.0, origin.1);
+                hits.push((Key::new(i, e.generation), e.payload, d));
+            }
+        });
+        hits.sort_unstable_by(|a, b| {
+            a.2.partial_cmp(&b.2)
+                .unwrap_or(core::cmp::Ordering::Equal)
+                .then(a.0.idx().cmp(&b.0.idx()))
+        });
+        hits.into_iter().map(|(k, p, _)| (k, p)).collect()
+    }
+
+    /// Return the entry nearest to the point, or `None` if the index is empty.
+    ///
+    /// Like [`query_knn`](Self::query_knn) with `k = 1`, but backed by
+    /// [`Backend::nearest`], which prunes instead of keeping a frontier.
+    pub fn query_nearest(&self, x: T, y: T) -> Option<(Key<I>, P)> {
+        let Some((i, _)) = self.backend.nearest(x, y) else {
+            // Backends without nearest-neighbor support leave it to the index's scan.
+            return self.query_knn(x, y, 1).first().map(|&(k, p, _)| (k, p));
+        };
+        let e = self.entries.get(i)?.as_ref()?;
+        Some((Key::new(i, e.generation), e.payload))
+    }
+
+    /// Query for entries intersecting the box `[x - rx, y - ry] × [x + rx, y + ry]`.
+    ///
+    /// Handy for proximity hit-testing around a pointer. Integer coordinates saturate at
+    /// the scalar's range instead of wrapping; negative radii count as zero, and a zero
+    /// radius on both axes is a [`query_point`](Self::query_point).
+    pub fn query_around_point(
+        &self,
+        x: T,
+        y: T,
+        rx: T,
+        ry: T,
+    ) -> impl Iterator<Item = (Key<I>, P)> + '_ {
+        let (rx, ry) = (T::max_zero(rx), T::max_zero(ry));
+        let mut out = Vec::new();
+        if rx == T::zero() && ry == T::zero() {
+            self.visit_point(x, y, |k, p| out.push((k, p)));
+        } else {
+            let rect = Aabb2D::new(T::sub(x, rx), T::sub(y, ry), T::add(x, rx), T::add(y, ry));
+            self.visit_rect(rect, |k, p| out.push((k, p)));
+        }
+        out.into_iter()
+    }
+
+    /// Query for entries whose AABB lies within `radius` of `(cx, cy)`.
+    ///
+    /// Candidates come from a rect query over the circle's bounding box and are then
+    /// checked by comparing the squared distance from the center to the closest point of
+    /// each AABB against `radius * radius`, in `T::Acc` so integer scalars do not overflow.
+    /// Boxes touching the circle count. The order is backend-dependent.
+    pub fn query_circle(&self, cx: T, cy: T, radius: T) -> impl Iterator<Item = (Key<I>, P)> + '_ {
+        let bounds = Aabb2D::new(
+            T::sub(cx, radius),
+            T::sub(cy, radius),
+            T::add(cx, radius),
+            T::add(cy, radius),
+        );
+        let r2 = T::widen(radius) * T::widen(radius);
+        let mut out = Vec::new();
+        self.backend.visit_rect(bounds, |i| {
+            if let Some(Some(e)) = self.entries.get(i)
+                && point_dist2(&e.aabb, cx, cy) <= r2
+            {
+                out.push((Key::new(i, e.generation), e.payload));
+            }
+        });
+        out.into_iter()
+    }
+}
+
+impl<P: Copy + Debug, B: Backend<f64>, I: KeyIndex> IndexGeneric<f64, P, B, I> {
+    /// Find the nearest entry from `from` in direction `dir`, for directional navigation.
+    ///
+    /// Only entries whose center lies strictly in the half-plane in front of `from`'s
+    /// center (along `dir`) are considered. Candidates are scored by their distance
+    /// projected onto `dir` plus twice their perpendicular offset, so an entry straight
+    /// ahead beats a diagonal one at the same depth. `dir` need not be normalized;
+    /// returns `None` if it is zero or nothing lies in that direction.
+    ///
+    /// Considers committed entries (the same set the queries see) and scans them linearly.
+    pub fn nearest_in_direction(&self, from: Aabb2D<f64>, dir: (f64, f64)) -> Option<(Key<I>, P)> {
+        const PERPENDICULAR_PENALTY: f64 = 2.0;
+        let center = |a: &Aabb2D<f64>| (0.5 * (a.min_x + a.max_x), 0.5 * (a.min_y + a.max_y));
+        let (cx, cy) = center(&from);
+        let mut best: Option<(f64, Key<I>, P)> = None;
+        for (i, e) in self.entries.iter().enumerate() {
+            let Some(e) = e else { continue };
+            if matches!(e.mark, Some(Mark::Added { .. })) {
+                continue;
+            }
+            let (ex, ey) = center(&e.aabb);
+            let (vx, vy) = (ex - cx, ey - cy);
+            // Both terms are scaled by |dir|, which does not change the ordering.
+            let along = vx * dir.0 + vy * dir.1;
+            if along <= 0.0 {
+                continue;
+            }
+            let score = along + PERPENDICULAR_PENALTY * (vx * dir.1 - vy * dir.0).abs();
+            if best.is_none_or(|(b, _, _)| score < b) {
+                best = Some((score, Key::new(i, e.generation), e.payload));
+            }
+        }
+        best.map(|(_, k, p)| (k, p))
+    }
+
+    /// Query for entries whose AABB lies within `radius` of the segment `(x0, y0)–(x1, y1)`.
+    ///
+    /// This is a capsule (thick segment) query, e.g. for a stroke with width `2 * radius`.
+    /// Candidates come from the segment's bounding box inflated by `radius` and are then
+    /// checked with an exact box-to-segment distance. The order is backend-dependent.
+    pub fn query_capsule(
+        &self,
+        x0: f64,
+        y0: f64,
+        x1: f64,
+        y1: f64,
+        radius: f64,
+    ) -> impl Iterator<Item = (Key<I>, P)> + '_ {
+        let bounds = Aabb2D::new(
+            x0.min(x1) - radius,
+            y0.min(y1) - radius,
+            x0.max(x1) + radius,
+            y0.max(y1) + radius,
+        );
+        let r2 = radius * radius;
+        let mut out = Vec::new();
+        self.backend.visit_rect(bounds, |i| {
+            if let Some(Some(e)) = self.entries.get(i)
+                && segment_box_dist2((x0, y0), (x1, y1), &e.aabb) <= r2
+            {
+                out.push((Key::new(i, e.generation), e.payload));
+            }
+        });
+        out.into_iter()
+    }
+
+    /// Sample the nearest entry on a grid of points covering `region`, e.g. for an influence map.
+    ///
+    /// Samples start at `region`'s min corner and advance by `step` along each axis while
+    /// they stay within `region`. The result holds one row per sample `y` (top to bottom),
+    /// each with one [`query_nearest`](Self::query_nearest) result per sample `x` (left to
+    /// right); entries are `None` only when the index is empty. The sample count per axis
+    /// is fixed up front from the extent of `region`, so a `step` below the precision of
+    /// its coordinates still terminates; a region with a non-finite side has no samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is not positive and finite.
+    pub fn nearest_grid(&self, region: Aabb2D<f64>, step: f64) -> Vec<Vec<Option<Key<I>>>> {
+        assert!(
+            step > 0.0 && step.is_finite(),
+            "nearest_grid step must be positive and finite"
+        );
+        let samples = |min: f64, max: f64| {
+            let count = if min.is_finite() && max.is_finite() && min <= max {
+                usize::try_from(crate::types::floor_i64((max - min) / step)).map_or(0, |n| n + 1)
+            } else {
+                0
+            };
+            (0..count).map(move |i| min + i as f64 * step)
+        };
+        samples(region.min_y, region.max_y)
+            .map(|y| {
+                samples(region.min_x, region.max_x)
+                    .map(|x| self.query_nearest(x, y).map(|(k, _)| k))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Find pairs of entries that overlap by more than `fraction` of their area.
+    ///
+    /// The overlap fraction of a pair is the area of their intersection divided by the
+    /// area of the smaller box, so a box lying entirely inside another scores `1.0`.
+    /// Pairs with a zero-area box are skipped. Each pair is reported once, as
+    /// `(a, b, overlap)` with `a` in an earlier slot than `b`, in slot order.
+    ///
+    /// Considers committed entries and finds candidates with one backend rect query per
+    /// entry.
+    pub fn overlaps_over(&self, fraction: f64) -> impl Iterator<Item = (Key<I>, Key<I>, f64)> + '_ {
+        let box_area =
+            |a: &Aabb2D<f64>| (a.max_x - a.min_x).max(0.0) * (a.max_y - a.min_y).max(0.0);
+        let mut out = Vec::new();
+        for (i, e) in self.entries.iter().enumerate() {
+            let Some(e) = e else { continue };
+            if matches!(e.mark, Some(Mark::Added { .. })) {
+                continue;
+            }
+            let area_a = box_area(&e.aabb);
+            let mut hits = Vec::new();
+            self.backend.visit_rect(e.aabb, |j| {
+                if j > i {
+                    hits.push(j);
+                }
+            });
+            hits.sort_unstable();
+            for j in hits {
+                let Some(Some(f)) = self.entries.get(j) else {
+                    continue;
+                };
+                let smaller = area_a.min(box_area(&f.aabb));
+                if smaller <= 0.0 {
+                    continue;
+                }
+                let overlap = box_area(&e.aabb.intersect(&f.aabb)) / smaller;
+                if overlap > fraction {
+                    out.push((
+                        Key::new(i, e.generation),
+                        Key::new(j, f.generation),
+                        overlap,
+                    ));
+                }
+            }
+        }
+        out.into_iter()
+    }
+
+    /// Borrow the index as a view queried in screen space.
+    ///
+    /// `view` maps world space to screen space and is given as affine coefficients
+    /// `[a, b, c, d, e, f]` (the layout of `kurbo::Affine::as_coeffs`), i.e.
+    /// `x' = a·x + c·y + e` and `y' = b·x + d·y + f`. Query points and rects passed to
+    /// the view are mapped back to world space with the inverse transform. This only
+    /// affects queries: stored geometry is untouched and nothing is rebuilt.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `view` is not invertible.
+    pub fn with_view_transform(&self, view: [f64; 6]) -> ViewTransformed<'_, P, B, I> {
+        let [a, b, c, d, e, f] = view;
+        let det = a * d - b * c;
+        assert!(
+            det != 0.0 && det.is_finite(),
+            "view transform must be invertible"
+        );
+        let inv_det = 1.0 / det;
+        let (ia, ib, ic, id) = (d * inv_det, -b * inv_det, -c * inv_det, a * inv_det);
+        let inverse = [ia, ib, ic, id, -(ia * e + ic * f), -(ib * e + id * f)];
+        ViewTransformed {
+            index: self,
+            view,
+            inverse,
+        }
+    }
+}
+
+/// An [`IndexGeneric`] queried through a world→screen view transform.
+///
+/// Created with [`IndexGeneric::with_view_transform`].
+#[derive(Debug)]
+pub struct ViewTransformed<'a, P, B, I = u32>
+where
+    P: Copy + Debug,
+    B: Backend<f64>,
+    I: KeyIndex,
+{
+    index: &'a IndexGeneric<f64, P, B, I>,
+    view: [f64; 6],
+    inverse: [f64; 6],
+}
+
+impl<P, B, I> ViewTransformed<'_, P, B, I>
+where
+    P: Copy + Debug,
+    B: Backend<f64>,
+    I: KeyIndex,
+{
+    /// The world→screen transform, as affine coefficients.
+    pub fn view_transform(&self) -> [f64; 6] {
+        self.view
+    }
+
+    /// Map a screen-space point to world space.
+    pub fn to_world(&self, x: f64, y: f64) -> (f64, f64) {
+        let [a, b, c, d, e, f] = self.inverse;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    /// Query for entries containing the screen-space point.
+    pub fn query_point(&self, x: f64, y: f64) -> impl Iterator<Item = (Key<I>, P)> + '_ {
+        let (x, y) = self.to_world(x, y);
+        self.index.query_point(x, y)
+    }
+
+    /// Query for entries intersecting the screen-space rectangle.
+    ///
+    /// The rect is mapped to world space and replaced by its bounding box, so under a
+    /// rotating or skewing view the results are a superset of the exact answer.
+    pub fn query_rect(&self, rect: Aabb2D<f64>) -> impl Iterator<Item = (Key<I>, P)> + '_ {
+        let corners = [
+            self.to_world(rect.min_x, rect.min_y),
+            self.to_world(rect.max_x, rect.min_y),
+            self.to_world(rect.min_x, rect.max_y),
+            self.to_world(rect.max_x, rect.max_y),
+        ];
+        let (x0, y0) = corners[0];
+        let world = corners[1..]
+            .iter()
+            .fold(Aabb2D::new(x0, y0, x0, y0), |acc, &(x, y)| {
+                Aabb2D::new(
+                    acc.min_x.min(x),
+                    acc.min_y.min(y),
+                    acc.max_x.max(x),
+                    acc.max_y.max(y),
+                )
+            });
+        self.index.query_rect(world)
+    }
+}
+
+/// Squared distance between the segment `a–b` and a box (zero if they touch).
+fn segment_box_dist2(a: (f64, f64), b: (f64, f64), bx: &Aabb2D<f64>) -> f64 {
+    if segment_hits_box(a, b, bx) {
+        return 0.0;
+    }
+    // Disjoint convex shapes: the closest pair involves a vertex of one of them.
+    let corners = [
+        (bx.min_x, bx.min_y),
+        (bx.max_x, bx.min_y),
+        (bx.min_x, bx.max_y),
+        (bx.max_x, bx.max_y),
+    ];
+    let from_corners = corners
+        .iter()
+        .map(|&c| point_segment_dist2(c, a, b))
+        .fold(f64::INFINITY, f64::min);
+    from_corners
+        .min(point_dist2(bx, a.0, a.1))
+        .min(point_dist2(bx, b.0, b.1))
+}
+
+/// Liang–Barsky clip: does the segment `a–b` touch the box?
+fn segment_hits_box(a: (f64, f64), b: (f64, f64), bx: &Aabb2D<f64>) -> bool {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+    for (p, q) in [
+        (-dx, a.0 - bx.min_x),
+        (dx, bx.max_x - a.0),
+        (-dy, a.1 - bx.min_y),
+        (dy, bx.max_y - a.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return false;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                t0 = t0.max(r);
+            } else {
+                t1 = t1.min(r);
+            }
+            if t0 > t1 {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn point_segment_dist2(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (ex, ey) = (a.0 + t * dx - p.0, a.1 + t * dy - p.1);
+    ex * ex + ey * ey
+}
+
+/// Default index using a flat vector backend.
+pub type Index<T, P> = IndexGeneric<T, P, crate::backends::flatvec::FlatVec<T>>;
+
+impl<T: Copy + PartialOrd + Debug, P: Copy + Debug> Default for Index<T, P> {
     fn default() -> Self {
         Self::new()
     }
-}
+}
+
+impl<P: Copy + Debug> Index<f64, P> {
+    /// Create a BVH-backed index using SAH-like splits.
+    pub fn with_bvh() -> IndexGeneric<f64, P, crate::backends::bvh::BvhF64> {
+        IndexGeneric::with_backend(crate::backends::bvh::BvhF64::default())
+    }
+
+    /// Create a BVH-backed index that updates entries in place while they move less than
+    /// `slack` outside their leaf; see [`Bvh::with_update_slack`](crate::backends::bvh::Bvh::with_update_slack).
+    pub fn with_bvh_update_slack(slack: f64) -> IndexGeneric<f64, P, crate::backends::bvh::BvhF64> {
+        IndexGeneric::with_backend(crate::backends::bvh::BvhF64::with_update_slack(slack))
+    }
+
+    /// Create a BVH-backed index with at most `max_leaf` items per leaf; see
+    /// [`Bvh::with_max_leaf`](crate::backends::bvh::Bvh::with_max_leaf).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_leaf < 2`.
+    pub fn with_bvh_params(max_leaf: usize) -> IndexGeneric<f64, P, crate::backends::bvh::BvhF64> {
+        IndexGeneric::with_backend(crate::backends::bvh::BvhF64::with_max_leaf(max_leaf))
+    }
+
+    /// Build a BVH-backed index in bulk from entries; see
+    /// [`Bvh::bulk_build_default`](crate::backends::bvh::Bvh::bulk_build_default).
+    pub fn with_bvh_bulk(
+        entries: &[(Aabb2D<f64>, P)],
+    ) -> IndexGeneric<f64, P, crate::backends::bvh::BvhF64> {
+        IndexGeneric::from_bulk(entries, crate::backends::bvh::BvhF64::bulk_build_default)
+    }
+
+    /// Like [`with_bvh_bulk`](Self::with_bvh_bulk), with leaves of at most `max_leaf` items;
+    /// see [`Bvh::bulk_build_with_params`](crate::backends::bvh::Bvh::bulk_build_with_params).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_leaf < 2`.
+    pub fn with_bvh_bulk_params(
+        entries: &[(Aabb2D<f64>, P)],
+        max_leaf: usize,
+    ) -> IndexGeneric<f64, P, crate::backends::bvh::BvhF64> {
+        IndexGeneric::from_bulk(entries, |pairs| {
+            crate::backends::bvh::BvhF64::bulk_build_with_params(pairs, max_leaf)
+        })
+    }
+
+    /// Create an R-tree-backed index (f64 coordinates).
+    pub fn with_rtree() -> IndexGeneric<f64, P, crate::backends::rtree::RTreeF64<P>> {
+        IndexGeneric::with_backend(crate::backends::rtree::RTreeF64::default())
+    }
+
+    /// Create an R-tree-backed index with a custom fan-out; see
+    /// [`RTree::with_params`](crate::backends::rtree::RTree::with_params).
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `min_children >= 2` and `2 * min_children <= max_children`.
+    pub fn with_rtree_params(
+        max_children: usize,
+        min_children: usize,
+    ) -> IndexGeneric<f64, P, crate::backends::rtree::RTreeF64<P>> {
+        IndexGeneric::with_backend(crate::backends::rtree::RTreeF64::with_params(
+            max_children,
+            min_children,
+        ))
+    }
+
+    /// Create a quadtree-backed index over `bounds`; see
+    /// [`Quadtree::new`](crate::backends::quadtree::Quadtree::new).
+    pub fn with_quadtree(
+        bounds: Aabb2D<f64>,
+        max_depth: usize,
+        capacity: usize,
+    ) -> IndexGeneric<f64, P, crate::backends::quadtree::QuadtreeF64> {
+        IndexGeneric::with_backend(crate::backends::quadtree::QuadtreeF64::new(
+            bounds, max_depth, capacity,
+        ))
+    }
+
+    /// Create a hashed-grid-backed index with cells of `cell_w × cell_h` aligned to `origin`;
+    /// see [`HashGrid::new`](crate::backends::hashgrid::HashGrid::new).
+    pub fn with_hash_grid(
+        cell_w: f64,
+        cell_h: f64,
+        origin: (f64, f64),
+    ) -> IndexGeneric<f64, P, crate::backends::hashgrid::HashGridF64> {
+        IndexGeneric::with_backend(crate::backends::hashgrid::HashGridF64::new(
+            cell_w, cell_h, origin,
+        ))
+    }
+
+    /// Build an R-tree-backed index in bulk from entries.
+    pub fn with_rtree_bulk(
+        entries: &[(Aabb2D<f64>, P)],
+    ) -> IndexGeneric<f64, P, crate::backends::rtree::RTreeF64<P>> {
+        IndexGeneric::from_bulk(
+            entries,
+            crate::backends::rtree::RTreeF64::bulk_build_default,
+        )
+    }
+
+    /// Build an R-tree-backed index in bulk, packed for a known set of query rectangles.
+    ///
+    /// This is a specialized builder for static scenes queried with fixed rectangles
+    /// (for example viewport tiles); see [`RTree::bulk_build_for_queries`](crate::backends::rtree::RTree::bulk_build_for_queries).
+    /// Results are identical to [`Index::with_rtree_bulk`]; only the tree layout differs.
+    pub fn with_rtree_bulk_for_queries(
+        entries: &[(Aabb2D<f64>, P)],
+        query_rects: &[Aabb2D<f64>],
+    ) -> IndexGeneric<f64, P, crate::backends::rtree::RTreeF64<P>> {
+        IndexGeneric::from_bulk(entries, |pairs| {
+            crate::backends::rtree::RTreeF64::bulk_build_for_queries(pairs, query_rects)
+        })
+    }
+}
+
+impl<P: Copy + Debug> Index<i64, P> {
+    /// Create an i64 R-tree-backed index using integer SAH splits.
+    pub fn with_rtree() -> IndexGeneric<i64, P, crate::backends::rtree::RTreeI64<P>> {
+        IndexGeneric::with_backend(crate::backends::rtree::RTreeI64::default())
+    }
+
+    /// Build an i64 BVH-backed index in bulk from entries; see
+    /// [`Bvh::bulk_build_default`](crate::backends::bvh::Bvh::bulk_build_default).
+    pub fn with_bvh_bulk(
+        entries: &[(Aabb2D<i64>, P)],
+    ) -> IndexGeneric<i64, P, crate::backends::bvh::BvhI64> {
+        IndexGeneric::from_bulk(entries, crate::backends::bvh::BvhI64::bulk_build_default)
+    }
+
+    /// Like [`with_bvh_bulk`](Self::with_bvh_bulk), with leaves of at most `max_leaf` items;
+    /// see [`Bvh::bulk_build_with_params`](crate::backends::bvh::Bvh::bulk_build_with_params).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_leaf < 2`.
+    pub fn with_bvh_bulk_params(
+        entries: &[(Aabb2D<i64>, P)],
+        max_leaf: usize,
+    ) -> IndexGeneric<i64, P, crate::backends::bvh::BvhI64> {
+        IndexGeneric::from_bulk(entries, |pairs| {
+            crate::backends::bvh::BvhI64::bulk_build_with_params(pairs, max_leaf)
+        })
+    }
+
+    /// Create an i64 R-tree-backed index with a custom fan-out; see
+    /// [`RTree::with_params`](crate::backends::rtree::RTree::with_params).
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `min_children >= 2` and `2 * min_children <= max_children`.
+    pub fn with_rtree_params(
+        max_children: usize,
+        min_children: usize,
+    ) -> IndexGeneric<i64, P, crate::backends::rtree::RTreeI64<P>> {
+        IndexGeneric::with_backend(crate::backends::rtree::RTreeI64::with_params(
+            max_children,
+            min_children,
+        ))
+    }
+
+    /// Create an i64 quadtree-backed index over `bounds`; see
+    /// [`Quadtree::new`](crate::backends::quadtree::Quadtree::new).
+    pub fn with_quadtree(
+        bounds: Aabb2D<i64>,
+        max_depth: usize,
+        capacity: usize,
+    ) -> IndexGeneric<i64, P, crate::backends::quadtree::QuadtreeI64> {
+        IndexGeneric::with_backend(crate::backends::quadtree::QuadtreeI64::new(
+            bounds, max_depth, capacity,
+        ))
+    }
+
+    /// Create a hashed-grid-backed index with cells of `cell_w × cell_h` aligned to `origin`;
+    /// see [`HashGrid::new`](crate::backends::hashgrid::HashGrid::new).
+    pub fn with_hash_grid(
+        cell_w: i64,
+        cell_h: i64,
+        origin: (i64, i64),
+    ) -> IndexGeneric<i64, P, crate::backends::hashgrid::HashGridI64> {
+        IndexGeneric::with_backend(crate::backends::hashgrid::HashGridI64::new(
+            cell_w, cell_h, origin,
+        ))
+    }
+
+    /// Build an i64 R-tree-backed index in bulk from entries.
+    pub fn with_rtree_bulk(
+        entries: &[(Aabb2D<i64>, P)],
+    ) -> IndexGeneric<i64, P, crate::backends::rtree::RTreeI64<P>> {
+        IndexGeneric::from_bulk(
+            entries,
+            crate::backends::rtree::RTreeI64::bulk_build_default,
+        )
+    }
+}
+
+impl<P: Copy + Debug> Index<f32, P> {
+    /// Create a BVH-backed index (f32 coordinates).
+    pub fn with_bvh() -> IndexGeneric<f32, P, crate::backends::bvh::BvhF32> {
+        IndexGeneric::with_backend(crate::backends::bvh::BvhF32::default())
+    }
+
+    /// Create a BVH-backed index (f32 coordinates) with in-place updates within `slack`;
+    /// see [`Bvh::with_update_slack`](crate::backends::bvh::Bvh::with_update_slack).
+    pub fn with_bvh_update_slack(slack: f32) -> IndexGeneric<f32, P, crate::backends::bvh::BvhF32> {
+        IndexGeneric::with_backend(crate::backends::bvh::BvhF32::with_update_slack(slack))
+    }
+
+    /// Create a BVH-backed index with at most `max_leaf` items per leaf; see
+    /// [`Bvh::with_max_leaf`](crate::backends::bvh::Bvh::with_max_leaf).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_leaf < 2`.
+    pub fn with_bvh_params(max_leaf: usize) -> IndexGeneric<f32, P, crate::backends::bvh::BvhF32> {
+        IndexGeneric::with_backend(crate::backends::bvh::BvhF32::with_max_leaf(max_leaf))
+    }
+
+    /// Build an f32 BVH-backed index in bulk from entries; see
+    /// [`Bvh::bulk_build_default`](crate::backends::bvh::Bvh::bulk_build_default).
+    pub fn with_bvh_bulk(
+        entries: &[(Aabb2D<f32>, P)],
+    ) -> IndexGeneric<f32, P, crate::backends::bvh::BvhF32> {
+        IndexGeneric::from_bulk(entries, crate::backends::bvh::BvhF32::bulk_build_default)
+    }
+
+    /// Like [`with_bvh_bulk`](Self::with_bvh_bulk), with leaves of at most `max_leaf` items;
+    /// see [`Bvh::bulk_build_with_params`](crate::backends::bvh::Bvh::bulk_build_with_params).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_leaf < 2`.
+    pub fn with_bvh_bulk_params(
+        entries: &[(Aabb2D<f32>, P)],
+        max_leaf: usize,
+    ) -> IndexGeneric<f32, P, crate::backends::bvh::BvhF32> {
+        IndexGeneric::from_bulk(entries, |pairs| {
+            crate::backends::bvh::BvhF32::bulk_build_with_params(pairs, max_leaf)
+        })
+    }
+
+    /// Create an R-tree-backed index (f32 coordinates).
+    pub fn with_rtree() -> IndexGeneric<f32, P, crate::backends::rtree::RTreeF32<P>> {
+        IndexGeneric::with_backend(crate::backends::rtree::RTreeF32::default())
+    }
+
+    /// Create an f32 R-tree-backed index with a custom fan-out; see
+    /// [`RTree::with_params`](crate::backends::rtree::RTree::with_params).
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `min_children >= 2` and `2 * min_children <= max_children`.
+    pub fn with_rtree_params(
+        max_children: usize,
+        min_children: usize,
+    ) -> IndexGeneric<f32, P, crate::backends::rtree::RTreeF32<P>> {
+        IndexGeneric::with_backend(crate::backends::rtree::RTreeF32::with_params(
+            max_children,
+            min_children,
+        ))
+    }
+
+    /// Build an f32 R-tree-backed index in bulk from entries.
+    pub fn with_rtree_bulk(
+        entries: &[(Aabb2D<f32>, P)],
+    ) -> IndexGeneric<f32, P, crate::backends::rtree::RTreeF32<P>> {
+        IndexGeneric::from_bulk(
+            entries,
+            crate::backends::rtree::RTreeF32::bulk_build_default,
+        )
+    }
+}
+
+impl<P: Copy + Debug> Index<i32, P> {
+    /// Create an i32 R-tree-backed index using integer SAH splits with i64 metrics.
+    pub fn with_rtree() -> IndexGeneric<i32, P, crate::backends::rtree::RTreeI32<P>> {
+        IndexGeneric::with_backend(crate::backends::rtree::RTreeI32::default())
+    }
+
+    /// Create an i32 R-tree-backed index with a custom fan-out; see
+    /// [`RTree::with_params`](crate::backends::rtree::RTree::with_params).
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `min_children >= 2` and `2 * min_children <= max_children`.
+    pub fn with_rtree_params(
+        max_children: usize,
+        min_children: usize,
+    ) -> IndexGeneric<i32, P, crate::backends::rtree::RTreeI32<P>> {
+        IndexGeneric::with_backend(crate::backends::rtree::RTreeI32::with_params(
+            max_children,
+            min_children,
+        ))
+    }
+
+    /// Create an i32 BVH-backed index using integer SAH splits with i64 metrics.
+    pub fn with_bvh() -> IndexGeneric<i32, P, crate::backends::bvh::BvhI32> {
+        IndexGeneric::with_backend(crate::backends::bvh::BvhI32::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn insert_update_commit_and_query() {
+        let mut idx: Index<i64, u32> = Index::new();
+        let k1 = idx.insert(Aabb2D::new(0, 0, 10, 10), 1);
+        let _ = idx.commit();
+        idx.update(k1, Aabb2D::new(5, 5, 15, 15));
+        let dmg = idx.commit();
+        assert!(!dmg.is_empty());
+
+        let hits: Vec<_> = idx.query_point(6, 6).collect();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].1, 1);
+    }
+
+    #[test]
+    fn with_rtree_params_exists_for_every_rtree_scalar() {
+        let mut f64_idx = Index::<f64, u32>::with_rtree_params(16, 4);
+        let mut f32_idx = Index::<f32, u32>::with_rtree_params(16, 4);
+        let mut i64_idx = Index::<i64, u32>::with_rtree_params(16, 4);
+        let mut i32_idx = Index::<i32, u32>::with_rtree_params(16, 4);
+        for i in 0..100_u16 {
+            let (x, y) = (i % 10 * 10, i / 10 * 10);
+            let _ = f64_idx.insert(
+                Aabb2D::new(
+                    f64::from(x),
+                    f64::from(y),
+                    f64::from(x + 5),
+                    f64::from(y + 5),
+                ),
+                u32::from(i),
+            );
+            let _ = f32_idx.insert(
+                Aabb2D::new(
+                    f32::from(x),
+                    f32::from(y),
+                    f32::from(x + 5),
+                    f32::from(y + 5),
+                ),
+                u32::from(i),
+            );
+            let _ = i64_idx.insert(
+                Aabb2D::new(
+                    i64::from(x),
+                    i64::from(y),
+                    i64::from(x + 5),
+                    i64::from(y + 5),
+                ),
+                u32::from(i),
+            );
+            let _ = i32_idx.insert(
+                Aabb2D::new(
+                    i32::from(x),
+                    i32::from(y),
+                    i32::from(x + 5),
+                    i32::from(y + 5),
+                ),
+                u32::from(i),
+            );
+        }
+        let _ = (
+            f64_idx.commit(),
+            f32_idx.commit(),
+            i64_idx.commit(),
+            i32_idx.commit(),
+        );
+        assert_eq!(f64_idx.query_point(12.0, 32.0).collect::<Vec<_>>()[0].1, 31);
+        assert_eq!(f32_idx.query_point(12.0, 32.0).collect::<Vec<_>>()[0].1, 31);
+        assert_eq!(i64_idx.query_point(12, 32).collect::<Vec<_>>()[0].1, 31);
+        assert_eq!(i32_idx.query_point(12, 32).collect::<Vec<_>>()[0].1, 31);
+        assert!(f32_idx.stats().max_depth >= 1);
+    }
+
+    #[test]
+    fn key_display_and_raw_round_trip() {
+        let mut idx: Index<i64, u32> = Index::new();
+        let _ = idx.insert(Aabb2D::new(0, 0, 1, 1), 0);
+        let k = idx.insert(Aabb2D::new(0, 0, 1, 1), 1);
+        assert_eq!(alloc::format!("{k}"), "#1@g1");
+        assert_eq!(Key::from_raw(k.to_raw()), k);
+
+        let big = Key::new(u32::MAX as usize, u32::MAX - 1);
+        assert_eq!(alloc::format!("{big}"), "#4294967295@g4294967294");
+        assert_eq!(Key::from_raw(big.to_raw()), big);
+        assert_ne!(Key::new(1, 2).to_raw(), Key::new(2, 1).to_raw());
+    }
+
+    #[test]
+    fn flatvec_grouped_query_is_a_single_group() {
+        let mut idx: Index<i64, u32> = Index::new();
+        let _ = idx.insert(Aabb2D::new(0, 0, 10, 10), 1);
+        let _ = idx.insert(Aabb2D::new(100, 100, 110, 110), 2);
+        let _ = idx.commit();
+        let groups: Vec<_> = idx
+            .query_rect_grouped(Aabb2D::new(0, 0, 200, 200))
+            .collect();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].1.len(), 2);
+        assert_eq!(
+            idx.query_rect_grouped(Aabb2D::new(500, 500, 600, 600))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn query_rect_budgeted_stops_early_with_subset() {
+        let mut idx = Index::<f64, u32>::with_rtree();
+        for i in 0..1024_u32 {
+            let (x, y) = (f64::from(i % 32), f64::from(i / 32));
+            let _ = idx.insert(Aabb2D::new(x, y, x + 1.0, y + 1.0), i);
+        }
+        let _ = idx.commit();
+        let q = Aabb2D::new(0.0, 0.0, 32.0, 32.0);
+        let full: Vec<_> = idx.query_rect(q).collect();
+
+        let (partial, complete) = idx.query_rect_budgeted(q, 3);
+        assert!(!complete);
+        assert!(partial.len() < full.len());
+        assert!(partial.iter().all(|hit| full.contains(hit)));
+
+        let (all, complete) = idx.query_rect_budgeted(q, usize::MAX);
+        assert!(complete);
+        assert_eq!(all.len(), full.len());
+    }
+
+    #[test]
+    fn query_rect_map_matches_mapped_query() {
+        let mut idx = Index::<i64, u32>::with_rtree();
+        for i in 0..64_u32 {
+            let x = i64::from(i % 8) * 10;
+            let y = i64::from(i / 8) * 10;
+            let _ = idx.insert(Aabb2D::new(x, y, x + 12, y + 12), i);
+        }
+        let _ = idx.commit();
+        let q = Aabb2D::new(15, 15, 45, 35);
+        let mapped: Vec<u32> = idx.query_rect_map(q, |_, p| p).collect();
+        let expected: Vec<u32> = idx.query_rect(q).map(|(_, p)| p).collect();
+        assert!(!mapped.is_empty());
+        assert_eq!(mapped, expected);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn query_stats_count_traversal_and_reset_on_take() {
+        let entries: Vec<_> = (0..1024_u32)
+            .map(|i| {
+                let (x, y) = (f64::from(i % 32), f64::from(i / 32));
+                (Aabb2D::new(x, y, x + 1.0, y + 1.0), i)
+            })
+            .collect();
+        let idx = Index::<f64, u32>::with_rtree_bulk(&entries);
+        assert_eq!(idx.take_query_stats(), crate::QueryStats::default());
+
+        assert_eq!(idx.query_point(10.5, 20.5).count(), 1);
+        let stats = idx.take_query_stats();
+        assert_eq!(stats.queries, 1);
+        assert!(stats.nodes_visited > 0);
+        assert!(stats.nodes_visited < 1024 / 8, "{stats:?}");
+        assert!(stats.items_tested < 1024 / 8, "{stats:?}");
+        assert_eq!(idx.take_query_stats(), crate::QueryStats::default());
+
+        // A flat backend tests every entry.
+        let mut flat: Index<f64, u32> = Index::new();
+        for &(aabb, payload) in &entries[..100] {
+            let _ = flat.insert(aabb, payload);
+        }
+        let _ = flat.commit();
+        let _ = flat.query_rect(Aabb2D::new(0.0, 0.0, 1.0, 1.0)).count();
+        let stats = flat.take_query_stats();
+        assert_eq!((stats.nodes_visited, stats.items_tested), (0, 100));
+    }
+
+    #[test]
+    fn view_transform_queries_in_screen_space() {
+        let mut idx = Index::<f64, u32>::with_rtree();
+        for i in 0..50_u32 {
+            let x = f64::from(i % 10) * 10.0;
+            let y = f64::from(i / 10) * 10.0;
+            let _ = idx.insert(Aabb2D::new(x, y, x + 8.0, y + 8.0), i);
+        }
+        let _ = idx.commit();
+        // Screen = 2 × world + (100, 50).
+        let view = idx.with_view_transform([2.0, 0.0, 0.0, 2.0, 100.0, 50.0]);
+        assert_eq!(view.to_world(120.0, 70.0), (10.0, 10.0));
+
+        let payloads = |it: &mut dyn Iterator<Item = (Key, u32)>| {
+            let mut v: Vec<u32> = it.map(|(_, p)| p).collect();
+            v.sort_unstable();
+            v
+        };
+        let screen = payloads(&mut view.query_rect(Aabb2D::new(130.0, 70.0, 190.0, 110.0)));
+        let world = payloads(&mut idx.query_rect(Aabb2D::new(15.0, 10.0, 45.0, 30.0)));
+        assert!(!screen.is_empty());
+        assert_eq!(screen, world);
+
+        let screen = payloads(&mut view.query_point(125.0, 75.0));
+        assert_eq!(screen, payloads(&mut idx.query_point(12.5, 12.5)));
+        assert_eq!(screen, vec![11]);
+    }
+
+    #[test]
+    fn query_rect_flags_filters_by_mask() {
+        const SELECTED: u8 = 1;
+        const LOCKED: u8 = 2;
+        let mut idx = Index::<i64, u32>::with_rtree();
+        let mut keys = Vec::new();
+        for i in 0..10_u32 {
+            let x = i64::from(i) * 10;
+            let flags = match i % 3 {
+                0 => SELECTED,
+                1 => LOCKED,
+                _ => 0,
+            };
+            keys.push(idx.insert_with_flags(Aabb2D::new(x, 0, x + 10, 10), i, flags));
+        }
+        let _ = idx.commit();
+        let q = Aabb2D::new(0, 0, 100, 10);
+        let payloads = |idx: &IndexGeneric<i64, u32, _>, mask| {
+            let mut v: Vec<u32> = idx.query_rect_flags(q, mask).map(|(_, p)| p).collect();
+            v.sort_unstable();
+            v
+        };
+        assert_eq!(payloads(&idx, SELECTED), vec![0, 3, 6, 9]);
+        assert_eq!(payloads(&idx, LOCKED), vec![1, 4, 7]);
+        assert_eq!(payloads(&idx, SELECTED | LOCKED).len(), 7);
+        assert!(payloads(&idx, 0).is_empty());
+
+        idx.set_flags(keys[2], SELECTED | LOCKED);
+        assert_eq!(idx.flags(keys[2]), Some(3));
+        assert_eq!(payloads(&idx, LOCKED), vec![1, 2, 4, 7]);
+        idx.remove(keys[2]);
+        let _ = idx.commit();
+        assert_eq!(idx.flags(keys[2]), None);
+        // A reused slot starts without flags.
+        let k = idx.insert(Aabb2D::new(20, 0, 30, 10), 42);
+        assert_eq!(idx.flags(k), Some(0));
+    }
+
+    #[test]
+    fn overlaps_over_reports_pairs_above_threshold() {
+        let mut idx = Index::<f64, u32>::with_bvh();
+        // Overlaps its neighbor by 10% of its area.
+        let a = idx.insert(Aabb2D::new(0.0, 0.0, 10.0, 10.0), 0);
+        let b = idx.insert(Aabb2D::new(9.0, 0.0, 19.0, 10.0), 1);
+        // Overlaps its neighbor by 60% of its area.
+        let c = idx.insert(Aabb2D::new(100.0, 0.0, 110.0, 10.0), 2);
+        let d = idx.insert(Aabb2D::new(104.0, 0.0, 114.0, 10.0), 3);
+        let _ = idx.commit();
+
+        let pairs: Vec<_> = idx.overlaps_over(0.5).collect();
+        assert_eq!(pairs.len(), 1);
+        let (k0, k1, overlap) = pairs[0];
+        assert_eq!((k0, k1), (c, d));
+        assert!((overlap - 0.6).abs() < 1e-9);
+
+        let mut all: Vec<_> = idx
+            .overlaps_over(0.05)
+            .map(|(k0, k1, _)| (k0, k1))
+            .collect();
+        all.sort_unstable_by_key(|(k, _)| k.idx());
+        assert_eq!(all, [(a, b), (c, d)]);
+        assert_eq!(idx.overlaps_over(0.6).count(), 0);
+    }
+
+    #[test]
+    fn clear_retain_keeps_capacity() {
+        let mut idx = Index::<f64, u32>::with_rtree();
+        for i in 0..100_u32 {
+            let x = f64::from(i);
+            let _ = idx.insert(Aabb2D::new(x, 0.0, x + 1.0, 1.0), i);
+        }
+        let _ = idx.commit();
+        let bytes = idx.memory_bytes();
+
+        idx.clear_retain();
+        assert_eq!(idx.capacity(), 0);
+        assert_eq!(idx.query_rect(Aabb2D::new(0.0, 0.0, 200.0, 1.0)).count(), 0);
+        assert!(idx.commit().is_empty());
+
+        for i in 0..100_u32 {
+            let x = f64::from(i);
+            let k = idx.insert(Aabb2D::new(x, 0.0, x + 1.0, 1.0), i);
+            assert_eq!(k.idx(), i as usize);
+        }
+        let _ = idx.commit();
+        assert_eq!(idx.memory_bytes(), bytes);
+        assert_eq!(
+            idx.query_rect(Aabb2D::new(0.0, 0.0, 200.0, 1.0)).count(),
+            100
+        );
+    }
+
+    #[test]
+    fn bvh_bulk_params_match_default_bulk_build() {
+        let entries: Vec<(Aabb2D<f64>, u32)> = (0..300_u32)
+            .map(|i| {
+                let (x, y) = (f64::from(i % 30) * 4.0, f64::from(i / 30) * 4.0);
+                (Aabb2D::new(x, y, x + 3.0, y + 3.0), i)
+            })
+            .collect();
+        let plain = Index::<f64, u32>::with_bvh_bulk(&entries);
+        let wide = Index::<f64, u32>::with_bvh_bulk_params(&entries, 32);
+        assert!(wide.stats().leaf_count < plain.stats().leaf_count);
+        let q = Aabb2D::new(10.0, 10.0, 30.0, 22.0);
+        let mut a: Vec<_> = plain.query_rect(q).collect();
+        let mut b: Vec<_> = wide.query_rect(q).collect();
+        a.sort_unstable_by_key(|(k, _)| k.idx());
+        b.sort_unstable_by_key(|(k, _)| k.idx());
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn backends_keep_storage_on_clear_retain() {
+        fn check<B: Backend<f64> + Debug>(mut b: B) {
+            let fill = |b: &mut B| {
+                for i in 0..200_usize {
+                    let x = (i % 20) as f64 * 10.0;
+                    let y = (i / 20) as f64 * 10.0;
+                    b.insert(i, Aabb2D::new(x, y, x + 5.0, y + 5.0));
+                }
+            };
+            fill(&mut b);
+            let full = b.memory_bytes();
+            b.clear_retain();
+            let retained = b.memory_bytes();
+            assert!(retained > 0);
+            assert_eq!(b.query_rect(Aabb2D::new(0.0, 0.0, 200.0, 200.0)).count(), 0);
+            fill(&mut b);
+            assert_eq!(b.memory_bytes(), full, "{b:?}");
+            assert_eq!(
+                b.query_rect(Aabb2D::new(0.0, 0.0, 200.0, 200.0)).count(),
+                200
+            );
+            b.clear();
+            assert!(b.memory_bytes() < retained);
+        }
+        check(crate::backends::flatvec::FlatVec::default());
+        check(crate::backends::rtree::RTreeF64::<u32>::default());
+        check(crate::backends::bvh::BvhF64::default());
+        check(crate::backends::quadtree::QuadtreeF64::new(
+            Aabb2D::new(0.0, 0.0, 256.0, 256.0),
+            6,
+            4,
+        ));
+        check(crate::backends::hashgrid::HashGridF64::new(
+            32.0,
+            32.0,
+            (0.0, 0.0),
+        ));
+    }
+
+    #[test]
+    fn query_knn_matches_brute_force_on_all_backends() {
+        fn check<B: Backend<i64>>(mut idx: IndexGeneric<i64, u32, B>) {
+            let mut boxes = Vec::new();
+            for i in 0..60_u32 {
+                let x = i64::from((i * 37) % 101);
+                let y = i64::from((i * 53) % 97);
+                let aabb = Aabb2D::new(x, y, x + i64::from(i % 5) + 1, y + 2);
+                let _ = idx.insert(aabb, i);
+                boxes.push(aabb);
+            }
+            // Duplicates at the same spot tie in slot order.
+            for i in 60..63_u32 {
+                let _ = idx.insert(Aabb2D::new(200, 200, 201, 201), i);
+                boxes.push(Aabb2D::new(200, 200, 201, 201));
+            }
+            let _ = idx.commit();
+            for &(x, y) in &[(0, 0), (50, 50), (210, 205), (-30, 120)] {
+                let mut expected: Vec<(i128, u32)> = boxes
+                    .iter()
+                    .zip(0_u32..)
+                    .map(|(b, i)| (point_dist2(b, x, y), i))
+                    .collect();
+                expected.sort_unstable();
+                for k in [1, 5, 63, 100] {
+                    let got: Vec<(i128, u32)> = idx
+                        .query_knn(x, y, k)
+                        .into_iter()
+                        .map(|(_, p, d)| (d, p))
+                        .collect();
+                    assert_eq!(got, expected[..k.min(expected.len())]);
+                }
+            }
+            // A point inside a box is at distance zero.
+            let hits = idx.query_knn(200, 200, 3);
+            let payloads: Vec<u32> = hits.iter().map(|h| h.1).collect();
+            assert_eq!(payloads, [60, 61, 62]);
+            assert!(hits.iter().all(|h| h.2 == 0));
+            assert!(idx.query_knn(0, 0, 0).is_empty());
+        }
+        check(Index::<i64, u32>::new());
+        check(Index::<i64, u32>::with_rtree());
+        check(IndexGeneric::<i64, u32, crate::backends::bvh::Bvh<i64>>::new());
+        check(Index::<i64, u32>::with_hash_grid(8, 8, (0, 0)));
+    }
+
+    #[test]
+    fn query_nearest_agrees_with_knn() {
+        fn check<B: Backend<f64>>(mut idx: IndexGeneric<f64, u32, B>) {
+            assert_eq!(idx.query_nearest(0.0, 0.0), None);
+            let mut keys = Vec::new();
+            for i in 0..50_u32 {
+                let x = f64::from((i * 29) % 83);
+                let y = f64::from((i * 17) % 61);
+                keys.push(idx.insert(Aabb2D::new(x, y, x + 1.5, y + 0.5), i));
+            }
+            // Two boxes equally near (5, 200); the lower slot wins.
+            let _ = idx.insert(Aabb2D::new(0.0, 199.0, 1.0, 201.0), 50);
+            let _ = idx.insert(Aabb2D::new(9.0, 199.0, 10.0, 201.0), 51);
+            let _ = idx.commit();
+            for &(x, y) in &[(0.0, 0.0), (40.5, 30.25), (100.0, -20.0), (5.0, 200.0)] {
+                let (k, p, _) = idx.query_knn(x, y, 1)[0];
+                assert_eq!(idx.query_nearest(x, y), Some((k, p)));
+            }
+            assert_eq!(idx.query_nearest(5.0, 200.0).map(|(_, p)| p), Some(50));
+            for k in keys {
+                idx.remove(k);
+            }
+            let _ = idx.commit();
+            assert_eq!(idx.query_nearest(0.0, 0.0).map(|(_, p)| p), Some(50));
+        }
+        check(Index::<f64, u32>::new());
+        check(Index::<f64, u32>::with_rtree());
+        check(Index::<f64, u32>::with_bvh());
+        check(Index::<f64, u32>::with_hash_grid(4.0, 4.0, (0.0, 0.0)));
+    }
+
+    #[test]
+    fn query_point_strict_excludes_edges() {
+        let mut idx = Index::<i64, u32>::with_rtree();
+        let a = idx.insert(Aabb2D::new(0, 0, 10, 10), 1);
+        let b = idx.insert(Aabb2D::new(10, 0, 20, 10), 2);
+        let _ = idx.commit();
+
+        // On the shared edge: both boxes contain it, neither strictly.
+        let mut loose: Vec<_> = idx.query_point(10, 5).map(|(k, _)| k).collect();
+        loose.sort_unstable_by_key(|k| k.idx());
+        assert_eq!(loose, [a, b]);
+        assert_eq!(idx.query_point_strict(10, 5).count(), 0);
+        // Corners are edges too.
+        assert_eq!(idx.query_point_strict(0, 0).count(), 0);
+        // Interior points match as usual.
+        let strict: Vec<_> = idx.query_point_strict(5, 5).collect();
+        assert_eq!(strict, [(a, 1)]);
+    }
+
+    #[test]
+    fn query_circle_filters_bounding_box_corners() {
+        let mut idx = Index::<i64, u32>::with_rtree();
+        // Fully inside the circle of radius 10 around the origin.
+        let _ = idx.insert(Aabb2D::new(-2, -2, 2, 2), 0);
+        // Straddles the circle's edge.
+        let _ = idx.insert(Aabb2D::new(8, -1, 12, 1), 1);
+        // Inside the circle's bounding box, but its nearest corner (8, 8) is outside.
+        let _ = idx.insert(Aabb2D::new(8, 8, 10, 10), 2);
+        // Just outside along the axis.
+        let _ = idx.insert(Aabb2D::new(0, 11, 1, 12), 3);
+        // Touching at exactly the radius.
+        let _ = idx.insert(Aabb2D::new(-20, 6, -8, 7), 4);
+        let _ = idx.commit();
+
+        let mut hits: Vec<u32> = idx.query_circle(0, 0, 10).map(|(_, p)| p).collect();
+        hits.sort_unstable();
+        assert_eq!(hits, [0, 1, 4]);
+        let rect: Vec<u32> = idx
+            .query_rect(Aabb2D::new(-10, -10, 10, 10))
+            .map(|(_, p)| p)
+            .collect();
+        assert!(rect.contains(&2));
+
+        // Large coordinates do not overflow the squared-distance comparison.
+        let mut big = Index::<i64, u32>::new();
+        let _ = big.insert(Aabb2D::new(3_000_000_000, 0, 3_000_000_001, 1), 0);
+        let _ = big.commit();
+        assert_eq!(big.query_circle(0, 0, 3_000_000_000).count(), 1);
+        assert_eq!(big.query_circle(0, 0, 2_999_999_999).count(), 0);
+    }
+
+    #[test]
+    fn query_visit_stops_early() {
+        fn check<B: Backend<f64>>(mut idx: IndexGeneric<f64, u32, B>) {
+            for i in 0..40_u32 {
+                let x = f64::from(i % 8) * 10.0;
+                let y = f64::from(i / 8) * 10.0;
+                let _ = idx.insert(Aabb2D::new(x, y, x + 15.0, y + 15.0), i);
+            }
+            let _ = idx.commit();
+            let rect = Aabb2D::new(0.0, 0.0, 100.0, 100.0);
+
+            let mut all = Vec::new();
+            assert!(idx.query_rect_visit(rect, |_, p| {
+                all.push(p);
+                true
+            }));
+            let mut expected: Vec<u32> = idx.query_rect(rect).map(|(_, p)| p).collect();
+            all.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(all, expected);
+
+            let mut seen = 0;
+            assert!(!idx.query_rect_visit(rect, |_, _| {
+                seen += 1;
+                seen < 3
+            }));
+            assert_eq!(seen, 3);
+
+            // "Is anything here?"
+            assert!(!idx.query_point_visit(12.0, 12.0, |_, _| false));
+            assert!(idx.query_point_visit(500.0, 500.0, |_, _| false));
+            let mut under = 0;
+            assert!(idx.query_point_visit(12.0, 12.0, |_, _| {
+                under += 1;
+                true
+            }));
+            assert_eq!(under, idx.query_point(12.0, 12.0).count());
+        }
+        check(Index::<f64, u32>::new());
+        check(Index::<f64, u32>::with_rtree());
+        check(Index::<f64, u32>::with_bvh());
+    }
+
+    #[test]
+    fn query_rect_limit_returns_a_bounded_subset() {
+        fn check<B: Backend<f64>>(mut idx: IndexGeneric<f64, u32, B>) {
+            for i in 0..50_u32 {
+                let x = f64::from(i) * 4.0;
+                let _ = idx.insert(Aabb2D::new(x, 0.0, x + 6.0, 6.0), i);
+            }
+            let _ = idx.commit();
+            let rect = Aabb2D::new(20.0, 0.0, 120.0, 6.0);
+            let full: Vec<_> = idx.query_rect(rect).collect();
+            assert_eq!(full.len(), 27);
+
+            for k in [0, 1, 10] {
+                let some = idx.query_rect_limit(rect, k);
+                assert_eq!(some.len(), k);
+                assert!(some.iter().all(|hit| full.contains(hit)));
+                let mut payloads: Vec<u32> = some.iter().map(|&(_, p)| p).collect();
+                payloads.sort_unstable();
+                payloads.dedup();
+                assert_eq!(payloads.len(), k);
+            }
+            let mut all = idx.query_rect_limit(rect, 100);
+            let mut expected = full.clone();
+            all.sort_unstable_by_key(|&(_, p)| p);
+            expected.sort_unstable_by_key(|&(_, p)| p);
+            assert_eq!(all, expected);
+        }
+        check(Index::<f64, u32>::new());
+        check(Index::<f64, u32>::with_rtree());
+        check(Index::<f64, u32>::with_bvh());
+    }
+
+    #[test]
+    fn quadtree_matches_flatvec_under_churn() {
+        let bounds = Aabb2D::new(0, 0, 256, 256);
+        let mut quad = Index::<i64, u32>::with_quadtree(bounds, 6, 4);
+        let mut flat = Index::<i64, u32>::new();
+        let mut keys = Vec::new();
+        for i in 0..120_u32 {
+            let (x, y) = (i64::from(i * 37 % 250), i64::from(i * 91 % 250));
+            let w = i64::from(i % 7) * 6 + 1;
+            let b = Aabb2D::new(x, y, x + w, y + w);
+            keys.push((quad.insert(b, i), flat.insert(b, i)));
+        }
+        let _ = quad.commit();
+        let _ = flat.commit();
+        for (n, &(kq, kf)) in keys.iter().enumerate() {
+            let n = i64::try_from(n).unwrap();
+            if n % 3 == 0 {
+                quad.remove(kq);
+                flat.remove(kf);
+            } else {
+                // Some moves leave the root bounds entirely.
+                let x = (n * 53) % 300 - 20;
+                let b = Aabb2D::new(x, x / 2, x + 9, x / 2 + 9);
+                quad.update(kq, b);
+                flat.update(kf, b);
+            }
+        }
+        let _ = quad.commit();
+        let _ = flat.commit();
+
+        for r in [
+            Aabb2D::new(0, 0, 256, 256),
+            Aabb2D::new(-30, -30, 0, 0),
+            Aabb2D::new(100, 40, 160, 90),
+            Aabb2D::new(128, 128, 128, 128),
+        ] {
+            let mut a: Vec<u32> = quad.query_rect(r).map(|(_, p)| p).collect();
+            let mut b: Vec<u32> = flat.query_rect(r).map(|(_, p)| p).collect();
+            a.sort_unstable();
+            b.sort_unstable();
+            assert_eq!(a, b);
+        }
+        for (x, y) in [(5, 2), (128, 64), (-15, -8), (270, 135)] {
+            let mut a: Vec<u32> = quad.query_point(x, y).map(|(_, p)| p).collect();
+            let mut b: Vec<u32> = flat.query_point(x, y).map(|(_, p)| p).collect();
+            a.sort_unstable();
+            b.sort_unstable();
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn hash_grid_matches_flatvec_under_churn() {
+        use crate::backends::hashgrid::HashGridF64;
+
+        let mut grid =
+            IndexGeneric::<f64, u32, _>::with_backend(HashGridF64::new(16.0, 12.0, (3.0, -5.0)));
+        let mut flat = Index::<f64, u32>::new();
+        let mut keys = Vec::new();
+        for i in 0..100_u32 {
+            let x = f64::from(i * 37 % 200) - 60.0;
+            let y = f64::from(i * 53 % 180) - 40.0;
+            let s = f64::from(i % 5) * 11.0;
+            let b = Aabb2D::new(x, y, x + s, y + s * 0.5);
+            keys.push((grid.insert(b, i), flat.insert(b, i)));
+        }
+        let _ = grid.commit();
+        let _ = flat.commit();
+        for (n, &(kg, kf)) in keys.iter().enumerate() {
+            let n = f64::from(u32::try_from(n).unwrap());
+            if n % 4.0 == 0.0 {
+                grid.remove(kg);
+                flat.remove(kf);
+            } else if n % 4.0 == 1.0 {
+                // Snap onto cell edges to exercise closed-box semantics.
+                let b = Aabb2D::new(3.0 + n * 16.0 % 160.0, -5.0, 19.0 + n * 16.0 % 160.0, 7.0);
+                grid.update(kg, b);
+                flat.update(kf, b);
+            }
+        }
+        let _ = grid.commit();
+        let _ = flat.commit();
+
+        let sorted = |mut v: Vec<u32>| {
+            v.sort_unstable();
+            v
+        };
+        for r in [
+            Aabb2D::new(-100.0, -100.0, 300.0, 300.0),
+            Aabb2D::new(19.0, 7.0, 19.0, 7.0),
+            Aabb2D::new(30.0, 10.0, 70.5, 42.0),
+            Aabb2D::new(-1e9, -1e9, 1e9, 1e9),
+        ] {
+            assert_eq!(
+                sorted(grid.query_rect(r).map(|(_, p)| p).collect()),
+                sorted(flat.query_rect(r).map(|(_, p)| p).collect())
+            );
+        }
+        for (x, y) in [(3.0, -5.0), (35.0, 7.0), (-20.0, 10.0), (100.0, 100.0)] {
+            assert_eq!(
+                sorted(grid.query_point(x, y).map(|(_, p)| p).collect()),
+                sorted(flat.query_point(x, y).map(|(_, p)| p).collect())
+            );
+        }
+    }
 
-impl<P: Copy + Debug> Index<f64, P> {
-    /// Create a BVH-backed index using SAH-like splits.
-    pub fn with_bvh() -> IndexGeneric<f64, P, crate::backends::bvh::BvhF64> {
-        IndexGeneric {
-            entries: Vec::new(),
-            free_list: Vec::new(),
-            backend: crate::backends::bvh::BvhF64::default(),
+    #[test]
+    fn query_into_reuses_caller_buffer() {
+        let mut idx = Index::<i64, u32>::with_rtree();
+        for i in 0..30_u32 {
+            let x = i64::from(i) * 10;
+            let _ = idx.insert(Aabb2D::new(x, 0, x + 15, 10), i);
         }
+        let _ = idx.commit();
+
+        let mut buf = Vec::new();
+        idx.query_rect_into(Aabb2D::new(0, 0, 300, 10), &mut buf);
+        assert_eq!(buf.len(), 30);
+        let cap = buf.capacity();
+        let ptr = buf.as_ptr();
+
+        idx.query_rect_into(Aabb2D::new(50, 0, 60, 10), &mut buf);
+        let mut payloads: Vec<u32> = buf.iter().map(|&(_, p)| p).collect();
+        payloads.sort_unstable();
+        assert_eq!(payloads, [4, 5, 6]);
+
+        idx.query_point_into(12, 5, &mut buf);
+        let mut payloads: Vec<u32> = buf.iter().map(|&(_, p)| p).collect();
+        payloads.sort_unstable();
+        assert_eq!(payloads, [0, 1]);
+        let expected: Vec<_> = idx.query_point(12, 5).collect();
+        assert_eq!(buf.len(), expected.len());
+
+        idx.query_point_into(1000, 1000, &mut buf);
+        assert!(buf.is_empty());
+        assert_eq!((buf.capacity(), buf.as_ptr()), (cap, ptr));
     }
 
-    /// Create an R-tree-backed index (f64 coordinates).
-    pub fn with_rtree() -> IndexGeneric<f64, P, crate::backends::rtree::RTreeF64<P>> {
-        IndexGeneric {
-            entries: Vec::new(),
-            free_list: Vec::new(),
-            backend: crate::backends::rtree::RTreeF64::default(),
+    #[test]
+    fn get_resolves_keys_and_rejects_stale_ones() {
+        let mut idx = Index::<i64, u32>::with_rtree();
+        let a = idx.insert(Aabb2D::new(0, 0, 10, 10), 7);
+        let b = idx.insert(Aabb2D::new(20, 20, 30, 30), 8);
+        // Visible before the first commit.
+        assert_eq!(idx.get(a), Some((Aabb2D::new(0, 0, 10, 10), 7)));
+        let _ = idx.commit();
+
+        idx.update(b, Aabb2D::new(40, 40, 50, 50));
+        assert_eq!(idx.aabb_of(b), Some(Aabb2D::new(40, 40, 50, 50)));
+        assert_eq!(idx.payload_of(b), Some(8));
+
+        idx.remove(a);
+        assert_eq!(idx.get(a), None);
+        let _ = idx.commit();
+        // The freed slot is reused with a new generation; the old key stays stale.
+        let c = idx.insert(Aabb2D::new(1, 1, 2, 2), 9);
+        assert_eq!(c.idx(), a.idx());
+        assert_eq!(idx.get(a), None);
+        assert_eq!(idx.aabb_of(a), None);
+        assert_eq!(idx.payload_of(a), None);
+        assert_eq!(idx.payload_of(c), Some(9));
+    }
+
+    #[test]
+    fn maintenance_never_remaps_slots() {
+        let mut idx = Index::<f64, u32>::with_rtree();
+        let keys: Vec<_> = (0..300_u32)
+            .map(|i| {
+                let (x, y) = (f64::from(i % 20) * 7.0, f64::from(i / 20) * 7.0);
+                idx.insert(Aabb2D::new(x, y, x + 5.0, y + 5.0), i)
+            })
+            .collect();
+        let _ = idx.commit();
+        for &k in keys.iter().filter(|k| k.idx() % 3 != 0) {
+            idx.remove(k);
+        }
+        let _ = idx.commit();
+        let snapshot = |idx: &IndexGeneric<f64, u32, _>| -> Vec<_> {
+            keys.iter().map(|&k| (k.idx(), idx.get(k))).collect()
+        };
+        let before = snapshot(&idx);
+
+        idx.compact();
+        assert_eq!(snapshot(&idx), before);
+        idx.rebuild();
+        assert_eq!(snapshot(&idx), before);
+        let _ = idx.replace_backend(crate::RTreeF64::<u32>::default());
+        assert_eq!(snapshot(&idx), before);
+        // Queries resolve to the same slots as before.
+        for (k, p) in idx.query_rect(Aabb2D::new(0.0, 0.0, 200.0, 200.0)) {
+            assert_eq!(k, keys[p as usize]);
+            assert_eq!(k.idx() % 3, 0);
         }
     }
 
-    /// Build an R-tree-backed index in bulk from entries.
-    pub fn with_rtree_bulk(
-        entries: &[(Aabb2D<f64>, P)],
-    ) -> IndexGeneric<f64, P, crate::backends::rtree::RTreeF64<P>> {
-        let mut idx = IndexGeneric {
-            entries: Vec::with_capacity(entries.len()),
-            free_list: Vec::new(),
-            backend: crate::backends::rtree::RTreeF64::default(),
+    #[test]
+    fn compact_slots_reports_a_remap_that_resolves_old_keys() {
+        let mut idx = Index::<i64, u32>::with_rtree();
+        let keys: Vec<_> = (0..40_u32)
+            .map(|i| {
+                let x = i64::from(i) * 10;
+                idx.insert(Aabb2D::new(x, 0, x + 5, 5), i)
+            })
+            .collect();
+        let _ = idx.commit();
+        for &k in keys.iter().step_by(2) {
+            idx.remove(k);
+        }
+        let _ = idx.commit();
+        // Pending at compaction time: stays pending and still commits.
+        let late = idx.insert(Aabb2D::new(1000, 0, 1005, 5), 99);
+
+        let remap = idx.compact_slots();
+        assert!(!remap.is_empty());
+        assert!(remap.iter().all(|(old, new)| new.idx() < old.idx()));
+        assert_eq!(idx.get(late), None, "moved keys go stale");
+        let late = remap.apply(late);
+        for (i, &k) in (0_u32..).zip(&keys) {
+            let expected = (i % 2 == 1).then(|| {
+                let x = i64::from(i) * 10;
+                (Aabb2D::new(x, 0, x + 5, 5), i)
+            });
+            assert_eq!(idx.get(remap.apply(k)), expected);
+        }
+        // Live entries fill a dense prefix of the slots.
+        let live: Vec<_> = idx.query_rect(Aabb2D::new(0, 0, 2000, 10)).collect();
+        assert!(live.iter().all(|(k, _)| k.idx() < 21));
+        assert!(late.idx() < 21);
+
+        let dmg = idx.commit();
+        assert!(dmg.slots_remapped);
+        assert_eq!(dmg.added, vec![Aabb2D::new(1000, 0, 1005, 5)]);
+        assert_eq!(idx.get(late), Some((Aabb2D::new(1000, 0, 1005, 5), 99)));
+        assert_eq!(idx.query_rect(Aabb2D::new(0, 0, 2000, 10)).count(), 21);
+        assert!(!idx.commit().slots_remapped);
+
+        // Already dense: nothing moves and nothing is reported.
+        assert!(idx.compact_slots().is_empty());
+        assert!(!idx.commit().slots_remapped);
+        // Freed slots are reused from the bottom, under fresh generations.
+        let reused = idx.insert(Aabb2D::new(0, 0, 1, 1), 7);
+        assert_eq!(reused.idx(), 21);
+    }
+
+    #[test]
+    fn query_around_point_matches_inflated_rect() {
+        let mut idx = Index::<i64, u32>::with_rtree();
+        for i in 0..50_u32 {
+            let x = i64::from(i) * 10;
+            let _ = idx.insert(Aabb2D::new(x, 0, x + 5, 5), i);
+        }
+        let _ = idx.insert(
+            Aabb2D::new(i64::MAX - 5, i64::MAX - 5, i64::MAX, i64::MAX),
+            99,
+        );
+        let _ = idx.commit();
+        let sorted = |it: &mut dyn Iterator<Item = (Key, u32)>| {
+            let mut v: Vec<_> = it.map(|(_, p)| p).collect();
+            v.sort_unstable();
+            v
         };
-        let mut pairs: Vec<(usize, Aabb2D<f64>)> = Vec::with_capacity(entries.len());
-        for (i, (aabb, payload)) in entries.iter().copied().enumerate() {
-            idx.entries.push(Some(Entry {
-                generation: 1,
-                aabb,
-                payload,
-                mark: None,
-                prev_aabb: None,
-            }));
-            pairs.push((i, aabb));
+
+        let around = sorted(&mut idx.query_around_point(102, 2, 7, 1));
+        assert_eq!(
+            around,
+            sorted(&mut idx.query_rect(Aabb2D::new(95, 1, 109, 3)))
+        );
+        assert_eq!(around, vec![9, 10]);
+        // Zero radius is a point query, edges included.
+        assert_eq!(sorted(&mut idx.query_around_point(105, 5, 0, 0)), vec![10]);
+        assert!(idx.query_around_point(107, 2, 0, 0).next().is_none());
+        // Near the scalar range the box saturates instead of wrapping.
+        assert_eq!(
+            sorted(&mut idx.query_around_point(i64::MAX - 1, i64::MAX - 1, 10, 10)),
+            vec![99]
+        );
+    }
+
+    #[test]
+    fn stats_describe_backend_structure() {
+        fn fill<B: Backend<f64>>(mut idx: IndexGeneric<f64, u32, B>) -> IndexGeneric<f64, u32, B> {
+            for i in 0..200_u32 {
+                let (x, y) = (f64::from(i % 20) * 10.0, f64::from(i / 20) * 10.0);
+                let _ = idx.insert(Aabb2D::new(x, y, x + 5.0, y + 5.0), i);
+            }
+            let _ = idx.commit();
+            idx
+        }
+        let flat = fill(Index::<f64, u32>::new());
+        assert_eq!(
+            flat.stats(),
+            crate::BackendStats {
+                item_count: 200,
+                ..Default::default()
+            }
+        );
+
+        let rtree = fill(Index::<f64, u32>::with_rtree()).stats();
+        let bvh = fill(Index::<f64, u32>::with_bvh()).stats();
+        for s in [rtree, bvh] {
+            assert_eq!(s.item_count, 200);
+            assert!(s.max_depth >= 2, "{s:?}");
+            assert!(s.leaf_count > 1 && s.leaf_count < s.node_count, "{s:?}");
         }
-        idx.backend = crate::backends::rtree::RTreeF64::bulk_build_default(&pairs);
-        idx
+        // A binary tree has one fewer internal node than leaves.
+        assert_eq!(bvh.node_count, 2 * bvh.leaf_count - 1);
+
+        let bounds = Aabb2D::new(0.0, 0.0, 256.0, 256.0);
+        let quad = fill(Index::<f64, u32>::with_quadtree(bounds, 3, 4)).stats();
+        assert_eq!(quad.max_depth, 3);
+        assert_eq!((quad.node_count - 1) % 4, 0);
+
+        let mut grid = fill(IndexGeneric::with_backend(crate::HashGridF64::new(
+            50.0,
+            50.0,
+            (0.0, 0.0),
+        )));
+        // Each 5×5 box sits inside one 50×50 cell; the 200×100 layout covers 4×2 cells.
+        assert_eq!(
+            grid.stats(),
+            crate::BackendStats {
+                node_count: 8,
+                leaf_count: 8,
+                max_depth: 0,
+                item_count: 200,
+                dead_nodes: 0,
+            }
+        );
+        let k = grid.insert(Aabb2D::new(0.0, 0.0, 1.0, 1.0), 0);
+        assert_eq!(grid.stats().item_count, 200);
+        let _ = grid.commit();
+        assert_eq!(grid.stats().item_count, 201);
+        grid.remove(k);
+        let _ = grid.commit();
+        assert_eq!(grid.stats().item_count, 200);
     }
-}
 
-impl<P: Copy + Debug> Index<i64, P> {
-    /// Create an i64 R-tree-backed index using integer SAH splits.
-    pub fn with_rtree() -> IndexGeneric<i64, P, crate::backends::rtree::RTreeI64<P>> {
-        IndexGeneric {
-            entries: Vec::new(),
-            free_list: Vec::new(),
-            backend: crate::backends::rtree::RTreeI64::default(),
+    #[test]
+    fn maintain_converges_under_small_budget() {
+        fn check<B: Backend<f64>>(mut idx: IndexGeneric<f64, u32, B>, fragments: bool) {
+            let mut boxes: Vec<Aabb2D<f64>> = Vec::new();
+            let keys: Vec<_> = (0..400_u32)
+                .map(|i| {
+                    let (x, y) = (f64::from(i % 20) * 10.0, f64::from(i / 20) * 10.0);
+                    boxes.push(Aabb2D::new(x, y, x + 5.0, y + 5.0));
+                    idx.insert(boxes[i as usize], i)
+                })
+                .collect();
+            let _ = idx.commit();
+            // Scatter every box several times.
+            for round in 0..4_u32 {
+                for (i, &k) in keys.iter().enumerate() {
+                    let j = u32::try_from(i).unwrap() * 7 + round * 13;
+                    let (x, y) = (f64::from(j % 23) * 9.0, f64::from(j % 19) * 11.0);
+                    boxes[i] = Aabb2D::new(x, y, x + 5.0, y + 5.0);
+                    idx.update(k, boxes[i]);
+                }
+                let _ = idx.commit();
+            }
+            let before = idx.stats();
+            assert_eq!(
+                before.dead_nodes > before.node_count / 4,
+                fragments,
+                "{before:?}"
+            );
+            assert_eq!(
+                idx.backend.arena_usage(),
+                (before.node_count, before.dead_nodes)
+            );
+
+            let q = Aabb2D::new(40.0, 40.0, 120.0, 90.0);
+            let mut expected: Vec<u32> = (0..400)
+                .filter(|&i| !boxes[i as usize].intersect(&q).is_empty())
+                .collect();
+            expected.sort_unstable();
+            let budget = MaintenanceBudget {
+                max_reinserts: 16,
+                max_compact_nodes: 16,
+            };
+            let mut calls = 0;
+            while idx.maintain(budget) {
+                calls += 1;
+                assert!(calls < 1000, "maintenance did not converge");
+                let mut hits: Vec<u32> = idx.query_rect(q).map(|(_, p)| p).collect();
+                hits.sort_unstable();
+                assert_eq!(hits, expected);
+            }
+            // The sweep alone takes 400 / 16 calls.
+            assert!(calls >= 25, "{calls}");
+            let after = idx.stats();
+            assert!(after.dead_nodes <= after.node_count / 4, "{after:?}");
+            assert_eq!(
+                idx.backend.arena_usage(),
+                (after.node_count, after.dead_nodes)
+            );
+            assert_eq!(after.item_count, 400);
+            assert!(!idx.maintain(budget));
+            for (i, &k) in keys.iter().enumerate() {
+                assert_eq!(idx.get(k).map(|(b, _)| b), Some(boxes[i]));
+            }
         }
+        // This churn strands many BVH nodes but few R-tree ones.
+        check(Index::<f64, u32>::with_bvh(), true);
+        check(Index::<f64, u32>::with_rtree(), false);
     }
 
-    /// Build an i64 R-tree-backed index in bulk from entries.
-    pub fn with_rtree_bulk(
-        entries: &[(Aabb2D<i64>, P)],
-    ) -> IndexGeneric<i64, P, crate::backends::rtree::RTreeI64<P>> {
-        let mut idx = IndexGeneric {
-            entries: Vec::with_capacity(entries.len()),
-            free_list: Vec::new(),
-            backend: crate::backends::rtree::RTreeI64::default(),
+    #[test]
+    fn memory_bytes_counts_entries_and_backend() {
+        fn fill<B: Backend<f64>>(mut idx: IndexGeneric<f64, u32, B>) -> (usize, usize) {
+            for i in 0..500_u32 {
+                let (x, y) = (f64::from(i % 25) * 10.0, f64::from(i / 25) * 10.0);
+                let _ = idx.insert(Aabb2D::new(x, y, x + 5.0, y + 5.0), i);
+            }
+            let pending = idx.memory_bytes();
+            let _ = idx.commit();
+            (pending, idx.memory_bytes())
+        }
+        assert_eq!(Index::<f64, u32>::new().memory_bytes(), 0);
+        let boxes = 500 * size_of::<Aabb2D<f64>>();
+        let (flat_pending, flat) = fill(Index::<f64, u32>::new());
+        assert!(flat_pending >= boxes);
+        // Committing hands the boxes to the backend as well.
+        assert!(flat >= flat_pending + boxes);
+        let bounds = Aabb2D::new(0.0, 0.0, 256.0, 256.0);
+        for (pending, tree) in [
+            fill(Index::<f64, u32>::with_rtree()),
+            fill(Index::<f64, u32>::with_bvh()),
+            fill(Index::<f64, u32>::with_quadtree(bounds, 6, 8)),
+            fill(IndexGeneric::with_backend(crate::HashGridF64::new(
+                32.0,
+                32.0,
+                (0.0, 0.0),
+            ))),
+        ] {
+            assert!(pending >= flat_pending);
+            // Trees keep a box per slot and another in their nodes.
+            assert!(tree > flat, "{tree} <= {flat}");
+        }
+    }
+
+    #[test]
+    fn snapshot_round_trip_keeps_keys() {
+        let mut idx = Index::<f64, u32>::with_rtree();
+        let keys: Vec<_> = (0..40_u32)
+            .map(|i| {
+                let (x, y) = (f64::from(i % 8) * 10.0, f64::from(i / 8) * 10.0);
+                idx.insert_with_flags(Aabb2D::new(x, y, x + 5.0, y + 5.0), i, 1 << (i % 3))
+            })
+            .collect();
+        let _ = idx.commit();
+        idx.remove(keys[3]);
+        idx.remove(keys[17]);
+        let _ = idx.commit();
+        // Pending changes are captured as if committed.
+        let pending = idx.insert(Aabb2D::new(-20.0, -20.0, -10.0, -10.0), 100);
+        idx.update(keys[5], Aabb2D::new(200.0, 0.0, 210.0, 10.0));
+        idx.remove(keys[9]);
+
+        let snap = idx.snapshot();
+        assert_eq!(snap.entries.len(), 38);
+        let mut restored = IndexGeneric::<f64, u32, crate::RTreeF64<u32>>::from_snapshot(
+            snap.clone(),
+            crate::RTreeF64::<u32>::default(),
+        )
+        .unwrap();
+        assert_eq!(restored.snapshot(), snap);
+        for &k in keys.iter().chain([&pending]) {
+            assert_eq!(restored.get(k), idx.get(k));
+            if idx.get(k).is_some() {
+                assert_eq!(restored.flags(k), idx.flags(k));
+            }
+        }
+        let q = Aabb2D::new(-50.0, -50.0, 250.0, 250.0);
+        let sorted = |idx: &IndexGeneric<f64, u32, crate::RTreeF64<u32>>| {
+            let mut v: Vec<_> = idx.query_rect(q).collect();
+            v.sort_unstable_by_key(|&(k, _)| k.idx());
+            v
         };
-        let mut pairs: Vec<(usize, Aabb2D<i64>)> = Vec::with_capacity(entries.len());
-        for (i, (aabb, payload)) in entries.iter().copied().enumerate() {
-            idx.entries.push(Some(Entry {
-                generation: 1,
-                aabb,
-                payload,
-                mark: None,
-                prev_aabb: None,
-            }));
-            pairs.push((i, aabb));
+        let _ = idx.commit();
+        assert_eq!(sorted(&restored), sorted(&idx));
+        // Freed slots are reused in the same order and with fresh generations.
+        for _ in 0..3 {
+            let b = Aabb2D::new(0.0, 0.0, 1.0, 1.0);
+            let k = idx.insert(b, 0);
+            assert_eq!(restored.insert(b, 0), k);
+            assert!(!keys.contains(&k));
         }
-        idx.backend = crate::backends::rtree::RTreeI64::bulk_build_default(&pairs);
-        idx
     }
-}
 
-impl<P: Copy + Debug> Index<f32, P> {
-    /// Create a BVH-backed index (f32 coordinates).
-    pub fn with_bvh() -> IndexGeneric<f32, P, crate::backends::bvh::BvhF32> {
-        IndexGeneric {
-            entries: Vec::new(),
-            free_list: Vec::new(),
-            backend: crate::backends::bvh::BvhF32::default(),
+    #[test]
+    fn snapshot_fills_unlisted_slots() {
+        let key = |i: usize, g: u32| Key::new(i, g);
+        let b = Aabb2D::new(0, 0, 1, 1);
+        let snap = IndexSnapshot {
+            entries: vec![
+                SnapshotEntry {
+                    key: key(4, 2),
+                    aabb: b,
+                    payload: 'a',
+                    flags: 0,
+                },
+                SnapshotEntry {
+                    key: key(1, 1),
+                    aabb: b,
+                    payload: 'b',
+                    flags: 0,
+                },
+            ],
+            // Slot 1 is also live and slot 2 is listed twice.
+            free: vec![key(2, 3), key(1, 5), key(2, 3)],
+        };
+        let mut idx = IndexGeneric::<i64, char, crate::FlatVec<i64>>::from_snapshot(
+            snap,
+            crate::FlatVec::default(),
+        )
+        .unwrap();
+        assert_eq!(idx.len(), 2);
+        assert_eq!(idx.get(key(4, 2)), Some((b, 'a')));
+        // The listed slot comes first, then the unlisted ones (0 and 3) at their first generation.
+        let reused: Vec<_> = (0..4).map(|_| idx.insert(b, 'z')).collect();
+        assert_eq!(reused, vec![key(2, 4), key(3, 2), key(0, 2), key(5, 1)]);
+    }
+
+    #[test]
+    fn snapshot_rejects_slots_beyond_its_keys() {
+        let b = Aabb2D::new(0, 0, 1, 1);
+        let entry = |idx: usize| SnapshotEntry {
+            key: Key::new(idx, 1_u32),
+            aabb: b,
+            payload: (),
+            flags: 0,
+        };
+        let load = |snap| {
+            IndexGeneric::<i64, (), crate::FlatVec<i64>>::from_snapshot(
+                snap,
+                crate::FlatVec::default(),
+            )
+        };
+        // One key naming a huge slot must not size the slot table by it.
+        let hostile = IndexSnapshot {
+            entries: vec![entry(4_000_000_000)],
+            free: vec![],
+        };
+        assert_eq!(
+            load(hostile).unwrap_err(),
+            SnapshotError::SlotOutOfRange {
+                idx: 4_000_000_000,
+                listed: 1,
+            }
+        );
+        let free_too_far = IndexSnapshot {
+            entries: vec![entry(0)],
+            free: vec![Key::new(2, 1)],
+        };
+        assert!(load(free_too_far).is_err());
+        let fits = IndexSnapshot {
+            entries: vec![entry(0)],
+            free: vec![Key::new(1, 1)],
+        };
+        assert_eq!(load(fits).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn any_hit_queries_match_full_queries() {
+        let mut idx = Index::<i64, u32>::with_rtree();
+        for i in 0..400_u32 {
+            let (x, y) = (i64::from(i % 20) * 10, i64::from(i / 20) * 10);
+            let _ = idx.insert(Aabb2D::new(x, y, x + 5, y + 5), i);
+        }
+        let _ = idx.commit();
+        for q in [
+            Aabb2D::new(0, 0, 500, 500),
+            Aabb2D::new(6, 6, 9, 9),
+            Aabb2D::new(9, 9, 10, 10),
+            Aabb2D::new(-5, -5, -1, -1),
+        ] {
+            assert_eq!(
+                idx.any_in_rect(q),
+                idx.query_rect(q).next().is_some(),
+                "{q:?}"
+            );
         }
+        assert!(idx.any_at_point(15, 15));
+        assert!(!idx.any_at_point(17, 17));
+        assert!(!Index::<i64, u32>::new().any_in_rect(Aabb2D::new(0, 0, 1, 1)));
     }
 
-    /// Create an R-tree-backed index (f32 coordinates).
-    pub fn with_rtree() -> IndexGeneric<f32, P, crate::backends::rtree::RTreeF32<P>> {
-        IndexGeneric {
-            entries: Vec::new(),
-            free_list: Vec::new(),
-            backend: crate::backends::rtree::RTreeF32::default(),
+    #[cfg(feature = "stats")]
+    #[test]
+    fn any_in_rect_stops_at_first_hit() {
+        let mut idx = Index::<f64, u32>::with_bvh();
+        for i in 0..1024_u32 {
+            let (x, y) = (f64::from(i % 32), f64::from(i / 32));
+            let _ = idx.insert(Aabb2D::new(x, y, x + 0.5, y + 0.5), i);
         }
+        let _ = idx.commit();
+        let all = Aabb2D::new(0.0, 0.0, 32.0, 32.0);
+        let _ = idx.take_query_stats();
+        assert!(idx.any_in_rect(all));
+        let early = idx.take_query_stats();
+        assert_eq!(idx.query_rect(all).count(), 1024);
+        let full = idx.take_query_stats();
+        assert!(
+            early.items_tested * 16 < full.items_tested,
+            "{early:?} vs {full:?}"
+        );
     }
 
-    /// Build an f32 R-tree-backed index in bulk from entries.
-    pub fn with_rtree_bulk(
-        entries: &[(Aabb2D<f32>, P)],
-    ) -> IndexGeneric<f32, P, crate::backends::rtree::RTreeF32<P>> {
-        let mut idx = IndexGeneric {
-            entries: Vec::with_capacity(entries.len()),
-            free_list: Vec::new(),
-            backend: crate::backends::rtree::RTreeF32::default(),
+    #[test]
+    fn query_rect_ordered_sorts_by_distance_then_slot() {
+        let mut idx = Index::<i64, char>::with_rtree();
+        let far = idx.insert(Aabb2D::new(40, 0, 45, 5), 'f');
+        let inside = idx.insert(Aabb2D::new(-2, -2, 2, 2), 'i');
+        let near_a = idx.insert(Aabb2D::new(10, -1, 12, 1), 'a');
+        // Same distance as `near_a`, from the other side.
+        let near_b = idx.insert(Aabb2D::new(-12, -1, -10, 1), 'b');
+        let _outside = idx.insert(Aabb2D::new(100, 100, 101, 101), 'o');
+        let _ = idx.commit();
+
+        let got = idx.query_rect_ordered(Aabb2D::new(-50, -50, 50, 50), (0, 0));
+        assert_eq!(
+            got,
+            vec![(inside, 'i'), (near_a, 'a'), (near_b, 'b'), (far, 'f')]
+        );
+        // The origin need not lie inside the query rect.
+        let keys: Vec<_> = idx
+            .query_rect_ordered(Aabb2D::new(0, -5, 50, 5), (60, 0))
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec![far, near_a, inside]);
+        assert!(
+            idx.query_rect_ordered(Aabb2D::new(60, 60, 70, 70), (0, 0))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn get_many_matches_get_in_order() {
+        let mut idx = Index::<f64, u32>::with_bvh();
+        let keys: Vec<_> = (0..5_u32)
+            .map(|i| {
+                let x = f64::from(i) * 10.0;
+                idx.insert(Aabb2D::new(x, 0.0, x + 5.0, 5.0), i)
+            })
+            .collect();
+        let _ = idx.commit();
+        idx.remove(keys[1]);
+        idx.update(keys[3], Aabb2D::new(100.0, 100.0, 101.0, 101.0));
+        let _ = idx.commit();
+        let reused = idx.insert(Aabb2D::new(7.0, 7.0, 8.0, 8.0), 9);
+        assert_eq!(reused.idx(), keys[1].idx());
+
+        let query = [keys[4], keys[1], reused, keys[3], keys[0], keys[1]];
+        let got = idx.get_many(&query);
+        assert_eq!(
+            got,
+            vec![
+                Some((Aabb2D::new(40.0, 0.0, 45.0, 5.0), 4)),
+                None,
+                Some((Aabb2D::new(7.0, 7.0, 8.0, 8.0), 9)),
+                Some((Aabb2D::new(100.0, 100.0, 101.0, 101.0), 3)),
+                Some((Aabb2D::new(0.0, 0.0, 5.0, 5.0), 0)),
+                None,
+            ]
+        );
+        assert!(query.iter().zip(&got).all(|(&k, g)| idx.get(k) == *g));
+        assert!(idx.get_many(&[]).is_empty());
+    }
+
+    #[test]
+    fn retain_removes_rejected_entries_with_damage() {
+        let mut idx = Index::<i64, u32>::with_rtree();
+        let keys: Vec<_> = (0..6_u32)
+            .map(|i| {
+                let x = i64::from(i) * 100;
+                idx.insert(Aabb2D::new(x, 0, x + 10, 10), i)
+            })
+            .collect();
+        let _ = idx.commit();
+        let fresh = idx.insert(Aabb2D::new(900, 0, 910, 10), 9);
+
+        let screen = Aabb2D::new(0, 0, 250, 10);
+        let mut visited = 0;
+        idx.retain(|_, aabb, _| {
+            visited += 1;
+            !screen.intersect(aabb).is_empty()
+        });
+        assert_eq!(visited, 7);
+        assert_eq!(idx.len(), 3);
+        assert!(!idx.contains_key(fresh));
+        assert!(keys[..3].iter().all(|&k| idx.contains_key(k)));
+
+        let dmg = idx.commit();
+        let expected = Damage {
+            removed: (3..6_i64)
+                .map(|i| Aabb2D::new(i * 100, 0, i * 100 + 10, 10))
+                .collect(),
+            ..Damage::default()
         };
-        let mut pairs: Vec<(usize, Aabb2D<f32>)> = Vec::with_capacity(entries.len());
-        for (i, (aabb, payload)) in entries.iter().copied().enumerate() {
-            idx.entries.push(Some(Entry {
-                generation: 1,
-                aabb,
-                payload,
-                mark: None,
-                prev_aabb: None,
-            }));
-            pairs.push((i, aabb));
+        assert!(
+            dmg.eq_unordered(&expected),
+            "{:?}",
+            dmg.diff_unordered(&expected)
+        );
+        let mut left: Vec<u32> = idx
+            .query_rect(Aabb2D::new(0, 0, 1000, 10))
+            .map(|(_, p)| p)
+            .collect();
+        left.sort_unstable();
+        assert_eq!(left, [0, 1, 2]);
+    }
+
+    #[test]
+    fn len_counts_present_entries() {
+        let mut idx = Index::<i64, u32>::new();
+        assert!(idx.is_empty());
+        let keys: Vec<_> = (0..5_i64)
+            .map(|i| idx.insert(Aabb2D::new(i, i, i + 1, i + 1), 0))
+            .collect();
+        // Uncommitted inserts count.
+        assert_eq!(idx.len(), 5);
+        let _ = idx.commit();
+        assert_eq!(idx.len(), 5);
+        idx.remove(keys[0]);
+        idx.remove(keys[1]);
+        // Pending removals do not.
+        assert_eq!(idx.len(), 3);
+        let _ = idx.commit();
+        assert_eq!(idx.len(), 3);
+        // Freed slots still count toward capacity until they are reused.
+        assert_eq!(idx.capacity(), 5);
+        let _ = idx.insert(Aabb2D::new(0, 0, 1, 1), 0);
+        assert_eq!(idx.capacity(), 5);
+        idx.clear();
+        assert!(idx.is_empty());
+        assert_eq!(idx.capacity(), 0);
+    }
+
+    #[test]
+    fn contains_key_tracks_liveness() {
+        let mut idx = Index::<i64, u32>::new();
+        let a = idx.insert(Aabb2D::new(0, 0, 10, 10), 1);
+        assert!(idx.contains_key(a));
+        let _ = idx.commit();
+        assert!(idx.contains_key(a));
+        idx.remove(a);
+        assert!(!idx.contains_key(a));
+        let _ = idx.commit();
+        assert!(!idx.contains_key(a));
+        let b = idx.insert(Aabb2D::new(0, 0, 10, 10), 2);
+        assert_eq!(b.idx(), a.idx());
+        assert!(idx.contains_key(b));
+        assert!(!idx.contains_key(a));
+        // Keys for slots the index never had are not live either.
+        assert!(!idx.contains_key(Key::new(99, 0)));
+    }
+
+    #[test]
+    fn nearest_grid_maps_samples_to_closest_box() {
+        let mut idx = Index::<f64, u32>::with_rtree();
+        assert_eq!(
+            idx.nearest_grid(Aabb2D::new(0.0, 0.0, 10.0, 0.0), 5.0),
+            [[None, None, None]]
+        );
+        let a = idx.insert(Aabb2D::new(0.0, 0.0, 10.0, 10.0), 0);
+        let b = idx.insert(Aabb2D::new(90.0, 0.0, 100.0, 10.0), 1);
+        let c = idx.insert(Aabb2D::new(45.0, 90.0, 55.0, 100.0), 2);
+        let _ = idx.commit();
+
+        let grid = idx.nearest_grid(Aabb2D::new(0.0, 0.0, 100.0, 100.0), 10.0);
+        assert_eq!(grid.len(), 11);
+        assert!(grid.iter().all(|row| row.len() == 11));
+        // Rows are y, columns are x.
+        assert_eq!(grid[0][0], Some(a));
+        assert_eq!(grid[1][2], Some(a));
+        assert_eq!(grid[0][10], Some(b));
+        assert_eq!(grid[2][8], Some(b));
+        assert_eq!(grid[10][5], Some(c));
+        assert_eq!(grid[8][4], Some(c));
+
+        // Steps below the coordinate spacing and unbounded regions still terminate.
+        let row = &idx.nearest_grid(Aabb2D::new(1e17, 0.0, 1e17 + 64.0, 0.0), 1.0)[0];
+        assert_eq!(row.len(), 65);
+        let unbounded = idx.nearest_grid(Aabb2D::new(0.0, 0.0, f64::INFINITY, 1.0), 1.0);
+        assert!(unbounded.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn is_rect_covered_unions_opaque_boxes() {
+        let q = Aabb2D::new(10, 10, 20, 20);
+        let mut idx: Index<i64, bool> = Index::new();
+        let _ = idx.insert(Aabb2D::new(0, 0, 30, 30), true);
+        let _ = idx.commit();
+        assert!(idx.is_rect_covered(q, |&o| o));
+
+        // Two halves meeting at x = 15 cover the rect; a translucent box does not count.
+        let mut idx: Index<i64, bool> = Index::new();
+        let _ = idx.insert(Aabb2D::new(0, 0, 15, 30), true);
+        let _ = idx.insert(Aabb2D::new(15, 0, 30, 30), true);
+        let _ = idx.insert(Aabb2D::new(0, 0, 30, 30), false);
+        let _ = idx.commit();
+        assert!(idx.is_rect_covered(q, |&o| o));
+
+        // A one-unit gap between the halves.
+        let mut idx: Index<i64, bool> = Index::new();
+        let _ = idx.insert(Aabb2D::new(0, 0, 14, 30), true);
+        let _ = idx.insert(Aabb2D::new(15, 0, 30, 30), true);
+        let _ = idx.commit();
+        assert!(!idx.is_rect_covered(q, |&o| o));
+    }
+
+    #[test]
+    fn query_point_buf_matches_allocating_query_and_reports_truncation() {
+        let mut idx = Index::<i64, u32>::with_rtree();
+        for i in 0..5_u32 {
+            let c = i64::from(i);
+            let _ = idx.insert(Aabb2D::new(c, c, 10, 10), i);
         }
-        idx.backend = crate::backends::rtree::RTreeF32::bulk_build_default(&pairs);
-        idx
+        let _ = idx.insert(Aabb2D::new(20, 20, 30, 30), 99);
+        let _ = idx.commit();
+        let full: Vec<_> = idx.query_point(5, 5).collect();
+        assert_eq!(full.len(), 5);
+
+        let blank = (Key::new(0, 0), 0);
+        let mut big = [blank; 8];
+        assert_eq!(idx.query_point_buf(5, 5, &mut big), 5);
+        assert_eq!(&big[..5], &full[..]);
+
+        let mut small = [blank; 3];
+        assert_eq!(
+            idx.query_point_buf(5, 5, &mut small),
+            5,
+            "count exceeds capacity"
+        );
+        assert_eq!(&small[..], &full[..3]);
+
+        let mut slots = [0_usize; 2];
+        assert_eq!(idx.backend.query_point_into_buf(25, 25, &mut slots), 1);
+        assert_eq!(slots[0], 5);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use alloc::vec::Vec;
+    #[test]
+    fn insert_near_is_query_equivalent() {
+        let mut near = Index::<f64, u32>::with_bvh();
+        let mut plain = Index::<f64, u32>::with_bvh();
+        let mut prev = near.insert(Aabb2D::new(0.0, 0.0, 200.0, 10.0), 0);
+        let _ = plain.insert(Aabb2D::new(0.0, 0.0, 200.0, 10.0), 0);
+        let _ = near.commit();
+        for i in 1..100_u32 {
+            let x = f64::from(i) * 2.0;
+            let b = Aabb2D::new(x, 2.0, x + 1.0, 3.0);
+            prev = near.insert_near(b, i, prev);
+            let _ = plain.insert(b, i);
+            if i % 10 == 0 {
+                let _ = near.commit();
+            }
+        }
+        // A stale hint is ignored.
+        near.remove(prev);
+        let _ = near.commit();
+        let _ = near.insert_near(Aabb2D::new(50.0, 50.0, 51.0, 51.0), 500, prev);
+        let _ = near.commit();
+        let _ = plain.insert(Aabb2D::new(50.0, 50.0, 51.0, 51.0), 500);
+        let _ = plain.commit();
+        plain.remove(Key::new(99, 1));
+        let _ = plain.commit();
+
+        for q in [
+            Aabb2D::new(0.0, 0.0, 300.0, 300.0),
+            Aabb2D::new(40.0, 0.0, 60.0, 2.5),
+        ] {
+            let mut a: Vec<u32> = near.query_rect(q).map(|(_, p)| p).collect();
+            let mut b: Vec<u32> = plain.query_rect(q).map(|(_, p)| p).collect();
+            a.sort_unstable();
+            b.sort_unstable();
+            assert_eq!(a, b);
+        }
+
+        // Hinted inserts test fewer node bounds per insert than descending from the root.
+        // (The hinted index never applied the insert it removed before committing.)
+        #[cfg(feature = "stats")]
+        {
+            let (n, p) = (near.take_query_stats(), plain.take_query_stats());
+            assert_eq!((n.inserts, p.inserts), (100, 101));
+            assert!(
+                n.insert_nodes_visited * p.inserts < p.insert_nodes_visited * n.inserts,
+                "hinted {n:?}, plain {p:?}"
+            );
+        }
+    }
 
     #[test]
-    fn insert_update_commit_and_query() {
-        let mut idx: Index<i64, u32> = Index::new();
+    fn query_capsule_uses_exact_distance() {
+        let mut idx: Index<f64, u32> = Index::new();
+        let _ = idx.insert(Aabb2D::new(10.0, -9.0, 20.0, -4.0), 1); // 4 above
+        let _ = idx.insert(Aabb2D::new(50.0, 3.0, 60.0, 8.0), 2); // 3 below
+        let _ = idx.insert(Aabb2D::new(30.0, 6.0, 40.0, 10.0), 3); // 6 below
+        let _ = idx.insert(Aabb2D::new(104.0, 4.0, 110.0, 10.0), 4); // ~5.66 past the end
+        let _ = idx.insert(Aabb2D::new(70.0, -1.0, 72.0, 1.0), 5); // on the centerline
+        let _ = idx.commit();
+
+        let mut hits: Vec<u32> = idx
+            .query_capsule(0.0, 0.0, 100.0, 0.0, 5.0)
+            .map(|(_, p)| p)
+            .collect();
+        hits.sort_unstable();
+        assert_eq!(hits, vec![1, 2, 5]);
+    }
+
+    #[test]
+    fn nearest_in_direction_prefers_straight_neighbor() {
+        // 3x3 grid of 10x10 cells on a 20-unit pitch; payload is row * 3 + col.
+        let mut idx: Index<f64, u32> = Index::new();
+        let mut cells = Vec::new();
+        for row in 0..3_u32 {
+            for col in 0..3_u32 {
+                let (x, y) = (f64::from(col) * 20.0, f64::from(row) * 20.0);
+                let aabb = Aabb2D::new(x, y, x + 10.0, y + 10.0);
+                let _ = idx.insert(aabb, row * 3 + col);
+                cells.push(aabb);
+            }
+        }
+        let _ = idx.commit();
+
+        let center = cells[4];
+        assert_eq!(idx.nearest_in_direction(center, (1.0, 0.0)).unwrap().1, 5);
+        assert_eq!(idx.nearest_in_direction(center, (-1.0, 0.0)).unwrap().1, 3);
+        assert_eq!(idx.nearest_in_direction(center, (0.0, 5.0)).unwrap().1, 7);
+        // From the left column, "right" still picks the same-row neighbor, not a diagonal.
+        assert_eq!(idx.nearest_in_direction(cells[3], (1.0, 0.0)).unwrap().1, 4);
+        // Nothing further right of the right column, and a zero direction finds nothing.
+        assert!(idx.nearest_in_direction(cells[5], (1.0, 0.0)).is_none());
+        assert!(idx.nearest_in_direction(center, (0.0, 0.0)).is_none());
+    }
+
+    type SmallIndex = IndexGeneric<i64, u32, crate::backends::flatvec::FlatVec<i64>, u16>;
+
+    #[test]
+    fn u16_keys_are_half_size_and_bump_generation() {
+        assert_eq!(size_of::<Key<u16>>(), 4);
+        assert_eq!(size_of::<Key>(), 8);
+
+        let mut idx = SmallIndex::new();
         let k1 = idx.insert(Aabb2D::new(0, 0, 10, 10), 1);
         let _ = idx.commit();
-        idx.update(k1, Aabb2D::new(5, 5, 15, 15));
-        let dmg = idx.commit();
-        assert!(!dmg.is_empty());
+        idx.remove(k1);
+        let _ = idx.commit();
+        let k2 = idx.insert(Aabb2D::new(0, 0, 10, 10), 2);
+        let _ = idx.commit();
+        assert_eq!(alloc::format!("{k2}"), "#0@g2");
+        assert_ne!(k1, k2);
 
-        let hits: Vec<_> = idx.query_point(6, 6).collect();
-        assert_eq!(hits.len(), 1);
-        assert_eq!(hits[0].1, 1);
+        // The stale key no longer addresses the reused slot.
+        idx.update(k1, Aabb2D::new(50, 50, 60, 60));
+        idx.remove(k1);
+        let _ = idx.commit();
+        let hits: Vec<_> = idx.query_point(5, 5).collect();
+        assert_eq!(hits, vec![(k2, 2)]);
+    }
+
+    #[test]
+    fn u16_generation_wraps_at_width_limit() {
+        let mut idx = SmallIndex::new();
+        let mut key = idx.insert(Aabb2D::new(0, 0, 1, 1), 0);
+        while key.1 < u16::MAX {
+            idx.remove(key);
+            key = idx.insert(Aabb2D::new(0, 0, 1, 1), 0);
+        }
+        assert_eq!(key, Key(0, u16::MAX));
+        let _ = idx.commit();
+
+        idx.remove(key);
+        let _ = idx.commit();
+        let wrapped = idx.insert(Aabb2D::new(0, 0, 1, 1), 1);
+        let _ = idx.commit();
+        assert_eq!(wrapped, Key(0, 1));
+
+        // The key from just before the wrap is stale.
+        idx.remove(key);
+        let _ = idx.commit();
+        assert_eq!(
+            idx.query_point(0, 0).collect::<Vec<_>>(),
+            vec![(wrapped, 1)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "index is full")]
+    fn u16_index_panics_past_slot_limit() {
+        let mut idx = SmallIndex::new();
+        for _ in 0..=usize::from(u16::MAX) {
+            let _ = idx.insert(Aabb2D::new(0, 0, 1, 1), 0);
+        }
+        // Slot 65536 does not fit in a u16 key.
+        let _ = idx.insert(Aabb2D::new(0, 0, 1, 1), 0);
+    }
+
+    #[test]
+    fn accumulated_damage_coalesces_moves() {
+        let a = Aabb2D::new(0, 0, 10, 10);
+        let b = Aabb2D::new(20, 0, 30, 10);
+        let c = Aabb2D::new(40, 0, 50, 10);
+        let mut idx: Index<i64, u32> = Index::new();
+        let k = idx.insert(a, 1);
+        let gone = idx.insert(Aabb2D::new(0, 50, 10, 60), 2);
+        let _ = idx.commit();
+
+        idx.set_accumulate_damage(true);
+        idx.update(k, b);
+        let _ = idx.commit();
+        idx.update(k, c);
+        let transient = idx.insert(Aabb2D::new(90, 90, 95, 95), 3);
+        let _ = idx.commit();
+        idx.remove(transient);
+        idx.remove(gone);
+        let d = Aabb2D::new(70, 0, 80, 10);
+        let _ = idx.insert(d, 4);
+        let _ = idx.commit();
+
+        let dmg = idx.take_accumulated_damage();
+        assert_eq!(dmg.moved, vec![(a, c)]);
+        assert_eq!(dmg.added, vec![d]);
+        assert_eq!(dmg.removed, vec![Aabb2D::new(0, 50, 10, 60)]);
+        assert!(idx.take_accumulated_damage().is_empty());
+
+        // Disabled: nothing accumulates.
+        idx.set_accumulate_damage(false);
+        idx.update(k, a);
+        let _ = idx.commit();
+        assert!(idx.take_accumulated_damage().is_empty());
+    }
+
+    fn check_query_points<B: Backend<f64>>(mut idx: IndexGeneric<f64, u32, B>) {
+        // Overlapping 10x10 grid on an 8-unit pitch.
+        for i in 0..400_u32 {
+            let (x, y) = (f64::from(i % 20) * 8.0, f64::from(i / 20) * 8.0);
+            let _ = idx.insert(Aabb2D::new(x, y, x + 10.0, y + 10.0), i);
+        }
+        let _ = idx.commit();
+        // A tight stroke-like cluster plus a few scattered and out-of-range points.
+        let mut pts: Vec<(f64, f64)> = (0..24)
+            .map(|i| (40.0 + f64::from(i) * 0.7, 40.0 + f64::from(i) * 0.3))
+            .collect();
+        pts.extend([(1.0, 1.0), (150.0, 3.0), (500.0, 500.0), (9.0, 9.0)]);
+
+        let batched = idx.query_points(&pts);
+        assert_eq!(batched.len(), pts.len());
+        for (&(x, y), got) in pts.iter().zip(batched) {
+            let mut got = got;
+            let mut want: Vec<_> = idx.query_point(x, y).collect();
+            got.sort_unstable_by_key(|&(_, p)| p);
+            want.sort_unstable_by_key(|&(_, p)| p);
+            assert_eq!(got, want, "mismatch at ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn query_points_matches_individual_queries() {
+        check_query_points(Index::<f64, u32>::new());
+        check_query_points(Index::<f64, u32>::with_rtree());
+        check_query_points(Index::<f64, u32>::with_bvh());
     }
 
     #[test]
@@ -427,6 +3795,44 @@ mod tests {
         assert_eq!(b, Aabb2D::new(5, 5, 15, 15));
     }
 
+    #[test]
+    fn rect_query_cursor_reports_only_new_entries() {
+        let mut idx: Index<i64, u32> = Index::new();
+        // A row of boxes revealed left to right by a growing rect.
+        for i in 0..5_u32 {
+            let x = i64::from(i) * 20;
+            let _ = idx.insert(Aabb2D::new(x, 0, x + 10, 10), i);
+        }
+        let _ = idx.commit();
+
+        let mut cursor = idx.rect_query_cursor();
+        let mut step = |max_x: i64| {
+            let mut p: Vec<u32> = cursor
+                .advance(Aabb2D::new(0, 0, max_x, 10))
+                .into_iter()
+                .map(|(_, p)| p)
+                .collect();
+            p.sort_unstable();
+            p
+        };
+        assert_eq!(step(5), vec![0]);
+        assert_eq!(step(25), vec![1]);
+        assert_eq!(step(30), Vec::<u32>::new());
+        assert_eq!(step(65), vec![2, 3]);
+        assert_eq!(step(100), vec![4]);
+
+        // A rect that does not contain the previous one starts over.
+        let mut p: Vec<u32> = cursor
+            .advance(Aabb2D::new(15, 0, 45, 10))
+            .into_iter()
+            .map(|(_, p)| p)
+            .collect();
+        p.sort_unstable();
+        assert_eq!(p, vec![1, 2]);
+        cursor.reset();
+        assert!(cursor.previous().is_none());
+    }
+
     #[test]
     fn visit_point_and_rect_match_query_counts() {
         let mut idx: Index<i64, u32> = Index::new();
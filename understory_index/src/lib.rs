@@ -11,13 +11,15 @@
 //! - Insert, update, and remove axis-aligned bounding boxes (AABBs) with user payloads.
 //! - Query by point or intersecting rectangle.
 //! - Batch updates with [`Index::commit`] and receive coarse damage (added/removed/moved boxes).
+//! - Keep static content in packed trees beside a dynamic index with [`TieredIndex`], a
+//!   wrapper so the static tier stays out of `IndexGeneric`'s backend and damage model.
 //!
 //! It is generic over the scalar type `T` and does not depend on any geometry crate.
 //! Higher layers (like a scene or region tree) can compute world-space AABBs and feed them here.
 //!
 //! Backends are pluggable via a simple trait so you can swap the spatial strategy without API churn.
 //! The default backend is a flat vector (linear scan).
-//! R-tree and BVH backends are generic over the scalar and use widened accumulator types (f32→f64, f64→f64, i64→i128) for SAH-like splits.
+//! R-tree and BVH backends are generic over the scalar and use widened accumulator types (f32→f64, f64→f64, i32→i64, i64→i128) for SAH-like splits.
 //!
 //! # Example
 //!
@@ -60,16 +62,39 @@
 //!
 //! - `FlatVec` (default): simplest and smallest, linear scans. Good for very small sets
 //!   or when inserts/updates vastly outnumber queries.
-//! - `RTreeF32`/`RTreeF64`/`RTreeI64`: R-tree with SAH-like splits and widened metrics; good
+//! - `RTreeF32`/`RTreeF64`/`RTreeI32`/`RTreeI64`: R-tree with SAH-like splits and widened metrics; good
 //!   general-purpose index when distribution is irregular and updates are frequent.
 //!   See the [`backends`] docs for a brief SAH overview.
-//! - `BvhF32`/`BvhF64`/`BvhI64`: binary hierarchy with SAH-like splits; excels when bulk-build
+//! - `BvhF32`/`BvhF64`/`BvhI32`/`BvhI64`: binary hierarchy with SAH-like splits; excels when bulk-build
 //!   and query performance matter; updates are supported but may be costlier than R-tree.
+//! - `QuadtreeF32`/`QuadtreeF64`/`QuadtreeI64`: region quadtree over fixed bounds; nodes split
+//!   lazily on overflow. Cheap updates for small, evenly spread boxes in a known world area
+//!   (`Index::with_quadtree`).
+//! - `HashGridF32`/`HashGridF64`/`HashGridI64`: unbounded uniform grid; fast updates when boxes
+//!   are about one cell in size and spread over a large or open-ended area
+//!   (`Index::with_hash_grid`).
+//! - `AnyBackend`: one of the above, picked at runtime by `BackendKind`. Switch an existing
+//!   index with `IndexGeneric::replace_backend` once its size calls for a different backend.
 //!
 //! ### Float semantics
 //!
 //! This crate assumes no NaNs for floating-point coordinates. Debug builds may assert.
 //! SAH metrics use widened accumulators to reduce precision pitfalls.
+//!
+//! ### Profiling
+//!
+//! The `stats` feature makes the built-in backends count tree nodes visited and leaf
+//! items tested per query, and the R-tree and BVH count node bounds tested per insert;
+//! read and reset the counts with `IndexGeneric::take_query_stats`.
+//! Independently of the feature, `IndexGeneric::stats` reports the backend's node, leaf
+//! and item counts and its depth.
+//!
+//! ### Persistence
+//!
+//! `IndexGeneric::snapshot` captures the entries, keys and free slots without the backend;
+//! `IndexGeneric::from_snapshot` validates and restores them and bulk-loads a backend. The
+//! `serde` feature implements `Serialize`/`Deserialize` for `Aabb2D`, `Key`, `Damage`, snapshots, and
+//! `IndexGeneric` itself (as its snapshot, deserializing into a default backend).
 
 #![no_std]
 
@@ -79,15 +104,27 @@ pub mod backend;
 pub mod backends;
 pub mod damage;
 pub mod index;
+pub mod stats;
+pub mod tiered;
 pub mod types;
 
-pub use backend::Backend;
-pub use backends::bvh::{BvhF32, BvhF64, BvhI64};
+pub use backend::{Backend, GroupId};
+pub use backends::any::{AnyBackend, BackendKind};
+pub use backends::bvh::{BvhF32, BvhF64, BvhI32, BvhI64};
 pub use backends::flatvec::FlatVec;
-pub use backends::rtree::{RTreeF32, RTreeF64, RTreeI64};
+pub use backends::hashgrid::{GridScalar, HashGridF32, HashGridF64, HashGridI64};
+pub use backends::quadtree::{QuadtreeF32, QuadtreeF64, QuadtreeI64};
+pub use backends::rtree::{
+    Fanout, FixedFanout, RTree8, RTree16, RTreeF32, RTreeF64, RTreeI32, RTreeI64, RuntimeFanout,
+};
 pub use damage::Damage;
-pub use index::{Index, IndexGeneric, Key};
-pub use types::Aabb2D;
+pub use index::{
+    Index, IndexGeneric, IndexSnapshot, Key, KeyIndex, MaintenanceBudget, RectQueryCursor,
+    SlotRemap, SnapshotEntry, SnapshotError, ViewTransformed,
+};
+pub use stats::{BackendStats, QueryStats};
+pub use tiered::{TieredIndex, TieredKey};
+pub use types::{Aabb2D, Axis, tile_ranges_for_box, tiles_for_box, try_area};
 
 #[cfg(test)]
 mod tests {
@@ -108,6 +145,338 @@ mod tests {
         assert_eq!(hits[0].1, 1);
     }
 
+    #[test]
+    fn try_area_and_sah_cost_detect_overflow() {
+        use crate::types::{area, sah_cost};
+
+        // The widest i64 box: its width saturates at i64::MAX, so the area itself
+        // still fits in i128, but weighting it for SAH does not.
+        let huge = Aabb2D::new(i64::MIN, i64::MIN, i64::MAX, i64::MAX);
+        let max = i128::from(i64::MAX);
+        assert_eq!(try_area(&huge), Some(max * max));
+        assert_eq!(try_area(&huge), Some(area(&huge)));
+        assert_eq!(sah_cost(&huge, 2, &huge, 2), None);
+        assert_eq!(
+            <i64 as types::Scalar>::checked_mul_acc(i128::MAX, 2),
+            None::<i128>
+        );
+        assert!(try_area(&Aabb2D::new(0.0_f64, 0.0, f64::MAX, f64::MAX)).is_some());
+
+        // Trees still build and answer queries when every SAH candidate overflows.
+        let mut rtree = Index::<i64, u32>::with_rtree();
+        let mut bvh: IndexGeneric<i64, u32, BvhI64> = IndexGeneric::with_backend(BvhI64::default());
+        for i in 0..32 {
+            let _ = rtree.insert(huge, i);
+            let _ = bvh.insert(huge, i);
+        }
+        let _ = rtree.commit();
+        let _ = bvh.commit();
+        assert_eq!(rtree.query_point(0, 0).count(), 32);
+        assert_eq!(bvh.query_point(0, 0).count(), 32);
+    }
+
+    #[test]
+    fn i32_scalar_widens_to_i64_for_sah() {
+        use crate::types::{area, sah_cost};
+
+        // Areas beyond the i32 range are exact in the i64 accumulator.
+        let b = Aabb2D::new(-100_000_i32, -100_000, 0, 0);
+        assert_eq!(area(&b), 10_000_000_000_i64);
+        assert_eq!(sah_cost(&b, 3, &b, 1), Some(40_000_000_000_i64));
+        let huge = Aabb2D::new(i32::MIN, i32::MIN, i32::MAX, i32::MAX);
+        let max = i64::from(i32::MAX);
+        assert_eq!(try_area(&huge), Some(max * max));
+        assert_eq!(sah_cost(&huge, 2, &huge, 2), None);
+        assert_eq!(<i32 as types::Scalar>::mid(i32::MIN, i32::MAX), -1);
+        assert_eq!(<i32 as types::Scalar>::mid(-7, -2), -5);
+
+        let mut rtree = Index::<i32, u32>::with_rtree();
+        let mut bvh = Index::<i32, u32>::with_bvh();
+        for i in 0..64_i32 {
+            let (x, y) = (-640 + (i % 8) * 160, -640 + (i / 8) * 160);
+            let r = Aabb2D::new(x, y, x + 100, y + 100);
+            let _ = rtree.insert(r, i.unsigned_abs());
+            let _ = bvh.insert(r, i.unsigned_abs());
+        }
+        let _ = rtree.commit();
+        let _ = bvh.commit();
+        for idx_hits in [
+            rtree
+                .query_point(-600, -600)
+                .map(|(_, p)| p)
+                .collect::<Vec<_>>(),
+            bvh.query_point(-600, -600)
+                .map(|(_, p)| p)
+                .collect::<Vec<_>>(),
+        ] {
+            assert_eq!(idx_hits, [0]);
+        }
+        let q = Aabb2D::new(-500, -500, -100, -100);
+        let mut r: Vec<u32> = rtree.query_rect(q).map(|(_, p)| p).collect();
+        let mut b: Vec<u32> = bvh.query_rect(q).map(|(_, p)| p).collect();
+        r.sort_unstable();
+        b.sort_unstable();
+        assert_eq!(r, [9, 10, 11, 17, 18, 19, 25, 26, 27]);
+        assert_eq!(r, b);
+    }
+
+    #[test]
+    fn quadrants_tile_and_split_axis_respects_position() {
+        let b = Aabb2D::new(0_i64, 0, 9, 4);
+        let [nw, ne, sw, se] = b.quadrants();
+        assert_eq!(nw, Aabb2D::new(0, 0, 4, 2));
+        assert_eq!(ne, Aabb2D::new(4, 0, 9, 2));
+        assert_eq!(sw, Aabb2D::new(0, 2, 4, 4));
+        assert_eq!(se, Aabb2D::new(4, 2, 9, 4));
+        assert_eq!(types::union_aabb(nw, se), b);
+        let total: i128 = b.quadrants().iter().map(types::area).sum();
+        assert_eq!(total, types::area(&b));
+
+        let f = Aabb2D::new(-1.0_f64, 0.0, 1.0, 3.0);
+        let [nw, .., se] = f.quadrants();
+        assert_eq!(nw, Aabb2D::new(-1.0, 0.0, 0.0, 1.5));
+        assert_eq!(se, Aabb2D::new(0.0, 1.5, 1.0, 3.0));
+
+        assert_eq!(
+            b.split_axis(Axis::X, 3),
+            (Aabb2D::new(0, 0, 3, 4), Aabb2D::new(3, 0, 9, 4))
+        );
+        assert_eq!(
+            f.split_axis(Axis::Y, 0.5),
+            (
+                Aabb2D::new(-1.0, 0.0, 1.0, 0.5),
+                Aabb2D::new(-1.0, 0.5, 1.0, 3.0)
+            )
+        );
+        // Out-of-range positions are clamped into the box.
+        assert_eq!(b.split_axis(Axis::Y, 10).1, Aabb2D::new(0, 4, 9, 4));
+    }
+
+    #[test]
+    fn integer_from_xywh_saturates_on_overflow() {
+        let x = i64::MAX - 10;
+        let a = Aabb2D::<i64>::from_xywh(x, 0, 100, i64::MAX);
+        assert_eq!(a, Aabb2D::new(x, 0, i64::MAX, i64::MAX));
+        assert!(!a.is_empty());
+        assert_eq!(Aabb2D::<i64>::try_from_xywh(x, 0, 100, 5), None);
+        assert_eq!(Aabb2D::<i64>::try_from_xywh(0, x, 5, 100), None);
+        assert_eq!(
+            Aabb2D::<i64>::try_from_xywh(x, 0, 10, 5),
+            Some(Aabb2D::new(x, 0, i64::MAX, 5))
+        );
+    }
+
+    #[test]
+    fn geometry_helpers_handle_degenerate_and_inverted_boxes() {
+        let a = Aabb2D::new(0_i64, 0, 10, 4);
+        let b = Aabb2D::new(5_i64, -2, 7, 1);
+        assert_eq!(a.union(b), Aabb2D::new(0, -2, 10, 4));
+        assert_eq!(a.area(), 40_i128);
+        assert_eq!(a.center(), (5, 2));
+        assert_eq!(Aabb2D::new(-3_i64, -3, 0, 0).center(), (-2, -2));
+        assert!(a.contains_aabb(&Aabb2D::new(0, 0, 10, 0)));
+        assert_eq!(a.expand(1, 2), Aabb2D::new(-1, -2, 11, 6));
+        assert_eq!(a.expand(-2, -1), Aabb2D::new(2, 1, 8, 3));
+        // Integer growth saturates instead of wrapping.
+        let edge = Aabb2D::new(i64::MIN + 1, 0, i64::MAX - 1, 0);
+        assert_eq!(edge.expand(5, 0), Aabb2D::new(i64::MIN, 0, i64::MAX, 0));
+
+        // A point is degenerate: no area, but it still unions and is contained.
+        let p = Aabb2D::new(2.0_f64, 3.0, 2.0, 3.0);
+        assert_eq!(p.area(), 0.0);
+        assert_eq!(p.center(), (2.0, 3.0));
+        assert!(!p.is_empty());
+        assert_eq!(p.union(p), p);
+        assert_eq!(p.expand(1.0, 0.5), Aabb2D::new(1.0, 2.5, 3.0, 3.5));
+        assert!(Aabb2D::new(0.0, 0.0, 4.0, 4.0).contains_aabb(&p));
+
+        // Inverted boxes have no area and contain nothing; union takes edges as given.
+        let inv = Aabb2D::new(4.0_f64, 4.0, 1.0, 1.0);
+        assert!(inv.is_empty());
+        assert_eq!(inv.area(), 0.0);
+        assert_eq!(inv.center(), (2.5, 2.5));
+        assert!(!inv.contains_aabb(&p));
+        assert_eq!(inv.union(p), Aabb2D::new(2.0, 3.0, 2.0, 3.0));
+        assert_eq!(
+            inv.union(Aabb2D::new(0.0, 0.0, 5.0, 2.0)),
+            Aabb2D::new(0.0, 0.0, 5.0, 2.0)
+        );
+        // Shrinking past the center inverts the box.
+        assert!(p.expand(-1.0, 0.0).is_empty());
+    }
+
+    #[test]
+    fn bounds_from_points_and_boxes() {
+        let tri = [(3.0_f64, -1.0), (-2.0, 4.0), (0.5, 0.5)];
+        assert_eq!(
+            Aabb2D::from_points(tri),
+            Some(Aabb2D::new(-2.0, -1.0, 3.0, 4.0))
+        );
+        assert_eq!(
+            Aabb2D::from_points([(7_i64, 8)]),
+            Some(Aabb2D::new(7, 8, 7, 8))
+        );
+        assert_eq!(Aabb2D::<f64>::from_points([]), None);
+        // Negative zero compares equal to zero and does not disturb the bounds.
+        assert_eq!(
+            Aabb2D::from_points([(0.0_f64, -0.0), (-0.0, 0.0)]).map(|b| b.area()),
+            Some(0.0)
+        );
+
+        let parts = [
+            Aabb2D::new(0_i32, 0, 2, 2),
+            Aabb2D::new(5, -3, 6, 1),
+            Aabb2D::new(1, 1, 1, 1),
+        ];
+        assert_eq!(Aabb2D::from_iter(parts), Some(Aabb2D::new(0, -3, 6, 2)));
+        assert_eq!(
+            Aabb2D::from_iter(parts.iter().copied().take(1)),
+            Some(parts[0])
+        );
+        assert_eq!(Aabb2D::<i32>::from_iter([]), None);
+    }
+
+    #[test]
+    fn translate_and_scale_about_origin() {
+        let a = Aabb2D::new(2_i64, 3, 6, 5);
+        assert_eq!(a.translate(-10, -4), Aabb2D::new(-8, -1, -4, 1));
+        assert_eq!(a.translate(-10, -4).translate(10, 4), a);
+        assert_eq!(
+            a.translate(i64::MAX, 0),
+            Aabb2D::new(i64::MAX, 3, i64::MAX, 5)
+        );
+        assert_eq!(a.scale_about_origin(2, 3), Aabb2D::new(4, 9, 12, 15));
+        // Negative factors mirror the box and swap its edges.
+        assert_eq!(a.scale_about_origin(-1, 2), Aabb2D::new(-6, 6, -2, 10));
+        assert_eq!(a.scale_about_origin(0, 1), Aabb2D::new(0, 3, 0, 5));
+
+        let f = Aabb2D::new(-1.0_f64, -2.0, 3.0, 0.5);
+        assert_eq!(
+            f.translate(-0.5, -10.0),
+            Aabb2D::new(-1.5, -12.0, 2.5, -9.5)
+        );
+        assert_eq!(
+            f.scale_about_origin(-2.0, -2.0),
+            Aabb2D::new(-6.0, -1.0, 2.0, 4.0)
+        );
+        assert_eq!(f.scale_about_origin(0.5, 2.0).area(), f.area());
+        // An inverted box stays inverted under mirroring.
+        let inv = Aabb2D::new(1.0_f64, 1.0, 0.0, 0.0);
+        assert!(inv.scale_about_origin(-1.0, 2.0).is_empty());
+    }
+
+    #[test]
+    fn tiles_for_box_inverts_tiled_queries() {
+        let tiles = |a, o| tiles_for_box(a, 256.0, 256.0, o).collect::<Vec<_>>();
+        // Spans two tiles horizontally.
+        let a = Aabb2D::new(200.0, 10.0, 300.0, 20.0);
+        assert_eq!(tiles(a, (0.0, 0.0)), [(0, 0), (1, 0)]);
+        // Negative coordinates and a shifted origin.
+        let b = Aabb2D::new(-10.0, -10.0, -5.0, -5.0);
+        assert_eq!(tiles(b, (0.0, 0.0)), [(-1, -1)]);
+        assert_eq!(tiles(b, (-100.0, -100.0)), [(0, 0)]);
+        // Touching an edge counts, as it does for rect queries.
+        let c = Aabb2D::new(100.0, 100.0, 256.0, 200.0);
+        assert_eq!(tiles(c, (0.0, 0.0)), [(0, 0), (1, 0)]);
+        assert!(tiles(Aabb2D::new(5.0, 5.0, 1.0, 1.0), (0.0, 0.0)).is_empty());
+
+        // Matches querying each tile, for the boxes a commit reports as damaged.
+        let mut idx = Index::<f64, u32>::with_rtree();
+        let _ = idx.insert(a, 0);
+        let _ = idx.insert(c, 1);
+        let _ = idx.insert(Aabb2D::new(500.0, 600.0, 900.0, 700.0), 2);
+        let dmg = idx.commit();
+        let mut dirty: Vec<(i64, i64)> = dmg
+            .added
+            .iter()
+            .flat_map(|&b| tiles_for_box(b, 256.0, 256.0, (0.0, 0.0)))
+            .collect();
+        dirty.sort_unstable();
+        dirty.dedup();
+        let mut queried = Vec::new();
+        for j in -1..4_i32 {
+            for i in -1..5_i32 {
+                let (x, y) = (f64::from(i) * 256.0, f64::from(j) * 256.0);
+                let tile = Aabb2D::new(x, y, x + 256.0, y + 256.0);
+                if idx.query_rect(tile).next().is_some() {
+                    queried.push((i64::from(i), i64::from(j)));
+                }
+            }
+        }
+        queried.sort_unstable();
+        assert_eq!(dirty, queried);
+    }
+
+    #[test]
+    fn floor_and_ceil_saturate() {
+        use crate::types::{ceil_i64, floor_i64};
+
+        assert_eq!(floor_i64(-1.5), -2);
+        assert_eq!(ceil_i64(-1.5), -1);
+        assert_eq!(floor_i64(2.0), 2);
+        assert_eq!(ceil_i64(2.0), 2);
+        for (v, sat) in [
+            (1e300, i64::MAX),
+            (-1e300, i64::MIN),
+            (f64::INFINITY, i64::MAX),
+            (f64::NEG_INFINITY, i64::MIN),
+        ] {
+            assert_eq!(floor_i64(v), sat);
+            assert_eq!(ceil_i64(v), sat);
+        }
+        assert_eq!(floor_i64(f64::NAN), 0);
+        assert_eq!(ceil_i64(f64::NAN), 0);
+    }
+
+    #[test]
+    fn tiles_for_box_saturates_huge_boxes() {
+        let far_left = Aabb2D::new(-1e300, 0.0, -1e299, 1.0);
+        let tiles: Vec<_> = tiles_for_box(far_left, 256.0, 256.0, (0.0, 0.0)).collect();
+        assert_eq!(tiles, [(i64::MIN, -1), (i64::MIN, 0)]);
+        let far_right = Aabb2D::new(1e299, 0.0, 1e300, 1.0);
+        let tiles: Vec<_> = tiles_for_box(far_right, 256.0, 256.0, (0.0, 0.0)).collect();
+        assert_eq!(tiles, [(i64::MAX, -1), (i64::MAX, 0)]);
+
+        // An infinite box spans every tile; its ranges can be clamped before iterating.
+        let everywhere = Aabb2D::new(f64::NEG_INFINITY, 0.0, f64::INFINITY, 1.0);
+        let (xs, ys) = tile_ranges_for_box(everywhere, 256.0, 256.0, (0.0, 0.0));
+        assert_eq!((xs.clone(), ys), (i64::MIN..=i64::MAX, -1..=0));
+        let visible = (*xs.start()).max(0)..=(*xs.end()).min(3);
+        assert_eq!(visible.count(), 4);
+        let (xs, ys) =
+            tile_ranges_for_box(Aabb2D::new(5.0, 5.0, 1.0, 1.0), 256.0, 256.0, (0.0, 0.0));
+        assert!(xs.is_empty() && ys.is_empty());
+    }
+
+    #[test]
+    fn mtv_pushes_along_the_shorter_axis() {
+        let a = Aabb2D::new(0.0, 0.0, 10.0, 10.0);
+
+        // Horizontal overlap: `b` pokes 2 into `a` from the right.
+        let b = Aabb2D::new(8.0, 0.0, 20.0, 10.0);
+        assert_eq!(b.mtv(&a), Some((2.0, 0.0)));
+        assert_eq!(a.mtv(&b), Some((-2.0, 0.0)));
+
+        // Vertical overlap from above.
+        let c = Aabb2D::new(2.0, -7.0, 8.0, 3.0);
+        assert_eq!(c.mtv(&a), Some((0.0, -3.0)));
+
+        // Corner overlap: 3 deep in x, 1 deep in y, so push along y.
+        let d = Aabb2D::new(7.0, 9.0, 15.0, 15.0);
+        assert_eq!(d.mtv(&a), Some((0.0, 1.0)));
+        let (dx, dy) = d.mtv(&a).unwrap();
+        let moved = Aabb2D::new(d.min_x + dx, d.min_y + dy, d.max_x + dx, d.max_y + dy);
+        assert!(moved.mtv(&a).is_none());
+
+        // Touching or disjoint boxes need no push.
+        assert_eq!(Aabb2D::new(10.0, 0.0, 12.0, 5.0).mtv(&a), None);
+        assert_eq!(Aabb2D::new(30.0, 30.0, 40.0, 40.0).mtv(&a), None);
+
+        // Integer boxes work the same way.
+        let p = Aabb2D::new(0_i64, 0, 10, 10);
+        assert_eq!(Aabb2D::new(6_i64, 1, 9, 4).mtv(&p), Some((4, 0)));
+    }
+
     #[test]
     fn added_then_removed_before_commit_is_ignored() {
         let mut idx: Index<i64, u32> = Index::new();
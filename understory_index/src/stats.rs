@@ -0,0 +1,126 @@
+// Copyright 2025 the Understory Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Query traversal counters for profiling backends.
+//!
+//! Counting is compiled in only with the `stats` feature; without it the counters are
+//! zero-sized and recording is a no-op.
+
+#[cfg(feature = "stats")]
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Traversal work aggregated over point and rect queries, and over tree inserts.
+///
+/// Returned by [`IndexGeneric::take_query_stats`](crate::IndexGeneric::take_query_stats)
+/// (requires the `stats` feature). A tree whose `nodes_visited` per query approaches its
+/// entry count is over-descending and may benefit from a rebuild.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryStats {
+    /// Number of queries recorded.
+    pub queries: u64,
+    /// Tree nodes whose bounds were tested (zero for flat backends).
+    pub nodes_visited: u64,
+    /// Leaf items whose AABB was tested against the query.
+    pub items_tested: u64,
+    /// Number of inserts recorded by the R-tree and BVH backends.
+    pub inserts: u64,
+    /// Tree nodes whose bounds were tested while placing inserted boxes.
+    ///
+    /// For [`insert_near`](crate::IndexGeneric::insert_near) this includes the nodes
+    /// tested while walking up from the hint, so it shows whether hints save work over
+    /// descending from the root.
+    pub insert_nodes_visited: u64,
+}
+
+/// A snapshot of a backend's structure, for tuning.
+///
+/// Returned by [`IndexGeneric::stats`](crate::IndexGeneric::stats). Unlike [`QueryStats`]
+/// this needs no feature: backends compute it on demand by walking their structure.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BackendStats {
+    /// Tree nodes, or occupied cells for grids (zero for flat backends).
+    pub node_count: usize,
+    /// Tree nodes without children, or occupied cells for grids (zero for flat backends).
+    pub leaf_count: usize,
+    /// Levels below the root of the deepest node (zero for grids and flat backends).
+    pub max_depth: usize,
+    /// Live boxes stored, each counted once.
+    pub item_count: usize,
+    /// Allocated nodes no longer reachable, until [`Backend::compact`](crate::Backend::compact)
+    /// reclaims them.
+    pub dead_nodes: usize,
+}
+
+/// Per-backend query counters; zero-sized unless the `stats` feature is enabled.
+///
+/// With the feature enabled the counts are relaxed atomics, so backends stay `Sync` and
+/// concurrent queries each add their own work.
+#[derive(Default)]
+pub(crate) struct StatsCounter {
+    #[cfg(feature = "stats")]
+    queries: AtomicU64,
+    #[cfg(feature = "stats")]
+    nodes_visited: AtomicU64,
+    #[cfg(feature = "stats")]
+    items_tested: AtomicU64,
+    #[cfg(feature = "stats")]
+    inserts: AtomicU64,
+    #[cfg(feature = "stats")]
+    insert_nodes_visited: AtomicU64,
+}
+
+impl StatsCounter {
+    /// Record one query that tested `nodes` tree nodes and `items` leaf items.
+    #[inline]
+    pub(crate) fn record(&self, nodes: u64, items: u64) {
+        #[cfg(feature = "stats")]
+        {
+            self.queries.fetch_add(1, Ordering::Relaxed);
+            self.nodes_visited.fetch_add(nodes, Ordering::Relaxed);
+            self.items_tested.fetch_add(items, Ordering::Relaxed);
+        }
+        #[cfg(not(feature = "stats"))]
+        let _ = (nodes, items);
+    }
+
+    /// Record one insert that tested the bounds of `nodes` tree nodes.
+    #[inline]
+    pub(crate) fn record_insert(&self, nodes: u64) {
+        #[cfg(feature = "stats")]
+        {
+            self.inserts.fetch_add(1, Ordering::Relaxed);
+            self.insert_nodes_visited
+                .fetch_add(nodes, Ordering::Relaxed);
+        }
+        #[cfg(not(feature = "stats"))]
+        let _ = nodes;
+    }
+
+    /// Return the counts recorded so far and reset them.
+    #[cfg(feature = "stats")]
+    pub(crate) fn take(&self) -> QueryStats {
+        QueryStats {
+            queries: self.queries.swap(0, Ordering::Relaxed),
+            nodes_visited: self.nodes_visited.swap(0, Ordering::Relaxed),
+            items_tested: self.items_tested.swap(0, Ordering::Relaxed),
+            inserts: self.inserts.swap(0, Ordering::Relaxed),
+            insert_nodes_visited: self.insert_nodes_visited.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BvhF64, FlatVec, HashGridF64, IndexGeneric, QuadtreeF64, RTreeF64};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn backends_are_send_and_sync() {
+        assert_send_sync::<IndexGeneric<f64, u32, FlatVec<f64>>>();
+        assert_send_sync::<IndexGeneric<f64, u32, RTreeF64<u32>>>();
+        assert_send_sync::<IndexGeneric<f64, u32, BvhF64>>();
+        assert_send_sync::<IndexGeneric<f64, u32, QuadtreeF64>>();
+        assert_send_sync::<IndexGeneric<f64, u32, HashGridF64>>();
+    }
+}
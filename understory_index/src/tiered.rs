@@ -0,0 +1,372 @@
+// Copyright 2025 the Understory Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Two-tier index: packed static trees plus a dynamic backend.
+
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use crate::backend::Backend;
+use crate::backends::rtree::RTree;
+use crate::damage::Damage;
+use crate::index::{IndexGeneric, Key, KeyIndex};
+use crate::types::{Aabb2D, Scalar};
+
+/// Handle for an entry of a [`TieredIndex`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TieredKey<I: KeyIndex = u32> {
+    /// A static entry, numbered in insertion order.
+    Static(usize),
+    /// A dynamic entry, keyed by the dynamic tier.
+    Dynamic(Key<I>),
+}
+
+/// An index split into a static tier and a dynamic tier.
+///
+/// Scenes often pair a large static background with a few moving objects. Static
+/// entries, added with [`insert_static`](Self::insert_static), are never moved or removed.
+/// Each [`commit`](Self::commit) STR-packs the ones added since the last commit into a new
+/// R-tree run; a run is merged into the previous one while that is less than twice its
+/// size, so there are O(log n) runs and each static entry is repacked O(log n) times
+/// overall. Everything else goes through the dynamic tier, an ordinary [`IndexGeneric`]
+/// over backend `B`, which can be updated and [rebuilt](Self::rebuild_dynamic) without
+/// touching the static runs.
+///
+/// Queries search both tiers and merge the results: static hits first, in insertion
+/// order, then dynamic hits in backend order. The `visit_*` methods skip that merge and
+/// allocate nothing.
+///
+/// This is a wrapper rather than an `insert_static` method on [`IndexGeneric`] because
+/// the static tier does not go through the dynamic backend: it is always packed R-tree
+/// runs whatever `B` is, its entries never get generations or damage marks after they
+/// are added, and [`rebuild_dynamic`](Self::rebuild_dynamic) must leave it alone. Keeping
+/// it out of [`IndexGeneric`] leaves that type's keys, damage and backend contract as they
+/// are for indexes with no static content.
+#[derive(Debug)]
+pub struct TieredIndex<T: Scalar, P: Copy + Debug, B: Backend<T>, I: KeyIndex = u32> {
+    statics: Vec<(Aabb2D<T>, P)>,
+    // Packed runs over consecutive ranges of `statics`, oldest (and largest) first. Each
+    // tree's slots are relative to its run's first static entry.
+    runs: Vec<StaticRun<T, P>>,
+    dynamic: IndexGeneric<T, P, B, I>,
+}
+
+#[derive(Debug)]
+struct StaticRun<T: Scalar, P: Copy + Debug> {
+    start: usize,
+    len: usize,
+    tree: RTree<T, P>,
+}
+
+impl<T: Scalar, P: Copy + Debug> StaticRun<T, P> {
+    fn pack(start: usize, entries: &[(Aabb2D<T>, P)]) -> Self {
+        let pairs: Vec<(usize, Aabb2D<T>)> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, (aabb, _))| (i, *aabb))
+            .collect();
+        Self {
+            start,
+            len: entries.len(),
+            tree: RTree::bulk_build_default(&pairs),
+        }
+    }
+}
+
+impl<T, P, B, I> TieredIndex<T, P, B, I>
+where
+    T: Scalar,
+    P: Copy + Debug,
+    B: Backend<T> + Default,
+    I: KeyIndex,
+{
+    /// Create an empty index using the dynamic backend's default constructor.
+    pub fn new() -> Self {
+        Self::with_backend(B::default())
+    }
+}
+
+impl<T, P, B, I> Default for TieredIndex<T, P, B, I>
+where
+    T: Scalar,
+    P: Copy + Debug,
+    B: Backend<T> + Default,
+    I: KeyIndex,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, P, B, I> TieredIndex<T, P, B, I>
+where
+    T: Scalar,
+    P: Copy + Debug,
+    B: Backend<T>,
+    I: KeyIndex,
+{
+    /// Create an empty index using an explicit dynamic backend instance.
+    pub fn with_backend(backend: B) -> Self {
+        Self {
+            statics: Vec::new(),
+            runs: Vec::new(),
+            dynamic: IndexGeneric::with_backend(backend),
+        }
+    }
+
+    /// Insert a static entry. It becomes visible to queries on the next commit.
+    pub fn insert_static(&mut self, aabb: Aabb2D<T>, payload: P) -> TieredKey<I> {
+        self.statics.push((aabb, payload));
+        TieredKey::Static(self.statics.len() - 1)
+    }
+
+    /// Insert a dynamic entry; see [`IndexGeneric::insert`].
+    pub fn insert(&mut self, aabb: Aabb2D<T>, payload: P) -> TieredKey<I> {
+        TieredKey::Dynamic(self.dynamic.insert(aabb, payload))
+    }
+
+    /// Update a dynamic entry; see [`IndexGeneric::update`].
+    ///
+    /// Static entries cannot move: returns `false`, and does nothing, for a static key.
+    pub fn update(&mut self, key: TieredKey<I>, aabb: Aabb2D<T>) -> bool {
+        match key {
+            TieredKey::Dynamic(k) => {
+                self.dynamic.update(k, aabb);
+                true
+            }
+            TieredKey::Static(_) => false,
+        }
+    }
+
+    /// Remove a dynamic entry; see [`IndexGeneric::remove`].
+    ///
+    /// Static entries cannot be removed: returns `false`, and does nothing, for a static key.
+    pub fn remove(&mut self, key: TieredKey<I>) -> bool {
+        match key {
+            TieredKey::Dynamic(k) => {
+                self.dynamic.remove(k);
+                true
+            }
+            TieredKey::Static(_) => false,
+        }
+    }
+
+    /// Number of static entries, including ones not yet committed.
+    pub fn static_len(&self) -> usize {
+        self.statics.len()
+    }
+
+    /// The dynamic tier.
+    pub fn dynamic(&self) -> &IndexGeneric<T, P, B, I> {
+        &self.dynamic
+    }
+
+    /// Rebuild the dynamic tier's backend; see [`IndexGeneric::rebuild`].
+    ///
+    /// The static runs are left as is.
+    pub fn rebuild_dynamic(&mut self) {
+        self.dynamic.rebuild();
+    }
+
+    /// Clear both tiers (without reporting damage).
+    pub fn clear(&mut self) {
+        self.statics.clear();
+        self.runs.clear();
+        self.dynamic.clear();
+    }
+
+    /// Apply pending changes to both tiers and compute batched damage.
+    ///
+    /// Static entries inserted since the last commit are packed into a new run (merging
+    /// runs as described on [`TieredIndex`]) and reported as added.
+    pub fn commit(&mut self) -> Damage<T> {
+        let mut dmg = self.dynamic.commit();
+        let packed = self.runs.last().map_or(0, |r| r.start + r.len);
+        if packed == self.statics.len() {
+            return dmg;
+        }
+        dmg.added
+            .extend(self.statics[packed..].iter().map(|(aabb, _)| *aabb));
+        let mut start = packed;
+        while let Some(prev) = self.runs.last()
+            && prev.len < 2 * (self.statics.len() - start)
+        {
+            start = prev.start;
+            self.runs.pop();
+        }
+        self.runs
+            .push(StaticRun::pack(start, &self.statics[start..]));
+        dmg
+    }
+
+    /// Number of packed static runs.
+    pub fn static_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Query both tiers for entries whose AABB contains the point.
+    pub fn query_point(&self, x: T, y: T) -> impl Iterator<Item = (TieredKey<I>, P)> + '_ {
+        let mut slots = Vec::new();
+        for run in &self.runs {
+            run.tree.visit_point(x, y, |i| slots.push(run.start + i));
+        }
+        self.merge(slots, self.dynamic.query_point(x, y))
+    }
+
+    /// Query both tiers for entries whose AABB intersects the rectangle.
+    pub fn query_rect(&self, rect: Aabb2D<T>) -> impl Iterator<Item = (TieredKey<I>, P)> + '_ {
+        let mut slots = Vec::new();
+        for run in &self.runs {
+            run.tree.visit_rect(rect, |i| slots.push(run.start + i));
+        }
+        self.merge(slots, self.dynamic.query_rect(rect))
+    }
+
+    /// Visit entries of both tiers whose AABB contains the point, without allocating.
+    ///
+    /// Calls `f(key, payload)` for each match: static runs first, then the dynamic tier.
+    /// Unlike [`query_point`](Self::query_point), static hits are not sorted.
+    pub fn visit_point<F: FnMut(TieredKey<I>, P)>(&self, x: T, y: T, mut f: F) {
+        for run in &self.runs {
+            run.tree.visit_point(x, y, |i| {
+                let i = run.start + i;
+                f(TieredKey::Static(i), self.statics[i].1);
+            });
+        }
+        self.dynamic
+            .visit_point(x, y, |k, p| f(TieredKey::Dynamic(k), p));
+    }
+
+    /// Visit entries of both tiers whose AABB intersects `rect`, without allocating.
+    ///
+    /// Calls `f(key, payload)` for each match: static runs first, then the dynamic tier.
+    /// Unlike [`query_rect`](Self::query_rect), static hits are not sorted.
+    pub fn visit_rect<F: FnMut(TieredKey<I>, P)>(&self, rect: Aabb2D<T>, mut f: F) {
+        for run in &self.runs {
+            run.tree.visit_rect(rect, |i| {
+                let i = run.start + i;
+                f(TieredKey::Static(i), self.statics[i].1);
+            });
+        }
+        self.dynamic
+            .visit_rect(rect, |k, p| f(TieredKey::Dynamic(k), p));
+    }
+
+    fn merge(
+        &self,
+        mut slots: Vec<usize>,
+        dynamic: impl Iterator<Item = (Key<I>, P)>,
+    ) -> impl Iterator<Item = (TieredKey<I>, P)> + '_ {
+        slots.sort_unstable();
+        let mut out: Vec<_> = slots
+            .into_iter()
+            .map(|i| (TieredKey::Static(i), self.statics[i].1))
+            .collect();
+        out.extend(dynamic.map(|(k, p)| (TieredKey::Dynamic(k), p)));
+        out.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::rtree::RTreeI64;
+
+    #[test]
+    fn static_entries_survive_dynamic_rebuild() {
+        let mut idx: TieredIndex<i64, u32, RTreeI64<u32>> = TieredIndex::new();
+        let mut statics = Vec::new();
+        for i in 0..20_u32 {
+            let x = i64::from(i) * 10;
+            statics.push(idx.insert_static(Aabb2D::new(x, 0, x + 10, 100), i));
+        }
+        let mover = idx.insert(Aabb2D::new(0, 0, 5, 5), 100);
+        let dmg = idx.commit();
+        assert_eq!(dmg.added.len(), 21);
+        assert_eq!(idx.static_len(), 20);
+
+        let hits: Vec<_> = idx.query_point(3, 3).collect();
+        assert_eq!(hits, [(statics[0], 0), (mover, 100)]);
+
+        let before: Vec<_> = idx
+            .query_rect(Aabb2D::new(0, 0, 200, 100))
+            .filter(|(k, _)| matches!(k, TieredKey::Static(_)))
+            .collect();
+        for step in 1..50 {
+            idx.update(mover, Aabb2D::new(step * 3, 50, step * 3 + 5, 55));
+            let dmg = idx.commit();
+            assert!(dmg.added.is_empty());
+        }
+        idx.rebuild_dynamic();
+        let after: Vec<_> = idx
+            .query_rect(Aabb2D::new(0, 0, 200, 100))
+            .filter(|(k, _)| matches!(k, TieredKey::Static(_)))
+            .collect();
+        assert_eq!(before, after);
+        assert_eq!(before.len(), 20);
+
+        // Static keys cannot be moved or removed; the dynamic entry moved away.
+        assert!(!idx.update(statics[0], Aabb2D::new(500, 500, 510, 510)));
+        assert!(!idx.remove(statics[1]));
+        let _ = idx.commit();
+        let hits: Vec<_> = idx.query_point(3, 3).collect();
+        assert_eq!(hits, [(statics[0], 0)]);
+        let hits: Vec<_> = idx.query_point(148, 52).collect();
+        assert_eq!(hits, [(statics[14], 14), (mover, 100)]);
+    }
+
+    #[test]
+    fn incremental_static_inserts_pack_into_few_runs() {
+        let mut idx: TieredIndex<i64, u32, RTreeI64<u32>> = TieredIndex::new();
+        let mut keys = Vec::new();
+        for i in 0..200_u32 {
+            let x = i64::from(i) * 10;
+            keys.push(idx.insert_static(Aabb2D::new(x, 0, x + 5, 5), i));
+            let dmg = idx.commit();
+            assert_eq!(dmg.added, [Aabb2D::new(x, 0, x + 5, 5)]);
+            // Each run is at least twice the size of the next, so there are O(log n).
+            assert!(idx.static_runs() <= 8, "{} runs", idx.static_runs());
+        }
+        let hits: Vec<_> = idx.query_rect(Aabb2D::new(0, 0, 2000, 5)).collect();
+        let expected: Vec<_> = keys.iter().copied().zip(0..).collect();
+        assert_eq!(hits, expected);
+        assert_eq!(
+            idx.query_point(1233, 3).collect::<Vec<_>>(),
+            [(keys[123], 123)]
+        );
+    }
+
+    #[test]
+    fn visits_match_queries() {
+        let mut idx: TieredIndex<i64, u32, RTreeI64<u32>> = TieredIndex::new();
+        for i in 0..50_u32 {
+            let x = i64::from(i) * 4;
+            let _ = idx.insert_static(Aabb2D::new(x, 0, x + 6, 6), i);
+            if i % 7 == 0 {
+                let _ = idx.insert(Aabb2D::new(x, 2, x + 3, 4), 100 + i);
+                let _ = idx.commit();
+            }
+        }
+        let _ = idx.commit();
+        assert!(idx.static_runs() > 1);
+
+        let mut visited = Vec::new();
+        idx.visit_point(57, 3, |k, p| visited.push((k, p)));
+        let queried: Vec<_> = idx.query_point(57, 3).collect();
+        assert_eq!(visited.len(), 3);
+        let key = |&(k, _): &(TieredKey, u32)| match k {
+            TieredKey::Static(i) => (0, i),
+            TieredKey::Dynamic(_) => (1, 0),
+        };
+        visited.sort_by_key(key);
+        assert_eq!(visited, queried);
+
+        let rect = Aabb2D::new(30, 3, 90, 3);
+        let mut visited = Vec::new();
+        idx.visit_rect(rect, |k, p| visited.push((k, p)));
+        let queried: Vec<_> = idx.query_rect(rect).collect();
+        assert!(queried.len() > 10);
+        visited.sort_by_key(key);
+        assert_eq!(visited, queried);
+    }
+}
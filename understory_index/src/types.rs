@@ -5,9 +5,11 @@
 
 use core::cmp::Ordering;
 use core::fmt::Debug;
+use core::ops::RangeInclusive;
 
 /// Axis-aligned bounding box in 2D.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Aabb2D<T> {
     /// Minimum x (left)
     pub min_x: T,
@@ -37,6 +39,17 @@ impl<T: Copy + PartialOrd> Aabb2D<T> {
         le(self.min_x, x) && le(self.min_y, y) && le(x, self.max_x) && le(y, self.max_y)
     }
 
+    /// Whether this AABB strictly contains the point, i.e. the point is not on an edge.
+    pub fn contains_point_strict(&self, x: T, y: T) -> bool {
+        lt(self.min_x, x) && lt(self.min_y, y) && lt(x, self.max_x) && lt(y, self.max_y)
+    }
+
+    /// Whether this AABB fully contains `inner` (shared edges count as contained).
+    pub fn contains_aabb(&self, inner: &Self) -> bool {
+        self.contains_point(inner.min_x, inner.min_y)
+            && self.contains_point(inner.max_x, inner.max_y)
+    }
+
     /// The intersection of two AABBs.
     pub fn intersect(&self, other: &Self) -> Self {
         let min_x = max_t(self.min_x, other.min_x);
@@ -55,6 +68,180 @@ impl<T: Copy + PartialOrd> Aabb2D<T> {
     pub fn is_empty(&self) -> bool {
         lt(self.max_x, self.min_x) || lt(self.max_y, self.min_y)
     }
+
+    /// The smallest AABB covering both, taking mins and maxes edge by edge.
+    ///
+    /// Inputs are not normalized: an inverted box still contributes its edges, so it only
+    /// widens the result where they reach past the other box.
+    pub fn union(self, other: Self) -> Self {
+        union_aabb(self, other)
+    }
+}
+
+/// A coordinate axis, for splitting boxes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Axis {
+    /// The x axis; splits produce left and right halves.
+    X,
+    /// The y axis; splits produce top and bottom halves.
+    Y,
+}
+
+impl<T: Scalar> Aabb2D<T> {
+    /// The smallest AABB containing every point, or `None` if there are none.
+    ///
+    /// A single point gives a degenerate (zero-size) box. Assumes no NaN.
+    pub fn from_points(points: impl IntoIterator<Item = (T, T)>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let (x, y) = points.next()?;
+        Some(points.fold(Self::new(x, y, x, y), |b, (x, y)| {
+            Self::new(
+                min_t(b.min_x, x),
+                min_t(b.min_y, y),
+                max_t(b.max_x, x),
+                max_t(b.max_y, y),
+            )
+        }))
+    }
+
+    /// The [`union`](Self::union) of all `boxes`, or `None` if there are none.
+    #[allow(
+        clippy::should_implement_trait,
+        reason = "Returns `None` for no boxes, which `FromIterator` cannot express."
+    )]
+    pub fn from_iter(boxes: impl IntoIterator<Item = Self>) -> Option<Self> {
+        boxes.into_iter().reduce(Self::union)
+    }
+
+    /// Area in the scalar's widened accumulator; zero for degenerate or inverted boxes.
+    ///
+    /// Same as [`area`](crate::types::area); see [`try_area`] to detect overflow.
+    pub fn area(&self) -> T::Acc {
+        area(self)
+    }
+
+    /// The center point; for integer scalars it is rounded toward negative infinity.
+    pub fn center(&self) -> (T, T) {
+        (
+            T::mid(self.min_x, self.max_x),
+            T::mid(self.min_y, self.max_y),
+        )
+    }
+
+    /// Grow by `dx` on the left and right and by `dy` on the top and bottom.
+    ///
+    /// Negative amounts shrink the box and can invert it. Integer coordinates saturate
+    /// instead of wrapping.
+    pub fn expand(self, dx: T, dy: T) -> Self {
+        Self::new(
+            T::sub(self.min_x, dx),
+            T::sub(self.min_y, dy),
+            T::add(self.max_x, dx),
+            T::add(self.max_y, dy),
+        )
+    }
+
+    /// Move by `(dx, dy)`. Integer coordinates saturate instead of wrapping.
+    pub fn translate(self, dx: T, dy: T) -> Self {
+        Self::new(
+            T::add(self.min_x, dx),
+            T::add(self.min_y, dy),
+            T::add(self.max_x, dx),
+            T::add(self.max_y, dy),
+        )
+    }
+
+    /// Scale every coordinate by `sx` and `sy` about the origin `(0, 0)`.
+    ///
+    /// A negative factor mirrors the box; its edges are swapped so `min <= max` still holds
+    /// (an inverted box stays inverted). Coordinates are multiplied with `T`'s `*`, so
+    /// integer overflow behaves as it does there.
+    pub fn scale_about_origin(self, sx: T, sy: T) -> Self
+    where
+        T: core::ops::Mul<Output = T>,
+    {
+        let (mut min_x, mut max_x) = (self.min_x * sx, self.max_x * sx);
+        let (mut min_y, mut max_y) = (self.min_y * sy, self.max_y * sy);
+        if sx < T::zero() {
+            core::mem::swap(&mut min_x, &mut max_x);
+        }
+        if sy < T::zero() {
+            core::mem::swap(&mut min_y, &mut max_y);
+        }
+        Self::new(min_x, min_y, max_x, max_y)
+    }
+
+    /// Split into four boxes at the center: `[NW, NE, SW, SE]`.
+    ///
+    /// "North" is `min_y` (y grows downward). The quadrants share edges and exactly tile
+    /// `self`; for integer scalars the center is rounded toward negative infinity.
+    pub fn quadrants(&self) -> [Self; 4] {
+        let cx = T::mid(self.min_x, self.max_x);
+        let cy = T::mid(self.min_y, self.max_y);
+        [
+            Self::new(self.min_x, self.min_y, cx, cy),
+            Self::new(cx, self.min_y, self.max_x, cy),
+            Self::new(self.min_x, cy, cx, self.max_y),
+            Self::new(cx, cy, self.max_x, self.max_y),
+        ]
+    }
+
+    /// Split at `at` along `axis`, returning the (lower, upper) halves.
+    ///
+    /// `at` is clamped into the box, so both halves always lie within `self`
+    /// (one of them is degenerate if `at` is outside).
+    pub fn split_axis(&self, axis: Axis, at: T) -> (Self, Self) {
+        match axis {
+            Axis::X => {
+                let at = min_t(max_t(at, self.min_x), self.max_x);
+                (
+                    Self::new(self.min_x, self.min_y, at, self.max_y),
+                    Self::new(at, self.min_y, self.max_x, self.max_y),
+                )
+            }
+            Axis::Y => {
+                let at = min_t(max_t(at, self.min_y), self.max_y);
+                (
+                    Self::new(self.min_x, self.min_y, self.max_x, at),
+                    Self::new(self.min_x, at, self.max_x, self.max_y),
+                )
+            }
+        }
+    }
+
+    /// Minimum translation vector: the shortest axis-aligned move of `self` out of `other`.
+    ///
+    /// Returns `(dx, dy)` with one component zero, such that `self` shifted by it no longer
+    /// overlaps `other` (the boxes end up touching). Picks the axis needing the smaller
+    /// move, preferring x on ties, and the direction along it needing the smaller move.
+    /// Returns `None` if the boxes do not overlap with positive area (touching edges do
+    /// not count).
+    pub fn mtv(&self, other: &Self) -> Option<(T, T)> {
+        if !(self.min_x < other.max_x
+            && other.min_x < self.max_x
+            && self.min_y < other.max_y
+            && other.min_y < self.max_y)
+        {
+            return None;
+        }
+        // Shortest signed push along one axis, and its magnitude.
+        let push = |lo: T, hi: T, other_lo: T, other_hi: T| {
+            let forward = T::sub(other_hi, lo);
+            let back = T::sub(hi, other_lo);
+            if forward <= back {
+                (forward, forward)
+            } else {
+                (T::sub(T::zero(), back), back)
+            }
+        };
+        let (dx, mx) = push(self.min_x, self.max_x, other.min_x, other.max_x);
+        let (dy, my) = push(self.min_y, self.max_y, other.min_y, other.max_y);
+        Some(if mx <= my {
+            (dx, T::zero())
+        } else {
+            (T::zero(), dy)
+        })
+    }
 }
 
 impl Aabb2D<f32> {
@@ -83,21 +270,38 @@ impl Aabb2D<f64> {
 
 impl Aabb2D<i64> {
     /// Create an AABB from origin and size in i64.
+    ///
+    /// The far edges saturate at the `i64` bounds instead of wrapping, so a box whose
+    /// `x + w` or `y + h` overflows is clamped to `i64::MAX` rather than inverted. Use
+    /// [`try_from_xywh`](Self::try_from_xywh) to detect overflow instead.
     pub const fn from_xywh(x: i64, y: i64, w: i64, h: i64) -> Self {
         Self {
             min_x: x,
             min_y: y,
-            max_x: x + w,
-            max_y: y + h,
+            max_x: x.saturating_add(w),
+            max_y: y.saturating_add(h),
         }
     }
+
+    /// Create an AABB from origin and size in i64, or `None` if `x + w` or `y + h` overflows.
+    pub const fn try_from_xywh(x: i64, y: i64, w: i64, h: i64) -> Option<Self> {
+        let (Some(max_x), Some(max_y)) = (x.checked_add(w), y.checked_add(h)) else {
+            return None;
+        };
+        Some(Self {
+            min_x: x,
+            min_y: y,
+            max_x,
+            max_y,
+        })
+    }
 }
 
 /// Numeric scalar abstraction for 2D AABBs used by backends.
 ///
 /// This trait provides a minimal set of operations required for SAH metrics and
 /// centroid computations, and an associated widened accumulator type for area
-/// (e.g., f32→f64, i64→i128).
+/// (e.g., f32→f64, i32→i64, i64→i128).
 pub trait Scalar: Copy + PartialOrd + Debug {
     /// Widened accumulator type suitable for area/cost computations.
     type Acc: Copy
@@ -127,6 +331,22 @@ pub trait Scalar: Copy + PartialOrd + Debug {
 
     /// Convert a `usize` to the accumulator type (for SAH weighting).
     fn acc_from_usize(n: usize) -> Self::Acc;
+
+    /// Multiply two accumulator values, returning `None` on overflow.
+    ///
+    /// Default: never overflows (appropriate for floating-point accumulators).
+    #[inline]
+    fn checked_mul_acc(a: Self::Acc, b: Self::Acc) -> Option<Self::Acc> {
+        Some(a * b)
+    }
+
+    /// Add two accumulator values, returning `None` on overflow.
+    ///
+    /// Default: never overflows (appropriate for floating-point accumulators).
+    #[inline]
+    fn checked_add_acc(a: Self::Acc, b: Self::Acc) -> Option<Self::Acc> {
+        Some(a + b)
+    }
 }
 
 impl Scalar for f32 {
@@ -245,6 +465,81 @@ impl Scalar for i64 {
     fn acc_from_usize(n: usize) -> Self::Acc {
         n as i128
     }
+
+    #[inline]
+    fn checked_mul_acc(a: Self::Acc, b: Self::Acc) -> Option<Self::Acc> {
+        a.checked_mul(b)
+    }
+
+    #[inline]
+    fn checked_add_acc(a: Self::Acc, b: Self::Acc) -> Option<Self::Acc> {
+        a.checked_add(b)
+    }
+}
+
+impl Scalar for i32 {
+    type Acc = i64;
+
+    #[inline]
+    fn add(a: Self, b: Self) -> Self {
+        a.saturating_add(b)
+    }
+
+    #[inline]
+    fn sub(a: Self, b: Self) -> Self {
+        a.saturating_sub(b)
+    }
+
+    #[inline]
+    fn zero() -> Self {
+        0
+    }
+
+    #[inline]
+    fn max_zero(v: Self) -> Self {
+        v.max(0)
+    }
+
+    #[inline]
+    fn mid(a: Self, b: Self) -> Self {
+        // Average without overflow: (a & b) + ((a ^ b) >> 1)
+        (a & b) + ((a ^ b) >> 1)
+    }
+
+    #[inline]
+    fn widen(v: Self) -> Self::Acc {
+        i64::from(v)
+    }
+
+    #[inline]
+    fn acc_from_usize(n: usize) -> Self::Acc {
+        i64::try_from(n).unwrap_or(i64::MAX)
+    }
+
+    #[inline]
+    fn checked_mul_acc(a: Self::Acc, b: Self::Acc) -> Option<Self::Acc> {
+        a.checked_mul(b)
+    }
+
+    #[inline]
+    fn checked_add_acc(a: Self::Acc, b: Self::Acc) -> Option<Self::Acc> {
+        a.checked_add(b)
+    }
+}
+
+/// Squared distance from the point `(x, y)` to an AABB, zero if the point is inside.
+#[inline]
+pub(crate) fn point_dist2<T: Scalar>(a: &Aabb2D<T>, x: T, y: T) -> T::Acc {
+    // At most one of each pair is non-zero.
+    let dx = T::add(
+        T::max_zero(T::sub(a.min_x, x)),
+        T::max_zero(T::sub(x, a.max_x)),
+    );
+    let dy = T::add(
+        T::max_zero(T::sub(a.min_y, y)),
+        T::max_zero(T::sub(y, a.max_y)),
+    );
+    T::widen(dx) * T::widen(dx) + T::widen(dy) * T::widen(dy)
 }
 
 /// Compute the area of an AABB using the scalar's widened accumulator type.
@@ -255,6 +550,127 @@ pub fn area<T: Scalar>(a: &Aabb2D<T>) -> T::Acc {
     T::widen(w) * T::widen(h)
 }
 
+/// Compute the area of an AABB, returning `None` if the accumulator overflows.
+///
+/// Uses checked arithmetic for integer accumulators; floating-point accumulators
+/// never return `None`. Prefer [`area`] on hot paths where overflow is impossible.
+#[inline]
+pub fn try_area<T: Scalar>(a: &Aabb2D<T>) -> Option<T::Acc> {
+    let w = T::max_zero(T::sub(a.max_x, a.min_x));
+    let h = T::max_zero(T::sub(a.max_y, a.min_y));
+    T::checked_mul_acc(T::widen(w), T::widen(h))
+}
+
+/// Coordinates of the tiles of a regular grid that a query for each tile would return `aabb` for.
+///
+/// Tile `(i, j)` covers `[ox + i·tile_w, ox + (i + 1)·tile_w] × [oy + j·tile_h, oy + (j + 1)·tile_h]`
+/// with `origin = (ox, oy)`. This is the inverse of running [`query_rect`](crate::IndexGeneric::query_rect)
+/// per tile: since queries treat boxes as closed, a box touching a tile edge is also
+/// reported for the neighboring tile. Map each box of a [`Damage`](crate::Damage) through
+/// this to find the tiles it dirties. Tiles are yielded row by row; an empty box yields none.
+///
+/// Tile indices saturate at the `i64` range, so a huge or infinite box yields an
+/// effectively endless iterator. When boxes may be unbounded, get the span from
+/// [`tile_ranges_for_box`] and clamp it to the tiles you care about instead.
+///
+/// Tile sizes must be positive and finite.
+pub fn tiles_for_box(
+    aabb: Aabb2D<f64>,
+    tile_w: f64,
+    tile_h: f64,
+    origin: (f64, f64),
+) -> impl Iterator<Item = (i64, i64)> {
+    let (xs, ys) = tile_ranges_for_box(aabb, tile_w, tile_h, origin);
+    ys.flat_map(move |j| xs.clone().map(move |i| (i, j)))
+}
+
+/// Column and row ranges `(i0..=i1, j0..=j1)` of the tiles [`tiles_for_box`] yields.
+///
+/// Both ranges are empty for an empty box. Indices saturate at the `i64` range, so
+/// intersect the ranges with a viewport or tile budget before iterating them when
+/// boxes may be huge or infinite.
+///
+/// Tile sizes must be positive and finite.
+#[allow(
+    clippy::reversed_empty_ranges,
+    reason = "An empty box maps to empty ranges on purpose."
+)]
+pub fn tile_ranges_for_box(
+    aabb: Aabb2D<f64>,
+    tile_w: f64,
+    tile_h: f64,
+    origin: (f64, f64),
+) -> (RangeInclusive<i64>, RangeInclusive<i64>) {
+    debug_assert!(tile_w > 0.0 && tile_h > 0.0, "tile sizes must be positive");
+    if aabb.is_empty() {
+        return (1..=0, 1..=0);
+    }
+    let (x0, x1) = tile_span(aabb.min_x, aabb.max_x, origin.0, tile_w);
+    let (y0, y1) = tile_span(aabb.min_y, aabb.max_y, origin.1, tile_h);
+    (x0..=x1, y0..=y1)
+}
+
+/// Inclusive range of tile indices whose closed span meets `[min, max]` along one axis.
+fn tile_span(min: f64, max: f64, origin: f64, size: f64) -> (i64, i64) {
+    // Tile `i` meets the span iff `(min - origin) / size - 1 <= i <= (max - origin) / size`.
+    let lo = ceil_i64((min - origin) / size - 1.0);
+    let hi = floor_i64((max - origin) / size);
+    (lo, hi)
+}
+
+/// `floor(v)` saturated to the `i64` range; NaN maps to 0.
+#[allow(
+    clippy::cast_possible_truncation,
+    reason = "Tile indices saturate at the i64 range by design."
+)]
+pub(crate) fn floor_i64(v: f64) -> i64 {
+    if v.is_nan() {
+        return 0;
+    }
+    if v <= i64::MIN as f64 {
+        return i64::MIN;
+    }
+    if v >= i64::MAX as f64 {
+        return i64::MAX;
+    }
+    let t = v as i64;
+    if (t as f64) > v { t - 1 } else { t }
+}
+
+/// `ceil(v)` saturated to the `i64` range; NaN maps to 0.
+#[allow(
+    clippy::cast_possible_truncation,
+    reason = "Tile indices saturate at the i64 range by design."
+)]
+pub(crate) fn ceil_i64(v: f64) -> i64 {
+    if v.is_nan() {
+        return 0;
+    }
+    if v <= i64::MIN as f64 {
+        return i64::MIN;
+    }
+    if v >= i64::MAX as f64 {
+        return i64::MAX;
+    }
+    let t = v as i64;
+    if (t as f64) < v { t + 1 } else { t }
+}
+
+/// SAH split cost `area(lb) * nl + area(rb) * nr`, or `None` if it overflows.
+///
+/// Backends treat an overflowing candidate as maximally costly.
+#[inline]
+pub(crate) fn sah_cost<T: Scalar>(
+    lb: &Aabb2D<T>,
+    nl: usize,
+    rb: &Aabb2D<T>,
+    nr: usize,
+) -> Option<T::Acc> {
+    let l = T::checked_mul_acc(try_area(lb)?, T::acc_from_usize(nl))?;
+    let r = T::checked_mul_acc(try_area(rb)?, T::acc_from_usize(nr))?;
+    T::checked_add_acc(l, r)
+}
+
 // Helper type to access Scalar::Acc in type aliases elsewhere.
 /// Helper alias for the widened accumulator type associated with a scalar `T`.
 pub type ScalarAcc<T> = <T as Scalar>::Acc;
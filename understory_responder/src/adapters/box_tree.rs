@@ -13,6 +13,10 @@
 //! They do not perform ordering; when only a single candidate exists (e.g., top hit), the depth key value is irrelevant.
 //! For lists (e.g., viewport queries), consumers can apply their own ordering if needed.
 //!
+//! Every hit these helpers produce refers to a node that was live when the hit was built,
+//! and every path they provide is non-empty and ends at the hit's node, so the hits pass
+//! [`ResolvedHit::is_valid`].
+//!
 //! ## Navigation
 //!
 //! The [`navigation`] module provides filtered tree traversal with wraparound semantics,
@@ -33,7 +37,8 @@ use crate::types::{DepthKey, Localizer, ResolvedHit};
 ///
 /// Notes
 /// - Path is populated from the box tree's hit test result so the router does
-///   not need a parent lookup.
+///   not need a parent lookup. Should that path ever be empty or not end at the hit
+///   node, the singleton path `[node]` is used instead.
 /// - `DepthKey` is derived from the node's z-index; since only a single candidate
 ///   is returned, ordering is irrelevant.
 pub fn top_hit_for_point(
@@ -46,9 +51,14 @@ pub fn top_hit_for_point(
         .z_index(hit.node)
         .map(DepthKey::Z)
         .unwrap_or(DepthKey::Z(0));
+    let path = if hit.path.last() == Some(&hit.node) {
+        hit.path
+    } else {
+        alloc::vec![hit.node]
+    };
     Some(ResolvedHit {
         node: hit.node,
-        path: Some(hit.path),
+        path: Some(path),
         depth_key,
         localizer: Localizer::default(),
         meta: (),
@@ -67,16 +77,53 @@ pub fn hits_for_rect(
     filter: QueryFilter,
 ) -> Vec<ResolvedHit<understory_box_tree::NodeId, ()>> {
     tree.intersect_rect(rect, filter)
-        .map(|id| ResolvedHit {
-            node: id,
-            path: None,
-            depth_key: tree.z_index(id).map(DepthKey::Z).unwrap_or(DepthKey::Z(0)),
-            localizer: Localizer::default(),
-            meta: (),
+        .map(|id| {
+            debug_assert!(tree.is_alive(id), "intersect_rect yielded a stale id");
+            ResolvedHit {
+                node: id,
+                path: None,
+                depth_key: tree.z_index(id).map(DepthKey::Z).unwrap_or(DepthKey::Z(0)),
+                localizer: Localizer::default(),
+                meta: (),
+            }
         })
         .collect()
 }
 
+/// Resolve the target for a pointer event, honoring pointer capture.
+///
+/// When `captured` is a live node, returns a hit for it regardless of what lies under
+/// `pt` (and regardless of `filter`), with its root→node path reconstructed from the
+/// tree and its z-index as the depth key. Otherwise (no capture, or a stale captured
+/// id) this is [`top_hit_for_point`].
+pub fn resolve_for_event(
+    tree: &Tree,
+    pt: Point,
+    filter: QueryFilter,
+    captured: Option<understory_box_tree::NodeId>,
+) -> Option<ResolvedHit<understory_box_tree::NodeId, ()>> {
+    let Some(node) = captured.filter(|id| tree.is_alive(*id)) else {
+        return top_hit_for_point(tree, pt, filter);
+    };
+    let mut path = Vec::new();
+    let mut cur = Some(node);
+    while let Some(id) = cur {
+        path.push(id);
+        cur = tree.parent_of(id);
+    }
+    path.reverse();
+    Some(ResolvedHit {
+        node,
+        path: Some(path),
+        depth_key: tree
+            .z_index(node)
+            .map(DepthKey::Z)
+            .unwrap_or(DepthKey::Z(0)),
+        localizer: Localizer::default(),
+        meta: (),
+    })
+}
+
 /// Tree navigation utilities for UI focus/keyboard traversal.
 ///
 /// These methods provide filtered traversal with wraparound semantics,
@@ -247,6 +294,7 @@ pub mod navigation {
 
             let filter = QueryFilter {
                 required_flags: NodeFlags::VISIBLE,
+                ..Default::default()
             };
 
             // From root, next visible should be b (skipping hidden a)
@@ -302,6 +350,7 @@ pub mod navigation {
 
             let filter = QueryFilter {
                 required_flags: NodeFlags::PICKABLE,
+                ..Default::default()
             };
 
             // From root, next pickable should be b (skipping non-pickable a)
@@ -337,6 +386,7 @@ pub mod navigation {
 
             let filter = QueryFilter {
                 required_flags: NodeFlags::PICKABLE,
+                ..Default::default()
             };
 
             // Should return None since no nodes are pickable
@@ -377,6 +427,7 @@ pub mod navigation {
 
             let filter = QueryFilter {
                 required_flags: NodeFlags::VISIBLE,
+                ..Default::default()
             };
 
             // From visible_child (last visible), next should wrap to root
@@ -411,6 +462,7 @@ pub mod navigation {
 
             let filter = QueryFilter {
                 required_flags: NodeFlags::VISIBLE,
+                ..Default::default()
             };
 
             // Should work with live nodes
@@ -464,6 +516,7 @@ pub mod navigation {
 
             let filter = QueryFilter {
                 required_flags: NodeFlags::VISIBLE,
+                ..Default::default()
             };
 
             // From child1_visible (last visible in subtree1), should wrap to root1 (not cross to subtree2)
@@ -476,3 +529,97 @@ pub mod navigation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use understory_box_tree::LocalNode;
+
+    #[test]
+    fn resolve_for_event_prefers_capture() {
+        let mut tree = Tree::new();
+        let root = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 100.0, 100.0),
+                ..Default::default()
+            },
+        );
+        let a = tree.insert(
+            Some(root),
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 40.0, 40.0),
+                z_index: 3,
+                ..Default::default()
+            },
+        );
+        let b = tree.insert(
+            Some(root),
+            LocalNode {
+                local_bounds: Rect::new(60.0, 60.0, 100.0, 100.0),
+                ..Default::default()
+            },
+        );
+        let _ = tree.commit();
+        let pt = Point::new(80.0, 80.0);
+        let filter = QueryFilter::new().pickable();
+
+        // Without capture, the node under the pointer wins.
+        let hit = resolve_for_event(&tree, pt, filter, None).unwrap();
+        assert_eq!(hit.node, b);
+
+        // With capture, the captured node wins even though the pointer is over `b`.
+        let hit = resolve_for_event(&tree, pt, filter, Some(a)).unwrap();
+        assert_eq!(hit.node, a);
+        assert_eq!(hit.path, Some(alloc::vec![root, a]));
+        assert!(matches!(hit.depth_key, DepthKey::Z(3)));
+
+        // A stale capture falls back to the top hit.
+        tree.remove(a);
+        let _ = tree.commit();
+        let hit = resolve_for_event(&tree, pt, filter, Some(a)).unwrap();
+        assert_eq!(hit.node, b);
+    }
+
+    #[test]
+    fn adapter_hits_are_always_routable() {
+        let mut tree = Tree::new();
+        let root = tree.insert(
+            None,
+            LocalNode {
+                local_bounds: Rect::new(0.0, 0.0, 100.0, 100.0),
+                ..Default::default()
+            },
+        );
+        let mut kids = Vec::new();
+        for i in 0..4_u32 {
+            let o = 20.0 * f64::from(i);
+            kids.push(tree.insert(
+                Some(root),
+                LocalNode {
+                    local_bounds: Rect::new(o, o, o + 30.0, o + 30.0),
+                    ..Default::default()
+                },
+            ));
+        }
+        let _ = tree.commit();
+        // Removed, and the index left stale, before the hits are built.
+        tree.set_index_sync(false);
+        tree.remove(kids[1]);
+        let _ = tree.commit();
+
+        let filter = QueryFilter::new();
+        let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let hits = hits_for_rect(&tree, rect, filter);
+        assert!(!hits.is_empty());
+        assert!(hits.iter().all(|h| tree.is_alive(h.node)));
+        assert!(hits.iter().all(|h| h.node != kids[1]));
+        for pt in [(5.0, 5.0), (45.0, 45.0), (99.0, 1.0)] {
+            let hit = top_hit_for_point(&tree, Point::new(pt.0, pt.1), filter).unwrap();
+            let path = hit.path.as_ref().unwrap();
+            assert!(!path.is_empty());
+            assert!(hit.is_valid());
+        }
+        assert!(hits.iter().all(ResolvedHit::is_valid));
+    }
+}
@@ -11,6 +11,10 @@
 //! - [`box_tree`] (`box_tree_adapter` feature): Integration with [`understory_box_tree`] for 2D spatial queries
 //!   and UI navigation. Converts spatial query results into responder hits and provides filtered
 //!   tree traversal for keyboard navigation and focus cycling.
+//! - [`raycast`] (`raycast_adapter` feature): Converts 3D ray-cast hits into responder hits
+//!   keyed by ray distance.
 
 #[cfg(feature = "box_tree_adapter")]
 pub mod box_tree;
+#[cfg(feature = "raycast_adapter")]
+pub mod raycast;
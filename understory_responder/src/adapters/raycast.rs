@@ -0,0 +1,73 @@
+// Copyright 2025 the Understory Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Adapter helpers for 3D ray casts.
+//!
+//! ## Feature
+//!
+//! Enable with `raycast_adapter`.
+//!
+//! ## Notes
+//!
+//! Ray casting itself is left to the caller's 3D picker; these helpers only convert its
+//! hits into responder hits keyed by [`DepthKey::Distance`], so the router picks the
+//! nearest one the same way it picks the highest z-index for the box-tree adapter.
+
+use alloc::vec::Vec;
+
+use crate::types::{DepthKey, Localizer, ResolvedHit};
+
+/// Build resolved hits from ray-cast results given as `(node, distance, meta)`.
+///
+/// Notes
+/// - Depth keys are [`DepthKey::Distance`], where lower is nearer, so the router's
+///   target is the hit with the smallest distance.
+/// - Path is not populated; the router reconstructs it from its parent lookup.
+/// - The returned list preserves input order; hits need not be sorted by distance.
+pub fn hits_from_raycast<K, M>(
+    items: impl IntoIterator<Item = (K, f32, M)>,
+) -> Vec<ResolvedHit<K, M>> {
+    items
+        .into_iter()
+        .map(|(node, distance, meta)| ResolvedHit {
+            node,
+            path: None,
+            depth_key: DepthKey::Distance(distance),
+            localizer: Localizer::default(),
+            meta,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::Router;
+    use crate::types::{NoParent, Phase, WidgetLookup};
+
+    struct Lookup;
+    impl WidgetLookup<u32> for Lookup {
+        type WidgetId = u32;
+        fn widget_of(&self, node: &u32) -> Option<Self::WidgetId> {
+            Some(*node)
+        }
+    }
+
+    #[test]
+    fn nearest_ray_hit_is_target() {
+        // (node, distance, surface normal z)
+        let hits = hits_from_raycast([(1_u32, 7.5_f32, 0.0_f32), (2, 2.25, 1.0), (3, 4.0, -1.0)]);
+        assert_eq!(hits.len(), 3);
+        assert!(matches!(hits[1].depth_key, DepthKey::Distance(d) if d == 2.25));
+        assert_eq!(hits[1].meta, 1.0);
+
+        let router: Router<u32, Lookup, NoParent> = Router::new(Lookup);
+        let out = router.handle_with_hits(&hits);
+        let target: Vec<u32> = out
+            .iter()
+            .filter(|d| d.phase == Phase::Target)
+            .map(|d| d.node)
+            .collect();
+        assert_eq!(target, [2]);
+    }
+}
@@ -6,8 +6,10 @@
 //! ## Usage
 //!
 //! 1) Run the router to produce a dispatch sequence for a pointer move or similar.
-//! 2) Extract the root→target path from the dispatch with [`path_from_dispatch`].
-//! 3) Call [`HoverState::update_path`] with that path to get `Enter(..)` / `Leave(..)` transitions.
+//! 2) Extract the root→target path from the dispatch with [`path_from_dispatch`]
+//!    (or lazily with [`path_iter_from_dispatch`]).
+//! 3) Call [`HoverState::update_path`] (or [`HoverState::update_path_iter`]) with that path
+//!    to get `Enter(..)` / `Leave(..)` transitions.
 //!
 //! ## Minimal example
 //!
@@ -76,11 +78,27 @@ use crate::types::{Dispatch, Phase};
 ///
 /// This mirrors common UI expectations for hover transitions as the pointer
 /// moves across siblings and their ancestors.
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+///
+/// [`HoverState::update_path_sticky`] can hold the current path for a few updates
+/// after the pointer leaves it, e.g. so a menu item stays hovered while the pointer
+/// crosses a gap on its way to the submenu.
+///
+/// Two states are equal when they hover the same path; the sticky hold count is ignored.
+#[derive(Clone, Debug, Default)]
 pub struct HoverState<K: Copy + Eq> {
     current: Vec<K>,
+    // Consecutive sticky updates that have held `current` instead of leaving it.
+    held: u8,
 }
 
+impl<K: Copy + Eq> PartialEq for HoverState<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.current == other.current
+    }
+}
+
+impl<K: Copy + Eq> Eq for HoverState<K> {}
+
 /// A hover transition event.
 ///
 /// Returned by [`HoverState::update_path`]. Use
@@ -100,6 +118,7 @@ impl<K: Copy + Eq> HoverState<K> {
     pub fn new() -> Self {
         Self {
             current: Vec::new(),
+            held: 0,
         }
     }
 
@@ -116,6 +135,7 @@ impl<K: Copy + Eq> HoverState<K> {
             out.push(HoverEvent::Leave(k));
         }
         self.current.clear();
+        self.held = 0;
         out
     }
 
@@ -125,29 +145,140 @@ impl<K: Copy + Eq> HoverState<K> {
     /// Leaves are emitted from inner-most to outer-most, then enters from
     /// outer-most to inner-most (matching common UI expectations).
     pub fn update_path(&mut self, new_path: &[K]) -> Vec<HoverEvent<K>> {
-        // Compute the length of the common prefix (the shared ancestry)
-        // which corresponds to the lowest common ancestor (LCA) depth.
+        self.update_path_iter(new_path.iter().copied())
+    }
+
+    /// Like [`HoverState::update_path`], but takes the new root→target path as an iterator.
+    ///
+    /// The path is consumed once, so it can come straight from
+    /// [`path_iter_from_dispatch`] without collecting it first.
+    pub fn update_path_iter(
+        &mut self,
+        new_path: impl IntoIterator<Item = K>,
+    ) -> Vec<HoverEvent<K>> {
+        self.held = 0;
+        let mut new_path = new_path.into_iter();
+        // Walk the common prefix (the shared ancestry), which ends at the
+        // lowest common ancestor (LCA) depth.
         let mut lca = 0;
-        while lca < self.current.len() && lca < new_path.len() && self.current[lca] == new_path[lca]
-        {
-            lca += 1;
-        }
+        let first_new = loop {
+            match new_path.next() {
+                Some(k) if self.current.get(lca) == Some(&k) => lca += 1,
+                other => break other,
+            }
+        };
 
         let mut out = Vec::new();
         // Leaves: from old tail back to the LCA (exclusive), inner→outer.
         for &k in self.current[lca..].iter().rev() {
             out.push(HoverEvent::Leave(k));
         }
+        self.current.truncate(lca);
 
         // Enters: from LCA down to new tail, outer→inner.
-        for &k in &new_path[lca..] {
+        for k in first_new.into_iter().chain(new_path) {
             out.push(HoverEvent::Enter(k));
+            self.current.push(k);
         }
+        out
+    }
+}
 
-        self.current.clear();
-        self.current.extend_from_slice(new_path);
+impl<K: Copy + Eq> HoverState<K> {
+    /// Like [`HoverState::update_path`], but keeps the current innermost node hovered for
+    /// up to `grace_frames` updates after the pointer leaves it.
+    ///
+    /// An update that would leave the innermost node (i.e. `new_path` does not extend the
+    /// current path) is held: it returns no events and leaves the current path as is. If
+    /// the pointer returns to the current path, or moves deeper into it, before the grace
+    /// period runs out, the leave is canceled. Once `grace_frames` updates have been held,
+    /// the next one is applied with its leaves and enters. A `grace_frames` of zero makes
+    /// this the same as [`HoverState::update_path`].
+    pub fn update_path_sticky(&mut self, new_path: &[K], grace_frames: u8) -> Vec<HoverEvent<K>> {
+        let leaves = !new_path.starts_with(&self.current);
+        if leaves && self.held < grace_frames {
+            self.held += 1;
+            return Vec::new();
+        }
+        self.update_path(new_path)
+    }
+}
+
+/// Hover state for several concurrent pointers (e.g., multi-touch or pen + mouse).
+///
+/// Each pointer id owns an independent [`HoverState`], so transitions for one
+/// pointer never produce enter/leave events for another pointer's chain.
+/// Pointer ids are typically few, so they are kept in a small vector and looked
+/// up linearly.
+///
+/// ```
+/// use understory_responder::hover::{HoverEvent, HoverManager};
+/// let mut m: HoverManager<u32> = HoverManager::new();
+/// assert_eq!(m.update(0, &[1, 2]), vec![HoverEvent::Enter(1), HoverEvent::Enter(2)]);
+/// assert_eq!(m.update(1, &[1, 3]), vec![HoverEvent::Enter(1), HoverEvent::Enter(3)]);
+/// assert_eq!(m.remove(0), vec![HoverEvent::Leave(2), HoverEvent::Leave(1)]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HoverManager<K: Copy + Eq, P: Copy + Eq = u64> {
+    pointers: Vec<(P, HoverState<K>)>,
+}
+
+impl<K: Copy + Eq, P: Copy + Eq> Default for HoverManager<K, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Copy + Eq, P: Copy + Eq> HoverManager<K, P> {
+    /// Create a manager with no tracked pointers.
+    pub fn new() -> Self {
+        Self {
+            pointers: Vec::new(),
+        }
+    }
+
+    /// Return the hover state for `pointer`, if it is tracked.
+    pub fn get(&self, pointer: P) -> Option<&HoverState<K>> {
+        self.pointers
+            .iter()
+            .find(|(p, _)| *p == pointer)
+            .map(|(_, s)| s)
+    }
+
+    /// Return the current root→target path for `pointer` (empty if untracked).
+    pub fn current_path(&self, pointer: P) -> &[K] {
+        self.get(pointer)
+            .map(HoverState::current_path)
+            .unwrap_or(&[])
+    }
+
+    /// Iterate the ids of all tracked pointers.
+    pub fn pointers(&self) -> impl Iterator<Item = P> + '_ {
+        self.pointers.iter().map(|(p, _)| *p)
+    }
+
+    /// Update the hover path for `pointer` and return its enter/leave events.
+    ///
+    /// An untracked pointer starts from an empty path. Only `pointer`'s chain
+    /// is affected; see [`HoverState::update_path`] for event ordering.
+    pub fn update(&mut self, pointer: P, new_path: &[K]) -> Vec<HoverEvent<K>> {
+        if let Some((_, s)) = self.pointers.iter_mut().find(|(p, _)| *p == pointer) {
+            return s.update_path(new_path);
+        }
+        let mut s = HoverState::new();
+        let out = s.update_path(new_path);
+        self.pointers.push((pointer, s));
         out
     }
+
+    /// Stop tracking `pointer` (e.g., when it lifts or leaves the surface),
+    /// returning leave events for its chain from inner-most to outer-most.
+    pub fn remove(&mut self, pointer: P) -> Vec<HoverEvent<K>> {
+        match self.pointers.iter().position(|(p, _)| *p == pointer) {
+            Some(i) => self.pointers.swap_remove(i).1.clear(),
+            None => Vec::new(),
+        }
+    }
 }
 
 /// Extract a root→target path from a router dispatch sequence.
@@ -158,14 +289,19 @@ impl<K: Copy + Eq> HoverState<K> {
 /// router in this crate). Pass the returned path to
 /// [`HoverState::update_path`] to compute hover transitions.
 pub fn path_from_dispatch<K: Copy, W, M>(seq: &[Dispatch<K, W, M>]) -> Vec<K> {
-    let mut path = Vec::new();
-    for d in seq {
-        match d.phase {
-            Phase::Capture | Phase::Target => path.push(d.node),
-            Phase::Bubble => break,
-        }
-    }
-    path
+    path_iter_from_dispatch(seq).collect()
+}
+
+/// Lazily yield the root→target path of a router dispatch sequence.
+///
+/// Yields the same nodes as [`path_from_dispatch`] without allocating. Feed it to
+/// [`HoverState::update_path_iter`] to run the hover pipeline without an intermediate `Vec`.
+pub fn path_iter_from_dispatch<K: Copy, W, M>(
+    seq: &[Dispatch<K, W, M>],
+) -> impl Iterator<Item = K> + '_ {
+    seq.iter()
+        .take_while(|d| d.phase != Phase::Bubble)
+        .map(|d| d.node)
 }
 
 #[cfg(test)]
@@ -173,6 +309,59 @@ mod tests {
     use super::*;
     use alloc::vec;
 
+    // Sticky: a brief exit and return emits nothing.
+    #[test]
+    fn sticky_hover_brief_exit_emits_no_leave() {
+        let mut h: HoverState<u32> = HoverState::new();
+        let _ = h.update_path_sticky(&[1, 2], 2);
+        assert!(h.update_path_sticky(&[1], 2).is_empty());
+        assert!(h.update_path_sticky(&[], 2).is_empty());
+        assert_eq!(h.current_path(), &[1, 2]);
+        // Back on the item; moving into its submenu only enters.
+        assert!(h.update_path_sticky(&[1, 2], 2).is_empty());
+        assert_eq!(
+            h.update_path_sticky(&[1, 2, 5], 2),
+            vec![HoverEvent::Enter(5)]
+        );
+        // The grace period restarts after a return.
+        assert!(h.update_path_sticky(&[1, 3], 2).is_empty());
+        assert!(h.update_path_sticky(&[1, 3], 2).is_empty());
+        assert_eq!(h.current_path(), &[1, 2, 5]);
+    }
+
+    // Equality compares hovered paths only, not a pending sticky hold.
+    #[test]
+    fn equality_ignores_sticky_hold() {
+        let mut held: HoverState<u32> = HoverState::new();
+        let _ = held.update_path(&[1, 2]);
+        let plain = held.clone();
+        assert!(held.update_path_sticky(&[1], 2).is_empty());
+        assert_eq!(held, plain);
+        let _ = held.update_path(&[1, 3]);
+        assert_ne!(held, plain);
+    }
+
+    // Sticky: a sustained exit leaves once the grace period is over.
+    #[test]
+    fn sticky_hover_sustained_exit_leaves_after_grace() {
+        let mut h: HoverState<u32> = HoverState::new();
+        let _ = h.update_path_sticky(&[1, 2], 3);
+        for _ in 0..3 {
+            assert!(h.update_path_sticky(&[1, 4], 3).is_empty());
+        }
+        assert_eq!(
+            h.update_path_sticky(&[1, 4], 3),
+            vec![HoverEvent::Leave(2), HoverEvent::Enter(4)]
+        );
+        assert_eq!(h.current_path(), &[1, 4]);
+        // Zero grace behaves like `update_path`.
+        assert_eq!(
+            h.update_path_sticky(&[], 0),
+            vec![HoverEvent::Leave(4), HoverEvent::Leave(1)]
+        );
+        assert!(h.current_path().is_empty());
+    }
+
     // Fresh path: expect outer→inner enters.
     #[test]
     fn hover_enter_on_fresh_path() {
@@ -266,6 +455,50 @@ mod tests {
         assert_eq!(h.current_path(), &[7, 8]);
     }
 
+    // Two pointers on different branches: each pointer only sees its own transitions.
+    #[test]
+    fn hover_manager_pointers_are_independent() {
+        let mut m: HoverManager<u32, u8> = HoverManager::new();
+        assert_eq!(
+            m.update(0, &[1, 2, 3]),
+            vec![
+                HoverEvent::Enter(1),
+                HoverEvent::Enter(2),
+                HoverEvent::Enter(3)
+            ]
+        );
+        // Second pointer shares the root but hovers a different branch.
+        assert_eq!(
+            m.update(1, &[1, 4]),
+            vec![HoverEvent::Enter(1), HoverEvent::Enter(4)]
+        );
+        // Moving pointer 0 does not touch pointer 1's chain.
+        assert_eq!(
+            m.update(0, &[1, 2, 5]),
+            vec![HoverEvent::Leave(3), HoverEvent::Enter(5)]
+        );
+        assert_eq!(m.current_path(0), &[1, 2, 5]);
+        assert_eq!(m.current_path(1), &[1, 4]);
+    }
+
+    // Lifting a pointer leaves only its chain and forgets it.
+    #[test]
+    fn hover_manager_remove_leaves_only_that_chain() {
+        let mut m: HoverManager<u32, u8> = HoverManager::new();
+        let _ = m.update(0, &[1, 2]);
+        let _ = m.update(1, &[1, 3]);
+        assert_eq!(
+            m.remove(0),
+            vec![HoverEvent::Leave(2), HoverEvent::Leave(1)]
+        );
+        assert!(m.get(0).is_none());
+        assert!(m.current_path(0).is_empty());
+        assert_eq!(m.current_path(1), &[1, 3]);
+        assert_eq!(m.pointers().collect::<Vec<_>>(), vec![1]);
+        // Removing an unknown pointer is a no-op.
+        assert!(m.remove(7).is_empty());
+    }
+
     // Test that `path_from_dispatch` includes `Target` phase in the path
     #[test]
     fn path_from_dispatch_includes_target_phase() {
@@ -320,4 +553,45 @@ mod tests {
         // Should include all `Capture` phases plus the `Target` phase
         assert_eq!(path, vec![1, 2, 3]);
     }
+
+    #[test]
+    fn path_iter_and_update_path_iter_match_vec_versions() {
+        use crate::types::{Dispatch, Localizer, Phase};
+
+        let dispatch = |phase, node: u32| Dispatch {
+            phase,
+            node,
+            widget: None::<u32>,
+            localizer: Localizer::default(),
+            meta: None::<()>,
+        };
+        let seq = vec![
+            dispatch(Phase::Capture, 1),
+            dispatch(Phase::Capture, 4),
+            dispatch(Phase::Target, 5),
+            dispatch(Phase::Bubble, 4),
+            dispatch(Phase::Bubble, 1),
+        ];
+        let lazy: Vec<u32> = path_iter_from_dispatch(&seq).collect();
+        assert_eq!(lazy, path_from_dispatch(&seq));
+
+        // Walk through extend, branch, shrink, and clear transitions.
+        let paths: [&[u32]; 5] = [&[1, 2, 3], &[1, 2, 3, 6], &[1, 4, 5], &[1], &[]];
+        let mut by_slice: HoverState<u32> = HoverState::new();
+        let mut by_iter: HoverState<u32> = HoverState::new();
+        for path in paths {
+            assert_eq!(
+                by_iter.update_path_iter(path.iter().copied()),
+                by_slice.update_path(path)
+            );
+            assert_eq!(by_iter.current_path(), path);
+        }
+        let _ = by_slice.update_path(&[1, 2]);
+        let _ = by_iter.update_path_iter([1, 2]);
+        assert_eq!(
+            by_iter.update_path_iter(path_iter_from_dispatch(&seq)),
+            by_slice.update_path(&path_from_dispatch(&seq))
+        );
+        assert_eq!(by_iter, by_slice);
+    }
 }
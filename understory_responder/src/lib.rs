@@ -47,6 +47,8 @@
 //! 3) Hover — derive the path from the dispatch via [`path_from_dispatch`](crate::hover::path_from_dispatch)
 //!    and feed it to [`HoverState`](crate::hover::HoverState). `HoverState` emits leave (inner→outer)
 //!    and enter (outer→inner) events for the minimal transition between old and new paths.
+//!    With several concurrent pointers, [`HoverManager`](crate::hover::HoverManager) keeps one chain per pointer id.
+//!    [`Router::hover`](crate::router::Router::hover) does the routing and hover update in one call.
 //!
 //! ## Focus
 //!
@@ -89,6 +91,8 @@
 //!
 //! - **Box Tree Adapter** (`box_tree_adapter` feature): Converts [`understory_box_tree`] spatial queries
 //!   into [`ResolvedHit`](types::ResolvedHit) items. Includes filtered tree traversal for keyboard navigation.
+//! - **Ray Cast Adapter** (`raycast_adapter` feature): Converts 3D ray-cast hits into
+//!   [`ResolvedHit`](types::ResolvedHit) items keyed by [`DepthKey::Distance`](types::DepthKey::Distance).
 //!
 //! This crate is `no_std` and uses `alloc`.
 
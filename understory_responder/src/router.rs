@@ -18,21 +18,34 @@
 //!
 //! ## Ties and Policies
 //!
-//! - Equal‑depth ties are stable and the last wins.
-//! - Use [`TieBreakPolicy`] to document intent or pre‑order your input when you have a stronger ordering.
-//! - `set_scope` filters candidates before ranking.
+//! - Equal‑depth ties go to [`TieBreakPolicy`] first. Ties it cannot resolve go to the candidate
+//!   that appears last in the input, so by default the winner depends on input order.
+//! - [`Router::set_id_order`] supplies an ordering for `K`, used by the `MinId`/`MaxId` policies.
+//! - [`Router::set_stable_selection`] breaks remaining ties by that ordering (larger id wins), so
+//!   the winner no longer depends on input order. Pre‑order your input when you have a stronger ordering.
+//! - `set_scope` (or `set_scope_fn`, for closures) filters candidates before ranking.
+//! - Malformed hits are skipped before ranking; check one up front with
+//!   [`Router::validate_hit`] (or [`ResolvedHit::is_valid`]).
 //! - `capture` overrides selection entirely until released.
+//! - [`Router::explain`] reports the ranking and why the winner won, for debugging picks.
+//! - [`rank_hits`] ranks hits the same way without a router (default policies).
 //!
 //! ## See Also
 //!
 //! [`hover`](crate::hover) for hover transitions derived from the dispatch sequence.
 
+use alloc::boxed::Box;
 use alloc::vec::Vec;
 
+use crate::hover::{HoverEvent, HoverState, path_iter_from_dispatch};
 use crate::types::{
-    Dispatch, Localizer, NoParent, ParentLookup, Phase, ResolvedHit, TieBreakPolicy, WidgetLookup,
+    DepthKey, Dispatch, Localizer, NoParent, ParentLookup, Phase, RankedCandidate, ResolvedHit,
+    SelectionExplanation, SelectionReason, TieBreakPolicy, WidgetLookup,
 };
 
+// Reduce clippy::type_complexity noise for `Router::hover`.
+type HoverDispatch<K, W, M> = (Vec<Dispatch<K, W, M>>, Vec<HoverEvent<K>>);
+
 /// Deterministic responder chain router.
 ///
 /// ## Usage
@@ -42,6 +55,8 @@ use crate::types::{
 ///   path reconstruction via a [`crate::types::ParentLookup`].
 /// - Optionally configure policies:
 ///   - [`Router::set_default_tie_break`] to document equal‑depth intent.
+///   - [`Router::set_id_order`] / [`Router::set_stable_selection`] to make equal‑depth
+///     selection independent of input order.
 ///   - [`Router::set_scope`] to filter candidates (e.g., visibility/pickability).
 ///   - [`Router::capture`] to override target selection until released.
 /// - Call [`Router::handle_with_hits`] each input event to select the winning
@@ -55,13 +70,30 @@ pub struct Router<K, L: WidgetLookup<K>, P: ParentLookup<K> = NoParent> {
     pub(crate) lookup: L,
     pub(crate) parent: P,
     pub(crate) default_tie_break: TieBreakPolicy,
-    pub(crate) scope: Option<fn(&K) -> bool>,
+    pub(crate) scope: Option<Scope<K>>,
+    pub(crate) id_order: Option<fn(&K, &K) -> core::cmp::Ordering>,
+    pub(crate) stable_selection: bool,
     pub(crate) focus: Option<K>,
     // Minimal capture for skeleton; production would be per-pointer id.
     pub(crate) capture: Option<K>,
     pub(crate) _phantom: core::marker::PhantomData<fn() -> K>,
 }
 
+/// A scope filter: a plain function or a closure that may capture state.
+pub(crate) enum Scope<K> {
+    Fn(fn(&K) -> bool),
+    Boxed(Box<dyn Fn(&K) -> bool + Send + Sync>),
+}
+
+impl<K> Scope<K> {
+    fn allows(&self, node: &K) -> bool {
+        match self {
+            Self::Fn(f) => f(node),
+            Self::Boxed(f) => f(node),
+        }
+    }
+}
+
 impl<K: Copy + Eq, L: WidgetLookup<K>, P: ParentLookup<K>> core::fmt::Debug for Router<K, L, P> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Router")
@@ -78,6 +110,8 @@ impl<K: Copy + Eq, L: WidgetLookup<K>, P: ParentLookup<K> + Default> Router<K, L
             parent: P::default(),
             default_tie_break: TieBreakPolicy::Newer,
             scope: None,
+            id_order: None,
+            stable_selection: false,
             focus: None,
             capture: None,
             _phantom: core::marker::PhantomData,
@@ -93,6 +127,8 @@ impl<K: Copy + Eq, L: WidgetLookup<K>, P: ParentLookup<K>> Router<K, L, P> {
             parent,
             default_tie_break: TieBreakPolicy::Newer,
             scope: None,
+            id_order: None,
+            stable_selection: false,
             focus: None,
             capture: None,
             _phantom: core::marker::PhantomData,
@@ -104,9 +140,35 @@ impl<K: Copy + Eq, L: WidgetLookup<K>, P: ParentLookup<K>> Router<K, L, P> {
         self.default_tie_break = p;
     }
 
+    /// Set an optional total order on node keys, used for id-based tie-breaking.
+    ///
+    /// Consulted by [`TieBreakPolicy::MinId`] / [`TieBreakPolicy::MaxId`] and by
+    /// [stable selection](Router::set_stable_selection). Without an order, all ids compare equal.
+    pub fn set_id_order(&mut self, order: Option<fn(&K, &K) -> core::cmp::Ordering>) {
+        self.id_order = order;
+    }
+
+    /// Make selection independent of input order for equal-depth hits.
+    ///
+    /// When enabled, ties left by the [tie-break policy](Router::set_default_tie_break) go
+    /// to the larger id under [`Router::set_id_order`] instead of the last candidate.
+    /// Hits that still compare equal (e.g. duplicates, or no id order set) keep last-wins.
+    /// Disabled by default.
+    pub fn set_stable_selection(&mut self, enabled: bool) {
+        self.stable_selection = enabled;
+    }
+
     /// Set an optional scope filter; only nodes that satisfy the predicate are considered.
     pub fn set_scope(&mut self, scope: Option<fn(&K) -> bool>) {
-        self.scope = scope;
+        self.scope = scope.map(Scope::Fn);
+    }
+
+    /// Set a scope filter that may capture state, such as a set of disabled nodes.
+    ///
+    /// Replaces any filter set by [`Router::set_scope`]; clear it with `set_scope(None)`.
+    /// The closure must be `Send + Sync`, so a router stays shareable across threads.
+    pub fn set_scope_fn(&mut self, scope: impl Fn(&K) -> bool + Send + Sync + 'static) {
+        self.scope = Some(Scope::Boxed(Box::new(scope)));
     }
 
     /// Set the focused node (reserved for higher-level policies; currently not used in routing).
@@ -119,7 +181,17 @@ impl<K: Copy + Eq, L: WidgetLookup<K>, P: ParentLookup<K>> Router<K, L, P> {
         self.capture = node;
     }
 
+    /// Whether [`handle_with_hits`](Self::handle_with_hits) would consider `hit`.
+    ///
+    /// This is [`ResolvedHit::is_valid`]: a provided path must be non-empty and end at
+    /// the hit's node.
+    pub fn validate_hit<M>(&self, hit: &ResolvedHit<K, M>) -> bool {
+        hit.is_valid()
+    }
+
     /// Handle a pre-resolved sequence of hits and produce a propagation sequence.
+    ///
+    /// Hits rejected by [`ResolvedHit::is_valid`] are ignored.
     pub fn handle_with_hits<M>(
         &self,
         hits: &[ResolvedHit<K, M>],
@@ -134,7 +206,7 @@ impl<K: Copy + Eq, L: WidgetLookup<K>, P: ParentLookup<K>> Router<K, L, P> {
             // Find any hit for the captured node (prefer the last if multiple exist).
             let cap_hit = hits.iter().rev().find(|h| h.node == cap);
             let (path, localizer, meta) = match cap_hit {
-                Some(h) if h.path.is_some() => (
+                Some(h) if h.path.is_some() && h.is_valid() => (
                     h.path.clone().unwrap(),
                     h.localizer.clone(),
                     Some(h.meta.clone()),
@@ -154,12 +226,15 @@ impl<K: Copy + Eq, L: WidgetLookup<K>, P: ParentLookup<K>> Router<K, L, P> {
         }
 
         // Single-pass selection without allocation/sort. Equal-depth ties are
-        // resolved by the tie-break policy, and if still equal we prefer the
-        // last candidate (stable last-wins behavior).
+        // resolved by the tie-break policy (then id order under stable selection),
+        // and if still equal we prefer the last candidate (last-wins).
         let mut best_idx: Option<usize> = None;
         for (i, h) in hits.iter().enumerate() {
-            if let Some(f) = self.scope
-                && !f(&h.node)
+            if !h.is_valid() {
+                continue;
+            }
+            if let Some(f) = &self.scope
+                && !f.allows(&h.node)
             {
                 continue;
             }
@@ -171,7 +246,7 @@ impl<K: Copy + Eq, L: WidgetLookup<K>, P: ParentLookup<K>> Router<K, L, P> {
                     let better = match a.depth_key.cmp(&h.depth_key) {
                         Less => true,     // h nearer than a
                         Greater => false, // a nearer than h
-                        Equal => match self.tiebreak(&a.node, &h.node) {
+                        Equal => match self.tie_order(&a.node, &h.node) {
                             Less => true,     // h preferred by policy
                             Greater => false, // a preferred by policy
                             Equal => true,    // stable last wins
@@ -199,6 +274,91 @@ impl<K: Copy + Eq, L: WidgetLookup<K>, P: ParentLookup<K>> Router<K, L, P> {
         self.emit_path(path, best.localizer.clone(), Some(best.meta.clone()))
     }
 
+    /// Route hits and update `hover` from the resulting path in one call.
+    ///
+    /// Equivalent to [`Router::handle_with_hits`], then [`path_iter_from_dispatch`], then
+    /// [`HoverState::update_path_iter`]. Returns the dispatch sequence and the hover
+    /// transitions; an empty dispatch (no winning hit) leaves every hovered node.
+    pub fn hover<M>(
+        &self,
+        hits: &[ResolvedHit<K, M>],
+        hover: &mut HoverState<K>,
+    ) -> HoverDispatch<K, L::WidgetId, M>
+    where
+        M: Clone,
+    {
+        let seq = self.handle_with_hits(hits);
+        let events = hover.update_path_iter(path_iter_from_dispatch(&seq));
+        (seq, events)
+    }
+
+    /// Explain how [`Router::handle_with_hits`] would select a target for `hits`.
+    ///
+    /// This is a diagnostics aid: it reports every candidate ranked nearest first,
+    /// which ones the scope filter rejected, whether capture overrode ranking, and
+    /// why the winner won. It does not change router state or emit dispatches.
+    pub fn explain<M>(&self, hits: &[ResolvedHit<K, M>]) -> SelectionExplanation<K> {
+        let in_scope = |h: &ResolvedHit<K, M>| {
+            h.is_valid() && self.scope.as_ref().is_none_or(|f| f.allows(&h.node))
+        };
+        let mut candidates: Vec<RankedCandidate<K>> = hits
+            .iter()
+            .enumerate()
+            .map(|(index, h)| RankedCandidate {
+                index,
+                node: h.node,
+                depth_key: h.depth_key,
+                in_scope: in_scope(h),
+            })
+            .collect();
+        // Nearest first: depth, then tie-break policy, then later input first
+        // (mirrors the stable last-wins rule in `handle_with_hits`).
+        candidates.sort_by(|a, b| {
+            nearest_first(
+                (a.depth_key, a.index),
+                (b.depth_key, b.index),
+                self.tie_order(&b.node, &a.node),
+            )
+        });
+
+        if let Some(cap) = self.capture {
+            return SelectionExplanation {
+                candidates,
+                captured: Some(cap),
+                winner: Some(cap),
+                reason: Some(SelectionReason::Capture),
+            };
+        }
+
+        let mut scoped = candidates.iter().filter(|c| c.in_scope);
+        let winner = scoped.next();
+        let reason = winner.map(|w| {
+            let mut reason = SelectionReason::OnlyCandidate;
+            for other in scoped {
+                if other.depth_key != w.depth_key {
+                    // Ranked order: every later candidate is at least as far.
+                    if reason == SelectionReason::OnlyCandidate {
+                        reason = SelectionReason::Depth;
+                    }
+                    break;
+                }
+                if self.tie_order(&w.node, &other.node) == core::cmp::Ordering::Equal {
+                    reason = SelectionReason::InputOrder;
+                    break;
+                }
+                reason = SelectionReason::TieBreak;
+            }
+            reason
+        });
+        let winner = winner.map(|w| w.node);
+        SelectionExplanation {
+            candidates,
+            captured: None,
+            winner,
+            reason,
+        }
+    }
+
     /// Emit a dispatch sequence for a specific target node by reconstructing its path.
     ///
     /// Uses [`ParentLookup`] to derive the root→target path. `scope` and capture settings
@@ -306,15 +466,26 @@ impl<K: Copy + Eq, L: WidgetLookup<K>, P: ParentLookup<K>> Router<K, L, P> {
                 }
             }
             // Fallbacks when no inherent ordering is known for K.
-            TieBreakPolicy::MinId => Self::id_cmp(a, b).reverse(),
-            TieBreakPolicy::MaxId => Self::id_cmp(a, b),
+            TieBreakPolicy::MinId => self.id_cmp(a, b).reverse(),
+            TieBreakPolicy::MaxId => self.id_cmp(a, b),
         }
     }
 
+    /// Full equal-depth ordering: the tie-break policy, then id order under stable selection.
+    fn tie_order(&self, a: &K, b: &K) -> core::cmp::Ordering {
+        self.tiebreak(a, b).then_with(|| {
+            if self.stable_selection {
+                self.id_cmp(a, b)
+            } else {
+                core::cmp::Ordering::Equal
+            }
+        })
+    }
+
     // Default id comparisons assume K is comparable by address or value if desired; we provide fallbacks.
-    // TODO: Implement meaningful tie-breaking by allowing injected comparators or a trait.
+    // TODO: Implement meaningful Newer/Older tie-breaking by allowing an injected comparator or a trait.
     // Consider:
-    // - `set_is_newer(fn: fn(&K, &K) -> bool)` and `set_id_cmp(fn: fn(&K, &K) -> Ordering)`;
+    // - `set_is_newer(fn: fn(&K, &K) -> bool)` alongside `set_id_order`;
     // - Or a generic `IdOrder<K>` trait with a default stable-last-wins implementation;
     // - Provide a NodeId-specific comparator in the box-tree adapter (generation, then slot).
     fn id_is_newer(_a: &K, _b: &K) -> bool {
@@ -322,17 +493,50 @@ impl<K: Copy + Eq, L: WidgetLookup<K>, P: ParentLookup<K>> Router<K, L, P> {
         false
     }
 
-    // TODO: As above, use an injected comparator or trait to define ordering for K.
-    // Until then, return Equal so stable last-wins applies after Equal depth.
-    fn id_cmp(_a: &K, _b: &K) -> core::cmp::Ordering {
-        core::cmp::Ordering::Equal
+    // Without an injected order, return Equal so last-wins applies after Equal depth.
+    fn id_cmp(&self, a: &K, b: &K) -> core::cmp::Ordering {
+        self.id_order
+            .map_or(core::cmp::Ordering::Equal, |f| f(a, b))
     }
 }
 
+/// Rank `hits` nearest first, as a router with default policies would.
+///
+/// Returns indices into `hits`, ordered by [`DepthKey`] (higher `Z` first, then lower
+/// `Distance`; `Z` outranks `Distance`), with equal depths ordered later input first.
+/// Malformed hits (see [`ResolvedHit::is_valid`]) are left out. Without scope or capture,
+/// the first index is the hit [`Router::handle_with_hits`] targets under the default
+/// [`TieBreakPolicy`]; use [`Router::explain`] to rank under a configured router.
+pub fn rank_hits<K: PartialEq, M>(hits: &[ResolvedHit<K, M>]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..hits.len()).filter(|&i| hits[i].is_valid()).collect();
+    order.sort_by(|&a, &b| {
+        nearest_first(
+            (hits[a].depth_key, a),
+            (hits[b].depth_key, b),
+            core::cmp::Ordering::Equal,
+        )
+    });
+    order
+}
+
+/// Ranking order shared by [`rank_hits`] and [`Router::explain`]: nearer depth first, then
+/// the equal-depth `tie` order, then later input first (the router's last-wins rule).
+fn nearest_first(
+    (a_depth, a_index): (DepthKey, usize),
+    (b_depth, b_index): (DepthKey, usize),
+    tie: core::cmp::Ordering,
+) -> core::cmp::Ordering {
+    b_depth
+        .cmp(&a_depth)
+        .then(tie)
+        .then_with(|| b_index.cmp(&a_index))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::dispatcher;
+    use crate::hover::path_from_dispatch;
     use crate::types::*;
     use alloc::vec;
 
@@ -388,6 +592,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hover_matches_manual_steps() {
+        let router: Router<Node, Lookup, NoParent> = Router::new(Lookup);
+        let hit = |node: u32, path: Vec<Node>| ResolvedHit {
+            node: Node(node),
+            path: Some(path),
+            depth_key: DepthKey::Z(0),
+            localizer: Localizer::default(),
+            meta: (),
+        };
+        let frames = [
+            vec![hit(3, vec![Node(1), Node(2), Node(3)])],
+            vec![hit(4, vec![Node(1), Node(4)])],
+            vec![],
+        ];
+
+        let mut combined = HoverState::new();
+        let mut manual = HoverState::new();
+        for hits in &frames {
+            let (seq, events) = router.hover(hits, &mut combined);
+            let expected_seq = router.handle_with_hits(hits);
+            let expected_events = manual.update_path(&path_from_dispatch(&expected_seq));
+            let nodes = |s: &[Dispatch<Node, u32, ()>]| -> Vec<(Phase, Node)> {
+                s.iter().map(|d| (d.phase, d.node)).collect()
+            };
+            assert_eq!(nodes(&seq), nodes(&expected_seq));
+            assert_eq!(events, expected_events);
+            assert_eq!(combined.current_path(), manual.current_path());
+        }
+    }
+
     #[test]
     fn capture_prefers_hit_metadata_when_available() {
         let lookup = Lookup;
@@ -491,6 +726,222 @@ mod tests {
         assert_eq!(tgt.node.0, 2);
     }
 
+    #[test]
+    fn router_with_scope_closure_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        let mut router: Router<Node, Lookup, NoParent> = Router::new(Lookup);
+        router.set_scope_fn(|n: &Node| n.0 != 0);
+        assert_send_sync(&router);
+    }
+
+    #[test]
+    fn scope_closure_skips_captured_exclusions() {
+        let mut router: Router<Node, Lookup, NoParent> = Router::new(Lookup);
+        let disabled: alloc::collections::BTreeSet<u32> = [3, 2].into_iter().collect();
+        router.set_scope_fn(move |n: &Node| !disabled.contains(&n.0));
+        let hit = |n: u32, z: i32| ResolvedHit {
+            node: Node(n),
+            path: Some(vec![Node(n)]),
+            depth_key: DepthKey::Z(z),
+            localizer: Localizer::default(),
+            meta: (),
+        };
+        // Ranked by depth: 3, 2, 1; the two frontmost are excluded.
+        let hits = vec![hit(1, 10), hit(3, 30), hit(2, 20)];
+        let out = router.handle_with_hits::<()>(&hits);
+        let tgt = out
+            .iter()
+            .find(|d| matches!(d.phase, Phase::Target))
+            .unwrap();
+        assert_eq!(tgt.node, Node(1));
+
+        router.set_scope(None);
+        let out = router.handle_with_hits::<()>(&hits);
+        let tgt = out
+            .iter()
+            .find(|d| matches!(d.phase, Phase::Target))
+            .unwrap();
+        assert_eq!(tgt.node, Node(3));
+    }
+
+    #[test]
+    fn malformed_hits_are_skipped() {
+        let lookup = Lookup;
+        let mut router: Router<Node, Lookup, NoParent> = Router::new(lookup);
+        let hit = |n: u32, path: Vec<Node>, z: i32| ResolvedHit {
+            node: Node(n),
+            path: Some(path),
+            depth_key: DepthKey::Z(z),
+            localizer: Localizer::default(),
+            meta: (),
+        };
+        let empty = hit(5, vec![], 100);
+        let mismatched = hit(6, vec![Node(1), Node(9)], 90);
+        let good = hit(3, vec![Node(1), Node(3)], 10);
+        assert!(!router.validate_hit(&empty));
+        assert!(!router.validate_hit(&mismatched));
+        assert!(router.validate_hit(&good));
+
+        let hits = vec![empty.clone(), good, mismatched.clone()];
+        let out = router.handle_with_hits::<()>(&hits);
+        let phases: Vec<(Phase, u32)> = out.iter().map(|d| (d.phase, d.node.0)).collect();
+        assert_eq!(
+            phases,
+            vec![(Phase::Capture, 1), (Phase::Target, 3), (Phase::Bubble, 1)]
+        );
+        assert_eq!(router.explain(&hits).winner, Some(Node(3)));
+
+        // Only malformed hits: nothing to route, and no panic.
+        assert!(
+            router
+                .handle_with_hits::<()>(&[empty, mismatched.clone()])
+                .is_empty()
+        );
+
+        // A captured node with a malformed path falls back to a reconstructed path.
+        router.capture(Some(Node(6)));
+        let out = router.handle_with_hits::<()>(&[mismatched]);
+        let phases: Vec<(Phase, u32)> = out.iter().map(|d| (d.phase, d.node.0)).collect();
+        assert_eq!(phases, vec![(Phase::Target, 6)]);
+    }
+
+    #[test]
+    fn rank_hits_leads_with_router_target() {
+        let router: Router<Node, Lookup, NoParent> = Router::new(Lookup);
+        let hit = |n: u32, depth_key: DepthKey| ResolvedHit {
+            node: Node(n),
+            path: Some(vec![Node(n)]),
+            depth_key,
+            localizer: Localizer::default(),
+            meta: (),
+        };
+        let mut malformed = hit(9, DepthKey::Z(99));
+        malformed.path = Some(vec![Node(1)]);
+        let hits = vec![
+            hit(1, DepthKey::Distance(2.0)),
+            hit(2, DepthKey::Z(3)),
+            malformed,
+            hit(3, DepthKey::Distance(0.5)),
+            hit(4, DepthKey::Z(5)),
+            hit(5, DepthKey::Z(3)),
+        ];
+        let order = rank_hits(&hits);
+        // Z before Distance; equal Z goes to the later hit; the malformed hit is dropped.
+        assert_eq!(order, vec![4, 5, 1, 3, 0]);
+
+        let target = |hits: &[ResolvedHit<Node, ()>]| {
+            router
+                .handle_with_hits::<()>(hits)
+                .iter()
+                .find(|d| matches!(d.phase, Phase::Target))
+                .map(|d| d.node)
+        };
+        assert_eq!(target(&hits), Some(hits[order[0]].node));
+        let without_top: Vec<_> = hits
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != order[0])
+            .map(|(_, h)| h.clone())
+            .collect();
+        assert_eq!(target(&without_top), Some(Node(5)));
+    }
+
+    #[test]
+    fn explain_reports_scope_filter_and_depth_winner() {
+        let lookup = Lookup;
+        let mut router: Router<Node, Lookup, NoParent> = Router::new(lookup);
+        router.set_scope(Some(|n: &Node| (n.0 & 1) == 0));
+        let hit = |n: u32, z: i32| ResolvedHit {
+            node: Node(n),
+            path: Some(vec![Node(n)]),
+            depth_key: DepthKey::Z(z),
+            localizer: Localizer::default(),
+            meta: (),
+        };
+        let hits = vec![hit(4, 10), hit(1, 100), hit(2, 50)];
+
+        let ex = router.explain(&hits);
+        let ranked: Vec<(u32, bool)> = ex
+            .candidates
+            .iter()
+            .map(|c| (c.node.0, c.in_scope))
+            .collect();
+        assert_eq!(ranked, vec![(1, false), (2, true), (4, true)]);
+        assert_eq!(ex.candidates[0].index, 1);
+        assert_eq!(ex.captured, None);
+        assert_eq!(ex.winner, Some(Node(2)));
+        assert_eq!(ex.reason, Some(SelectionReason::Depth));
+
+        // Agrees with actual routing.
+        let out = router.handle_with_hits::<()>(&hits);
+        let tgt = out.iter().find(|d| d.phase == Phase::Target).unwrap();
+        assert_eq!(Some(tgt.node), ex.winner);
+
+        // Equal depth falls back to stable last-wins; capture overrides everything.
+        let ex = router.explain(&[hit(2, 5), hit(4, 5)]);
+        assert_eq!(ex.winner, Some(Node(4)));
+        assert_eq!(ex.reason, Some(SelectionReason::InputOrder));
+        router.capture(Some(Node(1)));
+        let ex = router.explain(&hits);
+        assert_eq!(ex.captured, Some(Node(1)));
+        assert_eq!(ex.reason, Some(SelectionReason::Capture));
+    }
+
+    #[test]
+    fn stable_selection_ignores_input_order() {
+        let lookup = Lookup;
+        let mut router: Router<Node, Lookup, NoParent> = Router::new(lookup);
+        let hit = |n: u32| ResolvedHit {
+            node: Node(n),
+            path: Some(vec![Node(n)]),
+            depth_key: DepthKey::Z(1),
+            localizer: Localizer::default(),
+            meta: (),
+        };
+        let target = |router: &Router<Node, Lookup, NoParent>, hits: &[ResolvedHit<Node>]| {
+            let out = router.handle_with_hits::<()>(hits);
+            out.iter().find(|d| d.phase == Phase::Target).unwrap().node
+        };
+        let forward = [hit(3), hit(7), hit(5)];
+        let backward = [hit(5), hit(7), hit(3)];
+
+        // Default: last wins, so the order decides.
+        assert_eq!(target(&router, &forward), Node(5));
+        assert_eq!(target(&router, &backward), Node(3));
+
+        router.set_id_order(Some(|a: &Node, b: &Node| a.0.cmp(&b.0)));
+        router.set_stable_selection(true);
+        assert_eq!(target(&router, &forward), Node(7));
+        assert_eq!(target(&router, &backward), Node(7));
+        assert_eq!(router.explain(&backward).winner, Some(Node(7)));
+    }
+
+    #[test]
+    fn chained_parent_spans_both_sources() {
+        // Box-tree side knows 3 -> 2; widget side knows 2 -> 1.
+        struct Lower;
+        impl ParentLookup<Node> for Lower {
+            fn parent_of(&self, node: &Node) -> Option<Node> {
+                (node.0 == 3).then_some(Node(2))
+            }
+        }
+        struct Upper;
+        impl ParentLookup<Node> for Upper {
+            fn parent_of(&self, node: &Node) -> Option<Node> {
+                (node.0 == 2).then_some(Node(1))
+            }
+        }
+        let router: Router<Node, Lookup, ChainedParent<Lower, Upper>> =
+            Router::with_parent(Lookup, ChainedParent(Lower, Upper));
+        let out = router.dispatch_for::<()>(Node(3));
+        let capture: Vec<u32> = out
+            .iter()
+            .filter(|d| d.phase != Phase::Bubble)
+            .map(|d| d.node.0)
+            .collect();
+        assert_eq!(capture, vec![1, 2, 3]);
+    }
+
     #[test]
     fn parent_of_reconstructs_path() {
         struct Parents;
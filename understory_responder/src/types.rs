@@ -41,9 +41,10 @@ pub enum Outcome {
 /// Policy for breaking ties after equal primary depth.
 ///
 /// Note: The [router](crate::router::Router) does not know how to compare arbitrary node keys `K`.
-/// Implementations can supply a custom tie-break outside the router by pre-sorting hits,
-/// or future versions may accept an ordering callback.
-/// For now, ties are stable with respect to input order, and the router selects the last.
+/// Supply an ordering with [`Router::set_id_order`](crate::router::Router::set_id_order)
+/// (used by `MinId`/`MaxId`), or pre-sort hits outside the router.
+/// Ties that remain are resolved by input order, and the router selects the last, unless
+/// [stable selection](crate::router::Router::set_stable_selection) is enabled.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum TieBreakPolicy {
     /// Prefer the more recently created identifier when available.
@@ -121,6 +122,19 @@ pub struct ResolvedHit<K, M = ()> {
     pub meta: M,
 }
 
+impl<K: PartialEq, M> ResolvedHit<K, M> {
+    /// Returns `false` if the provided path is empty or does not end at [`node`](Self::node).
+    ///
+    /// [`Router::handle_with_hits`](crate::router::Router::handle_with_hits) skips such hits,
+    /// since routing along their path would target the wrong node or nothing at all. Hits
+    /// without a path are valid; their path is derived via [`ParentLookup`].
+    pub fn is_valid(&self) -> bool {
+        self.path
+            .as_ref()
+            .is_none_or(|p| p.last() == Some(&self.node))
+    }
+}
+
 /// Map nodes to toolkit widget identifiers.
 ///
 /// Implement this trait and supply it to the router so that each [`Dispatch`]
@@ -155,6 +169,69 @@ impl<K> ParentLookup<K> for NoParent {
     }
 }
 
+/// Why the [router](crate::router::Router) picked its winning target.
+///
+/// Reported by [`Router::explain`](crate::router::Router::explain).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SelectionReason {
+    /// A pointer capture was active and overrode hit ranking.
+    Capture,
+    /// The winner was the only candidate left after scope filtering.
+    OnlyCandidate,
+    /// The winner was strictly nearer than every other in-scope candidate.
+    Depth,
+    /// The winner shared its depth with another candidate and [`TieBreakPolicy`] preferred it.
+    TieBreak,
+    /// The winner shared its depth and tie-break rank with another candidate and won by
+    /// appearing last in the input (stable last-wins).
+    InputOrder,
+}
+
+/// One hit as seen by the router's ranking, for diagnostics.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RankedCandidate<K> {
+    /// Position of the hit in the input slice.
+    pub index: usize,
+    /// Node key of the hit.
+    pub node: K,
+    /// Depth key the hit was ranked by.
+    pub depth_key: DepthKey,
+    /// `false` when the router's scope filter rejected this hit, or the hit is malformed
+    /// (see [`ResolvedHit::is_valid`]).
+    pub in_scope: bool,
+}
+
+/// Diagnostic breakdown of how the router would select a target for a set of hits.
+///
+/// Produced by [`Router::explain`](crate::router::Router::explain); routing is unaffected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelectionExplanation<K> {
+    /// Every input hit, nearest first, using the same ordering as
+    /// [`Router::handle_with_hits`](crate::router::Router::handle_with_hits).
+    /// Scope-filtered hits are kept in the list with `in_scope == false`.
+    pub candidates: Vec<RankedCandidate<K>>,
+    /// The captured node, when capture overrode ranking.
+    pub captured: Option<K>,
+    /// The node that would receive the target phase, if any.
+    pub winner: Option<K>,
+    /// Why [`winner`](Self::winner) was chosen; `None` when there is no winner.
+    pub reason: Option<SelectionReason>,
+}
+
+/// A parent lookup that consults `A` first and falls back to `B`.
+///
+/// Useful when part of the hierarchy is known to one source (for example a box tree)
+/// and the rest to another (for example a widget tree). Chains can be nested.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ChainedParent<A, B>(pub A, pub B);
+
+impl<K, A: ParentLookup<K>, B: ParentLookup<K>> ParentLookup<K> for ChainedParent<A, B> {
+    #[inline]
+    fn parent_of(&self, node: &K) -> Option<K> {
+        self.0.parent_of(node).or_else(|| self.1.parent_of(node))
+    }
+}
+
 /// A single dispatch item.
 ///
 /// Produced by [`Router::handle_with_hits`](crate::router::Router::handle_with_hits), and typically fed